@@ -0,0 +1,11 @@
+#![no_main]
+
+use lib::dns::DnsPacket;
+use lib::parser::DnsBytePacketBuffer;
+use libfuzzer_sys::fuzz_target;
+
+// Parsing arbitrary bytes must never panic, only return an error.
+fuzz_target!(|data: &[u8]| {
+    let mut dbuf = DnsBytePacketBuffer::from_bytes(data);
+    let _ = DnsPacket::from_buffer(&mut dbuf);
+});
@@ -0,0 +1,31 @@
+#![no_main]
+
+use lib::name::MAX_NAME_LEN;
+use lib::parser::DnsBytePacketBuffer;
+use libfuzzer_sys::fuzz_target;
+
+// The first byte picks the offset the name is decoded from, so compression
+// pointers can target data both before and after the start of the name.
+fuzz_target!(|data: &[u8]| {
+    let Some((&start, bytes)) = data.split_first() else {
+        return;
+    };
+    let mut dbuf = DnsBytePacketBuffer::from_bytes(bytes);
+    if dbuf._step(start as usize).is_err() {
+        return;
+    }
+    let Ok(name) = dbuf.read_label() else {
+        return;
+    };
+    assert!(name.wire_len() <= MAX_NAME_LEN);
+
+    // a decoded name must encode and decode back to the same labels
+    let mut out = DnsBytePacketBuffer::new();
+    out.write_label(&name).expect("decoded name must be encodable");
+    let wire = out.get_buf_range(0..out.get_pos()).unwrap();
+    assert_eq!(wire, name.to_wire().as_slice());
+    let decoded = DnsBytePacketBuffer::from_bytes(wire)
+        .read_label()
+        .expect("encoded name must decode");
+    assert!(name.labels().eq(decoded.labels()));
+});
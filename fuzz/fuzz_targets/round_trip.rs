@@ -0,0 +1,30 @@
+#![no_main]
+
+use lib::dns::DnsPacket;
+use lib::parser::DnsBytePacketBuffer;
+use libfuzzer_sys::fuzz_target;
+
+/// write a packet and return the bytes written
+fn encode(packet: &mut DnsPacket) -> Option<Vec<u8>> {
+    let mut dbuf = DnsBytePacketBuffer::new();
+    packet.write(&mut dbuf).ok()?;
+    Some(dbuf.get_buf_range(0..dbuf.get_pos()).ok()?.to_vec())
+}
+
+// parse -> write -> parse -> write must be idempotent: whatever we accept and
+// re-encode has to parse again and encode to exactly the same bytes.
+fuzz_target!(|data: &[u8]| {
+    let mut dbuf = DnsBytePacketBuffer::from_bytes(data);
+    let Ok(mut packet) = DnsPacket::from_buffer(&mut dbuf) else {
+        return;
+    };
+    // a packet that does not fit once re-encoded uncompressed is not an error
+    let Some(first) = encode(&mut packet) else {
+        return;
+    };
+
+    let mut dbuf = DnsBytePacketBuffer::from_bytes(&first);
+    let mut reparsed = DnsPacket::from_buffer(&mut dbuf).expect("re-encoded packet must parse");
+    let second = encode(&mut reparsed).expect("re-parsed packet must encode");
+    assert_eq!(first, second, "encoding is not idempotent");
+});
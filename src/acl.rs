@@ -0,0 +1,161 @@
+use super::name::Name;
+use super::{Error, Result};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+/// one element of an [`Acl`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AclEntry {
+    /// every client
+    ANY,
+    /// clients inside `addr/prefix`
+    NETWORK { addr: IpAddr, prefix: u8 },
+    /// requests signed with this TSIG key
+    KEY(Name),
+}
+
+impl AclEntry {
+    fn matches(&self, client: IpAddr, key: Option<&Name>) -> bool {
+        match self {
+            Self::ANY => true,
+            Self::NETWORK { addr, prefix } => in_network(client, *addr, *prefix),
+            Self::KEY(name) => key == Some(name),
+        }
+    }
+}
+
+/// whether the first `prefix` bits of `client` and `network` agree
+fn in_network(client: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    let client: IpAddr = match network {
+        IpAddr::V4(_) => unmap(client),
+        IpAddr::V6(_) => client,
+    };
+    match (client, network) {
+        (IpAddr::V4(_), IpAddr::V4(_)) | (IpAddr::V6(_), IpAddr::V6(_)) => {
+            mask(client, prefix) == mask(network, prefix)
+        }
+        _ => false,
+    }
+}
+
+/// `addr` as IPv4 when it is IPv4-mapped, as IPv4 clients on a dual stack
+/// socket show up
+pub(crate) fn unmap(addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(addr, IpAddr::V4),
+        IpAddr::V4(_) => addr,
+    }
+}
+
+/// `addr` with only its leading `prefix` bits
+pub(crate) fn mask(addr: IpAddr, prefix: u8) -> IpAddr {
+    match addr {
+        IpAddr::V4(addr) => {
+            let mask: u32 = u32::MAX
+                .checked_shl(32 - prefix.min(32) as u32)
+                .unwrap_or(0);
+            IpAddr::V4(Ipv4Addr::from(u32::from(addr) & mask))
+        }
+        IpAddr::V6(addr) => {
+            let mask: u128 = u128::MAX
+                .checked_shl(128 - prefix.min(128) as u32)
+                .unwrap_or(0);
+            IpAddr::V6(Ipv6Addr::from(u128::from(addr) & mask))
+        }
+    }
+}
+
+impl FromStr for AclEntry {
+    type Err = Error;
+
+    /// `any`, `key:<name>`, an address or `<address>/<prefix>`
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::InvalidRecord(format!("invalid ACL entry {}", s));
+        if s.eq_ignore_ascii_case("any") {
+            return Ok(Self::ANY);
+        }
+        if let Some(key) = s.strip_prefix("key:") {
+            return Ok(Self::KEY(key.parse()?));
+        }
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|_| invalid())?;
+        let bits: u8 = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        let prefix: u8 = match prefix {
+            Some(prefix) => prefix
+                .parse()
+                .ok()
+                .filter(|prefix| *prefix <= bits)
+                .ok_or_else(invalid)?,
+            None => bits,
+        };
+        Ok(Self::NETWORK { addr, prefix })
+    }
+}
+
+impl fmt::Display for AclEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ANY => write!(f, "any"),
+            Self::NETWORK { addr, prefix } => write!(f, "{}/{}", addr, prefix),
+            Self::KEY(name) => write!(f, "key:{:#}", name),
+        }
+    }
+}
+
+/// Who may do something, by client address or TSIG key.
+///
+/// An empty list allows nobody.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Acl {
+    entries: Vec<AclEntry>,
+}
+
+impl Acl {
+    /// the list allowing nobody
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    pub fn any() -> Self {
+        Self {
+            entries: vec![AclEntry::ANY],
+        }
+    }
+
+    pub fn push(&mut self, entry: AclEntry) {
+        self.entries.push(entry);
+    }
+
+    pub fn entries(&self) -> &[AclEntry] {
+        &self.entries
+    }
+
+    /// whether `client`, having signed with `key` if any, matches an entry
+    pub fn allows(&self, client: IpAddr, key: Option<&Name>) -> bool {
+        self.entries.iter().any(|entry| entry.matches(client, key))
+    }
+}
+
+impl FromStr for Acl {
+    type Err = Error;
+
+    /// comma separated entries, `none` for the empty list
+    fn from_str(s: &str) -> Result<Self> {
+        match s.eq_ignore_ascii_case("none") {
+            true => Ok(Self::none()),
+            false => Ok(Self {
+                entries: s
+                    .split(',')
+                    .map(str::parse)
+                    .collect::<Result<Vec<AclEntry>>>()?,
+            }),
+        }
+    }
+}
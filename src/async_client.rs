@@ -0,0 +1,309 @@
+use super::client::{check_response, random_id, UDP_PAYLOAD_SIZE};
+use super::dns::{DnsPacket, DnsQuestion, DnsRecord, Edns, QueryType, RecordData, ResponseCode};
+use super::name::Name;
+use super::parser::{DnsBytePacketBuffer, MAX_MESSAGE_SIZE};
+use super::{Error, Result};
+use log::debug;
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time;
+
+/// times a query is sent before the lookup times out
+pub const ATTEMPTS: usize = 3;
+
+/// queries waiting for their response, by id
+type Pending = Arc<Mutex<HashMap<u16, Waiting>>>;
+
+#[derive(Debug)]
+struct Waiting {
+    query: DnsPacket,
+    sender: oneshot::Sender<DnsPacket>,
+}
+
+/// The socket shared by the clones of an `AsyncClient` and the task
+/// reading responses off it, which ends with the last clone.
+#[derive(Debug)]
+struct Shared {
+    socket: Arc<UdpSocket>,
+    pending: Pending,
+    reader: JoinHandle<()>,
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+/// Asynchronous client sending recursive queries to a single server.
+///
+/// Every lookup goes out on the one UDP socket of the client, with a random
+/// id no other lookup in flight has, and a task hands each response to the
+/// lookup with its id and question. Queries that go unanswered within the
+/// timeout are sent again, truncated answers are asked again over TCP.
+///
+/// Clones share the socket, so any number of lookups can run at once from
+/// as many tasks. The client has to be made inside a tokio runtime.
+#[derive(Debug, Clone)]
+pub struct AsyncClient {
+    server: SocketAddr,
+    timeout: Duration,
+    attempts: usize,
+    shared: Arc<Shared>,
+}
+
+impl AsyncClient {
+    /// a client for `server` on a fresh socket with an ephemeral port
+    pub async fn new(server: SocketAddr) -> Result<Self> {
+        let socket: Arc<UdpSocket> = Arc::new(
+            UdpSocket::bind(match server {
+                SocketAddr::V4(_) => "0.0.0.0:0",
+                SocketAddr::V6(_) => "[::]:0",
+            })
+            .await?,
+        );
+        let pending: Pending = Arc::default();
+        let reader: JoinHandle<()> = tokio::spawn(read(socket.clone(), server, pending.clone()));
+        Ok(Self {
+            server,
+            timeout: Duration::from_secs(2),
+            attempts: ATTEMPTS,
+            shared: Arc::new(Shared {
+                socket,
+                pending,
+                reader,
+            }),
+        })
+    }
+
+    /// how long each attempt waits for the response
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// times a query is sent before giving up, at least once
+    pub fn set_attempts(&mut self, attempts: usize) {
+        self.attempts = attempts.max(1);
+    }
+
+    pub fn server(&self) -> SocketAddr {
+        self.server
+    }
+
+    /// the address the socket of the client is bound to
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.shared.socket.local_addr()?)
+    }
+
+    /// ask for `q_type` records of `name` with recursion desired
+    pub async fn lookup(&self, name: &Name, q_type: QueryType) -> Result<Lookup> {
+        let mut query: DnsPacket = DnsPacket::new();
+        query.header.rd = true;
+        query.questions.push(DnsQuestion::new(name.clone(), q_type));
+        query.edns = Some(Edns {
+            udp_payload_size: UDP_PAYLOAD_SIZE,
+            ..Edns::default()
+        });
+        let question: DnsQuestion = query.questions[0].clone();
+        let response: DnsPacket = self.exchange(query).await?;
+        Ok(Lookup::new(question, response, Instant::now()))
+    }
+
+    /// send `query` under a fresh id until the response comes, or every
+    /// attempt has timed out
+    pub async fn exchange(&self, mut query: DnsPacket) -> Result<DnsPacket> {
+        for attempt in 1..=self.attempts {
+            let (registration, receiver): (Registration, oneshot::Receiver<DnsPacket>) =
+                self.register(&mut query)?;
+            let mut buffer: DnsBytePacketBuffer = DnsBytePacketBuffer::new();
+            query.write(&mut buffer)?;
+            self.shared
+                .socket
+                .send_to(buffer.written(), self.server)
+                .await?;
+            let response: DnsPacket = match time::timeout(self.timeout, receiver).await {
+                Ok(Ok(response)) => response,
+                Ok(Err(_)) => return Err(Error::Io(io::Error::other("response reader stopped"))),
+                Err(_) => {
+                    debug!(
+                        "no response from {} to {} within {:?}, attempt {} of {}",
+                        self.server, query.questions[0].label, self.timeout, attempt, self.attempts
+                    );
+                    continue;
+                }
+            };
+            drop(registration);
+            return match response.header.tc {
+                false => Ok(response),
+                true => {
+                    let bytes: Vec<u8> =
+                        tcp_exchange(self.server, buffer.written(), self.timeout).await?;
+                    let response: DnsPacket =
+                        DnsPacket::from_buffer(&mut DnsBytePacketBuffer::from_bytes(&bytes))?;
+                    check_response(&query, &response, false)?;
+                    Ok(response)
+                }
+            };
+        }
+        Err(Error::Io(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("{} did not answer", self.server),
+        )))
+    }
+
+    /// give `query` an id no query in flight has and wait for a response
+    /// to it, until the registration is dropped
+    fn register(
+        &self,
+        query: &mut DnsPacket,
+    ) -> Result<(Registration, oneshot::Receiver<DnsPacket>)> {
+        let (sender, receiver) = oneshot::channel();
+        let mut pending: MutexGuard<HashMap<u16, Waiting>> = lock(&self.shared.pending);
+        let mut id: u16 = random_id()?;
+        while pending.contains_key(&id) {
+            id = random_id()?;
+        }
+        query.header.id = id;
+        pending.insert(
+            id,
+            Waiting {
+                query: query.clone(),
+                sender,
+            },
+        );
+        Ok((
+            Registration {
+                id,
+                pending: self.shared.pending.clone(),
+            },
+            receiver,
+        ))
+    }
+}
+
+/// Removes a query from those in flight once its lookup stops waiting,
+/// answered, timed out or cancelled.
+struct Registration {
+    id: u16,
+    pending: Pending,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        lock(&self.pending).remove(&self.id);
+    }
+}
+
+/// The outcome of a lookup.
+#[derive(Debug, Clone)]
+pub struct Lookup {
+    pub question: DnsQuestion,
+    pub r_code: ResponseCode,
+    /// the answer section, CNAME records leading to the name included
+    pub answers: Vec<DnsRecord>,
+    /// until when the answer may be cached, by the lowest TTL in it or the
+    /// negative TTL of the SOA when there is none, RFC 2308
+    pub valid_until: Instant,
+}
+
+impl Lookup {
+    /// the lookup of `question` answered by `response` at `now`
+    pub fn new(question: DnsQuestion, response: DnsPacket, now: Instant) -> Self {
+        let ttl: u32 = match response.answers.is_empty() {
+            false => response.answers.iter().map(|record| record.ttl).min(),
+            true => response
+                .authorities
+                .iter()
+                .find_map(|record| match record.r_data {
+                    RecordData::SOA { minimum, .. } => Some(record.ttl.min(minimum)),
+                    _ => None,
+                }),
+        }
+        .unwrap_or_default();
+        Self {
+            question,
+            r_code: response.header.r_code,
+            answers: response.answers,
+            valid_until: now + Duration::from_secs(ttl as u64),
+        }
+    }
+
+    /// the records of the type asked for
+    pub fn records(&self) -> impl Iterator<Item = &DnsRecord> {
+        self.answers
+            .iter()
+            .filter(|record| record.r_type == self.question.q_type)
+    }
+}
+
+/// hand the responses coming in on `socket` to the queries waiting for
+/// them, dropping anything not from `server` or answering no such query
+async fn read(socket: Arc<UdpSocket>, server: SocketAddr, pending: Pending) {
+    loop {
+        let mut buffer: DnsBytePacketBuffer =
+            DnsBytePacketBuffer::with_size(UDP_PAYLOAD_SIZE as usize);
+        let (len, from): (usize, SocketAddr) = match socket.recv_from(buffer.get_buf()).await {
+            Ok(received) => received,
+            // such as ICMP port unreachable reported for an earlier send
+            Err(error) => {
+                debug!("receiving from {} failed: {}", server, error);
+                continue;
+            }
+        };
+        if from != server {
+            continue;
+        }
+        buffer.set_bytes_read(len);
+        let Ok(response) = DnsPacket::from_buffer(&mut buffer) else {
+            continue;
+        };
+        let mut pending: MutexGuard<HashMap<u16, Waiting>> = lock(&pending);
+        let answers: bool = pending
+            .get(&response.header.id)
+            .is_some_and(|waiting| check_response(&waiting.query, &response, false).is_ok());
+        if answers {
+            if let Some(waiting) = pending.remove(&response.header.id) {
+                // the lookup may have given up just now
+                let _ = waiting.sender.send(response);
+            }
+        }
+    }
+}
+
+fn lock(pending: &Pending) -> MutexGuard<'_, HashMap<u16, Waiting>> {
+    pending
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// send one message over a fresh TCP connection and read the reply
+async fn tcp_exchange(server: SocketAddr, message: &[u8], timeout: Duration) -> Result<Vec<u8>> {
+    if message.len() > MAX_MESSAGE_SIZE {
+        return Err(Error::BufferFull(message.len()));
+    }
+    let exchange = async {
+        let mut stream: TcpStream = TcpStream::connect(server).await?;
+        let mut framed: Vec<u8> = Vec::with_capacity(message.len() + 2);
+        framed.extend_from_slice(&(message.len() as u16).to_be_bytes());
+        framed.extend_from_slice(message);
+        stream.write_all(&framed).await?;
+        let len: u16 = stream.read_u16().await?;
+        let mut reply: Vec<u8> = vec![0; len as usize];
+        stream.read_exact(&mut reply).await?;
+        Ok::<Vec<u8>, io::Error>(reply)
+    };
+    match time::timeout(timeout, exchange).await {
+        Ok(reply) => Ok(reply?),
+        Err(_) => Err(Error::Io(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("{} did not answer over TCP", server),
+        ))),
+    }
+}
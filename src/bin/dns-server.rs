@@ -0,0 +1,185 @@
+use std::env;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::thread;
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+use lib::{
+    blocklist::Blocklist,
+    config::{Config, ZoneConfig},
+    cookie::ServerCookies,
+    dns::QueryType,
+    hosts::Hosts,
+    name::Name,
+    rpz::ResponsePolicy,
+    server::{load_blocklist, load_catalog, load_hosts, load_zones, unix_time, Server, View},
+    tsig::KeyRing,
+    zone::Catalog,
+    Result,
+};
+
+/// prints library log records the way the server prints its own lines
+struct Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            println!("{}: {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// print what is served from `catalog`, in `view` if any
+fn print_zones(catalog: &Catalog, view: Option<&str>) {
+    for zone in catalog.zones() {
+        let signed: bool = !zone.rrset(zone.origin(), QueryType::DNSKEY).is_empty();
+        println!(
+            "INFO: serving {:#} serial {} signed {}{}",
+            zone.origin(),
+            zone.serial().unwrap_or_default(),
+            signed,
+            view.map(|view| format!(" in view {}", view))
+                .unwrap_or_default()
+        );
+    }
+}
+
+/// a server answering from `catalog`, with the queries, transfers, NOTIFY,
+/// UPDATE and policy of `zones` set up, and recursion through `forwarders`
+fn zone_server(
+    catalog: Catalog,
+    zones: &[ZoneConfig],
+    response_policy: &[Name],
+    forwarders: &[SocketAddr],
+    config: &Config,
+) -> Server {
+    let mut server: Server = Server::new(catalog);
+    server.set_query_acl(config.allow_query.clone());
+    server.set_recursion_acl(config.allow_recursion.clone());
+    server.set_forwarders(forwarders.to_vec());
+    for zone in zones {
+        if let Some(acl) = &zone.allow_query {
+            server.set_zone_query_acl(zone.origin.clone(), acl.clone());
+        }
+        server.set_transfer_acl(zone.origin.clone(), zone.allow_transfer.clone());
+        match zone.primary {
+            Some(_) => server.set_notify_acl(zone.origin.clone(), zone.allow_notify.clone()),
+            None => server.allow_updates(zone),
+        }
+    }
+    server.set_keyring(config.keyring.clone());
+    server.set_response_policy(ResponsePolicy::new(response_policy.to_vec()));
+    for origin in response_policy {
+        println!("INFO: rewriting answers by policy zone {:#}", origin);
+    }
+    for forwarder in forwarders {
+        println!("INFO: forwarding recursive queries to {}", forwarder);
+    }
+    server
+}
+
+fn main() -> Result<()> {
+    // DNS_SERVER_LOG=debug also logs the queries the ACLs let through
+    let level: LevelFilter = env::var("DNS_SERVER_LOG")
+        .ok()
+        .and_then(|level| LevelFilter::from_str(&level).ok())
+        .unwrap_or(LevelFilter::Info);
+    log::set_logger(&Logger).expect("no logger is set before");
+    log::set_max_level(level);
+    // the only argument is the config file, see `lib::config::Config`
+    let config_file: String = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "dns-server.conf".to_string());
+    let config: Config = Config::load(&config_file)?;
+    let catalog: Catalog = load_catalog(&config)?;
+    let hosts: Hosts = load_hosts(&config)?;
+    let blocklist: Blocklist = load_blocklist(&config)?;
+    print_zones(&catalog, None);
+    let mut server: Server = zone_server(
+        catalog,
+        &config.zones,
+        &config.response_policy,
+        &config.forwarders,
+        &config,
+    );
+    for view in &config.views {
+        let catalog: Catalog = load_zones(&view.zones, &config.keyring)?;
+        print_zones(&catalog, Some(&view.name));
+        let mut added: View = View::new(
+            view.name.clone(),
+            zone_server(
+                catalog,
+                &view.zones,
+                &view.response_policy,
+                &view.forwarders,
+                &config,
+            ),
+        );
+        added.match_clients = view.match_clients.clone();
+        added.match_destinations = view.match_destinations.clone();
+        server.add_view(added);
+    }
+    if !blocklist.blocked().is_empty() {
+        println!(
+            "INFO: blocking {} names with {}, {} allowed",
+            blocklist.blocked().len(),
+            blocklist.action(),
+            blocklist.allowed().len()
+        );
+    }
+    println!("INFO: listening on {}", config.listen);
+    server.set_tcp_clients(config.tcp_clients);
+    if let Some(limit) = &config.rate_limit {
+        println!(
+            "INFO: limiting UDP responses to {} per second per network, slip {}",
+            limit.responses_per_second, limit.slip
+        );
+        server.set_rate_limit(limit.clone());
+    }
+    let mut cookies: ServerCookies = ServerCookies::new(config.cookie_secret, unix_time())?;
+    cookies.required = config.require_cookies;
+    server.set_cookies(cookies);
+    server.set_hosts(hosts);
+    server.set_blocklist(blocklist);
+    let server: &Server = &server;
+    // every server paired with the zones it keeps up to date
+    let scopes: Vec<(&Server, &[ZoneConfig])> = std::iter::once((server, &config.zones[..]))
+        .chain(
+            server
+                .views()
+                .iter()
+                .zip(&config.views)
+                .map(|(view, view_config)| (&view.server, &view_config.zones[..])),
+        )
+        .collect();
+    let keyring: &KeyRing = &config.keyring;
+    thread::scope(|scope| {
+        for (server, zones) in scopes {
+            for zone in zones {
+                match zone.primary {
+                    Some(primary) => {
+                        println!("INFO: following {:#} from {}", zone.origin, primary);
+                        scope.spawn(move || server.follow(zone, keyring));
+                    }
+                    None => {
+                        scope.spawn(move || server.watch(zone, keyring));
+                    }
+                }
+            }
+        }
+        if !config.hosts.is_empty() || !config.static_records.is_empty() {
+            scope.spawn(|| server.watch_hosts(&config));
+        }
+        if !config.blocklists.is_empty() || !config.allowlists.is_empty() {
+            scope.spawn(|| server.watch_blocklists(&config));
+        }
+        server.run(config.listen)
+    })
+}
@@ -0,0 +1,86 @@
+use std::env;
+use std::fs;
+use std::process;
+
+use lib::{
+    dns::DnsRecord,
+    dnssec::{ds_record, signer::SigningKey, ALG_ED25519, DIGEST_SHA256},
+    name::Name,
+    Error, Result,
+};
+
+const USAGE: &str = "usage:
+  dnssec_tool keygen <zone> [--ksk] [--algorithm 13|15]
+  dnssec_tool ds <private key file> [--digest 1|2|4] [--ttl <seconds>]";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2)
+}
+
+/// write `K<zone>+<alg>+<tag>.private` and `.key` to the working directory
+fn keygen(mut args: impl Iterator<Item = String>) -> Result<()> {
+    let zone: Name = args.next().unwrap_or_else(|| usage()).parse()?;
+    let mut ksk: bool = false;
+    let mut algorithm: u8 = ALG_ED25519;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ksk" => ksk = true,
+            "--algorithm" => {
+                algorithm = args
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .unwrap_or_else(|| usage());
+            }
+            _ => usage(),
+        }
+    }
+    let key: SigningKey = SigningKey::generate(zone.clone(), algorithm, ksk)?;
+    let dnskey: DnsRecord = DnsRecord::new(zone, 3600, key.dnskey());
+    fs::write(format!("{}.private", key.file_stem()), key.to_string())?;
+    fs::write(format!("{}.key", key.file_stem()), format!("{}\n", dnskey))?;
+    println!("{}", key.file_stem());
+    Ok(())
+}
+
+/// print the DS record the parent zone publishes for a key
+fn ds(mut args: impl Iterator<Item = String>) -> Result<()> {
+    let key: SigningKey = SigningKey::load(&args.next().unwrap_or_else(|| usage()))?;
+    let mut digest_type: u8 = DIGEST_SHA256;
+    let mut ttl: u32 = 3600;
+    while let Some(arg) = args.next() {
+        let value: Option<String> = args.next();
+        match arg.as_str() {
+            "--digest" => {
+                digest_type = value
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or_else(|| usage())
+            }
+            "--ttl" => {
+                ttl = value
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or_else(|| usage())
+            }
+            _ => usage(),
+        }
+    }
+    match ds_record(key.zone(), ttl, &key.dnskey(), digest_type)? {
+        Some(record) => println!("{}", record),
+        None => {
+            return Err(Error::InvalidKey(format!(
+                "unsupported digest type {}",
+                digest_type
+            )))
+        }
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let mut args = env::args().skip(1);
+    match args.next().as_deref() {
+        Some("keygen") => keygen(args),
+        Some("ds") => ds(args),
+        _ => usage(),
+    }
+}
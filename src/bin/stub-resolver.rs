@@ -1,69 +1,95 @@
 use std::env;
-use std::net::UdpSocket;
+use std::net::SocketAddr;
 
 use lib::{
-    dns::{DnsPacket, DnsQuestion, QueryType},
-    parser::DnsBytePacketBuffer,
+    client::Client,
+    dns::{DnsPacket, DnsRecord, QueryType},
+    dnssec::{anchor::TrustAnchors, validator::Validator},
+    name::Name,
+    recursor::Recursor,
     Result,
 };
 
+/// print a record with its owner name decoded to U-labels
+fn print_unicode(rec: &DnsRecord) {
+    println!(
+        "{}\t{}\t{:?}\t{:?}",
+        rec.label.to_unicode(),
+        rec.ttl,
+        rec.r_type,
+        rec.r_data
+    );
+}
+
 fn main() -> Result<()> {
     let mut args = env::args();
     args.next();
-    // Perform an A query for google.com
-    let query_name: String = match args.next() {
-        Some(name) => name,
-        None => String::from("google.com"),
+    // `--unicode` prints owner names as U-labels instead of debug output
+    let mut unicode: bool = false;
+    // `--dnssec` validates the answer, `--anchors <file>` replaces the root anchors
+    let mut dnssec: bool = false;
+    let mut anchors_file: Option<String> = None;
+    // `--0x20` sends the name in random case and wants it echoed exactly
+    let mut randomize_case: bool = false;
+    // `--iterate` resolves from the root servers down with minimised queries
+    let mut iterate: bool = false;
+    let mut name_arg: Option<String> = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-u" | "--unicode" => unicode = true,
+            "--dnssec" => dnssec = true,
+            "--anchors" => anchors_file = args.next(),
+            "--0x20" => randomize_case = true,
+            "--iterate" => iterate = true,
+            _ => name_arg = Some(arg),
+        }
+    }
+    // Perform an A query for google.com, Unicode names are sent as A-labels
+    let query_name: Name = match name_arg {
+        Some(name) => Name::from_unicode(&name)?,
+        None => "google.com".parse()?,
     };
     let query_type: QueryType = QueryType::A;
-    // Using googles public DNS server
-    let server: (&str, u16) = ("8.8.8.8", 53);
-
-    // Build our query packet. It's important that we remember to set the
-    // `recursion_desired` flag. As noted earlier, the packet id is arbitrary.
-    let mut packet: DnsPacket = DnsPacket::new();
-    packet.header.id = 6666;
-    packet.header.qd_count = 1;
-    packet.header.rd = true;
-    packet
-        .questions
-        .push(DnsQuestion::new(query_name, query_type));
-
-    // Use our new write method to write the packet to a buffer...
-    let mut req_buffer = DnsBytePacketBuffer::new();
-    packet.write(&mut req_buffer)?;
+    // Using googles public DNS server. The client picks a random id and a
+    // fresh source port for every query and only takes a response from this
+    // server to the same id and question.
+    let mut client: Client = Client::new(SocketAddr::from(([8, 8, 8, 8], 53)));
+    client.set_randomize_case(randomize_case);
 
-    // Bind a UDP socket to an arbitrary port
-    let socket: UdpSocket = UdpSocket::bind(("0.0.0.0", 43210)).map_err(|err| {
-        eprintln!("ERROR: Unable to open UDP socket: {}", err);
-    })?;
-
-    // ...and send it off to the server using our socket:
-    socket
-        .send_to(
-            &req_buffer
-                .get_buf_range(0..req_buffer.get_pos())
-                .expect("Unable to read given range of buffer"),
-            server,
-        )
-        .map_err(|err| {
-            eprintln!("ERROR: error while standing data: {}", err);
-        })?;
-    let mut res_buffer = DnsBytePacketBuffer::new();
-    let (bytes_read, socket_addr) = socket.recv_from(&mut res_buffer.get_buf()).map_err(|err| {
-        eprintln!("ERROR: Unable to recieve data from socket:{}", err);
-    })?;
-    res_buffer.set_bytes_read(bytes_read);
-    println!(
-        "INFO: Bytes Read: {}, socket address: {}",
-        bytes_read, socket_addr
-    );
+    if dnssec {
+        let anchors: TrustAnchors = match anchors_file {
+            Some(file_name) => TrustAnchors::load(&file_name)?,
+            None => TrustAnchors::root(),
+        };
+        let mut res_packet: DnsPacket = client.query(&query_name, query_type, true)?;
+        let validator: Validator<&Client> = Validator::new(&client, anchors);
+        println!("DNSSEC: {}", validator.validate(&mut res_packet));
+        for rec in res_packet.answers {
+            println!("{}", rec);
+        }
+        return Ok(());
+    }
 
-    // As per the previous section, `DnsPacket::from_buffer()` is then used to
-    // actually parse the packet after which we can print the response.
-    let res_packet = DnsPacket::from_buffer(&mut res_buffer)?;
+    let res_packet: DnsPacket = match iterate {
+        true => Recursor::root().resolve(&query_name, query_type),
+        false => client.query(&query_name, query_type, false),
+    }
+    .inspect_err(|err| eprintln!("ERROR: query failed: {}", err))?;
     println!("{:#?}", res_packet.header);
 
+    if unicode {
+        for q in res_packet.questions {
+            println!("{}\t{:?}", q.label.to_unicode(), q.q_type);
+        }
+        res_packet
+            .answers
+            .iter()
+            .chain(res_packet.authorities.iter())
+            .chain(res_packet.additionals.iter())
+            .for_each(print_unicode);
+        return Ok(());
+    }
+
     for q in res_packet.questions {
         println!("{:#?}", q);
     }
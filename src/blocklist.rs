@@ -0,0 +1,303 @@
+use super::dns::{DnsPacket, DnsQuestion, DnsRecord, QueryType, RecordData, ResponseCode};
+use super::name::Name;
+use super::{Error, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+/// TTL of the addresses blocked names are answered with
+pub const BLOCK_TTL: u32 = 60;
+
+/// names hosts-style lists map to an address that are not meant as entries
+const HOSTS_NAMES: [&str; 6] = [
+    "localhost",
+    "localhost.localdomain",
+    "local",
+    "broadcasthost",
+    "ip6-localhost",
+    "ip6-loopback",
+];
+
+/// Set of names matching themselves and every name below them, kept as a
+/// trie of labels from the root down.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SuffixSet {
+    /// the name ending here is in the set
+    terminal: bool,
+    children: HashMap<Vec<u8>, SuffixSet>,
+    len: usize,
+}
+
+impl SuffixSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// names inserted, those below another one included
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// add `name`, returning whether it was new
+    pub fn insert(&mut self, name: &Name) -> bool {
+        let mut node: &mut SuffixSet = self;
+        for label in name.labels().rev() {
+            node = node.children.entry(label.to_ascii_lowercase()).or_default();
+        }
+        let new: bool = !node.terminal;
+        node.terminal = true;
+        self.len += new as usize;
+        new
+    }
+
+    /// `name` or a name above it is in the set
+    pub fn matches(&self, name: &Name) -> bool {
+        let mut node: &SuffixSet = self;
+        for label in name.labels().rev() {
+            if node.terminal {
+                return true;
+            }
+            node = match node.children.get(&label.to_ascii_lowercase()) {
+                Some(child) => child,
+                None => return false,
+            };
+        }
+        node.terminal
+    }
+}
+
+/// How blocked names are answered.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum BlockAction {
+    /// the name does not exist
+    #[default]
+    NXDOMAIN,
+    /// A and AAAA queries get 0.0.0.0 and ::
+    NULL,
+    /// A and AAAA queries get the addresses of their family, say a server
+    /// explaining why the name is blocked
+    SINKHOLE(Vec<IpAddr>),
+}
+
+impl BlockAction {
+    /// answer `question` for a blocked name
+    pub fn answer(&self, question: &DnsQuestion, response: &mut DnsPacket) {
+        let addrs: Vec<IpAddr> = match self {
+            BlockAction::NXDOMAIN => {
+                response.header.r_code = ResponseCode::NAMEERROR;
+                return;
+            }
+            BlockAction::NULL => vec![
+                IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            ],
+            BlockAction::SINKHOLE(addrs) => addrs.clone(),
+        };
+        for addr in addrs {
+            let r_data: RecordData = match (question.q_type, addr) {
+                (QueryType::A, IpAddr::V4(addr)) => RecordData::IPADDR(addr),
+                (QueryType::AAAA, IpAddr::V6(addr)) => RecordData::AAAA(addr),
+                _ => continue,
+            };
+            response
+                .answers
+                .push(DnsRecord::new(question.label.clone(), BLOCK_TTL, r_data));
+        }
+    }
+}
+
+impl FromStr for BlockAction {
+    type Err = Error;
+
+    /// `nxdomain`, `null` or comma-separated sinkhole addresses
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "nxdomain" => Ok(BlockAction::NXDOMAIN),
+            "null" => Ok(BlockAction::NULL),
+            _ => s
+                .split(',')
+                .map(|addr| {
+                    addr.parse()
+                        .map_err(|_| Error::InvalidRecord(format!("invalid block action {}", s)))
+                })
+                .collect::<Result<Vec<IpAddr>>>()
+                .map(BlockAction::SINKHOLE),
+        }
+    }
+}
+
+impl fmt::Display for BlockAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockAction::NXDOMAIN => f.write_str("nxdomain"),
+            BlockAction::NULL => f.write_str("null"),
+            BlockAction::SINKHOLE(addrs) => {
+                for (i, addr) in addrs.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", addr)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Names filtered out, each blocking the names below it as well, with an
+/// allowlist of exceptions.
+///
+/// Lists are read line by line in any of three formats: plain domains,
+/// hosts files mapping domains to an address such as 0.0.0.0, and adblock
+/// filters `||domain^`, where `@@||domain^` is an exception. Lines that are
+/// none of these, cosmetic adblock filters and URL patterns among them, are
+/// skipped.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Blocklist {
+    blocked: SuffixSet,
+    allowed: SuffixSet,
+    action: BlockAction,
+}
+
+impl Blocklist {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn action(&self) -> &BlockAction {
+        &self.action
+    }
+
+    pub fn set_action(&mut self, action: BlockAction) {
+        self.action = action;
+    }
+
+    pub fn blocked(&self) -> &SuffixSet {
+        &self.blocked
+    }
+
+    pub fn allowed(&self) -> &SuffixSet {
+        &self.allowed
+    }
+
+    /// block `name` and the names below it
+    pub fn block(&mut self, name: &Name) {
+        self.blocked.insert(name);
+    }
+
+    /// exempt `name` and the names below it from blocking
+    pub fn allow(&mut self, name: &Name) {
+        self.allowed.insert(name);
+    }
+
+    pub fn is_blocked(&self, name: &Name) -> bool {
+        self.blocked.matches(name) && !self.allowed.matches(name)
+    }
+
+    /// add the entries of a blocklist, returning how many there were
+    pub fn parse_blocklist(&mut self, text: &str) -> usize {
+        let mut count: usize = 0;
+        for (exception, name) in text.lines().flat_map(parse_line) {
+            match exception {
+                true => self.allow(&name),
+                false => self.block(&name),
+            }
+            count += 1;
+        }
+        count
+    }
+
+    /// add the entries of an allowlist, returning how many there were
+    pub fn parse_allowlist(&mut self, text: &str) -> usize {
+        let mut count: usize = 0;
+        for (_, name) in text.lines().flat_map(parse_line) {
+            self.allow(&name);
+            count += 1;
+        }
+        count
+    }
+
+    pub fn load_blocklist(&mut self, file_name: &str) -> Result<usize> {
+        Ok(self.parse_blocklist(&fs::read_to_string(file_name)?))
+    }
+
+    pub fn load_allowlist(&mut self, file_name: &str) -> Result<usize> {
+        Ok(self.parse_allowlist(&fs::read_to_string(file_name)?))
+    }
+
+    /// answer `question` when its name is blocked, returning whether it is
+    pub fn answer(&self, question: &DnsQuestion, response: &mut DnsPacket) -> bool {
+        if !self.is_blocked(&question.label) {
+            return false;
+        }
+        response.header.aa = true;
+        self.action.answer(question, response);
+        true
+    }
+}
+
+/// the names on a line of a list, with whether they are exceptions
+fn parse_line(line: &str) -> Vec<(bool, Name)> {
+    let line: &str = line.trim();
+    // adblock comments, headers and cosmetic filters
+    if line.starts_with('!')
+        || line.starts_with('[')
+        || ["##", "#@#", "#?#", "#$#"]
+            .iter()
+            .any(|cosmetic| line.contains(cosmetic))
+    {
+        return Vec::new();
+    }
+    let tokens: Vec<&str> = line
+        .split('#')
+        .next()
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect();
+    let (exception, filter): (bool, Option<Name>) = match tokens.as_slice() {
+        [] => return Vec::new(),
+        [first, names @ ..] if first.parse::<IpAddr>().is_ok() => {
+            return names
+                .iter()
+                .filter(|name| !HOSTS_NAMES.contains(name))
+                .filter_map(|name| parse_domain(name))
+                .map(|name| (false, name))
+                .collect()
+        }
+        [filter] => match (filter.strip_prefix("@@||"), filter.strip_prefix("||")) {
+            (Some(filter), _) => (true, parse_filter(filter)),
+            (None, Some(filter)) => (false, parse_filter(filter)),
+            (None, None) => (false, parse_domain(filter)),
+        },
+        _ => return Vec::new(),
+    };
+    filter.map(|name| (exception, name)).into_iter().collect()
+}
+
+/// the domain of `domain^`, optionally followed by `$options`
+fn parse_filter(filter: &str) -> Option<Name> {
+    let (domain, rest) = filter.split_once('^').unwrap_or((filter, ""));
+    match rest.is_empty() || rest.starts_with('$') || rest == "|" {
+        true => parse_domain(domain),
+        false => None,
+    }
+}
+
+/// a host name of letters, digits, hyphens and underscores, not an address
+fn parse_domain(domain: &str) -> Option<Name> {
+    let valid: bool = !domain.is_empty()
+        && domain.parse::<IpAddr>().is_err()
+        && domain
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'));
+    match valid {
+        true => domain.parse().ok(),
+        false => None,
+    }
+}
@@ -0,0 +1,151 @@
+use super::dns::{DnsPacket, DnsQuestion, DnsRecord, QueryType, RecordData, ResponseCode};
+use super::name::Name;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// answers a cache keeps by default
+pub const CACHE_SIZE: usize = 10000;
+
+/// longest an answer is kept, whatever its TTL
+const MAX_TTL: u32 = 86400;
+
+#[derive(Debug)]
+struct Entry {
+    r_code: ResponseCode,
+    answers: Vec<DnsRecord>,
+    authorities: Vec<DnsRecord>,
+    stored: Instant,
+    /// key of the entry in `Entries::by_expiry`
+    expiry: (Instant, u64),
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    by_question: HashMap<(Name, QueryType), Entry>,
+    /// questions by when their answer expires, in the order stored
+    by_expiry: BTreeMap<(Instant, u64), (Name, QueryType)>,
+    stored: u64,
+}
+
+/// Answers of resolved queries, each kept for the lowest TTL in it, RFC
+/// 1035 §7.4, or the negative TTL of the SOA of a denial, RFC 2308 §5.
+///
+/// Only answers and name errors are kept. A full cache makes room by
+/// dropping the answer expiring soonest, which is any already expired.
+#[derive(Debug)]
+pub struct Cache {
+    capacity: usize,
+    entries: Mutex<Entries>,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self::new(CACHE_SIZE)
+    }
+}
+
+impl Cache {
+    /// a cache keeping at most `capacity` answers
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    /// the answer to `question` if one is kept at `now`, its TTLs counted
+    /// down by the time it has been kept
+    pub fn get(&self, question: &DnsQuestion, now: Instant) -> Option<DnsPacket> {
+        let entries: MutexGuard<'_, Entries> = self.lock();
+        let entry: &Entry = entries
+            .by_question
+            .get(&(question.label.clone(), question.q_type))?;
+        if entry.expiry.0 <= now {
+            return None;
+        }
+        let elapsed: u32 = now.duration_since(entry.stored).as_secs() as u32;
+        let age = |records: &[DnsRecord]| -> Vec<DnsRecord> {
+            records
+                .iter()
+                .map(|record| {
+                    let mut record: DnsRecord = record.clone();
+                    record.ttl = record.ttl.saturating_sub(elapsed);
+                    record
+                })
+                .collect()
+        };
+        let mut packet: DnsPacket = DnsPacket::new();
+        packet.header.qr = true;
+        packet.header.r_code = entry.r_code;
+        packet.questions.push(question.clone());
+        packet.answers = age(&entry.answers);
+        packet.authorities = age(&entry.authorities);
+        Some(packet)
+    }
+
+    /// keep `response` to `question`, received at `now`
+    pub fn insert(&self, question: &DnsQuestion, response: &DnsPacket, now: Instant) {
+        if !matches!(
+            response.header.r_code,
+            ResponseCode::NOERROR | ResponseCode::NAMEERROR
+        ) || self.capacity == 0
+        {
+            return;
+        }
+        let ttl: u32 = match response.answers.is_empty() {
+            false => response.answers.iter().map(|record| record.ttl).min(),
+            true => response
+                .authorities
+                .iter()
+                .find_map(|record| match record.r_data {
+                    RecordData::SOA { minimum, .. } => Some(record.ttl.min(minimum)),
+                    _ => None,
+                }),
+        }
+        .unwrap_or_default()
+        .min(MAX_TTL);
+        if ttl == 0 {
+            return;
+        }
+        let key: (Name, QueryType) = (question.label.clone(), question.q_type);
+        let mut entries: MutexGuard<'_, Entries> = self.lock();
+        if let Some(old) = entries.by_question.remove(&key) {
+            entries.by_expiry.remove(&old.expiry);
+        }
+        while entries.by_question.len() >= self.capacity {
+            let Some((_, soonest)) = entries.by_expiry.pop_first() else {
+                break;
+            };
+            entries.by_question.remove(&soonest);
+        }
+        entries.stored += 1;
+        let expiry: (Instant, u64) = (now + Duration::from_secs(ttl as u64), entries.stored);
+        entries.by_expiry.insert(expiry, key.clone());
+        entries.by_question.insert(
+            key,
+            Entry {
+                r_code: response.header.r_code,
+                answers: response.answers.clone(),
+                authorities: response.authorities.clone(),
+                stored: now,
+                expiry,
+            },
+        );
+    }
+
+    /// answers kept, expired ones not dropped yet included
+    pub fn len(&self) -> usize {
+        self.lock().by_question.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Entries> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
@@ -0,0 +1,503 @@
+use super::cookie::{Cookie, BADCOOKIE};
+use super::dns::{
+    DnsPacket, DnsQuestion, DnsRecord, Edns, OpCode, QueryType, RecordData, ResponseCode,
+};
+use super::dnssec::validator::Lookup;
+use super::journal::Diff;
+use super::name::Name;
+use super::parser::{DnsBytePacketBuffer, MAX_MESSAGE_SIZE};
+use super::serial::Serial;
+use super::tsig::{StreamVerifier, TsigKey, BADSIG};
+use super::update::Update;
+use super::zone::Zone;
+use super::{Error, Result};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::cell::RefCell;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream, UdpSocket};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// EDNS payload size advertised in queries, the DNS flag day 2020 default
+pub const UDP_PAYLOAD_SIZE: u16 = 1232;
+
+/// times a NOTIFY is sent before the secondary is given up on
+pub const NOTIFY_ATTEMPTS: usize = 5;
+
+/// Blocking client sending recursive queries to a single server.
+///
+/// Queries go out over UDP and are retried over TCP when the answer comes
+/// back truncated. Every query gets a random id and a socket of its own on
+/// an ephemeral port, and only a response from the server to the same id
+/// and question is taken, RFC 5452. With a TSIG key every query is signed and every answer
+/// has to carry a valid signature.
+///
+/// With case randomization on, the letters of every question name go out
+/// in random case and the response has to echo them exactly, the 0x20 bit
+/// encoding of draft-vixie-dnsext-dns0x20.
+///
+/// Queries with EDNS carry a DNS cookie, RFC 7873, made up for this server
+/// alone. Answers echoing another client cookie are dropped as spoofed.
+#[derive(Debug)]
+pub struct Client {
+    server: SocketAddr,
+    timeout: Duration,
+    tsig: Option<TsigKey>,
+    /// the client cookie and the last server cookie the server sent
+    cookie: RefCell<Option<Cookie>>,
+    /// whether question names are sent in random case
+    randomize_case: bool,
+}
+
+impl Client {
+    pub fn new(server: SocketAddr) -> Self {
+        Self {
+            server,
+            timeout: Duration::from_secs(5),
+            tsig: None,
+            cookie: RefCell::new(Cookie::random().ok()),
+            randomize_case: false,
+        }
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    pub fn set_tsig(&mut self, key: Option<TsigKey>) {
+        self.tsig = key;
+    }
+
+    /// send question names in random case, taking only responses that
+    /// echo it, for servers known to preserve case
+    pub fn set_randomize_case(&mut self, randomize_case: bool) {
+        self.randomize_case = randomize_case;
+    }
+
+    pub fn server(&self) -> SocketAddr {
+        self.server
+    }
+
+    /// the cookie sent with queries, with the server cookie once one came
+    pub fn cookie(&self) -> Option<Cookie> {
+        self.cookie.borrow().clone()
+    }
+
+    /// ask for `q_type` records of `name` with recursion desired
+    ///
+    /// With `dnssec_ok` the query carries the DO bit so signatures come back,
+    /// and the CD bit so the server hands out data it failed to validate
+    /// itself, leaving the verdict to the caller.
+    pub fn query(&self, name: &Name, q_type: QueryType, dnssec_ok: bool) -> Result<DnsPacket> {
+        self.ask(name, q_type, true, dnssec_ok)
+    }
+
+    /// ask an authoritative server for `q_type` records of `name`, without
+    /// recursion, as iterative resolution does
+    pub fn iterate(&self, name: &Name, q_type: QueryType) -> Result<DnsPacket> {
+        self.ask(name, q_type, false, false)
+    }
+
+    fn ask(&self, name: &Name, q_type: QueryType, rd: bool, dnssec_ok: bool) -> Result<DnsPacket> {
+        let mut packet: DnsPacket = DnsPacket::new();
+        packet.header.id = random_id()?;
+        packet.header.rd = rd;
+        packet.header.cd = dnssec_ok;
+        let name: Name = match self.randomize_case {
+            true => name.with_case(&random_bytes(name.wire_len() / 8 + 1)?),
+            false => name.clone(),
+        };
+        packet.questions.push(DnsQuestion::new(name, q_type));
+        packet.edns = Some(Edns {
+            udp_payload_size: UDP_PAYLOAD_SIZE,
+            dnssec_ok,
+            ..Edns::default()
+        });
+        self.exchange(&mut packet)
+    }
+
+    /// send `query` and wait for the response to it
+    ///
+    /// A BADCOOKIE answer has the query sent again, with the server cookie
+    /// it carries, RFC 7873 §5.3.
+    pub fn exchange(&self, query: &mut DnsPacket) -> Result<DnsPacket> {
+        let response: DnsPacket = self.send(query)?;
+        match response.extended_rcode() == BADCOOKIE && query.edns.is_some() {
+            true => self.send(query),
+            false => Ok(response),
+        }
+    }
+
+    fn send(&self, query: &mut DnsPacket) -> Result<DnsPacket> {
+        if let Some(cookie) = &*self.cookie.borrow() {
+            cookie.attach(query);
+        }
+        let now: u64 = unix_now();
+        let request_mac: Option<Vec<u8>> = match &self.tsig {
+            Some(key) => Some(key.sign(query, now, None)?),
+            None => None,
+        };
+        let mut req_buffer: DnsBytePacketBuffer = DnsBytePacketBuffer::new();
+        query.write(&mut req_buffer)?;
+
+        let socket: UdpSocket = UdpSocket::bind(match self.server {
+            SocketAddr::V4(_) => "0.0.0.0:0",
+            SocketAddr::V6(_) => "[::]:0",
+        })?;
+        socket.send_to(req_buffer.written(), self.server)?;
+
+        // datagrams dropped below do not buy the server more time
+        let deadline: Instant = Instant::now() + self.timeout;
+        let timed_out = || {
+            Error::Io(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("{} did not answer", self.server),
+            ))
+        };
+        let mut res_buffer: DnsBytePacketBuffer =
+            DnsBytePacketBuffer::with_size(UDP_PAYLOAD_SIZE as usize);
+        let (response, bytes): (DnsPacket, Vec<u8>) = loop {
+            let remaining: Duration = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(timed_out());
+            }
+            socket.set_read_timeout(Some(remaining))?;
+            let (bytes_read, from) = match socket.recv_from(res_buffer.get_buf()) {
+                Ok(received) => received,
+                Err(error)
+                    if matches!(
+                        error.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    return Err(timed_out())
+                }
+                Err(error) => return Err(error.into()),
+            };
+            res_buffer.set_bytes_read(bytes_read);
+            // answers from elsewhere or to something else may be spoofed and
+            // are dropped, not an error
+            if from == self.server {
+                if let Ok(response) = DnsPacket::from_buffer(&mut res_buffer) {
+                    if check_response(query, &response, self.randomize_case).is_ok()
+                        && self.echoes_cookie(&response)
+                    {
+                        break (response, res_buffer.get_buf_range(0..bytes_read)?.to_vec());
+                    }
+                }
+            }
+            res_buffer = DnsBytePacketBuffer::with_size(UDP_PAYLOAD_SIZE as usize);
+        };
+        let (response, bytes): (DnsPacket, Vec<u8>) = match response.header.tc {
+            false => (response, bytes),
+            true => {
+                let bytes: Vec<u8> = tcp_exchange(self.server, req_buffer.written(), self.timeout)?;
+                let response: DnsPacket =
+                    DnsPacket::from_buffer(&mut DnsBytePacketBuffer::from_bytes(&bytes))?;
+                check_response(query, &response, self.randomize_case)?;
+                (response, bytes)
+            }
+        };
+        if let (Some(key), Some(request_mac)) = (&self.tsig, &request_mac) {
+            key.verify(&bytes, now, Some(request_mac))?;
+        }
+        if let (Some(cookie), Ok(Some(returned))) = (
+            self.cookie.borrow_mut().as_mut(),
+            Cookie::from_packet(&response),
+        ) {
+            if returned.client == cookie.client {
+                cookie.server = returned.server;
+            }
+        }
+        Ok(response)
+    }
+
+    /// whether `response` echoes the client cookie, or carries none as
+    /// servers without cookies answer
+    fn echoes_cookie(&self, response: &DnsPacket) -> bool {
+        match (&*self.cookie.borrow(), Cookie::from_packet(response)) {
+            (_, Err(_)) => false,
+            (Some(cookie), Ok(Some(returned))) => returned.client == cookie.client,
+            _ => true,
+        }
+    }
+
+    /// transfer the zone at `origin` over TCP, RFC 5936
+    ///
+    /// The records have to come bracketed by the same SOA and stay inside
+    /// the zone. With a TSIG key every message up to the last has to be
+    /// covered by a valid signature.
+    pub fn axfr(&self, origin: &Name) -> Result<Zone> {
+        let records: Vec<DnsRecord> = self.transfer(origin, QueryType::AXFR, None)?;
+        full_zone(origin, records)
+    }
+
+    /// bring `zone` up to date with an incremental transfer, RFC 1995
+    ///
+    /// The primary may answer with the whole zone instead, whose journal
+    /// then continues the one of `zone`. The zone is returned unchanged
+    /// when it is current.
+    pub fn ixfr(&self, zone: &Zone) -> Result<Zone> {
+        let soa: &DnsRecord = zone
+            .soa()
+            .ok_or_else(|| Error::InvalidZone(format!("{} has no SOA", zone.origin())))?;
+        let mut records: Vec<DnsRecord> =
+            self.transfer(zone.origin(), QueryType::IXFR, Some(soa.clone()))?;
+        match (records.len(), soa_serial(records.get(1))) {
+            // only the SOA: current, or the primary wants a full transfer
+            (1, _) => match soa_serial(records.first()).map(Serial) <= zone.serial().map(Serial) {
+                true => Ok(zone.clone()),
+                false => self.axfr(zone.origin()),
+            },
+            (_, Some(serial)) if Some(serial) != soa_serial(records.first()) => {
+                records.pop();
+                records.remove(0);
+                let mut updated: Zone = zone.clone();
+                for diff in Diff::from_records(records)? {
+                    updated.apply(diff)?;
+                }
+                Ok(updated)
+            }
+            _ => {
+                let mut updated: Zone = full_zone(zone.origin(), records)?;
+                updated.succeed(zone);
+                Ok(updated)
+            }
+        }
+    }
+
+    /// tell the server that the zone of `soa` changed, RFC 1996
+    ///
+    /// The NOTIFY is sent again when no answer comes within the timeout, up
+    /// to `NOTIFY_ATTEMPTS` times.
+    pub fn notify(&self, soa: &DnsRecord) -> Result<()> {
+        let mut packet: DnsPacket = DnsPacket::new();
+        packet.header.op_code = OpCode::NOTIFY;
+        packet.header.aa = true;
+        packet
+            .questions
+            .push(DnsQuestion::new(soa.label.clone(), QueryType::SOA));
+        packet.answers.push(soa.clone());
+        let mut attempt: usize = 1;
+        let response: DnsPacket = loop {
+            packet.header.id = random_id()?;
+            packet.tsig = None;
+            match self.exchange(&mut packet) {
+                Err(Error::Io(error))
+                    if attempt < NOTIFY_ATTEMPTS
+                        && matches!(
+                            error.kind(),
+                            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                        ) =>
+                {
+                    attempt += 1
+                }
+                result => break result?,
+            }
+        };
+        match (response.header.op_code, response.header.r_code) {
+            (OpCode::NOTIFY, ResponseCode::NOERROR) => Ok(()),
+            (_, r_code) => Err(Error::Transfer(format!(
+                "{} answered NOTIFY for {} with {:?}",
+                self.server, soa.label, r_code
+            ))),
+        }
+    }
+
+    /// ask the server to carry out a dynamic update, RFC 2136
+    ///
+    /// A refusal comes back as `Error::Update` with the RCODE saying why,
+    /// such as NXRRSET for a prerequisite that does not hold.
+    pub fn update(&self, update: &Update) -> Result<()> {
+        let mut packet: DnsPacket = update.to_packet();
+        packet.header.id = random_id()?;
+        let response: DnsPacket = self.exchange(&mut packet)?;
+        match (response.header.op_code, response.header.r_code) {
+            (OpCode::UPDATE, ResponseCode::NOERROR) => Ok(()),
+            (_, r_code) => Err(Error::Update(r_code)),
+        }
+    }
+
+    /// send a transfer request and collect the records of every message up
+    /// to the closing SOA
+    fn transfer(
+        &self,
+        origin: &Name,
+        q_type: QueryType,
+        authority: Option<DnsRecord>,
+    ) -> Result<Vec<DnsRecord>> {
+        let mut query: DnsPacket = DnsPacket::new();
+        query.header.id = random_id()?;
+        query
+            .questions
+            .push(DnsQuestion::new(origin.clone(), q_type));
+        query.authorities.extend(authority);
+        let request_mac: Option<Vec<u8>> = match &self.tsig {
+            Some(key) => Some(key.sign(&mut query, unix_now(), None)?),
+            None => None,
+        };
+        let mut req_buffer: DnsBytePacketBuffer = DnsBytePacketBuffer::new();
+        query.write(&mut req_buffer)?;
+
+        let mut stream: TcpStream = TcpStream::connect_timeout(&self.server, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        write_tcp_message(&mut stream, req_buffer.written())?;
+
+        let mut verifier: Option<StreamVerifier<'_>> = self
+            .tsig
+            .as_ref()
+            .zip(request_mac.as_deref())
+            .map(|(key, request_mac)| StreamVerifier::new(key, request_mac));
+        let mut records: Vec<DnsRecord> = Vec::new();
+        loop {
+            let bytes: Vec<u8> = read_tcp_message(&mut stream)?;
+            let response: DnsPacket =
+                DnsPacket::from_buffer(&mut DnsBytePacketBuffer::from_bytes(&bytes))?;
+            if response.header.id != query.header.id {
+                return Err(Error::IdMismatch {
+                    expected: query.header.id,
+                    received: response.header.id,
+                });
+            }
+            if let Some(verifier) = verifier.as_mut() {
+                verifier.verify(&bytes, unix_now())?;
+            }
+            if response.header.r_code != ResponseCode::NOERROR {
+                return Err(Error::Transfer(format!(
+                    "{} answered {:?}",
+                    self.server, response.header.r_code
+                )));
+            }
+            let single: bool = records.is_empty() && response.answers.len() == 1;
+            records.extend(response.answers);
+            let serial: Option<u32> = soa_serial(records.first());
+            if serial.is_none() {
+                return Err(Error::Transfer("first record is not an SOA".to_string()));
+            }
+            // the SOA closes an AXFR once, an IXFR has it end the last diff
+            // too, RFC 1995 §4
+            let incremental: bool = q_type == QueryType::IXFR
+                && soa_serial(records.get(1)).is_some_and(|second| Some(second) != serial);
+            let closing: usize = records[1..]
+                .iter()
+                .filter(|record| soa_serial(Some(record)) == serial)
+                .count();
+            let done: bool = match incremental {
+                true => closing == 2,
+                false => closing == 1,
+            };
+            if done || (single && q_type == QueryType::IXFR) {
+                break;
+            }
+        }
+        if verifier.is_some_and(|verifier| !verifier.is_complete()) {
+            return Err(Error::Tsig(BADSIG));
+        }
+        Ok(records)
+    }
+}
+
+/// serial of `record` if it is an SOA
+fn soa_serial(record: Option<&DnsRecord>) -> Option<u32> {
+    match record?.r_data {
+        RecordData::SOA { serial, .. } => Some(serial),
+        _ => None,
+    }
+}
+
+/// the zone sent as AXFR, its records bracketed by the SOA
+fn full_zone(origin: &Name, mut records: Vec<DnsRecord>) -> Result<Zone> {
+    let closing: Option<DnsRecord> = records.pop();
+    if records.is_empty() || closing.as_ref() != Some(&records[0]) || records[0].label != *origin {
+        return Err(Error::Transfer(format!(
+            "transfer of {} is not bracketed by its SOA",
+            origin
+        )));
+    }
+    Zone::from_records(origin.clone(), records)
+}
+
+/// a query id from the system CSPRNG
+pub(crate) fn random_id() -> Result<u16> {
+    let id: Vec<u8> = random_bytes(2)?;
+    Ok(u16::from_be_bytes([id[0], id[1]]))
+}
+
+pub(crate) fn random_bytes(len: usize) -> Result<Vec<u8>> {
+    let mut bytes: Vec<u8> = vec![0; len];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| Error::Io(io::Error::other("no randomness for queries")))?;
+    Ok(bytes)
+}
+
+/// whether `response` answers `query`, having its id and question, the
+/// question names in the same case with `exact_case`
+///
+/// Errors such as FORMERR may come without the question.
+pub(crate) fn check_response(
+    query: &DnsPacket,
+    response: &DnsPacket,
+    exact_case: bool,
+) -> Result<()> {
+    if response.header.id != query.header.id {
+        return Err(Error::IdMismatch {
+            expected: query.header.id,
+            received: response.header.id,
+        });
+    }
+    let same_case: bool = !exact_case
+        || response
+            .questions
+            .iter()
+            .zip(&query.questions)
+            .all(|(answered, asked)| answered.label.eq_case(&asked.label));
+    match (response.questions == query.questions && same_case)
+        || (response.questions.is_empty() && response.header.r_code != ResponseCode::NOERROR)
+    {
+        true => Ok(()),
+        false => Err(Error::QuestionMismatch),
+    }
+}
+
+/// seconds since the epoch, as TSIG timestamps count them
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// write a message prefixed with its two byte length, RFC 1035 §4.2.2
+pub fn write_tcp_message(stream: &mut TcpStream, message: &[u8]) -> Result<()> {
+    if message.len() > MAX_MESSAGE_SIZE {
+        return Err(Error::BufferFull(message.len()));
+    }
+    let mut framed: Vec<u8> = Vec::with_capacity(message.len() + 2);
+    framed.extend_from_slice(&(message.len() as u16).to_be_bytes());
+    framed.extend_from_slice(message);
+    stream.write_all(&framed)?;
+    Ok(())
+}
+
+/// read one length prefixed message
+pub fn read_tcp_message(stream: &mut TcpStream) -> Result<Vec<u8>> {
+    let mut len: [u8; 2] = [0; 2];
+    stream.read_exact(&mut len)?;
+    let mut message: Vec<u8> = vec![0; u16::from_be_bytes(len) as usize];
+    stream.read_exact(&mut message)?;
+    Ok(message)
+}
+
+/// send one message over a fresh TCP connection and read the reply
+pub fn tcp_exchange(server: SocketAddr, message: &[u8], timeout: Duration) -> Result<Vec<u8>> {
+    let mut stream: TcpStream = TcpStream::connect_timeout(&server, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    write_tcp_message(&mut stream, message)?;
+    read_tcp_message(&mut stream)
+}
+
+impl Lookup for Client {
+    fn lookup(&self, name: &Name, q_type: QueryType) -> Result<DnsPacket> {
+        self.query(name, q_type, true)
+    }
+}
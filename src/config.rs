@@ -0,0 +1,471 @@
+use super::acl::{Acl, AclEntry};
+use super::blocklist::BlockAction;
+use super::dnssec::signer::Nsec3Config;
+use super::name::Name;
+use super::rrl::RateLimit;
+use super::server::DEFAULT_TCP_CLIENTS;
+use super::tsig::{KeyRing, TsigKey};
+use super::{Error, Result};
+use data_encoding::{BASE64, HEXUPPER_PERMISSIVE};
+use std::ffi::OsString;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+/// address the server listens on when the config does not say
+pub const DEFAULT_LISTEN: &str = "127.0.0.1:5353";
+
+/// one zone served from a master file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZoneConfig {
+    pub origin: Name,
+    pub file: PathBuf,
+    /// private key files, the zone is signed on load when there are any
+    pub keys: Vec<PathBuf>,
+    /// deny with NSEC3 using these parameters instead of NSEC
+    pub nsec3: Option<Nsec3Config>,
+    /// clients that may query the zone, those of `Config::allow_query` when
+    /// not set
+    pub allow_query: Option<Acl>,
+    /// clients that may transfer the zone
+    pub allow_transfer: Acl,
+    /// where a secondary zone is transferred from, the file then caches it
+    pub primary: Option<SocketAddr>,
+    /// TSIG key transfers from the primary are signed with
+    pub transfer_key: Option<Name>,
+    /// secondaries sent a NOTIFY when the serial changes
+    pub notify: Vec<SocketAddr>,
+    /// clients that may NOTIFY a secondary zone, the primary by default
+    pub allow_notify: Acl,
+    /// clients that may change a primary zone with UPDATE
+    pub allow_update: Acl,
+}
+
+impl ZoneConfig {
+    /// where the diffs of the zone are kept, next to the zone file
+    pub fn journal_file(&self) -> PathBuf {
+        let mut file: OsString = self.file.clone().into_os_string();
+        file.push(".jnl");
+        PathBuf::from(file)
+    }
+
+    /// where the zone is saved once UPDATE or signing changed it, next to
+    /// the zone file, which is left as written
+    pub fn signed_file(&self) -> PathBuf {
+        let mut file: OsString = self.file.clone().into_os_string();
+        file.push(".signed");
+        PathBuf::from(file)
+    }
+}
+
+/// clients answered from zones of their own
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ViewConfig {
+    pub name: String,
+    /// client addresses and TSIG keys the view serves, any by default
+    pub match_clients: Acl,
+    /// local addresses queries to the view arrive at, any by default
+    pub match_destinations: Acl,
+    pub zones: Vec<ZoneConfig>,
+    /// policy zones among `zones`, see `Config::response_policy`
+    pub response_policy: Vec<Name>,
+    /// see `Config::forwarders`, the view resolves from the root when empty
+    pub forwarders: Vec<SocketAddr>,
+}
+
+/// Server configuration, one directive per line.
+///
+/// ```text
+/// # comment
+/// listen 127.0.0.1:5353
+/// tcp-clients 100
+/// allow-query 127.0.0.1,10.0.0.0/8,2001:db8::/32
+/// allow-recursion none
+/// forwarders 192.0.2.1,[2001:db8::1]:5353
+/// rate-limit responses-per-second=5 slip=2 ipv4-prefix=24 ipv6-prefix=56
+///     exempt=127.0.0.1,key:transfer.
+/// cookies secret=e5e973e5a6b2a43f48e7dc849e37bfcf require=no
+/// hosts /etc/hosts
+/// static pinned.records
+/// blocklist ads.txt
+/// allowlist allowed.txt
+/// block-action 192.0.2.80,2001:db8::80
+/// response-policy rpz.example.
+/// key transfer. hmac-sha256 c2VjcmV0IHNoYXJlZCBieSBwcmltYXJ5IGFuZCBzZWNvbmRhcnk=
+/// zone example. example.zone key=Kexample.+015+01234.private nsec3=0:-
+///     allow-query=any allow-transfer=192.0.2.0/24,key:transfer.
+///     notify=192.0.2.2:53,[2001:db8::2]:53 allow-update=key:transfer.
+/// zone example.net. example.net.zone primary=192.0.2.1:53 transfer-key=transfer.
+///     allow-notify=192.0.2.1,key:transfer.
+/// zone rpz.example. rpz.example.zone primary=127.0.0.1:5300
+/// view internal match-clients=10.0.0.0/8,key:internal.
+///     match-destinations=192.0.2.53
+/// zone example. internal/example.zone
+/// forwarders 10.0.0.1
+/// ```
+///
+/// Zone options follow the file on the same line, they are wrapped above
+/// for reading only.
+///
+/// Zones, `response-policy` and `forwarders` after a `view` belong to it,
+/// those before the first view serve clients matching no view. Queries go
+/// to the first view they match.
+///
+/// Relative paths are resolved against the directory of the config file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    pub listen: SocketAddr,
+    /// TCP connections served at once, see `Server::set_tcp_clients`
+    pub tcp_clients: usize,
+    pub zones: Vec<ZoneConfig>,
+    /// clients that may query the server, any by default
+    pub allow_query: Acl,
+    /// clients that may ask for recursion, none by default
+    pub allow_recursion: Acl,
+    /// recursive servers queries allowed recursion are forwarded to, port
+    /// 53 unless given, resolved from the root when empty
+    pub forwarders: Vec<SocketAddr>,
+    /// UDP responses are limited when set, see `lib::rrl::RateLimiter`
+    pub rate_limit: Option<RateLimit>,
+    /// secret server cookies are made with, shared by anycast instances,
+    /// a random rotated one when not set
+    pub cookie_secret: Option<[u8; 16]>,
+    /// whether UDP requests lacking a valid server cookie get BADCOOKIE
+    pub require_cookies: bool,
+    /// TSIG keys requests may be signed with
+    pub keyring: KeyRing,
+    /// files in `/etc/hosts` format whose names are answered locally
+    pub hosts: Vec<PathBuf>,
+    /// files of records in master file format answered locally
+    pub static_records: Vec<PathBuf>,
+    /// lists of domains filtered out, see `lib::blocklist::Blocklist`
+    pub blocklists: Vec<PathBuf>,
+    /// lists of domains exempt from the blocklists
+    pub allowlists: Vec<PathBuf>,
+    /// how blocked names are answered, NXDOMAIN by default
+    pub block_action: BlockAction,
+    /// zones of `zones` rewriting answers as response policy zones, the
+    /// first taking precedence, see `lib::rpz::ResponsePolicy`
+    pub response_policy: Vec<Name>,
+    pub views: Vec<ViewConfig>,
+}
+
+impl Config {
+    pub fn load(file_name: &str) -> Result<Self> {
+        let base: &Path = Path::new(file_name).parent().unwrap_or(Path::new(""));
+        Self::parse(&fs::read_to_string(file_name)?, base)
+    }
+
+    pub fn parse(text: &str, base: &Path) -> Result<Self> {
+        let mut config: Config = Config {
+            listen: DEFAULT_LISTEN
+                .parse()
+                .expect("default listen address parses"),
+            tcp_clients: DEFAULT_TCP_CLIENTS,
+            zones: Vec::new(),
+            allow_query: Acl::any(),
+            allow_recursion: Acl::none(),
+            forwarders: Vec::new(),
+            rate_limit: None,
+            cookie_secret: None,
+            require_cookies: false,
+            keyring: KeyRing::new(),
+            hosts: Vec::new(),
+            static_records: Vec::new(),
+            blocklists: Vec::new(),
+            allowlists: Vec::new(),
+            block_action: BlockAction::NXDOMAIN,
+            response_policy: Vec::new(),
+            views: Vec::new(),
+        };
+        for (index, line) in text.lines().enumerate() {
+            let syntax = |reason: String| Error::Syntax {
+                line: index + 1,
+                reason,
+            };
+            let mut tokens = line
+                .split('#')
+                .next()
+                .unwrap_or_default()
+                .split_whitespace();
+            let directive: &str = match tokens.next() {
+                Some(directive) => directive,
+                None => continue,
+            };
+            match directive {
+                "listen" => {
+                    config.listen = tokens
+                        .next()
+                        .and_then(|addr| addr.parse().ok())
+                        .ok_or_else(|| syntax("listen needs an address:port".to_string()))?;
+                }
+                "tcp-clients" => {
+                    config.tcp_clients = tokens
+                        .next()
+                        .and_then(|max| max.parse().ok())
+                        .filter(|max| *max > 0)
+                        .ok_or_else(|| syntax("tcp-clients needs a positive number".to_string()))?;
+                }
+                "allow-query" | "allow-recursion" => {
+                    let acl: Acl = tokens
+                        .next()
+                        .ok_or_else(|| syntax(format!("{} needs an ACL", directive)))?
+                        .parse()?;
+                    match directive {
+                        "allow-query" => config.allow_query = acl,
+                        _ => config.allow_recursion = acl,
+                    }
+                }
+                "rate-limit" => {
+                    let mut limit: RateLimit = RateLimit::default();
+                    for option in tokens.by_ref() {
+                        let number = |value: &str, max: u32| {
+                            value
+                                .parse::<u32>()
+                                .ok()
+                                .filter(|n| *n <= max)
+                                .ok_or_else(|| syntax(format!("invalid rate-limit {}", option)))
+                        };
+                        match option.split_once('=') {
+                            Some(("responses-per-second", n)) => {
+                                limit.responses_per_second = number(n, u32::MAX)?
+                            }
+                            Some(("slip", n)) => limit.slip = number(n, u32::MAX)?,
+                            Some(("ipv4-prefix", n)) => limit.ipv4_prefix = number(n, 32)? as u8,
+                            Some(("ipv6-prefix", n)) => limit.ipv6_prefix = number(n, 128)? as u8,
+                            Some(("exempt", acl)) => limit.exempt = acl.parse()?,
+                            _ => {
+                                return Err(syntax(format!("unknown rate-limit option {}", option)))
+                            }
+                        }
+                    }
+                    if limit.responses_per_second == 0 {
+                        return Err(syntax("responses-per-second must be positive".to_string()));
+                    }
+                    config.rate_limit = Some(limit);
+                }
+                "cookies" => {
+                    for option in tokens.by_ref() {
+                        match option.split_once('=') {
+                            Some(("secret", hex)) => {
+                                config.cookie_secret = Some(
+                                    HEXUPPER_PERMISSIVE
+                                        .decode(hex.as_bytes())
+                                        .ok()
+                                        .and_then(|secret| secret.try_into().ok())
+                                        .ok_or_else(|| {
+                                            syntax("cookie secret needs 32 hex digits".to_string())
+                                        })?,
+                                );
+                            }
+                            Some(("require", "yes")) => config.require_cookies = true,
+                            Some(("require", "no")) => config.require_cookies = false,
+                            _ => return Err(syntax(format!("unknown cookies option {}", option))),
+                        }
+                    }
+                }
+                "hosts" | "static" | "blocklist" | "allowlist" => {
+                    let file: PathBuf = tokens
+                        .next()
+                        .map(|file| base.join(file))
+                        .ok_or_else(|| syntax(format!("{} needs a file", directive)))?;
+                    match directive {
+                        "hosts" => config.hosts.push(file),
+                        "static" => config.static_records.push(file),
+                        "blocklist" => config.blocklists.push(file),
+                        _ => config.allowlists.push(file),
+                    }
+                }
+                "block-action" => {
+                    config.block_action = tokens
+                        .next()
+                        .and_then(|action| action.parse().ok())
+                        .ok_or_else(|| {
+                            syntax("block-action needs nxdomain, null or addresses".to_string())
+                        })?;
+                }
+                "response-policy" => {
+                    let policy: &mut Vec<Name> = match config.views.last_mut() {
+                        Some(view) => &mut view.response_policy,
+                        None => &mut config.response_policy,
+                    };
+                    for origin in tokens.by_ref() {
+                        policy.push(origin.parse()?);
+                    }
+                    if policy.is_empty() {
+                        return Err(syntax("response-policy needs zones".to_string()));
+                    }
+                }
+                "forwarders" => {
+                    let forwarders: &mut Vec<SocketAddr> = match config.views.last_mut() {
+                        Some(view) => &mut view.forwarders,
+                        None => &mut config.forwarders,
+                    };
+                    let addrs: &str = tokens
+                        .next()
+                        .ok_or_else(|| syntax("forwarders needs addresses".to_string()))?;
+                    for addr in addrs.split(',') {
+                        let forwarder: SocketAddr = addr
+                            .parse()
+                            .or_else(|_| addr.parse().map(|ip| SocketAddr::new(ip, 53)))
+                            .map_err(|_| syntax(format!("invalid forwarder {}", addr)))?;
+                        forwarders.push(forwarder);
+                    }
+                }
+                "view" => {
+                    let name: &str = tokens
+                        .next()
+                        .ok_or_else(|| syntax("view needs a name".to_string()))?;
+                    let mut view: ViewConfig = ViewConfig {
+                        name: name.to_string(),
+                        match_clients: Acl::any(),
+                        match_destinations: Acl::any(),
+                        zones: Vec::new(),
+                        response_policy: Vec::new(),
+                        forwarders: Vec::new(),
+                    };
+                    for option in tokens.by_ref() {
+                        match option.split_once('=') {
+                            Some(("match-clients", acl)) => view.match_clients = acl.parse()?,
+                            Some(("match-destinations", acl)) => {
+                                view.match_destinations = acl.parse()?
+                            }
+                            _ => return Err(syntax(format!("unknown view option {}", option))),
+                        }
+                    }
+                    if config.views.iter().any(|other| other.name == view.name) {
+                        return Err(syntax(format!("view {} defined twice", view.name)));
+                    }
+                    config.views.push(view);
+                }
+                "key" => {
+                    let (name, algorithm, secret) =
+                        match (tokens.next(), tokens.next(), tokens.next()) {
+                            (Some(name), Some(algorithm), Some(secret)) => {
+                                (name, algorithm, secret)
+                            }
+                            _ => {
+                                return Err(syntax(
+                                    "key needs a name, an algorithm and a secret".to_string(),
+                                ))
+                            }
+                        };
+                    let secret: Vec<u8> = BASE64
+                        .decode(secret.as_bytes())
+                        .map_err(|_| syntax(format!("invalid secret of key {}", name)))?;
+                    config
+                        .keyring
+                        .insert(TsigKey::new(name.parse()?, algorithm, secret)?);
+                }
+                "zone" => {
+                    let (origin, file) = match (tokens.next(), tokens.next()) {
+                        (Some(origin), Some(file)) => (origin, file),
+                        _ => return Err(syntax("zone needs an origin and a file".to_string())),
+                    };
+                    let mut zone: ZoneConfig = ZoneConfig {
+                        origin: origin.parse()?,
+                        file: base.join(file),
+                        keys: Vec::new(),
+                        nsec3: None,
+                        allow_query: None,
+                        allow_transfer: Acl::none(),
+                        primary: None,
+                        transfer_key: None,
+                        notify: Vec::new(),
+                        allow_notify: Acl::none(),
+                        allow_update: Acl::none(),
+                    };
+                    let mut allow_notify: Option<Acl> = None;
+                    for option in tokens.by_ref() {
+                        match option.split_once('=') {
+                            Some(("key", file)) => zone.keys.push(base.join(file)),
+                            Some(("nsec3", params)) => {
+                                zone.nsec3 =
+                                    Some(parse_nsec3(params).ok_or_else(|| {
+                                        syntax(format!("invalid nsec3 {}", params))
+                                    })?);
+                            }
+                            Some(("allow-query", acl)) => zone.allow_query = Some(acl.parse()?),
+                            Some(("allow-transfer", acl)) => zone.allow_transfer = acl.parse()?,
+                            Some(("primary", addr)) => {
+                                zone.primary =
+                                    Some(addr.parse().map_err(|_| {
+                                        syntax(format!("invalid primary {}", addr))
+                                    })?);
+                            }
+                            Some(("transfer-key", name)) => zone.transfer_key = Some(name.parse()?),
+                            Some(("notify", addrs)) => {
+                                for addr in addrs.split(',') {
+                                    zone.notify.push(
+                                        addr.parse().map_err(|_| {
+                                            syntax(format!("invalid notify {}", addr))
+                                        })?,
+                                    );
+                                }
+                            }
+                            Some(("allow-notify", acl)) => allow_notify = Some(acl.parse()?),
+                            Some(("allow-update", acl)) => zone.allow_update = acl.parse()?,
+                            _ => return Err(syntax(format!("unknown zone option {}", option))),
+                        }
+                    }
+                    zone.allow_notify = match (allow_notify, zone.primary) {
+                        (Some(acl), _) => acl,
+                        (None, Some(primary)) => {
+                            let mut acl: Acl = Acl::none();
+                            acl.push(primary.ip().to_string().parse::<AclEntry>()?);
+                            acl
+                        }
+                        (None, None) => Acl::none(),
+                    };
+                    match config.views.last_mut() {
+                        Some(view) => view.zones.push(zone),
+                        None => config.zones.push(zone),
+                    }
+                }
+                other => return Err(syntax(format!("unknown directive {}", other))),
+            }
+            if let Some(extra) = tokens.next() {
+                return Err(syntax(format!("unexpected {}", extra)));
+            }
+        }
+        let scopes = std::iter::once((&config.zones, &config.response_policy)).chain(
+            config
+                .views
+                .iter()
+                .map(|view| (&view.zones, &view.response_policy)),
+        );
+        for (zones, response_policy) in scopes {
+            for zone in zones {
+                if let Some(name) = &zone.transfer_key {
+                    if config.keyring.get(name).is_none() {
+                        return Err(Error::InvalidKey(format!(
+                            "transfer key {} of {} is not defined",
+                            name, zone.origin
+                        )));
+                    }
+                }
+            }
+            for origin in response_policy {
+                if !zones.iter().any(|zone| zone.origin == *origin) {
+                    return Err(Error::InvalidZone(format!(
+                        "response policy zone {} is not configured",
+                        origin
+                    )));
+                }
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// `<iterations>:<salt as hex or ->`
+fn parse_nsec3(params: &str) -> Option<Nsec3Config> {
+    let (iterations, salt) = params.split_once(':')?;
+    Some(Nsec3Config {
+        iterations: iterations.parse().ok()?,
+        salt: match salt {
+            "-" => Vec::new(),
+            hex => HEXUPPER_PERMISSIVE.decode(hex.as_bytes()).ok()?,
+        },
+    })
+}
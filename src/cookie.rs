@@ -0,0 +1,210 @@
+use super::acl::unmap;
+use super::dns::{DnsPacket, EdnsOption};
+use super::{Error, Result};
+use ring::rand::{SecureRandom, SystemRandom};
+use siphasher::sip::SipHasher24;
+use std::io;
+use std::net::IpAddr;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// EDNS option code of COOKIE, RFC 7873
+pub const COOKIE: u16 = 10;
+
+/// extended RCODE for a request lacking a valid server cookie
+pub const BADCOOKIE: u16 = 23;
+
+/// version of the server cookies made, RFC 9018 §4
+const VERSION: u8 = 1;
+
+/// server cookies older than this many seconds are not accepted
+const MAX_AGE: u32 = 3600;
+
+/// nor those stamped further than this ahead of the clock
+const MAX_SKEW: u32 = 300;
+
+/// a random secret is replaced this often, the one before still verifying
+const ROTATION: u32 = 86400;
+
+/// The COOKIE option, a client cookie and the server cookie if any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    pub client: [u8; 8],
+    /// 8 to 32 octets, empty until the server has handed one out
+    pub server: Vec<u8>,
+}
+
+impl Cookie {
+    /// a fresh random client cookie
+    pub fn random() -> Result<Self> {
+        let mut client: [u8; 8] = [0; 8];
+        fill(&mut client)?;
+        Ok(Self {
+            client,
+            server: Vec::new(),
+        })
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        match data.len() {
+            8 | 16..=40 => Ok(Self {
+                client: data[..8].try_into().expect("eight octets"),
+                server: data[8..].to_vec(),
+            }),
+            len => Err(Error::InvalidCookie(len)),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [&self.client[..], &self.server].concat()
+    }
+
+    /// the COOKIE option of `packet`, if it has one
+    pub fn from_packet(packet: &DnsPacket) -> Result<Option<Self>> {
+        packet
+            .edns
+            .as_ref()
+            .and_then(|edns| edns.option(COOKIE))
+            .map(Self::from_bytes)
+            .transpose()
+    }
+
+    /// add the cookie to `packet` in place of any it has, if it has EDNS
+    pub fn attach(&self, packet: &mut DnsPacket) {
+        if let Some(edns) = &mut packet.edns {
+            edns.options.retain(|option| option.code != COOKIE);
+            edns.options.push(EdnsOption {
+                code: COOKIE,
+                data: self.to_bytes(),
+            });
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Secrets {
+    current: [u8; 16],
+    /// the secret before `current`, still verifying
+    previous: Option<[u8; 16]>,
+    /// when `current` was made, `None` when it was configured
+    since: Option<u32>,
+}
+
+/// Server cookies of RFC 9018, a SipHash-2-4 over the client cookie, a
+/// timestamp and the client address, keyed with a secret.
+///
+/// A random secret is rotated once a day. A configured one is kept, so that
+/// servers sharing an anycast address can share it and accept the cookies
+/// of each other.
+#[derive(Debug)]
+pub struct ServerCookies {
+    secrets: RwLock<Secrets>,
+    /// whether UDP requests without a valid server cookie get BADCOOKIE
+    /// instead of an answer
+    pub required: bool,
+}
+
+impl ServerCookies {
+    /// cookies keyed with `secret`, a random rotated one when `None`
+    pub fn new(secret: Option<[u8; 16]>, now: u32) -> Result<Self> {
+        let (current, since): ([u8; 16], Option<u32>) = match secret {
+            Some(secret) => (secret, None),
+            None => (random_secret()?, Some(now)),
+        };
+        Ok(Self {
+            secrets: RwLock::new(Secrets {
+                current,
+                previous: None,
+                since,
+            }),
+            required: false,
+        })
+    }
+
+    /// replace the secret with a random one, cookies made with the one
+    /// before still verify until the next rotation
+    pub fn rotate(&self, now: u32) -> Result<()> {
+        let secret: [u8; 16] = random_secret()?;
+        let mut secrets: RwLockWriteGuard<Secrets> = self
+            .secrets
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        secrets.previous = Some(secrets.current);
+        secrets.current = secret;
+        secrets.since = Some(now);
+        Ok(())
+    }
+
+    /// a server cookie for `client` at `now`, rotating a random secret
+    /// that is due
+    pub fn make(&self, client_cookie: &[u8; 8], client: IpAddr, now: u32) -> Vec<u8> {
+        let due: bool = self
+            .read_secrets()
+            .since
+            .is_some_and(|since| now.wrapping_sub(since) >= ROTATION);
+        if due {
+            // without randomness the secret stays a while longer
+            let _ = self.rotate(now);
+        }
+        server_cookie(&self.read_secrets().current, client_cookie, client, now)
+    }
+
+    /// whether `cookie` carries a server cookie made for `client` by this
+    /// server, and not too long ago
+    pub fn verify(&self, cookie: &Cookie, client: IpAddr, now: u32) -> bool {
+        if cookie.server.len() != 16 || cookie.server[0] != VERSION {
+            return false;
+        }
+        let stamp: u32 = u32::from_be_bytes(cookie.server[4..8].try_into().expect("four octets"));
+        let age: i64 = now as i64 - stamp as i64;
+        if age > MAX_AGE as i64 || -age > MAX_SKEW as i64 {
+            return false;
+        }
+        let secrets: RwLockReadGuard<Secrets> = self.read_secrets();
+        std::iter::once(&secrets.current)
+            .chain(&secrets.previous)
+            .any(|secret| server_cookie(secret, &cookie.client, client, stamp) == cookie.server)
+    }
+
+    /// add the client cookie of the request and a fresh server cookie to
+    /// `response`
+    pub fn answer(&self, cookie: &Cookie, client: IpAddr, now: u32, response: &mut DnsPacket) {
+        Cookie {
+            client: cookie.client,
+            server: self.make(&cookie.client, client, now),
+        }
+        .attach(response);
+    }
+
+    fn read_secrets(&self) -> RwLockReadGuard<'_, Secrets> {
+        self.secrets
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// version, three reserved octets, the timestamp and the hash, RFC 9018 §4
+fn server_cookie(secret: &[u8; 16], client_cookie: &[u8; 8], client: IpAddr, now: u32) -> Vec<u8> {
+    let mut cookie: Vec<u8> = vec![VERSION, 0, 0, 0];
+    cookie.extend_from_slice(&now.to_be_bytes());
+    let mut input: Vec<u8> = client_cookie.to_vec();
+    input.extend_from_slice(&cookie);
+    match unmap(client) {
+        IpAddr::V4(addr) => input.extend_from_slice(&addr.octets()),
+        IpAddr::V6(addr) => input.extend_from_slice(&addr.octets()),
+    }
+    let hash: u64 = SipHasher24::new_with_key(secret).hash(&input);
+    cookie.extend_from_slice(&hash.to_le_bytes());
+    cookie
+}
+
+fn random_secret() -> Result<[u8; 16]> {
+    let mut secret: [u8; 16] = [0; 16];
+    fill(&mut secret)?;
+    Ok(secret)
+}
+
+fn fill(bytes: &mut [u8]) -> Result<()> {
+    SystemRandom::new()
+        .fill(bytes)
+        .map_err(|_| Error::Io(io::Error::other("no randomness for cookies")))
+}
@@ -1,16 +1,32 @@
+use super::name::Name;
 use super::parser::DnsBytePacketBuffer;
-use super::Result;
-use std::net::Ipv4Addr;
+use super::{Error, Result};
+use data_encoding::{BASE32_DNSSEC, BASE64, HEXUPPER};
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum ResponseCode {
+    #[default]
     NOERROR = 0,
     FORMATERROR = 1,
     SERVERFAILURE = 2,
     NAMEERROR = 3,
     NOTIMPLEMENTED = 4,
     REFUSED = 5,
+    /// 6 a name that should not exist does, RFC 2136
+    YXDOMAIN = 6,
+    /// 7 an RRset that should not exist does
+    YXRRSET = 7,
+    /// 8 an RRset that should exist does not
+    NXRRSET = 8,
+    /// 9 the server is not authoritative or a TSIG failed to verify
+    NOTAUTH = 9,
+    /// 10 a name is outside the zone of an update
+    NOTZONE = 10,
+    /// 11 to 15, not assigned
     FUTURE,
 }
 
@@ -22,22 +38,64 @@ impl From<u8> for ResponseCode {
             3 => Self::NAMEERROR,
             4 => Self::NOTIMPLEMENTED,
             5 => Self::REFUSED,
-            6..=15 => Self::FUTURE,
-            0 | _ => Self::NOERROR,
+            6 => Self::YXDOMAIN,
+            7 => Self::YXRRSET,
+            8 => Self::NXRRSET,
+            9 => Self::NOTAUTH,
+            10 => Self::NOTZONE,
+            11..=15 => Self::FUTURE,
+            _ => Self::NOERROR,
         }
     }
 }
 
-impl Default for ResponseCode {
-    fn default() -> Self {
-        Self::NOERROR
+/// kind of request a message makes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OpCode {
+    /// 0 a standard query
+    #[default]
+    QUERY,
+    /// 1 an inverse query, obsolete since RFC 3425
+    IQUERY,
+    /// 2 a server status request
+    STATUS,
+    /// 4 a zone changed, RFC 1996
+    NOTIFY,
+    /// 5 a dynamic update, RFC 2136
+    UPDATE,
+    UNKNOWN(u8),
+}
+
+impl From<u8> for OpCode {
+    fn from(n: u8) -> Self {
+        match n {
+            0 => Self::QUERY,
+            1 => Self::IQUERY,
+            2 => Self::STATUS,
+            4 => Self::NOTIFY,
+            5 => Self::UPDATE,
+            n => Self::UNKNOWN(n),
+        }
     }
 }
 
-#[derive(Debug, Default)]
+impl From<OpCode> for u8 {
+    fn from(op_code: OpCode) -> Self {
+        match op_code {
+            OpCode::QUERY => 0,
+            OpCode::IQUERY => 1,
+            OpCode::STATUS => 2,
+            OpCode::NOTIFY => 4,
+            OpCode::UPDATE => 5,
+            OpCode::UNKNOWN(n) => n,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
 pub struct DnsRecord {
     /// label sequence
-    pub label: String,
+    pub label: Name,
     /// 2bytes record type
     pub r_type: QueryType,
     /// 2bytes record class always set to 1
@@ -51,15 +109,43 @@ pub struct DnsRecord {
 }
 
 impl DnsRecord {
+    /// record of class IN, type and data length are derived from `r_data`
+    pub fn new(label: Name, ttl: u32, r_data: RecordData) -> Self {
+        Self {
+            label,
+            r_type: r_data.query_type(),
+            r_class: DnsClass::IN,
+            ttl,
+            rd_len: r_data.wire_len() as u16,
+            r_data,
+        }
+    }
+
+    /// length of the record in wire format, names uncompressed
+    pub fn wire_len(&self) -> usize {
+        self.label.wire_len() + 10 + self.r_data.wire_len()
+    }
+
     fn read(dbuf: &mut DnsBytePacketBuffer, entries: usize) -> Result<Vec<DnsRecord>> {
         let mut records: Vec<DnsRecord> = Vec::new();
         for _ in 1..=entries {
-            let query: String = dbuf.read_label()?;
+            let query: Name = dbuf.read_label()?;
             let r_type: QueryType = dbuf.read_u16()?.into();
             let r_class: DnsClass = dbuf.read_u16()?.into();
             let ttl: u32 = dbuf.read_u32()?;
             let rd_len: u16 = dbuf.read_u16()?;
-            let r_data: RecordData = RecordData::from(&r_type, &mut *dbuf)?;
+            // UPDATE leaves the data out to match any, RFC 2136 §2.4, kept
+            // as empty opaque data for types that cannot be empty
+            let matches_any: bool = rd_len == 0
+                && matches!(r_class, DnsClass::ANY | DnsClass::NONE)
+                && !matches!(
+                    r_type,
+                    QueryType::NULL | QueryType::TXT | QueryType::OPT | QueryType::UNKNOWN(_)
+                );
+            let r_data: RecordData = match matches_any {
+                true => RecordData::UNKNOWN(r_type.into(), Vec::new()),
+                false => RecordData::read(r_type, rd_len, &mut *dbuf)?,
+            };
             records.push(DnsRecord {
                 label: query,
                 r_type,
@@ -75,58 +161,910 @@ impl DnsRecord {
         let start_pos = buffer.get_pos();
 
         buffer.write_label(&self.label)?;
-        buffer.write_u16(QueryType::A.into())?;
-        buffer.write_u16(1)?;
+        buffer.write_u16(self.r_type.into())?;
+        buffer.write_u16(self.r_class.into())?;
         buffer.write_u32(self.ttl)?;
-        buffer.write_u16(4)?;
 
-        match &self.r_data {
-            RecordData::IPADDR(addr) => {
-                let octets = addr.octets();
-                buffer.write_u8(octets[0])?;
-                buffer.write_u8(octets[1])?;
-                buffer.write_u8(octets[2])?;
-                buffer.write_u8(octets[3])?;
-            }
-            RecordData::UNKNOWN { .. } => {
-                println!("Skipping record: {:?}", self);
-            }
-        }
+        // the length is only known once the data is written
+        let len_pos: usize = buffer.get_pos();
+        buffer.write_u16(0)?;
+        self.r_data.write(buffer)?;
+        let rd_len: usize = buffer.get_pos() - (len_pos + 2);
+        buffer.set_u16(len_pos, rd_len as u16)?;
 
         Ok(buffer.get_pos() - start_pos)
     }
 }
 
-#[derive(Debug)]
+/// `rd_len` is left out, it differs between compressed and uncompressed data
+impl PartialEq for DnsRecord {
+    fn eq(&self, other: &Self) -> bool {
+        self.label == other.label
+            && self.r_type == other.r_type
+            && self.r_class == other.r_class
+            && self.ttl == other.ttl
+            && self.r_data == other.r_data
+    }
+}
+
+impl Eq for DnsRecord {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum RecordData {
     IPADDR(Ipv4Addr),
-    UNKNOWN(u16),
+    NS(Name),
+    MD(Name),
+    MF(Name),
+    CNAME(Name),
+    SOA {
+        /// name server that was the original source of data for the zone
+        mname: Name,
+        /// mailbox of the person responsible for the zone
+        rname: Name,
+        serial: u32,
+        refresh: u32,
+        retry: u32,
+        expire: u32,
+        /// minimum TTL, negative caching TTL since RFC 2308
+        minimum: u32,
+    },
+    MB(Name),
+    MG(Name),
+    MR(Name),
+    NULL(Vec<u8>),
+    WKS {
+        address: Ipv4Addr,
+        protocol: u8,
+        /// one bit per port, bit 0 of the first octet is port 0
+        bitmap: Vec<u8>,
+    },
+    PTR(Name),
+    HINFO {
+        cpu: Vec<u8>,
+        os: Vec<u8>,
+    },
+    MINFO {
+        rmailbx: Name,
+        emailbx: Name,
+    },
+    MX {
+        preference: u16,
+        exchange: Name,
+    },
+    /// one or more character-strings
+    TXT(Vec<Vec<u8>>),
+    /// IPv6 host address, RFC 3596
+    AAAA(Ipv6Addr),
+    /// location of a service, RFC 2782
+    SRV {
+        priority: u16,
+        /// relative share among targets of the same priority
+        weight: u16,
+        port: u16,
+        target: Name,
+    },
+    DS {
+        /// key tag of the referenced DNSKEY
+        key_tag: u16,
+        algorithm: u8,
+        digest_type: u8,
+        digest: Vec<u8>,
+    },
+    RRSIG {
+        type_covered: QueryType,
+        algorithm: u8,
+        /// labels of the original owner name, wildcard and root not counted
+        labels: u8,
+        original_ttl: u32,
+        /// seconds since the epoch in serial number arithmetic
+        expiration: u32,
+        inception: u32,
+        key_tag: u16,
+        signer_name: Name,
+        signature: Vec<u8>,
+    },
+    /// options of the EDNS(0) pseudo-record
+    OPT(Vec<EdnsOption>),
+    NSEC {
+        next_domain: Name,
+        /// types present at the owner, ascending
+        types: Vec<QueryType>,
+    },
+    DNSKEY {
+        flags: u16,
+        /// always 3
+        protocol: u8,
+        algorithm: u8,
+        public_key: Vec<u8>,
+    },
+    NSEC3 {
+        hash_algorithm: u8,
+        /// bit 0 is the opt-out flag
+        flags: u8,
+        iterations: u16,
+        salt: Vec<u8>,
+        /// unencoded hash of the next owner name in hash order
+        next_hashed: Vec<u8>,
+        /// types present at the original owner, ascending
+        types: Vec<QueryType>,
+    },
+    NSEC3PARAM {
+        hash_algorithm: u8,
+        flags: u8,
+        iterations: u16,
+        salt: Vec<u8>,
+    },
+    /// transaction signature, RFC 8945 §4.2
+    TSIG {
+        algorithm: Name,
+        /// seconds since the epoch, 48 bits on the wire
+        time_signed: u64,
+        /// seconds of clock skew allowed
+        fudge: u16,
+        mac: Vec<u8>,
+        /// id of the message before any forwarder changed it
+        original_id: u16,
+        /// extended RCODE such as BADSIG
+        error: u16,
+        other: Vec<u8>,
+    },
+    /// data of a type this crate does not understand, kept verbatim
+    UNKNOWN(u16, Vec<u8>),
 }
 
 impl Default for RecordData {
     fn default() -> Self {
-        Self::UNKNOWN(0u16)
+        Self::UNKNOWN(0u16, Vec::new())
     }
 }
 
 impl RecordData {
-    fn from(r_type: &QueryType, dbuf: &mut DnsBytePacketBuffer) -> Result<Self> {
-        match *r_type {
+    fn read(r_type: QueryType, rd_len: u16, dbuf: &mut DnsBytePacketBuffer) -> Result<Self> {
+        let start_pos: usize = dbuf.get_pos();
+        let end_pos: usize = start_pos + rd_len as usize;
+        let r_data: RecordData = match r_type {
             QueryType::A => {
                 let ip_addr: Ipv4Addr =
                     Ipv4Addr::new(dbuf.read()?, dbuf.read()?, dbuf.read()?, dbuf.read()?);
-                Ok(Self::IPADDR(ip_addr))
+                Self::IPADDR(ip_addr)
+            }
+            QueryType::NS => Self::NS(dbuf.read_label()?),
+            QueryType::MD => Self::MD(dbuf.read_label()?),
+            QueryType::MF => Self::MF(dbuf.read_label()?),
+            QueryType::CNAME => Self::CNAME(dbuf.read_label()?),
+            QueryType::SOA => Self::SOA {
+                mname: dbuf.read_label()?,
+                rname: dbuf.read_label()?,
+                serial: dbuf.read_u32()?,
+                refresh: dbuf.read_u32()?,
+                retry: dbuf.read_u32()?,
+                expire: dbuf.read_u32()?,
+                minimum: dbuf.read_u32()?,
+            },
+            QueryType::MB => Self::MB(dbuf.read_label()?),
+            QueryType::MG => Self::MG(dbuf.read_label()?),
+            QueryType::MR => Self::MR(dbuf.read_label()?),
+            QueryType::NULL => Self::NULL(dbuf.read_bytes(rd_len as usize)?),
+            QueryType::WKS => {
+                let address: Ipv4Addr =
+                    Ipv4Addr::new(dbuf.read()?, dbuf.read()?, dbuf.read()?, dbuf.read()?);
+                let protocol: u8 = dbuf.read()?;
+                let bitmap: Vec<u8> = dbuf.read_bytes(end_pos.saturating_sub(dbuf.get_pos()))?;
+                Self::WKS {
+                    address,
+                    protocol,
+                    bitmap,
+                }
+            }
+            QueryType::PTR => Self::PTR(dbuf.read_label()?),
+            QueryType::HINFO => Self::HINFO {
+                cpu: dbuf.read_character_string()?,
+                os: dbuf.read_character_string()?,
+            },
+            QueryType::MINFO => Self::MINFO {
+                rmailbx: dbuf.read_label()?,
+                emailbx: dbuf.read_label()?,
+            },
+            QueryType::MX => Self::MX {
+                preference: dbuf.read_u16()?,
+                exchange: dbuf.read_label()?,
+            },
+            QueryType::TXT => {
+                let mut strings: Vec<Vec<u8>> = Vec::new();
+                while dbuf.get_pos() < end_pos {
+                    strings.push(dbuf.read_character_string()?);
+                }
+                Self::TXT(strings)
+            }
+            QueryType::AAAA => {
+                let octets: [u8; 16] = dbuf
+                    .read_bytes(16)?
+                    .try_into()
+                    .expect("read_bytes returns 16 octets");
+                Self::AAAA(Ipv6Addr::from(octets))
+            }
+            QueryType::SRV => Self::SRV {
+                priority: dbuf.read_u16()?,
+                weight: dbuf.read_u16()?,
+                port: dbuf.read_u16()?,
+                target: dbuf.read_label()?,
+            },
+            QueryType::OPT => {
+                let mut options: Vec<EdnsOption> = Vec::new();
+                while dbuf.get_pos() < end_pos {
+                    let code: u16 = dbuf.read_u16()?;
+                    let len: u16 = dbuf.read_u16()?;
+                    options.push(EdnsOption {
+                        code,
+                        data: dbuf.read_bytes(len as usize)?,
+                    });
+                }
+                Self::OPT(options)
+            }
+            QueryType::DS => Self::DS {
+                key_tag: dbuf.read_u16()?,
+                algorithm: dbuf.read()?,
+                digest_type: dbuf.read()?,
+                digest: dbuf.read_bytes(end_pos.saturating_sub(start_pos + 4))?,
+            },
+            QueryType::RRSIG => Self::RRSIG {
+                type_covered: dbuf.read_u16()?.into(),
+                algorithm: dbuf.read()?,
+                labels: dbuf.read()?,
+                original_ttl: dbuf.read_u32()?,
+                expiration: dbuf.read_u32()?,
+                inception: dbuf.read_u32()?,
+                key_tag: dbuf.read_u16()?,
+                signer_name: dbuf.read_label()?,
+                signature: dbuf.read_bytes(end_pos.saturating_sub(dbuf.get_pos()))?,
+            },
+            QueryType::NSEC => Self::NSEC {
+                next_domain: dbuf.read_label()?,
+                types: read_type_bitmap(dbuf, end_pos)?,
+            },
+            QueryType::DNSKEY => Self::DNSKEY {
+                flags: dbuf.read_u16()?,
+                protocol: dbuf.read()?,
+                algorithm: dbuf.read()?,
+                public_key: dbuf.read_bytes(end_pos.saturating_sub(start_pos + 4))?,
+            },
+            QueryType::NSEC3 => {
+                let hash_algorithm: u8 = dbuf.read()?;
+                let flags: u8 = dbuf.read()?;
+                let iterations: u16 = dbuf.read_u16()?;
+                let salt: Vec<u8> = dbuf.read_character_string()?;
+                let next_hashed: Vec<u8> = dbuf.read_character_string()?;
+                Self::NSEC3 {
+                    hash_algorithm,
+                    flags,
+                    iterations,
+                    salt,
+                    next_hashed,
+                    types: read_type_bitmap(dbuf, end_pos)?,
+                }
+            }
+            QueryType::NSEC3PARAM => Self::NSEC3PARAM {
+                hash_algorithm: dbuf.read()?,
+                flags: dbuf.read()?,
+                iterations: dbuf.read_u16()?,
+                salt: dbuf.read_character_string()?,
+            },
+            QueryType::TSIG => {
+                let algorithm: Name = dbuf.read_label()?;
+                let time_signed: u64 = ((dbuf.read_u16()? as u64) << 32) | dbuf.read_u32()? as u64;
+                let fudge: u16 = dbuf.read_u16()?;
+                let mac_len: u16 = dbuf.read_u16()?;
+                let mac: Vec<u8> = dbuf.read_bytes(mac_len as usize)?;
+                let original_id: u16 = dbuf.read_u16()?;
+                let error: u16 = dbuf.read_u16()?;
+                let other_len: u16 = dbuf.read_u16()?;
+                Self::TSIG {
+                    algorithm,
+                    time_signed,
+                    fudge,
+                    mac,
+                    original_id,
+                    error,
+                    other: dbuf.read_bytes(other_len as usize)?,
+                }
+            }
+            // a question type, kept opaque should a record carry it
+            QueryType::IXFR | QueryType::AXFR | QueryType::ANY => {
+                Self::UNKNOWN(r_type.into(), dbuf.read_bytes(rd_len as usize)?)
+            }
+            QueryType::UNKNOWN(x) => Self::UNKNOWN(x, dbuf.read_bytes(rd_len as usize)?),
+        };
+        if dbuf.get_pos() != end_pos {
+            return Err(Error::RecordLength {
+                r_type: r_type.into(),
+                rd_len,
+                read: dbuf.get_pos() - start_pos,
+            });
+        }
+        Ok(r_data)
+    }
+
+    pub fn write(&self, buffer: &mut DnsBytePacketBuffer) -> Result<()> {
+        match self {
+            Self::IPADDR(addr) => buffer.write_bytes(&addr.octets())?,
+            Self::NS(name)
+            | Self::MD(name)
+            | Self::MF(name)
+            | Self::CNAME(name)
+            | Self::MB(name)
+            | Self::MG(name)
+            | Self::MR(name)
+            | Self::PTR(name) => buffer.write_label(name)?,
+            Self::SOA {
+                mname,
+                rname,
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+            } => {
+                buffer.write_label(mname)?;
+                buffer.write_label(rname)?;
+                buffer.write_u32(*serial)?;
+                buffer.write_u32(*refresh)?;
+                buffer.write_u32(*retry)?;
+                buffer.write_u32(*expire)?;
+                buffer.write_u32(*minimum)?;
+            }
+            Self::NULL(data) | Self::UNKNOWN(_, data) => buffer.write_bytes(data)?,
+            Self::WKS {
+                address,
+                protocol,
+                bitmap,
+            } => {
+                buffer.write_bytes(&address.octets())?;
+                buffer.write_u8(*protocol)?;
+                buffer.write_bytes(bitmap)?;
+            }
+            Self::HINFO { cpu, os } => {
+                buffer.write_character_string(cpu)?;
+                buffer.write_character_string(os)?;
+            }
+            Self::MINFO { rmailbx, emailbx } => {
+                buffer.write_label(rmailbx)?;
+                buffer.write_label(emailbx)?;
             }
-            QueryType::UNKNOWN(x) => Ok(Self::UNKNOWN(x)),
-            _ => Ok(Self::UNKNOWN(17)),
+            Self::MX {
+                preference,
+                exchange,
+            } => {
+                buffer.write_u16(*preference)?;
+                buffer.write_label(exchange)?;
+            }
+            Self::TXT(strings) => {
+                for string in strings {
+                    buffer.write_character_string(string)?;
+                }
+            }
+            Self::AAAA(addr) => buffer.write_bytes(&addr.octets())?,
+            Self::SRV {
+                priority,
+                weight,
+                port,
+                target,
+            } => {
+                buffer.write_u16(*priority)?;
+                buffer.write_u16(*weight)?;
+                buffer.write_u16(*port)?;
+                buffer.write_label(target)?;
+            }
+            Self::DS {
+                key_tag,
+                algorithm,
+                digest_type,
+                digest,
+            } => {
+                buffer.write_u16(*key_tag)?;
+                buffer.write_u8(*algorithm)?;
+                buffer.write_u8(*digest_type)?;
+                buffer.write_bytes(digest)?;
+            }
+            Self::RRSIG {
+                type_covered,
+                algorithm,
+                labels,
+                original_ttl,
+                expiration,
+                inception,
+                key_tag,
+                signer_name,
+                signature,
+            } => {
+                buffer.write_u16((*type_covered).into())?;
+                buffer.write_u8(*algorithm)?;
+                buffer.write_u8(*labels)?;
+                buffer.write_u32(*original_ttl)?;
+                buffer.write_u32(*expiration)?;
+                buffer.write_u32(*inception)?;
+                buffer.write_u16(*key_tag)?;
+                buffer.write_label(signer_name)?;
+                buffer.write_bytes(signature)?;
+            }
+            Self::OPT(options) => {
+                for option in options {
+                    buffer.write_u16(option.code)?;
+                    buffer.write_u16(option.data.len() as u16)?;
+                    buffer.write_bytes(&option.data)?;
+                }
+            }
+            Self::NSEC { next_domain, types } => {
+                buffer.write_label(next_domain)?;
+                buffer.write_bytes(&type_bitmap(types))?;
+            }
+            Self::DNSKEY {
+                flags,
+                protocol,
+                algorithm,
+                public_key,
+            } => {
+                buffer.write_u16(*flags)?;
+                buffer.write_u8(*protocol)?;
+                buffer.write_u8(*algorithm)?;
+                buffer.write_bytes(public_key)?;
+            }
+            Self::NSEC3 {
+                hash_algorithm,
+                flags,
+                iterations,
+                salt,
+                next_hashed,
+                types,
+            } => {
+                buffer.write_u8(*hash_algorithm)?;
+                buffer.write_u8(*flags)?;
+                buffer.write_u16(*iterations)?;
+                buffer.write_character_string(salt)?;
+                buffer.write_character_string(next_hashed)?;
+                buffer.write_bytes(&type_bitmap(types))?;
+            }
+            Self::NSEC3PARAM {
+                hash_algorithm,
+                flags,
+                iterations,
+                salt,
+            } => {
+                buffer.write_u8(*hash_algorithm)?;
+                buffer.write_u8(*flags)?;
+                buffer.write_u16(*iterations)?;
+                buffer.write_character_string(salt)?;
+            }
+            Self::TSIG {
+                algorithm,
+                time_signed,
+                fudge,
+                mac,
+                original_id,
+                error,
+                other,
+            } => {
+                buffer.write_label(algorithm)?;
+                buffer.write_u16((*time_signed >> 32) as u16)?;
+                buffer.write_u32(*time_signed as u32)?;
+                buffer.write_u16(*fudge)?;
+                buffer.write_u16(mac.len() as u16)?;
+                buffer.write_bytes(mac)?;
+                buffer.write_u16(*original_id)?;
+                buffer.write_u16(*error)?;
+                buffer.write_u16(other.len() as u16)?;
+                buffer.write_bytes(other)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// data in uncompressed wire format
+    pub fn to_wire(&self) -> Result<Vec<u8>> {
+        let mut buffer: DnsBytePacketBuffer = DnsBytePacketBuffer::with_size(self.wire_len());
+        self.write(&mut buffer)?;
+        Ok(buffer.written().to_vec())
+    }
+
+    /// decode uncompressed wire format data of `r_type`
+    pub fn from_wire(r_type: QueryType, data: &[u8]) -> Result<Self> {
+        if data.len() > u16::MAX as usize {
+            return Err(Error::BufferFull(data.len()));
+        }
+        let mut dbuf: DnsBytePacketBuffer = DnsBytePacketBuffer::from_bytes(data);
+        Self::read(r_type, data.len() as u16, &mut dbuf)
+    }
+
+    /// the record type this data belongs to
+    pub fn query_type(&self) -> QueryType {
+        match self {
+            Self::IPADDR(_) => QueryType::A,
+            Self::NS(_) => QueryType::NS,
+            Self::MD(_) => QueryType::MD,
+            Self::MF(_) => QueryType::MF,
+            Self::CNAME(_) => QueryType::CNAME,
+            Self::SOA { .. } => QueryType::SOA,
+            Self::MB(_) => QueryType::MB,
+            Self::MG(_) => QueryType::MG,
+            Self::MR(_) => QueryType::MR,
+            Self::NULL(_) => QueryType::NULL,
+            Self::WKS { .. } => QueryType::WKS,
+            Self::PTR(_) => QueryType::PTR,
+            Self::HINFO { .. } => QueryType::HINFO,
+            Self::MINFO { .. } => QueryType::MINFO,
+            Self::MX { .. } => QueryType::MX,
+            Self::TXT(_) => QueryType::TXT,
+            Self::AAAA(_) => QueryType::AAAA,
+            Self::SRV { .. } => QueryType::SRV,
+            Self::DS { .. } => QueryType::DS,
+            Self::RRSIG { .. } => QueryType::RRSIG,
+            Self::OPT(_) => QueryType::OPT,
+            Self::NSEC { .. } => QueryType::NSEC,
+            Self::DNSKEY { .. } => QueryType::DNSKEY,
+            Self::NSEC3 { .. } => QueryType::NSEC3,
+            Self::NSEC3PARAM { .. } => QueryType::NSEC3PARAM,
+            Self::TSIG { .. } => QueryType::TSIG,
+            Self::UNKNOWN(x, _) => QueryType::from(*x),
+        }
+    }
+
+    /// length of the data in wire format, names uncompressed
+    pub fn wire_len(&self) -> usize {
+        match self {
+            Self::IPADDR(_) => 4,
+            Self::NS(name)
+            | Self::MD(name)
+            | Self::MF(name)
+            | Self::CNAME(name)
+            | Self::MB(name)
+            | Self::MG(name)
+            | Self::MR(name)
+            | Self::PTR(name) => name.wire_len(),
+            Self::SOA { mname, rname, .. } => mname.wire_len() + rname.wire_len() + 20,
+            Self::NULL(data) | Self::UNKNOWN(_, data) => data.len(),
+            Self::WKS { bitmap, .. } => 5 + bitmap.len(),
+            Self::HINFO { cpu, os } => 2 + cpu.len() + os.len(),
+            Self::MINFO { rmailbx, emailbx } => rmailbx.wire_len() + emailbx.wire_len(),
+            Self::MX { exchange, .. } => 2 + exchange.wire_len(),
+            Self::TXT(strings) => strings.iter().map(|string| 1 + string.len()).sum(),
+            Self::AAAA(_) => 16,
+            Self::SRV { target, .. } => 6 + target.wire_len(),
+            Self::DS { digest, .. } => 4 + digest.len(),
+            Self::RRSIG {
+                signer_name,
+                signature,
+                ..
+            } => 18 + signer_name.wire_len() + signature.len(),
+            Self::OPT(options) => options.iter().map(|option| 4 + option.data.len()).sum(),
+            Self::NSEC { next_domain, types } => next_domain.wire_len() + type_bitmap(types).len(),
+            Self::DNSKEY { public_key, .. } => 4 + public_key.len(),
+            Self::NSEC3 {
+                salt,
+                next_hashed,
+                types,
+                ..
+            } => 6 + salt.len() + next_hashed.len() + type_bitmap(types).len(),
+            Self::NSEC3PARAM { salt, .. } => 5 + salt.len(),
+            Self::TSIG {
+                algorithm,
+                mac,
+                other,
+                ..
+            } => algorithm.wire_len() + 16 + mac.len() + other.len(),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+/// read an NSEC/NSEC3 type bitmap spanning up to `end_pos`
+fn read_type_bitmap(dbuf: &mut DnsBytePacketBuffer, end_pos: usize) -> Result<Vec<QueryType>> {
+    let mut types: Vec<QueryType> = Vec::new();
+    let mut last_window: Option<u8> = None;
+    while dbuf.get_pos() < end_pos {
+        let window: u8 = dbuf.read()?;
+        let len: u8 = dbuf.read()?;
+        if last_window.is_some_and(|last| window <= last) {
+            return Err(Error::InvalidTypeBitmap(format!(
+                "window {} out of order",
+                window
+            )));
+        }
+        if len == 0 || len > 32 {
+            return Err(Error::InvalidTypeBitmap(format!(
+                "window {} has length {}",
+                window, len
+            )));
+        }
+        for (i, byte) in dbuf.read_bytes(len as usize)?.iter().enumerate() {
+            for bit in 0..8 {
+                if byte & (0x80 >> bit) != 0 {
+                    types.push(QueryType::from(
+                        ((window as u16) << 8) | (i * 8 + bit) as u16,
+                    ));
+                }
+            }
+        }
+        last_window = Some(window);
+    }
+    Ok(types)
+}
+
+/// encode types into the windowed bitmap of RFC 4034 §4.1.2
+fn type_bitmap(types: &[QueryType]) -> Vec<u8> {
+    let mut values: Vec<u16> = types.iter().map(|&t| t.into()).collect();
+    values.sort_unstable();
+    values.dedup();
+    let mut bitmap: Vec<u8> = Vec::new();
+    for window in values.chunk_by(|a, b| a >> 8 == b >> 8) {
+        let mut bits: [u8; 32] = [0; 32];
+        for value in window {
+            let low: usize = (value & 0xFF) as usize;
+            bits[low / 8] |= 0x80 >> (low % 8);
+        }
+        let len: usize = (window[window.len() - 1] & 0xFF) as usize / 8 + 1;
+        bitmap.push((window[0] >> 8) as u8);
+        bitmap.push(len as u8);
+        bitmap.extend_from_slice(&bits[..len]);
+    }
+    bitmap
+}
+
+/// quoted character-string with `"` and `\` escaped, unprintable octets as `\DDD`
+fn fmt_character_string(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    f.write_str("\"")?;
+    for &b in bytes {
+        match b {
+            b'"' | b'\\' => write!(f, "\\{}", b as char)?,
+            0x20..=0x7E => write!(f, "{}", b as char)?,
+            _ => write!(f, "\\{:03}", b)?,
+        }
+    }
+    f.write_str("\"")
+}
+
+/// generic RFC 3597 form `\# <length> <hex>`
+fn fmt_generic(f: &mut fmt::Formatter<'_>, data: &[u8]) -> fmt::Result {
+    write!(f, "\\# {}", data.len())?;
+    if !data.is_empty() {
+        write!(f, " {}", HEXUPPER.encode(data))?;
+    }
+    Ok(())
+}
+
+fn fmt_types(f: &mut fmt::Formatter<'_>, types: &[QueryType]) -> fmt::Result {
+    for t in types {
+        write!(f, " {}", t)?;
+    }
+    Ok(())
+}
+
+/// hex salt, `-` when empty
+fn fmt_salt(f: &mut fmt::Formatter<'_>, salt: &[u8]) -> fmt::Result {
+    match salt.is_empty() {
+        true => f.write_str("-"),
+        false => f.write_str(&HEXUPPER.encode(salt)),
+    }
+}
+
+/// seconds since the epoch as the `YYYYMMDDHHmmSS` timestamp used by RRSIG
+fn fmt_timestamp(f: &mut fmt::Formatter<'_>, timestamp: u32) -> fmt::Result {
+    let secs: u32 = timestamp % 86400;
+    // days to civil date, http://howardhinnant.github.io/date_algorithms.html
+    let z: i64 = (timestamp / 86400) as i64 + 719468;
+    let era: i64 = z / 146097;
+    let doe: i64 = z - era * 146097;
+    let yoe: i64 = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy: i64 = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp: i64 = (5 * doy + 2) / 153;
+    let day: i64 = doy - (153 * mp + 2) / 5 + 1;
+    let month: i64 = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year: i64 = yoe + era * 400 + (month <= 2) as i64;
+    write!(
+        f,
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+impl fmt::Display for RecordData {
+    /// zone file presentation of the data
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IPADDR(addr) => write!(f, "{}", addr),
+            Self::NS(name)
+            | Self::MD(name)
+            | Self::MF(name)
+            | Self::CNAME(name)
+            | Self::MB(name)
+            | Self::MG(name)
+            | Self::MR(name)
+            | Self::PTR(name) => write!(f, "{:#}", name),
+            Self::SOA {
+                mname,
+                rname,
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+            } => write!(
+                f,
+                "{:#} {:#} {} {} {} {} {}",
+                mname, rname, serial, refresh, retry, expire, minimum
+            ),
+            Self::NULL(data) | Self::UNKNOWN(_, data) => fmt_generic(f, data),
+            Self::WKS {
+                address,
+                protocol,
+                bitmap,
+            } => {
+                write!(f, "{} {}", address, protocol)?;
+                for (i, byte) in bitmap.iter().enumerate() {
+                    for bit in 0..8 {
+                        if byte & (0x80 >> bit) != 0 {
+                            write!(f, " {}", i * 8 + bit)?;
+                        }
+                    }
+                }
+                Ok(())
+            }
+            Self::HINFO { cpu, os } => {
+                fmt_character_string(f, cpu)?;
+                f.write_str(" ")?;
+                fmt_character_string(f, os)
+            }
+            Self::MINFO { rmailbx, emailbx } => write!(f, "{:#} {:#}", rmailbx, emailbx),
+            Self::MX {
+                preference,
+                exchange,
+            } => write!(f, "{} {:#}", preference, exchange),
+            Self::TXT(strings) => {
+                for (i, string) in strings.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" ")?;
+                    }
+                    fmt_character_string(f, string)?;
+                }
+                Ok(())
+            }
+            Self::AAAA(addr) => write!(f, "{}", addr),
+            Self::SRV {
+                priority,
+                weight,
+                port,
+                target,
+            } => write!(f, "{} {} {} {:#}", priority, weight, port, target),
+            Self::DS {
+                key_tag,
+                algorithm,
+                digest_type,
+                digest,
+            } => write!(
+                f,
+                "{} {} {} {}",
+                key_tag,
+                algorithm,
+                digest_type,
+                HEXUPPER.encode(digest)
+            ),
+            Self::RRSIG {
+                type_covered,
+                algorithm,
+                labels,
+                original_ttl,
+                expiration,
+                inception,
+                key_tag,
+                signer_name,
+                signature,
+            } => {
+                write!(
+                    f,
+                    "{} {} {} {} ",
+                    type_covered, algorithm, labels, original_ttl
+                )?;
+                fmt_timestamp(f, *expiration)?;
+                f.write_str(" ")?;
+                fmt_timestamp(f, *inception)?;
+                write!(
+                    f,
+                    " {} {:#} {}",
+                    key_tag,
+                    signer_name,
+                    BASE64.encode(signature)
+                )
+            }
+            Self::OPT(options) => {
+                for (i, option) in options.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" ")?;
+                    }
+                    write!(f, "{}:{}", option.code, HEXUPPER.encode(&option.data))?;
+                }
+                Ok(())
+            }
+            Self::NSEC { next_domain, types } => {
+                write!(f, "{:#}", next_domain)?;
+                fmt_types(f, types)
+            }
+            Self::DNSKEY {
+                flags,
+                protocol,
+                algorithm,
+                public_key,
+            } => write!(
+                f,
+                "{} {} {} {}",
+                flags,
+                protocol,
+                algorithm,
+                BASE64.encode(public_key)
+            ),
+            Self::NSEC3 {
+                hash_algorithm,
+                flags,
+                iterations,
+                salt,
+                next_hashed,
+                types,
+            } => {
+                write!(f, "{} {} {} ", hash_algorithm, flags, iterations)?;
+                fmt_salt(f, salt)?;
+                write!(f, " {}", BASE32_DNSSEC.encode(next_hashed))?;
+                fmt_types(f, types)
+            }
+            Self::NSEC3PARAM {
+                hash_algorithm,
+                flags,
+                iterations,
+                salt,
+            } => {
+                write!(f, "{} {} {} ", hash_algorithm, flags, iterations)?;
+                fmt_salt(f, salt)
+            }
+            Self::TSIG {
+                algorithm,
+                time_signed,
+                fudge,
+                mac,
+                original_id,
+                error,
+                other,
+            } => {
+                // the layout BIND prints, empty fields are left out after their length
+                write!(f, "{:#} {} {} {}", algorithm, time_signed, fudge, mac.len())?;
+                if !mac.is_empty() {
+                    write!(f, " {}", BASE64.encode(mac))?;
+                }
+                write!(f, " {} {} {}", original_id, error, other.len())?;
+                if !other.is_empty() {
+                    write!(f, " {}", BASE64.encode(other))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for DnsRecord {
+    /// zone file line `owner ttl class type data`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:#}\t{}\t{}\t{}\t{}",
+            self.label, self.ttl, self.r_class, self.r_type, self.r_data
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
 #[repr(u16)]
 pub enum QueryType {
     /// 1 a host address
+    #[default]
     A = 1,
     /// 2 an authoritative name server
     NS = 2,
@@ -160,12 +1098,32 @@ pub enum QueryType {
     /// 16 text strings
     TXT = 16,
     UNKNOWN(u16) = 17,
-}
-
-impl Default for QueryType {
-    fn default() -> Self {
-        Self::A
-    }
+    /// 28 an IPv6 host address
+    AAAA = 28,
+    /// 33 location of a service
+    SRV = 33,
+    /// 41 EDNS(0) pseudo-record
+    OPT = 41,
+    /// 43 delegation signer
+    DS = 43,
+    /// 46 signature over an RRset
+    RRSIG = 46,
+    /// 47 next secure record for authenticated denial
+    NSEC = 47,
+    /// 48 public key of a zone
+    DNSKEY = 48,
+    /// 50 hashed next secure record
+    NSEC3 = 50,
+    /// 51 parameters of the NSEC3 chain of a zone
+    NSEC3PARAM = 51,
+    /// 250 transaction signature
+    TSIG = 250,
+    /// 251 incremental transfer of a zone, only valid in questions
+    IXFR = 251,
+    /// 252 transfer of an entire zone, only valid in questions
+    AXFR = 252,
+    /// 255 every type, in questions and in UPDATE prerequisites and deletes
+    ANY = 255,
 }
 
 impl From<QueryType> for u16 {
@@ -187,6 +1145,19 @@ impl From<QueryType> for u16 {
             QueryType::MINFO => 14,
             QueryType::MX => 15,
             QueryType::TXT => 16,
+            QueryType::AAAA => 28,
+            QueryType::SRV => 33,
+            QueryType::OPT => 41,
+            QueryType::DS => 43,
+            QueryType::RRSIG => 46,
+            QueryType::NSEC => 47,
+            QueryType::DNSKEY => 48,
+            QueryType::NSEC3 => 50,
+            QueryType::NSEC3PARAM => 51,
+            QueryType::TSIG => 250,
+            QueryType::IXFR => 251,
+            QueryType::AXFR => 252,
+            QueryType::ANY => 255,
             QueryType::UNKNOWN(y) => y,
         }
     }
@@ -211,24 +1182,93 @@ impl From<u16> for QueryType {
             14 => QueryType::MINFO,
             15 => QueryType::MX,
             16 => QueryType::TXT,
+            28 => QueryType::AAAA,
+            33 => QueryType::SRV,
+            41 => QueryType::OPT,
+            43 => QueryType::DS,
+            46 => QueryType::RRSIG,
+            47 => QueryType::NSEC,
+            48 => QueryType::DNSKEY,
+            50 => QueryType::NSEC3,
+            51 => QueryType::NSEC3PARAM,
+            250 => QueryType::TSIG,
+            251 => QueryType::IXFR,
+            252 => QueryType::AXFR,
+            255 => QueryType::ANY,
             y => QueryType::UNKNOWN(y),
         }
     }
 }
 
-#[derive(Debug)]
+impl fmt::Display for QueryType {
+    /// mnemonic of the type, `TYPE<n>` as in RFC 3597 for unknown ones
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryType::UNKNOWN(y) => write!(f, "TYPE{}", y),
+            known => write!(f, "{:?}", known),
+        }
+    }
+}
+
+impl FromStr for QueryType {
+    type Err = Error;
+
+    /// mnemonic or `TYPE<n>`, case-insensitive
+    fn from_str(s: &str) -> Result<Self> {
+        let upper: String = s.to_ascii_uppercase();
+        let r_type: QueryType = match upper.as_str() {
+            "A" => QueryType::A,
+            "NS" => QueryType::NS,
+            "MD" => QueryType::MD,
+            "MF" => QueryType::MF,
+            "CNAME" => QueryType::CNAME,
+            "SOA" => QueryType::SOA,
+            "MB" => QueryType::MB,
+            "MG" => QueryType::MG,
+            "MR" => QueryType::MR,
+            "NULL" => QueryType::NULL,
+            "WKS" => QueryType::WKS,
+            "PTR" => QueryType::PTR,
+            "HINFO" => QueryType::HINFO,
+            "MINFO" => QueryType::MINFO,
+            "MX" => QueryType::MX,
+            "TXT" => QueryType::TXT,
+            "AAAA" => QueryType::AAAA,
+            "SRV" => QueryType::SRV,
+            "OPT" => QueryType::OPT,
+            "DS" => QueryType::DS,
+            "RRSIG" => QueryType::RRSIG,
+            "NSEC" => QueryType::NSEC,
+            "DNSKEY" => QueryType::DNSKEY,
+            "NSEC3" => QueryType::NSEC3,
+            "NSEC3PARAM" => QueryType::NSEC3PARAM,
+            "TSIG" => QueryType::TSIG,
+            "IXFR" => QueryType::IXFR,
+            "AXFR" => QueryType::AXFR,
+            "ANY" => QueryType::ANY,
+            _ => match upper.strip_prefix("TYPE").map(str::parse::<u16>) {
+                Some(Ok(value)) => QueryType::from(value),
+                _ => return Err(Error::InvalidRecord(format!("unknown type {}", s))),
+            },
+        };
+        Ok(r_type)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u16)]
 pub enum DnsClass {
+    #[default]
     IN = 1,
     CS = 2,
     CH = 3,
     HS = 4,
-}
-
-impl Default for DnsClass {
-    fn default() -> Self {
-        Self::IN
-    }
+    /// 254 no class, UPDATE uses it to delete single records, RFC 2136
+    NONE = 254,
+    /// 255 matches every class, TSIG records use it
+    ANY = 255,
+    /// any other value, OPT records carry the UDP payload size here
+    UNKNOWN(u16) = 0,
 }
 
 impl From<u16> for DnsClass {
@@ -238,15 +1278,62 @@ impl From<u16> for DnsClass {
             2 => Self::CS,
             3 => Self::CH,
             4 => Self::HS,
-            _ => Self::IN,
+            254 => Self::NONE,
+            255 => Self::ANY,
+            y => Self::UNKNOWN(y),
         }
     }
 }
 
-#[derive(Debug, Default)]
+impl From<DnsClass> for u16 {
+    fn from(class: DnsClass) -> Self {
+        match class {
+            DnsClass::IN => 1,
+            DnsClass::CS => 2,
+            DnsClass::CH => 3,
+            DnsClass::HS => 4,
+            DnsClass::NONE => 254,
+            DnsClass::ANY => 255,
+            DnsClass::UNKNOWN(y) => y,
+        }
+    }
+}
+
+impl fmt::Display for DnsClass {
+    /// mnemonic of the class, `CLASS<n>` as in RFC 3597 for unknown ones
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DnsClass::UNKNOWN(y) => write!(f, "CLASS{}", y),
+            known => write!(f, "{:?}", known),
+        }
+    }
+}
+
+impl FromStr for DnsClass {
+    type Err = Error;
+
+    /// mnemonic or `CLASS<n>`, case-insensitive
+    fn from_str(s: &str) -> Result<Self> {
+        let upper: String = s.to_ascii_uppercase();
+        match upper.as_str() {
+            "IN" => Ok(DnsClass::IN),
+            "CS" => Ok(DnsClass::CS),
+            "CH" => Ok(DnsClass::CH),
+            "HS" => Ok(DnsClass::HS),
+            "NONE" => Ok(DnsClass::NONE),
+            "ANY" => Ok(DnsClass::ANY),
+            _ => match upper.strip_prefix("CLASS").map(str::parse::<u16>) {
+                Some(Ok(value)) => Ok(DnsClass::from(value)),
+                _ => Err(Error::InvalidRecord(format!("unknown class {}", s))),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct DnsQuestion {
     /// label sequence
-    pub label: String,
+    pub label: Name,
     /// 2byte record type
     pub q_type: QueryType,
     /// 2byte class always set to 1
@@ -254,7 +1341,7 @@ pub struct DnsQuestion {
 }
 
 impl DnsQuestion {
-    pub fn new(label: String, q_type: QueryType) -> Self {
+    pub fn new(label: Name, q_type: QueryType) -> Self {
         Self {
             label,
             q_type,
@@ -264,7 +1351,7 @@ impl DnsQuestion {
     fn read(dbuf: &mut DnsBytePacketBuffer, entries: usize) -> Result<Vec<DnsQuestion>> {
         let mut questions: Vec<DnsQuestion> = Vec::new();
         for _ in 1..=entries {
-            let query: String = dbuf.read_label()?;
+            let query: Name = dbuf.read_label()?;
             let q_type: QueryType = dbuf.read_u16()?.into();
             let q_class: DnsClass = dbuf.read_u16()?.into();
             questions.push(DnsQuestion {
@@ -278,13 +1365,13 @@ impl DnsQuestion {
     pub fn write(&self, buffer: &mut DnsBytePacketBuffer) -> Result<()> {
         buffer.write_label(&self.label)?;
         buffer.write_u16(self.q_type.into())?;
-        buffer.write_u16(1)?;
+        buffer.write_u16(self.q_class.into())?;
 
         Ok(())
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct DnsHeader {
     /// 16bits packet identifier
     pub id: u16,
@@ -292,7 +1379,7 @@ pub struct DnsHeader {
     /// 1bit query response (0 if query, 1 if response)
     pub qr: bool,
     /// 4bits operation code
-    pub op_code: u8,
+    pub op_code: OpCode,
     /// 1bit authoritative answer
     pub aa: bool,
     /// 1bit truncated message
@@ -302,8 +1389,12 @@ pub struct DnsHeader {
 
     /// 1bit recursion available
     pub ra: bool,
-    /// 3bits reserved for future use must be 0 in all case
+    /// 1bit reserved for future use must be 0 in all case
     pub z: bool,
+    /// 1bit authentic data, all data in the response was validated
+    pub ad: bool,
+    /// 1bit checking disabled, the resolver should not validate
+    pub cd: bool,
     /// 4bits response code
     pub r_code: ResponseCode,
 
@@ -325,12 +1416,14 @@ impl DnsHeader {
         Self {
             id: 0,
             qr: false,
-            op_code: 0,
+            op_code: OpCode::QUERY,
             aa: false,
             tc: false,
             rd: false,
             ra: false,
             z: false,
+            ad: false,
+            cd: false,
             r_code: ResponseCode::NOERROR,
             qd_count: 0,
             an_count: 0,
@@ -340,23 +1433,26 @@ impl DnsHeader {
     }
 
     pub fn read(dbuf: &mut DnsBytePacketBuffer) -> Result<Self> {
-        let mut header: DnsHeader = DnsHeader::default();
-        header.id = dbuf.read_u16()?;
+        let id: u16 = dbuf.read_u16()?;
         let a: u8 = dbuf.read()?;
         let b: u8 = dbuf.read()?;
-        header.qr = (a >> 7) > 0;
-        header.op_code = (a >> 3) & 0x0F;
-        header.aa = ((a >> 2) & 1) > 0;
-        header.tc = ((a >> 1) & 1) > 0;
-        header.rd = (a & 1) > 0;
-        header.ra = (b >> 7) > 0;
-        header.z = false;
-        header.r_code = (b & 0x0F).into();
-        header.qd_count = dbuf.read_u16()?;
-        header.an_count = dbuf.read_u16()?;
-        header.ns_count = dbuf.read_u16()?;
-        header.ar_count = dbuf.read_u16()?;
-        Ok(header)
+        Ok(DnsHeader {
+            id,
+            qr: (a >> 7) > 0,
+            op_code: ((a >> 3) & 0x0F).into(),
+            aa: ((a >> 2) & 1) > 0,
+            tc: ((a >> 1) & 1) > 0,
+            rd: (a & 1) > 0,
+            ra: (b >> 7) > 0,
+            z: ((b >> 6) & 1) > 0,
+            ad: ((b >> 5) & 1) > 0,
+            cd: ((b >> 4) & 1) > 0,
+            r_code: (b & 0x0F).into(),
+            qd_count: dbuf.read_u16()?,
+            an_count: dbuf.read_u16()?,
+            ns_count: dbuf.read_u16()?,
+            ar_count: dbuf.read_u16()?,
+        })
     }
     pub fn write(&self, buffer: &mut DnsBytePacketBuffer) -> Result<()> {
         buffer.write_u16(self.id)?;
@@ -365,16 +1461,16 @@ impl DnsHeader {
             (self.rd as u8)
                 | ((self.tc as u8) << 1)
                 | ((self.aa as u8) << 2)
-                | (self.op_code << 3)
-                | ((self.qr as u8) << 7) as u8,
+                | ((u8::from(self.op_code) & 0x0F) << 3)
+                | ((self.qr as u8) << 7),
         )?;
 
         buffer.write_u8(
             (self.r_code as u8)
-            // | ((self.checking_disabled as u8) << 4)
-            // | ((self.authed_data as u8) << 5)
-            | ((self.z as u8) << 6)
-            | ((self.ra as u8) << 7),
+                | ((self.cd as u8) << 4)
+                | ((self.ad as u8) << 5)
+                | ((self.z as u8) << 6)
+                | ((self.ra as u8) << 7),
         )?;
 
         buffer.write_u16(self.qd_count)?;
@@ -386,13 +1482,84 @@ impl DnsHeader {
     }
 }
 
-#[derive(Debug, Default)]
+/// EDNS(0) parameters carried by the OPT pseudo-record, RFC 6891
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edns {
+    /// largest UDP payload the sender is able to reassemble
+    pub udp_payload_size: u16,
+    /// upper 8 bits of the 12bit extended response code
+    pub extended_rcode: u8,
+    pub version: u8,
+    /// DNSSEC OK, the sender wants RRSIG and NSEC records
+    pub dnssec_ok: bool,
+    pub options: Vec<EdnsOption>,
+}
+
+impl Default for Edns {
+    fn default() -> Self {
+        Self {
+            udp_payload_size: 1232,
+            extended_rcode: 0,
+            version: 0,
+            dnssec_ok: false,
+            options: Vec::new(),
+        }
+    }
+}
+
+impl Edns {
+    fn from_record(rec: DnsRecord) -> Self {
+        Self {
+            udp_payload_size: rec.r_class.into(),
+            extended_rcode: (rec.ttl >> 24) as u8,
+            version: (rec.ttl >> 16) as u8,
+            dnssec_ok: rec.ttl & 0x8000 != 0,
+            options: match rec.r_data {
+                RecordData::OPT(options) => options,
+                _ => Vec::new(),
+            },
+        }
+    }
+
+    fn to_record(&self) -> DnsRecord {
+        DnsRecord {
+            r_class: DnsClass::from(self.udp_payload_size),
+            ..DnsRecord::new(
+                Name::root(),
+                ((self.extended_rcode as u32) << 24)
+                    | ((self.version as u32) << 16)
+                    | ((self.dnssec_ok as u32) << 15),
+                RecordData::OPT(self.options.clone()),
+            )
+        }
+    }
+
+    /// data of the first option with `code`
+    pub fn option(&self, code: u16) -> Option<&[u8]> {
+        self.options
+            .iter()
+            .find(|option| option.code == code)
+            .map(|option| option.data.as_slice())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EdnsOption {
+    pub code: u16,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct DnsPacket {
     pub header: DnsHeader,
     pub questions: Vec<DnsQuestion>,
     pub answers: Vec<DnsRecord>,
     pub authorities: Vec<DnsRecord>,
     pub additionals: Vec<DnsRecord>,
+    /// the OPT pseudo-record, kept out of `additionals`
+    pub edns: Option<Edns>,
+    /// the TSIG record, always the last record of a message
+    pub tsig: Option<DnsRecord>,
 }
 
 impl DnsPacket {
@@ -403,6 +1570,34 @@ impl DnsPacket {
             answers: Vec::new(),
             authorities: Vec::new(),
             additionals: Vec::new(),
+            edns: None,
+            tsig: None,
+        }
+    }
+
+    /// the 12bit response code, its upper bits kept in the OPT record
+    pub fn extended_rcode(&self) -> u16 {
+        let upper: u16 = self
+            .edns
+            .as_ref()
+            .map_or(0, |edns| edns.extended_rcode as u16);
+        (upper << 4) | self.header.r_code as u16
+    }
+
+    /// set a 12bit response code, adding an OPT record when the code needs
+    /// one
+    pub fn set_extended_rcode(&mut self, code: u16) {
+        self.header.r_code = ResponseCode::from((code & 0xf) as u8);
+        let upper: u8 = (code >> 4) as u8;
+        match (&mut self.edns, upper) {
+            (Some(edns), _) => edns.extended_rcode = upper,
+            (None, 0) => {}
+            (None, _) => {
+                self.edns = Some(Edns {
+                    extended_rcode: upper,
+                    ..Edns::default()
+                })
+            }
         }
     }
 
@@ -410,18 +1605,31 @@ impl DnsPacket {
         let mut packet: DnsPacket = Self::default();
         packet.header = DnsHeader::read(dbuf)?;
         packet.questions = DnsQuestion::read(dbuf, packet.header.qd_count as usize)?;
-        if packet.header.qr && packet.header.an_count > 0 {
-            packet.answers = DnsRecord::read(dbuf, packet.header.an_count as usize)?;
+        packet.answers = DnsRecord::read(dbuf, packet.header.an_count as usize)?;
+        packet.authorities = DnsRecord::read(dbuf, packet.header.ns_count as usize)?;
+        packet.additionals = DnsRecord::read(dbuf, packet.header.ar_count as usize)?;
+        if packet
+            .additionals
+            .last()
+            .is_some_and(|rec| rec.r_type == QueryType::TSIG)
+        {
+            packet.tsig = packet.additionals.pop();
         }
-        if packet.header.ns_count > 0 {
-            packet.answers = DnsRecord::read(dbuf, packet.header.ns_count as usize)?;
-        }
-        if packet.header.ar_count > 0 {
-            packet.answers = DnsRecord::read(dbuf, packet.header.ar_count as usize)?;
+        if let Some(i) = packet
+            .additionals
+            .iter()
+            .position(|rec| rec.r_type == QueryType::OPT)
+        {
+            packet.edns = Some(Edns::from_record(packet.additionals.remove(i)));
         }
         Ok(packet)
     }
 
+    /// whether the sender asked for DNSSEC records
+    pub fn dnssec_ok(&self) -> bool {
+        self.edns.as_ref().is_some_and(|edns| edns.dnssec_ok)
+    }
+
     pub fn read(file_name: &str) -> Result<Self> {
         let mut dbuf: DnsBytePacketBuffer = DnsBytePacketBuffer::load(file_name)?;
         Self::from_buffer(&mut dbuf)
@@ -430,7 +1638,9 @@ impl DnsPacket {
         self.header.qd_count = self.questions.len() as u16;
         self.header.an_count = self.answers.len() as u16;
         self.header.ns_count = self.authorities.len() as u16;
-        self.header.ar_count = self.additionals.len() as u16;
+        self.header.ar_count = (self.additionals.len()
+            + self.edns.is_some() as usize
+            + self.tsig.is_some() as usize) as u16;
 
         self.header.write(buffer)?;
 
@@ -446,6 +1656,12 @@ impl DnsPacket {
         for rec in &self.additionals {
             rec.write(buffer)?;
         }
+        if let Some(edns) = &self.edns {
+            edns.to_record().write(buffer)?;
+        }
+        if let Some(tsig) = &self.tsig {
+            tsig.write(buffer)?;
+        }
 
         Ok(())
     }
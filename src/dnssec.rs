@@ -0,0 +1,369 @@
+use super::dns::{DnsClass, DnsRecord, QueryType, RecordData};
+use super::name::Name;
+use super::Result;
+use ring::digest;
+use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+use std::cmp::Ordering;
+
+pub mod anchor;
+pub mod signer;
+pub mod validator;
+
+/// RSA/SHA-256, RFC 5702
+pub const ALG_RSASHA256: u8 = 8;
+/// ECDSA P-256 with SHA-256, RFC 6605
+pub const ALG_ECDSAP256SHA256: u8 = 13;
+/// ECDSA P-384 with SHA-384, RFC 6605
+pub const ALG_ECDSAP384SHA384: u8 = 14;
+/// Ed25519, RFC 8080
+pub const ALG_ED25519: u8 = 15;
+
+/// DS digest types
+pub const DIGEST_SHA1: u8 = 1;
+pub const DIGEST_SHA256: u8 = 2;
+pub const DIGEST_SHA384: u8 = 4;
+
+/// DNSKEY flag marking a zone key
+pub const FLAG_ZONE: u16 = 0x0100;
+/// DNSKEY flag marking a revoked key, RFC 5011
+pub const FLAG_REVOKE: u16 = 0x0080;
+/// DNSKEY flag marking a key signing key
+pub const FLAG_SEP: u16 = 0x0001;
+/// the only valid DNSKEY protocol value
+pub const PROTOCOL: u8 = 3;
+
+/// NSEC3 hash algorithm SHA-1, the only one defined
+pub const NSEC3_SHA1: u8 = 1;
+/// NSEC3 flag marking an opt-out span
+pub const NSEC3_OPT_OUT: u8 = 0x01;
+
+pub fn is_supported_algorithm(algorithm: u8) -> bool {
+    matches!(
+        algorithm,
+        ALG_RSASHA256 | ALG_ECDSAP256SHA256 | ALG_ECDSAP384SHA384 | ALG_ED25519
+    )
+}
+
+pub fn is_supported_digest(digest_type: u8) -> bool {
+    matches!(digest_type, DIGEST_SHA1 | DIGEST_SHA256 | DIGEST_SHA384)
+}
+
+/// key tag of a DNSKEY as in RFC 4034 Appendix B
+pub fn key_tag(dnskey: &RecordData) -> Result<u16> {
+    let rdata: Vec<u8> = dnskey.to_wire()?;
+    let mut ac: u32 = 0;
+    for (i, byte) in rdata.iter().enumerate() {
+        ac += match i & 1 {
+            0 => (*byte as u32) << 8,
+            _ => *byte as u32,
+        };
+    }
+    ac += (ac >> 16) & 0xFFFF;
+    Ok((ac & 0xFFFF) as u16)
+}
+
+/// digest of a DNSKEY owned by `owner` for its DS record, RFC 4034 §5.1.4
+///
+/// `None` for digest types that are not supported.
+pub fn ds_digest(owner: &Name, dnskey: &RecordData, digest_type: u8) -> Result<Option<Vec<u8>>> {
+    let algorithm: &digest::Algorithm = match digest_type {
+        DIGEST_SHA1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+        DIGEST_SHA256 => &digest::SHA256,
+        DIGEST_SHA384 => &digest::SHA384,
+        _ => return Ok(None),
+    };
+    let mut ctx: digest::Context = digest::Context::new(algorithm);
+    ctx.update(&owner.to_lowercase().to_wire());
+    ctx.update(&dnskey.to_wire()?);
+    Ok(Some(ctx.finish().as_ref().to_vec()))
+}
+
+/// DS record for `dnskey`, to be published in the parent zone
+pub fn ds_record(
+    owner: &Name,
+    ttl: u32,
+    dnskey: &RecordData,
+    digest_type: u8,
+) -> Result<Option<DnsRecord>> {
+    let (algorithm, digest) = match (dnskey, ds_digest(owner, dnskey, digest_type)?) {
+        (RecordData::DNSKEY { algorithm, .. }, Some(digest)) => (*algorithm, digest),
+        _ => return Ok(None),
+    };
+    let r_data: RecordData = RecordData::DS {
+        key_tag: key_tag(dnskey)?,
+        algorithm,
+        digest_type,
+        digest,
+    };
+    Ok(Some(DnsRecord::new(owner.clone(), ttl, r_data)))
+}
+
+/// record data with embedded names lowercased, RFC 4034 §6.2 as amended by
+/// RFC 6840 §5.1
+pub fn canonical(r_data: &RecordData) -> RecordData {
+    match r_data {
+        RecordData::NS(name) => RecordData::NS(name.to_lowercase()),
+        RecordData::MD(name) => RecordData::MD(name.to_lowercase()),
+        RecordData::MF(name) => RecordData::MF(name.to_lowercase()),
+        RecordData::CNAME(name) => RecordData::CNAME(name.to_lowercase()),
+        RecordData::MB(name) => RecordData::MB(name.to_lowercase()),
+        RecordData::MG(name) => RecordData::MG(name.to_lowercase()),
+        RecordData::MR(name) => RecordData::MR(name.to_lowercase()),
+        RecordData::PTR(name) => RecordData::PTR(name.to_lowercase()),
+        RecordData::SOA {
+            mname,
+            rname,
+            serial,
+            refresh,
+            retry,
+            expire,
+            minimum,
+        } => RecordData::SOA {
+            mname: mname.to_lowercase(),
+            rname: rname.to_lowercase(),
+            serial: *serial,
+            refresh: *refresh,
+            retry: *retry,
+            expire: *expire,
+            minimum: *minimum,
+        },
+        RecordData::MINFO { rmailbx, emailbx } => RecordData::MINFO {
+            rmailbx: rmailbx.to_lowercase(),
+            emailbx: emailbx.to_lowercase(),
+        },
+        RecordData::MX {
+            preference,
+            exchange,
+        } => RecordData::MX {
+            preference: *preference,
+            exchange: exchange.to_lowercase(),
+        },
+        RecordData::SRV {
+            priority,
+            weight,
+            port,
+            target,
+        } => RecordData::SRV {
+            priority: *priority,
+            weight: *weight,
+            port: *port,
+            target: target.to_lowercase(),
+        },
+        RecordData::RRSIG {
+            type_covered,
+            algorithm,
+            labels,
+            original_ttl,
+            expiration,
+            inception,
+            key_tag,
+            signer_name,
+            signature,
+        } => RecordData::RRSIG {
+            type_covered: *type_covered,
+            algorithm: *algorithm,
+            labels: *labels,
+            original_ttl: *original_ttl,
+            expiration: *expiration,
+            inception: *inception,
+            key_tag: *key_tag,
+            signer_name: signer_name.to_lowercase(),
+            signature: signature.clone(),
+        },
+        other => other.clone(),
+    }
+}
+
+/// number of labels of `owner` as counted by the RRSIG labels field,
+/// a leading wildcard label is not counted
+pub fn rrsig_labels(owner: &Name) -> u8 {
+    match owner.labels().next() {
+        Some(b"*") => (owner.num_labels() - 1) as u8,
+        _ => owner.num_labels() as u8,
+    }
+}
+
+/// the data an RRSIG signs, RFC 4034 §3.1.8.1
+///
+/// `rrsig` carries every field but the signature, which is ignored.
+/// `rrset` holds records of the same owner, class and type; they are
+/// canonically ordered and duplicates are dropped.
+pub fn signed_data(rrsig: &RecordData, rrset: &[&DnsRecord]) -> Result<Vec<u8>> {
+    let (
+        type_covered,
+        algorithm,
+        labels,
+        original_ttl,
+        expiration,
+        inception,
+        key_tag,
+        signer_name,
+    ) = match rrsig {
+        RecordData::RRSIG {
+            type_covered,
+            algorithm,
+            labels,
+            original_ttl,
+            expiration,
+            inception,
+            key_tag,
+            signer_name,
+            ..
+        } => (
+            *type_covered,
+            *algorithm,
+            *labels,
+            *original_ttl,
+            *expiration,
+            *inception,
+            *key_tag,
+            signer_name,
+        ),
+        _ => return Ok(Vec::new()),
+    };
+    let mut data: Vec<u8> = Vec::new();
+    data.extend_from_slice(&u16::from(type_covered).to_be_bytes());
+    data.push(algorithm);
+    data.push(labels);
+    data.extend_from_slice(&original_ttl.to_be_bytes());
+    data.extend_from_slice(&expiration.to_be_bytes());
+    data.extend_from_slice(&inception.to_be_bytes());
+    data.extend_from_slice(&key_tag.to_be_bytes());
+    data.extend_from_slice(&signer_name.to_lowercase().to_wire());
+
+    let mut rdatas: Vec<Vec<u8>> = Vec::new();
+    for record in rrset {
+        rdatas.push(canonical(&record.r_data).to_wire()?);
+    }
+    rdatas.sort();
+    rdatas.dedup();
+
+    let (owner, class): (Name, DnsClass) = match rrset.first() {
+        Some(record) => (record.label.to_lowercase(), record.r_class),
+        None => return Ok(data),
+    };
+    // a wildcard expansion is signed as the wildcard owner itself
+    let owner: Name = match (labels as usize) < rrsig_labels(&owner) as usize {
+        true => owner.trim_to(labels as usize).child(b"*")?,
+        false => owner,
+    };
+    let owner_wire: Vec<u8> = owner.to_wire();
+    for rdata in rdatas {
+        data.extend_from_slice(&owner_wire);
+        data.extend_from_slice(&u16::from(type_covered).to_be_bytes());
+        data.extend_from_slice(&u16::from(class).to_be_bytes());
+        data.extend_from_slice(&original_ttl.to_be_bytes());
+        data.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        data.extend_from_slice(&rdata);
+    }
+    Ok(data)
+}
+
+/// check `sig` over `message` with a DNSKEY public key
+///
+/// Unsupported algorithms and malformed keys never verify.
+pub fn verify(algorithm: u8, public_key: &[u8], message: &[u8], sig: &[u8]) -> bool {
+    match algorithm {
+        ALG_RSASHA256 => {
+            // RFC 3110 §2: exponent length, exponent, modulus
+            let (exp_len, rest): (usize, &[u8]) = match public_key {
+                [0, hi, lo, rest @ ..] => (((*hi as usize) << 8) | *lo as usize, rest),
+                [len, rest @ ..] => (*len as usize, rest),
+                [] => return false,
+            };
+            if exp_len == 0 || rest.len() <= exp_len {
+                return false;
+            }
+            let (e, n) = rest.split_at(exp_len);
+            RsaPublicKeyComponents { n, e }
+                .verify(
+                    &signature::RSA_PKCS1_1024_8192_SHA256_FOR_LEGACY_USE_ONLY,
+                    message,
+                    sig,
+                )
+                .is_ok()
+        }
+        ALG_ECDSAP256SHA256 | ALG_ECDSAP384SHA384 => {
+            // DNSKEY holds the bare point, ring wants it uncompressed-tagged
+            let mut point: Vec<u8> = vec![0x04];
+            point.extend_from_slice(public_key);
+            let alg: &signature::EcdsaVerificationAlgorithm = match algorithm {
+                ALG_ECDSAP256SHA256 => &signature::ECDSA_P256_SHA256_FIXED,
+                _ => &signature::ECDSA_P384_SHA384_FIXED,
+            };
+            UnparsedPublicKey::new(alg, point)
+                .verify(message, sig)
+                .is_ok()
+        }
+        ALG_ED25519 => UnparsedPublicKey::new(&signature::ED25519, public_key)
+            .verify(message, sig)
+            .is_ok(),
+        _ => false,
+    }
+}
+
+/// iterated SHA-1 hash of a name for NSEC3, RFC 5155 §5
+pub fn nsec3_hash(name: &Name, iterations: u16, salt: &[u8]) -> Vec<u8> {
+    let mut ctx: digest::Context = digest::Context::new(&digest::SHA1_FOR_LEGACY_USE_ONLY);
+    ctx.update(&name.to_lowercase().to_wire());
+    ctx.update(salt);
+    let mut hash: digest::Digest = ctx.finish();
+    for _ in 0..iterations {
+        let mut ctx: digest::Context = digest::Context::new(&digest::SHA1_FOR_LEGACY_USE_ONLY);
+        ctx.update(hash.as_ref());
+        ctx.update(salt);
+        hash = ctx.finish();
+    }
+    hash.as_ref().to_vec()
+}
+
+/// owner label of the NSEC3 record for a hash
+pub fn nsec3_label(hash: &[u8]) -> Vec<u8> {
+    data_encoding::BASE32HEX_NOPAD
+        .encode(hash)
+        .to_ascii_lowercase()
+        .into_bytes()
+}
+
+/// hash encoded in the first label of an NSEC3 owner
+pub fn nsec3_owner_hash(owner: &Name) -> Option<Vec<u8>> {
+    let label: &[u8] = owner.labels().next()?;
+    data_encoding::BASE32HEX_NOPAD
+        .decode(&label.to_ascii_uppercase())
+        .ok()
+}
+
+/// true if `value` lies strictly between `owner` and `next` of a chain,
+/// the last link wraps around to the start
+pub fn covers<T: Ord + ?Sized>(owner: &T, next: &T, value: &T) -> bool {
+    match owner.cmp(next) {
+        Ordering::Less => owner < value && value < next,
+        // the only link of a chain covers everything but its owner
+        Ordering::Equal => value != owner,
+        Ordering::Greater => owner < value || value < next,
+    }
+}
+
+/// `a` is at or before `b` in serial number arithmetic, RFC 1982
+pub fn serial_le(a: u32, b: u32) -> bool {
+    a == b || (b.wrapping_sub(a) as i32) > 0
+}
+
+/// records of `section` forming the RRset of `owner` and `r_type`
+pub fn rrset<'a>(section: &'a [DnsRecord], owner: &Name, r_type: QueryType) -> Vec<&'a DnsRecord> {
+    section
+        .iter()
+        .filter(|record| record.r_type == r_type && &record.label == owner)
+        .collect()
+}
+
+/// RRSIGs of `section` covering the RRset of `owner` and `r_type`
+pub fn rrsigs<'a>(section: &'a [DnsRecord], owner: &Name, r_type: QueryType) -> Vec<&'a DnsRecord> {
+    section
+        .iter()
+        .filter(|record| {
+            &record.label == owner
+                && matches!(record.r_data, RecordData::RRSIG { type_covered, .. } if type_covered == r_type)
+        })
+        .collect()
+}
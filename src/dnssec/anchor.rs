@@ -0,0 +1,124 @@
+use crate::dns::{DnsRecord, QueryType, RecordData};
+use crate::name::Name;
+use crate::{Error, Result};
+use std::fs;
+
+/// root zone KSK-2017 and KSK-2024 as published by IANA
+const ROOT_ANCHORS: &str = "
+. 172800 IN DS 20326 8 2 E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D
+. 172800 IN DS 38696 8 2 683D2D0ACB8C9B712A1948B27F741219298D0A450D612C483AF444A4C0FB2B16
+";
+
+/// DS or DNSKEY records validation chains start from
+#[derive(Debug, Clone, Default)]
+pub struct TrustAnchors {
+    anchors: Vec<DnsRecord>,
+}
+
+impl TrustAnchors {
+    /// no anchors at all, every answer validates as insecure
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the IANA root anchors
+    pub fn root() -> Self {
+        Self::parse(ROOT_ANCHORS).expect("built-in root anchors parse")
+    }
+
+    /// anchors read from a file, see [`TrustAnchors::parse`]
+    pub fn load(file_name: &str) -> Result<Self> {
+        Self::parse(&fs::read_to_string(file_name)?)
+    }
+
+    /// anchors in presentation format, one DS or DNSKEY record per line
+    ///
+    /// Lines look like `owner [ttl] [class] DS|DNSKEY rdata`, everything
+    /// after a `;` is a comment.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut anchors: TrustAnchors = Self::new();
+        for (index, line) in text.lines().enumerate() {
+            let line: &str = line.split(';').next().unwrap_or_default();
+            if line.trim().is_empty() {
+                continue;
+            }
+            let syntax = |reason: &str| Error::Syntax {
+                line: index + 1,
+                reason: reason.to_string(),
+            };
+            let mut tokens = line.split_whitespace();
+            let owner: Name = tokens.next().unwrap_or_default().parse()?;
+            let mut ttl: u32 = 0;
+            let r_type: &str = loop {
+                match tokens.next() {
+                    Some(token) if token.eq_ignore_ascii_case("IN") => {}
+                    Some(token) if token.bytes().all(|b| b.is_ascii_digit()) => {
+                        ttl = token.parse().map_err(|_| syntax("invalid ttl"))?;
+                    }
+                    Some(token) => break token,
+                    None => return Err(syntax("missing record type")),
+                }
+            };
+            let fields: Vec<&str> = tokens.collect();
+            let number = |i: usize| -> Result<u16> {
+                fields
+                    .get(i)
+                    .and_then(|field| field.parse().ok())
+                    .ok_or_else(|| syntax("invalid number"))
+            };
+            let r_data: RecordData = match r_type.to_ascii_uppercase().as_str() {
+                "DS" => RecordData::DS {
+                    key_tag: number(0)?,
+                    algorithm: number(1)? as u8,
+                    digest_type: number(2)? as u8,
+                    digest: data_encoding::HEXUPPER_PERMISSIVE
+                        .decode(fields.get(3..).unwrap_or_default().concat().as_bytes())
+                        .map_err(|_| syntax("invalid digest"))?,
+                },
+                "DNSKEY" => RecordData::DNSKEY {
+                    flags: number(0)?,
+                    protocol: number(1)? as u8,
+                    algorithm: number(2)? as u8,
+                    public_key: data_encoding::BASE64
+                        .decode(fields.get(3..).unwrap_or_default().concat().as_bytes())
+                        .map_err(|_| syntax("invalid public key"))?,
+                },
+                _ => return Err(syntax("trust anchors must be DS or DNSKEY records")),
+            };
+            anchors.add(DnsRecord::new(owner, ttl, r_data));
+        }
+        Ok(anchors)
+    }
+
+    pub fn add(&mut self, anchor: DnsRecord) {
+        self.anchors.push(anchor);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchors.is_empty()
+    }
+
+    /// anchors owned by `zone`
+    pub fn for_zone(&self, zone: &Name) -> Vec<&DnsRecord> {
+        self.anchors
+            .iter()
+            .filter(|anchor| &anchor.label == zone)
+            .collect()
+    }
+
+    /// DS anchors owned by `zone`
+    pub fn ds(&self, zone: &Name) -> Vec<&DnsRecord> {
+        self.for_zone(zone)
+            .into_iter()
+            .filter(|anchor| anchor.r_type == QueryType::DS)
+            .collect()
+    }
+
+    /// DNSKEY anchors owned by `zone`
+    pub fn dnskeys(&self, zone: &Name) -> Vec<&DnsRecord> {
+        self.for_zone(zone)
+            .into_iter()
+            .filter(|anchor| anchor.r_type == QueryType::DNSKEY)
+            .collect()
+    }
+}
@@ -0,0 +1,461 @@
+use super::{
+    key_tag, nsec3_hash, nsec3_label, rrsig_labels, signed_data, ALG_ECDSAP256SHA256, ALG_ED25519,
+    FLAG_SEP, FLAG_ZONE, NSEC3_SHA1, PROTOCOL,
+};
+use crate::dns::{DnsRecord, QueryType, RecordData};
+use crate::name::Name;
+use crate::zone::Zone;
+use crate::{Error, Result};
+use data_encoding::BASE64;
+use ring::rand::SystemRandom;
+use ring::signature::{EcdsaKeyPair, Ed25519KeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+
+/// default lifetime of a signature, thirty days
+pub const DEFAULT_VALIDITY: u32 = 30 * 86400;
+/// signatures become valid this long before signing to allow for clock skew
+const INCEPTION_OFFSET: u32 = 3600;
+
+#[derive(Debug)]
+enum Pair {
+    Ed25519(Ed25519KeyPair),
+    Ecdsa(EcdsaKeyPair),
+}
+
+/// Private key of a zone together with its DNSKEY parameters.
+///
+/// Keys are stored as the zone, flags, algorithm and PKCS#8 document, see
+/// the [`fmt::Display`] implementation for the file format.
+#[derive(Debug)]
+pub struct SigningKey {
+    zone: Name,
+    flags: u16,
+    algorithm: u8,
+    pkcs8: Vec<u8>,
+    pair: Pair,
+}
+
+impl SigningKey {
+    /// fresh key for `zone`, a key signing key if `ksk` is set
+    ///
+    /// Only Ed25519 (15) and ECDSA P-256 (13) keys can be generated.
+    pub fn generate(zone: Name, algorithm: u8, ksk: bool) -> Result<Self> {
+        let rng: SystemRandom = SystemRandom::new();
+        let pkcs8: Vec<u8> = match algorithm {
+            ALG_ED25519 => Ed25519KeyPair::generate_pkcs8(&rng)
+                .map_err(|_| Error::InvalidKey("key generation failed".to_string()))?
+                .as_ref()
+                .to_vec(),
+            ALG_ECDSAP256SHA256 => {
+                EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng)
+                    .map_err(|_| Error::InvalidKey("key generation failed".to_string()))?
+                    .as_ref()
+                    .to_vec()
+            }
+            other => {
+                return Err(Error::InvalidKey(format!(
+                    "cannot sign with algorithm {}",
+                    other
+                )))
+            }
+        };
+        let flags: u16 = match ksk {
+            true => FLAG_ZONE | FLAG_SEP,
+            false => FLAG_ZONE,
+        };
+        Self::from_pkcs8(zone, flags, algorithm, pkcs8)
+    }
+
+    pub fn from_pkcs8(zone: Name, flags: u16, algorithm: u8, pkcs8: Vec<u8>) -> Result<Self> {
+        let pair: Pair = match algorithm {
+            ALG_ED25519 => Pair::Ed25519(
+                Ed25519KeyPair::from_pkcs8_maybe_unchecked(&pkcs8)
+                    .map_err(|err| Error::InvalidKey(err.to_string()))?,
+            ),
+            ALG_ECDSAP256SHA256 => Pair::Ecdsa(
+                EcdsaKeyPair::from_pkcs8(
+                    &ECDSA_P256_SHA256_FIXED_SIGNING,
+                    &pkcs8,
+                    &SystemRandom::new(),
+                )
+                .map_err(|err| Error::InvalidKey(err.to_string()))?,
+            ),
+            other => {
+                return Err(Error::InvalidKey(format!(
+                    "cannot sign with algorithm {}",
+                    other
+                )))
+            }
+        };
+        Ok(Self {
+            zone,
+            flags,
+            algorithm,
+            pkcs8,
+            pair,
+        })
+    }
+
+    /// key read from a private key file
+    pub fn load(file_name: &str) -> Result<Self> {
+        Self::parse(&fs::read_to_string(file_name)?)
+    }
+
+    /// key in the `Field: value` format written by [`fmt::Display`]
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut fields: BTreeMap<String, String> = BTreeMap::new();
+        for (index, line) in text.lines().enumerate() {
+            let line: &str = line.split(';').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (field, value) = line.split_once(':').ok_or_else(|| Error::Syntax {
+                line: index + 1,
+                reason: "expected `Field: value`".to_string(),
+            })?;
+            fields.insert(field.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+        let field = |name: &str| -> Result<&str> {
+            fields
+                .get(name)
+                .map(String::as_str)
+                .ok_or_else(|| Error::InvalidKey(format!("missing field {}", name)))
+        };
+        let number = |name: &str| -> Result<u16> {
+            field(name)?
+                .parse()
+                .map_err(|_| Error::InvalidKey(format!("invalid {}", name)))
+        };
+        let pkcs8: Vec<u8> = BASE64
+            .decode(field("privatekey")?.as_bytes())
+            .map_err(|_| Error::InvalidKey("invalid private key".to_string()))?;
+        Self::from_pkcs8(
+            field("zone")?.parse()?,
+            number("flags")?,
+            number("algorithm")? as u8,
+            pkcs8,
+        )
+    }
+
+    pub fn zone(&self) -> &Name {
+        &self.zone
+    }
+
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    pub fn algorithm(&self) -> u8 {
+        self.algorithm
+    }
+
+    /// key signing keys carry the SEP flag and sign the DNSKEY RRset
+    pub fn is_ksk(&self) -> bool {
+        self.flags & FLAG_SEP != 0
+    }
+
+    /// the public half as published at the zone apex
+    pub fn dnskey(&self) -> RecordData {
+        let public_key: Vec<u8> = match &self.pair {
+            Pair::Ed25519(pair) => pair.public_key().as_ref().to_vec(),
+            // DNSKEY drops the uncompressed point prefix 0x04, RFC 6605 §4
+            Pair::Ecdsa(pair) => pair.public_key().as_ref()[1..].to_vec(),
+        };
+        RecordData::DNSKEY {
+            flags: self.flags,
+            protocol: PROTOCOL,
+            algorithm: self.algorithm,
+            public_key,
+        }
+    }
+
+    pub fn key_tag(&self) -> u16 {
+        key_tag(&self.dnskey()).unwrap_or_default()
+    }
+
+    /// conventional file name stem `K<zone>+<algorithm>+<key tag>`
+    pub fn file_stem(&self) -> String {
+        format!(
+            "K{:#}+{:03}+{:05}",
+            self.zone,
+            self.algorithm,
+            self.key_tag()
+        )
+    }
+
+    pub fn sign(&self, message: &[u8]) -> Result<Vec<u8>> {
+        match &self.pair {
+            Pair::Ed25519(pair) => Ok(pair.sign(message).as_ref().to_vec()),
+            Pair::Ecdsa(pair) => pair
+                .sign(&SystemRandom::new(), message)
+                .map(|sig| sig.as_ref().to_vec())
+                .map_err(|_| Error::InvalidKey("signing failed".to_string())),
+        }
+    }
+}
+
+impl fmt::Display for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "; {}", self.file_stem())?;
+        writeln!(f, "Zone: {:#}", self.zone)?;
+        writeln!(f, "Flags: {}", self.flags)?;
+        writeln!(f, "Algorithm: {}", self.algorithm)?;
+        writeln!(f, "PrivateKey: {}", BASE64.encode(&self.pkcs8))
+    }
+}
+
+/// parameters of an NSEC3 chain, RFC 9276 recommends no iterations and no salt
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Nsec3Config {
+    pub iterations: u16,
+    pub salt: Vec<u8>,
+}
+
+/// Signs whole zones with a set of keys.
+///
+/// Signing replaces any DNSKEY, RRSIG, NSEC, NSEC3 and NSEC3PARAM records,
+/// publishes the keys at the apex, builds an NSEC chain, or an NSEC3 chain
+/// when configured, and signs every authoritative RRset.
+#[derive(Debug)]
+pub struct Signer {
+    keys: Vec<SigningKey>,
+    nsec3: Option<Nsec3Config>,
+    validity: u32,
+}
+
+impl Signer {
+    pub fn new(keys: Vec<SigningKey>) -> Self {
+        Self {
+            keys,
+            nsec3: None,
+            validity: DEFAULT_VALIDITY,
+        }
+    }
+
+    /// deny with NSEC3 instead of NSEC
+    pub fn set_nsec3(&mut self, nsec3: Option<Nsec3Config>) {
+        self.nsec3 = nsec3;
+    }
+
+    /// seconds signatures stay valid after signing
+    pub fn set_validity(&mut self, validity: u32) {
+        self.validity = validity;
+    }
+
+    pub fn keys(&self) -> &[SigningKey] {
+        &self.keys
+    }
+
+    /// sign `zone` with signatures valid from shortly before `now`
+    pub fn sign_zone(&self, zone: &mut Zone, now: u32) -> Result<()> {
+        let origin: Name = zone.origin().clone();
+        if self.keys.is_empty() {
+            return Err(Error::InvalidKey(format!("no keys for {}", origin)));
+        }
+        if let Some(key) = self.keys.iter().find(|key| key.zone() != &origin) {
+            return Err(Error::InvalidKey(format!(
+                "key {} does not belong to {}",
+                key.file_stem(),
+                origin
+            )));
+        }
+        let (soa_ttl, minimum): (u32, u32) = match zone.soa() {
+            Some(DnsRecord {
+                ttl,
+                r_data: RecordData::SOA { minimum, .. },
+                ..
+            }) => (*ttl, *minimum),
+            _ => return Err(Error::InvalidZone(format!("{} has no SOA", origin))),
+        };
+        // negative answers are cached no longer than this, RFC 9077
+        let nsec_ttl: u32 = soa_ttl.min(minimum);
+
+        zone.retain(|record| {
+            !matches!(
+                record.r_type,
+                QueryType::DNSKEY
+                    | QueryType::RRSIG
+                    | QueryType::NSEC
+                    | QueryType::NSEC3
+                    | QueryType::NSEC3PARAM
+            )
+        });
+        for key in &self.keys {
+            zone.insert(DnsRecord::new(origin.clone(), soa_ttl, key.dnskey()));
+        }
+        if let Some(nsec3) = &self.nsec3 {
+            zone.insert(DnsRecord::new(
+                origin.clone(),
+                0,
+                RecordData::NSEC3PARAM {
+                    hash_algorithm: NSEC3_SHA1,
+                    flags: 0,
+                    iterations: nsec3.iterations,
+                    salt: nsec3.salt.clone(),
+                },
+            ));
+        }
+
+        let names: Vec<Name> = zone
+            .names()
+            .filter(|name| is_authoritative(zone, name))
+            .cloned()
+            .collect();
+        let chain: Vec<DnsRecord> = match &self.nsec3 {
+            Some(nsec3) => nsec3_chain(zone, &names, nsec3, nsec_ttl)?,
+            None => nsec_chain(zone, &names, nsec_ttl),
+        };
+        for record in chain {
+            zone.insert(record);
+        }
+
+        let mut signatures: Vec<DnsRecord> = Vec::new();
+        for name in zone.names().filter(|name| is_authoritative(zone, name)) {
+            let cut: bool = zone.delegation(name).as_ref() == Some(name);
+            let mut types: Vec<QueryType> = zone
+                .records_at(name)
+                .iter()
+                .map(|record| record.r_type)
+                .collect();
+            types.sort_by_key(|r_type| u16::from(*r_type));
+            types.dedup();
+            for r_type in types {
+                // only DS and the denial records are authoritative at a cut
+                if cut && !matches!(r_type, QueryType::DS | QueryType::NSEC) {
+                    continue;
+                }
+                let rrset: Vec<&DnsRecord> = zone.rrset(name, r_type);
+                for key in self.signing_keys(r_type) {
+                    signatures.push(self.sign_rrset(key, &origin, &rrset, now)?);
+                }
+            }
+        }
+        for record in signatures {
+            zone.insert(record);
+        }
+        Ok(())
+    }
+
+    /// KSKs sign the DNSKEY RRset and ZSKs the rest, a single kind of key signs everything
+    fn signing_keys(&self, r_type: QueryType) -> Vec<&SigningKey> {
+        let wanted: bool = r_type == QueryType::DNSKEY;
+        let keys: Vec<&SigningKey> = self
+            .keys
+            .iter()
+            .filter(|key| key.is_ksk() == wanted)
+            .collect();
+        match keys.is_empty() {
+            true => self.keys.iter().collect(),
+            false => keys,
+        }
+    }
+
+    fn sign_rrset(
+        &self,
+        key: &SigningKey,
+        origin: &Name,
+        rrset: &[&DnsRecord],
+        now: u32,
+    ) -> Result<DnsRecord> {
+        let first: &DnsRecord = rrset[0];
+        let mut rrsig: RecordData = RecordData::RRSIG {
+            type_covered: first.r_type,
+            algorithm: key.algorithm(),
+            labels: rrsig_labels(&first.label),
+            original_ttl: first.ttl,
+            expiration: now.wrapping_add(self.validity),
+            inception: now.wrapping_sub(INCEPTION_OFFSET),
+            key_tag: key.key_tag(),
+            signer_name: origin.clone(),
+            signature: Vec::new(),
+        };
+        let data: Vec<u8> = signed_data(&rrsig, rrset)?;
+        if let RecordData::RRSIG { signature, .. } = &mut rrsig {
+            *signature = key.sign(&data)?;
+        }
+        Ok(DnsRecord::new(first.label.clone(), first.ttl, rrsig))
+    }
+}
+
+/// names at or above the zone cuts, glue below a cut is not signed
+fn is_authoritative(zone: &Zone, name: &Name) -> bool {
+    match zone.delegation(name) {
+        Some(cut) => &cut == name,
+        None => true,
+    }
+}
+
+/// types present at `name` as an NSEC/NSEC3 bitmap sees them, plus `extra`
+fn bitmap(zone: &Zone, name: &Name, extra: &[QueryType]) -> Vec<QueryType> {
+    let cut: bool = zone.delegation(name).as_ref() == Some(name);
+    let mut types: Vec<QueryType> = zone
+        .records_at(name)
+        .iter()
+        .map(|record| record.r_type)
+        .filter(|r_type| !cut || matches!(r_type, QueryType::NS | QueryType::DS))
+        .chain(extra.iter().copied())
+        .collect();
+    types.sort_by_key(|r_type| u16::from(*r_type));
+    types.dedup();
+    types
+}
+
+/// NSEC records linking `names` in canonical order, RFC 4034 §4
+fn nsec_chain(zone: &Zone, names: &[Name], ttl: u32) -> Vec<DnsRecord> {
+    names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let next_domain: Name = names[(i + 1) % names.len()].clone();
+            let types: Vec<QueryType> = bitmap(zone, name, &[QueryType::RRSIG, QueryType::NSEC]);
+            DnsRecord::new(name.clone(), ttl, RecordData::NSEC { next_domain, types })
+        })
+        .collect()
+}
+
+/// NSEC3 records over the hashes of `names` and the empty non-terminals
+/// between them and the apex, RFC 5155 §7.1
+fn nsec3_chain(
+    zone: &Zone,
+    names: &[Name],
+    nsec3: &Nsec3Config,
+    ttl: u32,
+) -> Result<Vec<DnsRecord>> {
+    let origin: &Name = zone.origin();
+    let mut hashed: BTreeMap<Vec<u8>, Vec<QueryType>> = BTreeMap::new();
+    for name in names {
+        for num_labels in origin.num_labels() + 1..name.num_labels() {
+            let ancestor: Name = name.trim_to(num_labels);
+            if zone.records_at(&ancestor).is_empty() {
+                let hash: Vec<u8> = nsec3_hash(&ancestor, nsec3.iterations, &nsec3.salt);
+                hashed.entry(hash).or_default();
+            }
+        }
+        let cut: bool = zone.delegation(name).as_ref() == Some(name);
+        // an unsigned delegation has no RRSIG, its NSEC3 is signed elsewhere
+        let signed: bool = !cut || !zone.rrset(name, QueryType::DS).is_empty();
+        let types: Vec<QueryType> = match signed {
+            true => bitmap(zone, name, &[QueryType::RRSIG]),
+            false => bitmap(zone, name, &[]),
+        };
+        let hash: Vec<u8> = nsec3_hash(name, nsec3.iterations, &nsec3.salt);
+        hashed.insert(hash, types);
+    }
+    let hashes: Vec<&Vec<u8>> = hashed.keys().collect();
+    let mut chain: Vec<DnsRecord> = Vec::new();
+    for (i, (hash, types)) in hashed.iter().enumerate() {
+        let owner: Name = origin.child(&nsec3_label(hash))?;
+        let r_data: RecordData = RecordData::NSEC3 {
+            hash_algorithm: NSEC3_SHA1,
+            flags: 0,
+            iterations: nsec3.iterations,
+            salt: nsec3.salt.clone(),
+            next_hashed: hashes[(i + 1) % hashes.len()].clone(),
+            types: types.clone(),
+        };
+        chain.push(DnsRecord::new(owner, ttl, r_data));
+    }
+    Ok(chain)
+}
@@ -0,0 +1,785 @@
+use super::anchor::TrustAnchors;
+use super::{
+    covers, ds_digest, is_supported_algorithm, is_supported_digest, key_tag, nsec3_hash,
+    nsec3_owner_hash, rrset, rrsig_labels, rrsigs, serial_le, signed_data, verify, FLAG_REVOKE,
+    FLAG_ZONE, NSEC3_OPT_OUT, NSEC3_SHA1, PROTOCOL,
+};
+use crate::dns::{DnsPacket, DnsRecord, QueryType, RecordData, ResponseCode};
+use crate::name::Name;
+use crate::Result;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// NSEC3 chains hashed more often than this only prove insecurity, RFC 9276
+pub const MAX_NSEC3_ITERATIONS: u16 = 150;
+/// longest CNAME chain followed through an answer
+const MAX_CNAME_CHAIN: usize = 16;
+
+/// outcome of validating a response, RFC 4035 §4.3
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    /// every RRset chains up to a trust anchor
+    Secure,
+    /// the data lies below a provably unsigned delegation
+    Insecure,
+    /// signatures or proofs are missing or wrong
+    Bogus(String),
+}
+
+impl Status {
+    /// the weaker of two results, bogus beats insecure beats secure
+    fn and(self, other: Status) -> Status {
+        match (self, other) {
+            (Status::Bogus(reason), _) | (_, Status::Bogus(reason)) => Status::Bogus(reason),
+            (Status::Insecure, _) | (_, Status::Insecure) => Status::Insecure,
+            _ => Status::Secure,
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::Secure => write!(f, "secure"),
+            Status::Insecure => write!(f, "insecure"),
+            Status::Bogus(reason) => write!(f, "bogus: {}", reason),
+        }
+    }
+}
+
+/// source of the DS and DNSKEY records needed to build a chain of trust
+pub trait Lookup {
+    /// response to a query for `q_type` records of `name` with the DO bit set
+    fn lookup(&self, name: &Name, q_type: QueryType) -> Result<DnsPacket>;
+}
+
+impl<L: Lookup + ?Sized> Lookup for &L {
+    fn lookup(&self, name: &Name, q_type: QueryType) -> Result<DnsPacket> {
+        (**self).lookup(name, q_type)
+    }
+}
+
+/// what is known about the zone a name belongs to
+#[derive(Debug, Clone)]
+enum Trust {
+    /// the name lies in `zone`, whose DNSKEY RRset validated as `keys`
+    Secure {
+        zone: Name,
+        keys: Vec<DnsRecord>,
+    },
+    Insecure,
+    Bogus(String),
+}
+
+impl Trust {
+    fn status(&self) -> Status {
+        match self {
+            Trust::Secure { .. } => Status::Secure,
+            Trust::Insecure => Status::Insecure,
+            Trust::Bogus(reason) => Status::Bogus(reason.clone()),
+        }
+    }
+}
+
+/// what a set of NSEC or NSEC3 records proves about a name
+#[derive(Debug, PartialEq, Eq)]
+enum Denial {
+    NxDomain,
+    /// the name exists without the type, `delegation` marks an unsigned referral
+    NoData {
+        delegation: bool,
+    },
+    /// the name falls in an opt-out span or the chain is too costly to check
+    Insecure,
+}
+
+/// an NSEC record that validated
+struct Nsec {
+    owner: Name,
+    next: Name,
+    types: Vec<QueryType>,
+}
+
+/// an NSEC3 record that validated, hashes decoded
+struct Nsec3 {
+    hash: Vec<u8>,
+    next: Vec<u8>,
+    opt_out: bool,
+    iterations: u16,
+    salt: Vec<u8>,
+    types: Vec<QueryType>,
+}
+
+enum Chain {
+    Nsec(Vec<Nsec>),
+    Nsec3(Vec<Nsec3>),
+}
+
+/// RRset signed by a secure zone
+struct Signed {
+    zone: Name,
+    keys: Vec<DnsRecord>,
+    /// labels field of the RRSIG that verified
+    labels: u8,
+}
+
+/// Validating layer on top of a [`Lookup`].
+///
+/// Chains of trust are built top down from the anchors, one DS query per
+/// label, and are remembered for the life of the validator.
+pub struct Validator<L> {
+    lookup: L,
+    anchors: TrustAnchors,
+    zones: RefCell<HashMap<Name, Trust>>,
+    now: Option<u32>,
+}
+
+impl<L: Lookup> Validator<L> {
+    pub fn new(lookup: L, anchors: TrustAnchors) -> Self {
+        Self {
+            lookup,
+            anchors,
+            zones: RefCell::new(HashMap::new()),
+            now: None,
+        }
+    }
+
+    /// check signature validity periods against `now` instead of the clock
+    pub fn set_time(&mut self, now: u32) {
+        self.now = Some(now);
+    }
+
+    fn now(&self) -> u32 {
+        self.now.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs() as u32)
+                .unwrap_or_default()
+        })
+    }
+
+    /// classify `response` and set its AD bit when it is secure
+    pub fn validate(&self, response: &mut DnsPacket) -> Status {
+        let status: Status = self.classify(response);
+        response.header.ad = status == Status::Secure;
+        status
+    }
+
+    fn classify(&self, response: &DnsPacket) -> Status {
+        let (qname, q_type) = match response.questions.first() {
+            Some(question) => (question.label.clone(), question.q_type),
+            None => return Status::Bogus("response carries no question".to_string()),
+        };
+        let nx_domain: bool = match response.header.r_code {
+            ResponseCode::NOERROR => false,
+            ResponseCode::NAMEERROR => true,
+            // nothing to authenticate in an error
+            _ => return Status::Insecure,
+        };
+
+        let mut status: Status = Status::Secure;
+        let mut seen: Vec<(&Name, QueryType)> = Vec::new();
+        for record in &response.answers {
+            let key: (&Name, QueryType) = (&record.label, record.r_type);
+            if record.r_type == QueryType::RRSIG || seen.contains(&key) {
+                continue;
+            }
+            seen.push(key);
+            let set: Vec<&DnsRecord> = rrset(&response.answers, &record.label, record.r_type);
+            let sigs: Vec<&DnsRecord> = rrsigs(&response.answers, &record.label, record.r_type);
+            status = match self.verify_signed(&set, &sigs) {
+                Ok(signed) if (signed.labels) < rrsig_labels(&record.label) => {
+                    let proof: Status = self.wildcard_proof(
+                        &record.label,
+                        signed.labels,
+                        &response.authorities,
+                        &signed,
+                    );
+                    status.and(proof)
+                }
+                Ok(_) => status,
+                Err(failure) => status.and(failure),
+            };
+        }
+
+        // the name the answer ends up at after following aliases
+        let mut target: Name = qname;
+        for _ in 0..MAX_CNAME_CHAIN {
+            if q_type == QueryType::CNAME {
+                break;
+            }
+            match rrset(&response.answers, &target, QueryType::CNAME).first() {
+                Some(DnsRecord {
+                    r_data: RecordData::CNAME(alias),
+                    ..
+                }) => target = alias.clone(),
+                _ => break,
+            }
+        }
+        let answered: bool = response
+            .answers
+            .iter()
+            .any(|record| record.label == target && record.r_type == q_type);
+        if answered && nx_domain {
+            return Status::Bogus(format!("NXDOMAIN with an answer for {}", target));
+        }
+        match answered {
+            true => status,
+            false => status.and(self.deny(&target, q_type, nx_domain, &response.authorities)),
+        }
+    }
+
+    /// status of the NXDOMAIN or NODATA claim for `name` and `q_type`
+    fn deny(
+        &self,
+        name: &Name,
+        q_type: QueryType,
+        nx_domain: bool,
+        authorities: &[DnsRecord],
+    ) -> Status {
+        // the zone is named by the signatures over the denial records
+        let signer: Option<&Name> = authorities.iter().find_map(|record| match &record.r_data {
+            RecordData::RRSIG {
+                type_covered: QueryType::NSEC | QueryType::NSEC3,
+                signer_name,
+                ..
+            } => Some(signer_name),
+            _ => None,
+        });
+        let signer: &Name = match signer {
+            Some(signer) if name.ends_with(signer) => signer,
+            Some(signer) => {
+                return Status::Bogus(format!("denial for {} signed by {}", name, signer));
+            }
+            None => {
+                return match self.trust(name) {
+                    Trust::Secure { .. } => {
+                        Status::Bogus(format!("no signed denial for {} {}", name, q_type))
+                    }
+                    other => other.status(),
+                };
+            }
+        };
+        let (zone, keys) = match self.trust(signer) {
+            Trust::Secure { zone, keys } => (zone, keys),
+            other => return other.status(),
+        };
+        match self.prove(name, q_type, authorities, &zone, &keys) {
+            Ok(Denial::NxDomain) if nx_domain => Status::Secure,
+            Ok(Denial::NoData { .. }) if !nx_domain => Status::Secure,
+            Ok(Denial::Insecure) => Status::Insecure,
+            Ok(denial) => Status::Bogus(format!(
+                "{:?} proven for {} but the response code says otherwise",
+                denial, name
+            )),
+            Err(reason) => Status::Bogus(reason),
+        }
+    }
+
+    /// proof that `owner` itself does not exist, so a wildcard could answer
+    fn wildcard_proof(
+        &self,
+        owner: &Name,
+        labels: u8,
+        authorities: &[DnsRecord],
+        signed: &Signed,
+    ) -> Status {
+        let next_closer: Name = owner.trim_to(labels as usize + 1);
+        let proven: bool = match self.chain(authorities, &signed.zone, &signed.keys) {
+            Ok(Some(Chain::Nsec(nsecs))) => nsecs
+                .iter()
+                .any(|nsec| covers(&nsec.owner, &nsec.next, owner)),
+            Ok(Some(Chain::Nsec3(nsec3s))) => nsec3s.iter().any(|nsec3| {
+                let hash: Vec<u8> = nsec3_hash(&next_closer, nsec3.iterations, &nsec3.salt);
+                covers(&nsec3.hash[..], &nsec3.next[..], &hash[..])
+            }),
+            Ok(None) => false,
+            Err(reason) => return Status::Bogus(reason),
+        };
+        match proven {
+            true => Status::Secure,
+            false => Status::Bogus(format!("wildcard answer for {} without proof", owner)),
+        }
+    }
+
+    /// verify `set` through the signers named in `sigs`
+    fn verify_signed(
+        &self,
+        set: &[&DnsRecord],
+        sigs: &[&DnsRecord],
+    ) -> std::result::Result<Signed, Status> {
+        let owner: &Name = &set[0].label;
+        if sigs.is_empty() {
+            return Err(match self.trust(owner) {
+                Trust::Secure { .. } => {
+                    Status::Bogus(format!("{} {} is not signed", owner, set[0].r_type))
+                }
+                other => other.status(),
+            });
+        }
+        let mut failure: Status = Status::Bogus(String::new());
+        for sig in sigs {
+            let signer: &Name = match &sig.r_data {
+                RecordData::RRSIG { signer_name, .. } => signer_name,
+                _ => continue,
+            };
+            // a signer outside the owner's ancestry could claim any status
+            if !owner.ends_with(signer) {
+                failure = Status::Bogus(format!("{} signed by unrelated {}", owner, signer));
+                continue;
+            }
+            match self.trust(signer) {
+                Trust::Secure { zone, keys } => {
+                    match self.check_signature(set, &sig.r_data, &zone, &keys) {
+                        Ok(labels) => return Ok(Signed { zone, keys, labels }),
+                        Err(reason) => failure = Status::Bogus(reason),
+                    }
+                }
+                Trust::Insecure => return Err(Status::Insecure),
+                Trust::Bogus(reason) => failure = Status::Bogus(reason),
+            }
+        }
+        Err(failure)
+    }
+
+    /// verify `set` with the keys of `zone`, returning the RRSIG labels field
+    fn check(
+        &self,
+        set: &[&DnsRecord],
+        sigs: &[&DnsRecord],
+        zone: &Name,
+        keys: &[DnsRecord],
+    ) -> std::result::Result<u8, String> {
+        let mut reason: String = format!("{} {} is not signed", set[0].label, set[0].r_type);
+        for sig in sigs {
+            match self.check_signature(set, &sig.r_data, zone, keys) {
+                Ok(labels) => return Ok(labels),
+                Err(failure) => reason = failure,
+            }
+        }
+        Err(reason)
+    }
+
+    fn check_signature(
+        &self,
+        set: &[&DnsRecord],
+        rrsig: &RecordData,
+        zone: &Name,
+        keys: &[DnsRecord],
+    ) -> std::result::Result<u8, String> {
+        let (algorithm, labels, expiration, inception, tag, signer_name, signature) = match rrsig {
+            RecordData::RRSIG {
+                algorithm,
+                labels,
+                expiration,
+                inception,
+                key_tag,
+                signer_name,
+                signature,
+                ..
+            } => (
+                *algorithm,
+                *labels,
+                *expiration,
+                *inception,
+                *key_tag,
+                signer_name,
+                signature,
+            ),
+            _ => return Err("not an RRSIG".to_string()),
+        };
+        let owner: &Name = &set[0].label;
+        if signer_name != zone {
+            return Err(format!(
+                "{} signed by {} instead of {}",
+                owner, signer_name, zone
+            ));
+        }
+        if !owner.ends_with(zone) || labels > rrsig_labels(owner) {
+            return Err(format!("RRSIG does not fit owner {}", owner));
+        }
+        let now: u32 = self.now();
+        if !serial_le(inception, now) {
+            return Err(format!("signature over {} not yet valid", owner));
+        }
+        if !serial_le(now, expiration) {
+            return Err(format!("signature over {} expired", owner));
+        }
+        let data: Vec<u8> = signed_data(rrsig, set).map_err(|err| err.to_string())?;
+        for key in keys {
+            if let RecordData::DNSKEY {
+                flags,
+                protocol,
+                algorithm: key_algorithm,
+                public_key,
+            } = &key.r_data
+            {
+                if *key_algorithm == algorithm
+                    && *protocol == PROTOCOL
+                    && flags & FLAG_ZONE != 0
+                    && flags & FLAG_REVOKE == 0
+                    && key_tag(&key.r_data).ok() == Some(tag)
+                    && verify(algorithm, public_key, &data, signature)
+                {
+                    return Ok(labels);
+                }
+            }
+        }
+        Err(format!(
+            "no key of {} verifies the signature over {} {}",
+            zone, owner, set[0].r_type
+        ))
+    }
+
+    /// the trust status of the zone `name` belongs to, memoized
+    fn trust(&self, name: &Name) -> Trust {
+        if let Some(trust) = self.zones.borrow().get(name) {
+            return trust.clone();
+        }
+        let trust: Trust = match self.anchors.for_zone(name).is_empty() {
+            false => self.anchored(name),
+            true => match name.parent() {
+                // no anchor above the name
+                None => Trust::Insecure,
+                Some(parent) => match self.trust(&parent) {
+                    Trust::Secure { zone, keys } => self.delegation(name, &zone, &keys),
+                    other => other,
+                },
+            },
+        };
+        self.zones.borrow_mut().insert(name.clone(), trust.clone());
+        trust
+    }
+
+    /// DNSKEYs of a zone with a configured anchor
+    fn anchored(&self, zone: &Name) -> Trust {
+        let ds: Vec<&RecordData> = self
+            .anchors
+            .ds(zone)
+            .into_iter()
+            .map(|anchor| &anchor.r_data)
+            .filter(|ds| supported_ds(ds))
+            .collect();
+        let keys: Vec<&RecordData> = self
+            .anchors
+            .dnskeys(zone)
+            .into_iter()
+            .map(|anchor| &anchor.r_data)
+            .filter(|key| matches!(key, RecordData::DNSKEY { algorithm, .. } if is_supported_algorithm(*algorithm)))
+            .collect();
+        if ds.is_empty() && keys.is_empty() {
+            return Trust::Insecure;
+        }
+        self.zone_keys(zone, &ds, &keys)
+    }
+
+    /// whether `name`, below the secure `zone`, starts a zone of its own
+    fn delegation(&self, name: &Name, zone: &Name, keys: &[DnsRecord]) -> Trust {
+        let response: DnsPacket = match self.lookup.lookup(name, QueryType::DS) {
+            Ok(response) => response,
+            Err(err) => return Trust::Bogus(format!("DS lookup for {} failed: {}", name, err)),
+        };
+        let ds: Vec<&DnsRecord> = rrset(&response.answers, name, QueryType::DS);
+        if !ds.is_empty() {
+            let sigs: Vec<&DnsRecord> = rrsigs(&response.answers, name, QueryType::DS);
+            if let Err(reason) = self.check(&ds, &sigs, zone, keys) {
+                return Trust::Bogus(reason);
+            }
+            let ds: Vec<&RecordData> = ds
+                .into_iter()
+                .map(|record| &record.r_data)
+                .filter(|ds| supported_ds(ds))
+                .collect();
+            return match ds.is_empty() {
+                // nothing this validator can check, RFC 4035 §5.2
+                true => Trust::Insecure,
+                false => self.zone_keys(name, &ds, &[]),
+            };
+        }
+        // an alias is never a zone cut, anything it claims still needs the keys of `zone`
+        if !rrset(&response.answers, name, QueryType::CNAME).is_empty() {
+            return Trust::Secure {
+                zone: zone.clone(),
+                keys: keys.to_vec(),
+            };
+        }
+        match self.prove(name, QueryType::DS, &response.authorities, zone, keys) {
+            Ok(Denial::NoData { delegation: true }) | Ok(Denial::Insecure) => Trust::Insecure,
+            Ok(_) => Trust::Secure {
+                zone: zone.clone(),
+                keys: keys.to_vec(),
+            },
+            Err(reason) => Trust::Bogus(format!("no DS for {}: {}", name, reason)),
+        }
+    }
+
+    /// fetch the DNSKEY RRset of `zone` and check it against DS or key anchors
+    fn zone_keys(&self, zone: &Name, ds: &[&RecordData], anchor_keys: &[&RecordData]) -> Trust {
+        let response: DnsPacket = match self.lookup.lookup(zone, QueryType::DNSKEY) {
+            Ok(response) => response,
+            Err(err) => return Trust::Bogus(format!("DNSKEY lookup for {} failed: {}", zone, err)),
+        };
+        let dnskeys: Vec<&DnsRecord> = rrset(&response.answers, zone, QueryType::DNSKEY);
+        let sigs: Vec<&DnsRecord> = rrsigs(&response.answers, zone, QueryType::DNSKEY);
+        let entry: Vec<DnsRecord> = dnskeys
+            .iter()
+            .filter(|key| {
+                anchor_keys.contains(&&key.r_data)
+                    || ds.iter().any(|ds| ds_matches(zone, ds, &key.r_data))
+            })
+            .map(|key| (*key).clone())
+            .collect();
+        if entry.is_empty() {
+            return Trust::Bogus(format!("no DNSKEY of {} matches its DS", zone));
+        }
+        match self.check(&dnskeys, &sigs, zone, &entry) {
+            Ok(_) => Trust::Secure {
+                zone: zone.clone(),
+                keys: dnskeys.into_iter().cloned().collect(),
+            },
+            Err(reason) => Trust::Bogus(reason),
+        }
+    }
+
+    /// the NSEC or NSEC3 records of `authorities` that validate under `zone`
+    fn chain(
+        &self,
+        authorities: &[DnsRecord],
+        zone: &Name,
+        keys: &[DnsRecord],
+    ) -> std::result::Result<Option<Chain>, String> {
+        let mut nsecs: Vec<Nsec> = Vec::new();
+        let mut nsec3s: Vec<Nsec3> = Vec::new();
+        for record in authorities {
+            if !record.label.ends_with(zone) {
+                continue;
+            }
+            let set: Vec<&DnsRecord> = rrset(authorities, &record.label, record.r_type);
+            let sigs: Vec<&DnsRecord> = rrsigs(authorities, &record.label, record.r_type);
+            match &record.r_data {
+                RecordData::NSEC { next_domain, types } => {
+                    self.check(&set, &sigs, zone, keys)?;
+                    nsecs.push(Nsec {
+                        owner: record.label.clone(),
+                        next: next_domain.clone(),
+                        types: types.clone(),
+                    });
+                }
+                RecordData::NSEC3 {
+                    hash_algorithm: NSEC3_SHA1,
+                    flags,
+                    iterations,
+                    salt,
+                    next_hashed,
+                    types,
+                } => {
+                    // hashed owners sit directly below the apex
+                    if record.label.parent().as_ref() != Some(zone) {
+                        continue;
+                    }
+                    let hash: Vec<u8> = match nsec3_owner_hash(&record.label) {
+                        Some(hash) => hash,
+                        None => continue,
+                    };
+                    self.check(&set, &sigs, zone, keys)?;
+                    nsec3s.push(Nsec3 {
+                        hash,
+                        next: next_hashed.clone(),
+                        opt_out: flags & NSEC3_OPT_OUT != 0,
+                        iterations: *iterations,
+                        salt: salt.clone(),
+                        types: types.clone(),
+                    });
+                }
+                _ => {}
+            }
+        }
+        Ok(match (nsecs.is_empty(), nsec3s.is_empty()) {
+            (false, _) => Some(Chain::Nsec(nsecs)),
+            (true, false) => Some(Chain::Nsec3(nsec3s)),
+            (true, true) => None,
+        })
+    }
+
+    /// what the denial records in `authorities` prove about `name`
+    fn prove(
+        &self,
+        name: &Name,
+        q_type: QueryType,
+        authorities: &[DnsRecord],
+        zone: &Name,
+        keys: &[DnsRecord],
+    ) -> std::result::Result<Denial, String> {
+        match self.chain(authorities, zone, keys)? {
+            Some(Chain::Nsec(nsecs)) => nsec_proof(name, q_type, &nsecs),
+            Some(Chain::Nsec3(nsec3s)) => nsec3_proof(name, q_type, &nsec3s, zone),
+            None => Err(format!("no NSEC or NSEC3 records for {}", name)),
+        }
+    }
+}
+
+/// DS record with an algorithm and digest this validator implements
+fn supported_ds(ds: &RecordData) -> bool {
+    matches!(ds, RecordData::DS { algorithm, digest_type, .. }
+        if is_supported_algorithm(*algorithm) && is_supported_digest(*digest_type))
+}
+
+fn ds_matches(owner: &Name, ds: &RecordData, dnskey: &RecordData) -> bool {
+    match (ds, dnskey) {
+        (
+            RecordData::DS {
+                key_tag: tag,
+                algorithm,
+                digest_type,
+                digest,
+            },
+            RecordData::DNSKEY {
+                flags,
+                algorithm: key_algorithm,
+                ..
+            },
+        ) => {
+            algorithm == key_algorithm
+                && flags & FLAG_REVOKE == 0
+                && key_tag(dnskey).ok() == Some(*tag)
+                && ds_digest(owner, dnskey, *digest_type)
+                    .ok()
+                    .flatten()
+                    .as_ref()
+                    == Some(digest)
+        }
+        _ => false,
+    }
+}
+
+/// the deepest ancestor `a` and `b` share
+fn common_ancestor(a: &Name, b: &Name) -> Name {
+    let shared: usize = a
+        .labels()
+        .rev()
+        .zip(b.labels().rev())
+        .take_while(|(x, y)| x.eq_ignore_ascii_case(y))
+        .count();
+    a.trim_to(shared)
+}
+
+/// NODATA for a name or wildcard owning `types`, RFC 4035 §5.4
+fn no_data(
+    name: &Name,
+    q_type: QueryType,
+    types: &[QueryType],
+) -> std::result::Result<Denial, String> {
+    if types.contains(&q_type) || types.contains(&QueryType::CNAME) {
+        return Err(format!("denial shows {} owns {}", name, q_type));
+    }
+    let delegation: bool = types.contains(&QueryType::NS) && !types.contains(&QueryType::SOA);
+    // the parent side of a cut knows nothing about the child's data
+    if delegation && q_type != QueryType::DS {
+        return Err(format!("denial for {} comes from above a zone cut", name));
+    }
+    Ok(Denial::NoData { delegation })
+}
+
+fn nsec_proof(
+    name: &Name,
+    q_type: QueryType,
+    nsecs: &[Nsec],
+) -> std::result::Result<Denial, String> {
+    if let Some(nsec) = nsecs.iter().find(|nsec| &nsec.owner == name) {
+        return no_data(name, q_type, &nsec.types);
+    }
+    let covering: Vec<&Nsec> = nsecs
+        .iter()
+        .filter(|nsec| covers(&nsec.owner, &nsec.next, name))
+        // a delegation NSEC says nothing about names below the cut
+        .filter(|nsec| {
+            !(name.ends_with(&nsec.owner)
+                && nsec.types.contains(&QueryType::NS)
+                && !nsec.types.contains(&QueryType::SOA))
+        })
+        .collect();
+    let cover: &Nsec = match covering.first() {
+        Some(cover) => cover,
+        None => return Err(format!("no NSEC covers {}", name)),
+    };
+    // an empty non-terminal exists even though no NSEC is owned by it
+    if cover.next.ends_with(name) {
+        return Ok(Denial::NoData { delegation: false });
+    }
+    let closest_encloser: Name = match (
+        common_ancestor(name, &cover.owner),
+        common_ancestor(name, &cover.next),
+    ) {
+        (a, b) if a.num_labels() >= b.num_labels() => a,
+        (_, b) => b,
+    };
+    let wildcard: Name = closest_encloser
+        .child(b"*")
+        .map_err(|err| err.to_string())?;
+    if let Some(nsec) = nsecs.iter().find(|nsec| nsec.owner == wildcard) {
+        return no_data(&wildcard, q_type, &nsec.types);
+    }
+    match nsecs
+        .iter()
+        .any(|nsec| covers(&nsec.owner, &nsec.next, &wildcard))
+    {
+        true => Ok(Denial::NxDomain),
+        false => Err(format!("no NSEC denies {}", wildcard)),
+    }
+}
+
+fn nsec3_proof(
+    name: &Name,
+    q_type: QueryType,
+    nsec3s: &[Nsec3],
+    zone: &Name,
+) -> std::result::Result<Denial, String> {
+    let (iterations, salt): (u16, &[u8]) = (nsec3s[0].iterations, &nsec3s[0].salt);
+    if iterations > MAX_NSEC3_ITERATIONS {
+        return Ok(Denial::Insecure);
+    }
+    let matching = |name: &Name| -> Option<&Nsec3> {
+        let hash: Vec<u8> = nsec3_hash(name, iterations, salt);
+        nsec3s.iter().find(|nsec3| nsec3.hash == hash)
+    };
+    let covering = |name: &Name| -> Option<&Nsec3> {
+        let hash: Vec<u8> = nsec3_hash(name, iterations, salt);
+        nsec3s
+            .iter()
+            .find(|nsec3| covers(&nsec3.hash[..], &nsec3.next[..], &hash[..]))
+    };
+
+    if let Some(nsec3) = matching(name) {
+        return no_data(name, q_type, &nsec3.types);
+    }
+    // closest encloser proof, RFC 5155 §8.3
+    let closest_encloser: Name = match (zone.num_labels()..name.num_labels())
+        .rev()
+        .map(|num_labels| name.trim_to(num_labels))
+        .find(|ancestor| matching(ancestor).is_some())
+    {
+        Some(closest_encloser) => closest_encloser,
+        None => return Err(format!("no closest encloser for {}", name)),
+    };
+    let next_closer: Name = name.trim_to(closest_encloser.num_labels() + 1);
+    let next_closer: &Nsec3 = match covering(&next_closer) {
+        Some(nsec3) => nsec3,
+        None => return Err(format!("no NSEC3 covers {}", next_closer)),
+    };
+    // an unsigned delegation may hide in an opt-out span, RFC 5155 §8.6
+    if q_type == QueryType::DS && next_closer.opt_out {
+        return Ok(Denial::Insecure);
+    }
+    let wildcard: Name = closest_encloser
+        .child(b"*")
+        .map_err(|err| err.to_string())?;
+    if let Some(nsec3) = matching(&wildcard) {
+        return no_data(&wildcard, q_type, &nsec3.types);
+    }
+    match covering(&wildcard) {
+        Some(_) => Ok(Denial::NxDomain),
+        None => Err(format!("no NSEC3 denies {}", wildcard)),
+    }
+}
@@ -0,0 +1,160 @@
+use super::dns::ResponseCode;
+use super::name::Name;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum Error {
+    /// read at or past the number of bytes held by the buffer
+    EndOfBuffer(usize),
+    /// write past the capacity of the buffer
+    BufferFull(usize),
+    /// label longer than 63 octets
+    LabelTooLong(usize),
+    /// name longer than 255 octets in wire format
+    NameTooLong(usize),
+    /// empty label anywhere but at the root
+    EmptyLabel,
+    /// compression pointer at `at` jumps forward to `target`
+    ForwardPointer {
+        at: usize,
+        target: usize,
+    },
+    /// compression pointer at `at` jumps back into the name being decoded
+    PointerLoop {
+        at: usize,
+        target: usize,
+    },
+    /// label type 0x40 or 0x80 found at `at`
+    ReservedLabelType {
+        at: usize,
+        label_type: u8,
+    },
+    /// malformed presentation format name
+    InvalidName(String),
+    /// malformed presentation format record, type or class
+    InvalidRecord(String),
+    /// zone contents break a structural rule such as having one SOA at the apex
+    InvalidZone(String),
+    /// signing key that cannot be loaded or used
+    InvalidKey(String),
+    /// character-string longer than 255 octets
+    CharacterStringTooLong(usize),
+    /// malformed NSEC/NSEC3 type bitmap
+    InvalidTypeBitmap(String),
+    /// record data of `r_type` did not span exactly `rd_len` octets
+    RecordLength {
+        r_type: u16,
+        rd_len: u16,
+        read: usize,
+    },
+    /// zone transfer refused by the primary or breaking RFC 5936
+    Transfer(String),
+    /// TSIG failed to verify, carrying the extended RCODE such as BADSIG
+    Tsig(u16),
+    /// COOKIE option of a length RFC 7873 does not allow
+    InvalidCookie(usize),
+    /// dynamic update refused, carrying the RCODE that says why, RFC 2136
+    Update(ResponseCode),
+    /// response id differs from the id of the query it answers
+    IdMismatch {
+        expected: u16,
+        received: u16,
+    },
+    /// iterative resolution gave up
+    Resolve(String),
+    /// the server answered a lookup of `name` with an error such as NXDOMAIN
+    Lookup {
+        name: Name,
+        r_code: ResponseCode,
+    },
+    /// response question differs from the question of the query
+    QuestionMismatch,
+    /// malformed presentation format input on line `line`
+    Syntax {
+        line: usize,
+        reason: String,
+    },
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EndOfBuffer(pos) => write!(f, "no more bytes to read at {}", pos),
+            Self::BufferFull(pos) => write!(f, "end of buffer reached writing at {}", pos),
+            Self::LabelTooLong(len) => write!(f, "label of {} octets exceeds 63", len),
+            Self::NameTooLong(len) => write!(f, "name of {} octets exceeds 255", len),
+            Self::EmptyLabel => write!(f, "empty label is only allowed as root"),
+            Self::ForwardPointer { at, target } => {
+                write!(
+                    f,
+                    "compression pointer at {} jumps forward to {}",
+                    at, target
+                )
+            }
+            Self::PointerLoop { at, target } => {
+                write!(f, "compression pointer at {} loops back to {}", at, target)
+            }
+            Self::ReservedLabelType { at, label_type } => {
+                write!(f, "reserved label type {:#04x} at {}", label_type, at)
+            }
+            Self::InvalidName(reason) => write!(f, "invalid name: {}", reason),
+            Self::InvalidRecord(reason) => write!(f, "invalid record: {}", reason),
+            Self::InvalidZone(reason) => write!(f, "invalid zone: {}", reason),
+            Self::InvalidKey(reason) => write!(f, "invalid key: {}", reason),
+            Self::CharacterStringTooLong(len) => {
+                write!(f, "character-string of {} octets exceeds 255", len)
+            }
+            Self::InvalidTypeBitmap(reason) => write!(f, "invalid type bitmap: {}", reason),
+            Self::RecordLength {
+                r_type,
+                rd_len,
+                read,
+            } => write!(
+                f,
+                "record data of type {} spans {} octets, rd_len says {}",
+                r_type, read, rd_len
+            ),
+            Self::Transfer(reason) => write!(f, "zone transfer failed: {}", reason),
+            Self::Tsig(error) => match error {
+                16 => write!(f, "TSIG error BADSIG"),
+                17 => write!(f, "TSIG error BADKEY"),
+                18 => write!(f, "TSIG error BADTIME"),
+                22 => write!(f, "TSIG error BADTRUNC"),
+                other => write!(f, "TSIG error {}", other),
+            },
+            Self::InvalidCookie(len) => write!(f, "COOKIE option of {} octets", len),
+            Self::Update(r_code) => write!(f, "update failed with {:?}", r_code),
+            Self::IdMismatch { expected, received } => {
+                write!(
+                    f,
+                    "response id {} does not match query id {}",
+                    received, expected
+                )
+            }
+            Self::Resolve(reason) => write!(f, "resolution failed: {}", reason),
+            Self::Lookup { name, r_code } => {
+                write!(f, "lookup of {} failed with {:?}", name, r_code)
+            }
+            Self::QuestionMismatch => write!(f, "response question does not match the query"),
+            Self::Syntax { line, reason } => write!(f, "line {}: {}", line, reason),
+            Self::Io(err) => write!(f, "i/o error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
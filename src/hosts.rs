@@ -0,0 +1,159 @@
+use super::dns::{DnsPacket, DnsQuestion, DnsRecord, QueryType, RecordData};
+use super::name::Name;
+use super::zone::parse_records;
+use super::{Error, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::IpAddr;
+
+/// TTL of records from hosts files, nothing is cached so edits show at once
+pub const HOSTS_TTL: u32 = 0;
+
+/// Names pinned locally, answered ahead of everything else the server does.
+///
+/// Hosts files give the names on each line A or AAAA records, and the
+/// address a PTR record back to the first of them. Static lists hold any
+/// records in master file format, names fully qualified.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Hosts {
+    records: BTreeMap<Name, Vec<DnsRecord>>,
+}
+
+impl Hosts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// add a record, an identical one already present is replaced
+    pub fn insert(&mut self, record: DnsRecord) {
+        let records: &mut Vec<DnsRecord> = self.records.entry(record.label.clone()).or_default();
+        records.retain(|r| !(r.r_type == record.r_type && r.r_data == record.r_data));
+        records.push(record);
+    }
+
+    /// records pinned for `name`
+    pub fn records_at(&self, name: &Name) -> &[DnsRecord] {
+        self.records
+            .get(name)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// add the entries of a file in `/etc/hosts` format: an address, then
+    /// its names, `#` starting a comment
+    pub fn parse_hosts(&mut self, text: &str) -> Result<()> {
+        for (index, line) in text.lines().enumerate() {
+            let syntax = |reason: String| Error::Syntax {
+                line: index + 1,
+                reason,
+            };
+            let mut tokens = line
+                .split('#')
+                .next()
+                .unwrap_or_default()
+                .split_whitespace();
+            let addr: &str = match tokens.next() {
+                Some(addr) => addr,
+                None => continue,
+            };
+            // a scope as in fe80::1%lo0 only matters to the host itself
+            let addr: IpAddr = addr
+                .split('%')
+                .next()
+                .unwrap_or_default()
+                .parse()
+                .map_err(|_| syntax(format!("invalid address {}", addr)))?;
+            let names: Vec<Name> = tokens
+                .map(|name| {
+                    name.parse()
+                        .map_err(|_| syntax(format!("invalid name {}", name)))
+                })
+                .collect::<Result<Vec<Name>>>()?;
+            let first: &Name = match names.first() {
+                Some(first) => first,
+                None => return Err(syntax(format!("{} has no names", addr))),
+            };
+            let r_data: RecordData = match addr {
+                IpAddr::V4(addr) => RecordData::IPADDR(addr),
+                IpAddr::V6(addr) => RecordData::AAAA(addr),
+            };
+            // the first line naming an address wins, as with the resolver
+            let reverse: Name = reverse_name(addr);
+            if self.records_at(&reverse).is_empty() {
+                self.insert(DnsRecord::new(
+                    reverse,
+                    HOSTS_TTL,
+                    RecordData::PTR(first.clone()),
+                ));
+            }
+            for name in &names {
+                self.insert(DnsRecord::new(name.clone(), HOSTS_TTL, r_data.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// add records in master file format
+    pub fn parse_records(&mut self, text: &str) -> Result<()> {
+        for record in parse_records(text, &Name::root())? {
+            self.insert(record);
+        }
+        Ok(())
+    }
+
+    pub fn load_hosts(&mut self, file_name: &str) -> Result<()> {
+        self.parse_hosts(&fs::read_to_string(file_name)?)
+    }
+
+    pub fn load_records(&mut self, file_name: &str) -> Result<()> {
+        self.parse_records(&fs::read_to_string(file_name)?)
+    }
+
+    /// answer `question` from the pinned records, returning whether it was
+    ///
+    /// Addresses and PTRs of a pinned name come from here alone, even when
+    /// there are none. Other types are answered when pinned too.
+    pub fn answer(&self, question: &DnsQuestion, response: &mut DnsPacket) -> bool {
+        let records: &[DnsRecord] = self.records_at(&question.label);
+        let matching =
+            |r: &&DnsRecord| question.q_type == QueryType::ANY || r.r_type == question.q_type;
+        let pinned: bool = matches!(
+            question.q_type,
+            QueryType::A | QueryType::AAAA | QueryType::PTR | QueryType::ANY
+        ) || records.iter().any(|r| matching(&r));
+        if records.is_empty() || !pinned {
+            return false;
+        }
+        response.header.aa = true;
+        response
+            .answers
+            .extend(records.iter().filter(matching).cloned());
+        true
+    }
+}
+
+/// owner of the PTR record for `addr` under in-addr.arpa or ip6.arpa
+pub fn reverse_name(addr: IpAddr) -> Name {
+    let labels: Vec<String> = match addr {
+        IpAddr::V4(addr) => addr
+            .octets()
+            .iter()
+            .rev()
+            .map(u8::to_string)
+            .chain(["in-addr", "arpa"].map(String::from))
+            .collect(),
+        IpAddr::V6(addr) => addr
+            .octets()
+            .iter()
+            .rev()
+            .flat_map(|octet| [octet & 0x0F, octet >> 4])
+            .map(|nibble| format!("{:x}", nibble))
+            .chain(["ip6", "arpa"].map(String::from))
+            .collect(),
+    };
+    Name::from_labels(labels).expect("reverse names are short")
+}
@@ -0,0 +1,202 @@
+use super::dns::{DnsRecord, QueryType, RecordData};
+use super::name::Name;
+use super::zone::parse_records;
+use super::{Error, Result};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use std::io;
+
+/// diffs kept per zone before the oldest are dropped
+pub const DEFAULT_JOURNAL_SIZE: usize = 100;
+
+/// The changes taking a zone from one serial to the next, as IXFR sends
+/// them, RFC 1995 §4.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diff {
+    /// SOA before the change
+    pub old_soa: DnsRecord,
+    pub deleted: Vec<DnsRecord>,
+    /// SOA after the change
+    pub new_soa: DnsRecord,
+    pub added: Vec<DnsRecord>,
+}
+
+fn serial(soa: &DnsRecord) -> u32 {
+    match soa.r_data {
+        RecordData::SOA { serial, .. } => serial,
+        _ => 0,
+    }
+}
+
+impl Diff {
+    pub fn old_serial(&self) -> u32 {
+        serial(&self.old_soa)
+    }
+
+    pub fn new_serial(&self) -> u32 {
+        serial(&self.new_soa)
+    }
+
+    /// this change followed by `next` as a single one, records added by one
+    /// and deleted by the other cancel out
+    pub fn then(self, next: Diff) -> Diff {
+        let deleted: Vec<DnsRecord> = self
+            .deleted
+            .iter()
+            .chain(next.deleted.iter().filter(|r| !self.added.contains(r)))
+            .filter(|r| !next.added.contains(r))
+            .cloned()
+            .collect();
+        let added: Vec<DnsRecord> = self
+            .added
+            .iter()
+            .filter(|r| !next.deleted.contains(r))
+            .chain(next.added.iter())
+            .filter(|r| !self.deleted.contains(r))
+            .cloned()
+            .collect();
+        Diff {
+            old_soa: self.old_soa,
+            deleted,
+            new_soa: next.new_soa,
+            added,
+        }
+    }
+
+    /// the records in IXFR order: old SOA, deletions, new SOA, additions
+    pub fn records(&self) -> impl Iterator<Item = &DnsRecord> {
+        std::iter::once(&self.old_soa)
+            .chain(self.deleted.iter())
+            .chain(std::iter::once(&self.new_soa))
+            .chain(self.added.iter())
+    }
+
+    /// split a sequence of diffs in IXFR order, without the SOA that opens
+    /// and closes an IXFR response
+    pub fn from_records(records: Vec<DnsRecord>) -> Result<Vec<Diff>> {
+        let mut diffs: Vec<Diff> = Vec::new();
+        let mut records = records.into_iter().peekable();
+        let invalid = || Error::InvalidZone("diff sequence does not alternate SOAs".to_string());
+        while let Some(old_soa) = records.next() {
+            if old_soa.r_type != QueryType::SOA {
+                return Err(invalid());
+            }
+            let mut deleted: Vec<DnsRecord> = Vec::new();
+            while let Some(record) = records.next_if(|r| r.r_type != QueryType::SOA) {
+                deleted.push(record);
+            }
+            let new_soa: DnsRecord = records.next().ok_or_else(invalid)?;
+            let mut added: Vec<DnsRecord> = Vec::new();
+            while let Some(record) = records.next_if(|r| r.r_type != QueryType::SOA) {
+                added.push(record);
+            }
+            diffs.push(Diff {
+                old_soa,
+                deleted,
+                new_soa,
+                added,
+            });
+        }
+        Ok(diffs)
+    }
+}
+
+/// Recent diffs of a zone, oldest first, each starting at the serial the
+/// one before ends at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Journal {
+    diffs: VecDeque<Diff>,
+    limit: usize,
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Self::new(DEFAULT_JOURNAL_SIZE)
+    }
+}
+
+impl Journal {
+    /// journal keeping at most `limit` diffs
+    pub fn new(limit: usize) -> Self {
+        Self {
+            diffs: VecDeque::new(),
+            limit,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.diffs.is_empty()
+    }
+
+    pub fn diffs(&self) -> impl Iterator<Item = &Diff> {
+        self.diffs.iter()
+    }
+
+    /// serial the newest diff ends at
+    pub fn last_serial(&self) -> Option<u32> {
+        self.diffs.back().map(Diff::new_serial)
+    }
+
+    /// append a diff, history is dropped when it does not continue the
+    /// newest one
+    pub fn push(&mut self, diff: Diff) {
+        if self
+            .last_serial()
+            .is_some_and(|last| last != diff.old_serial())
+        {
+            self.diffs.clear();
+        }
+        self.diffs.push_back(diff);
+        while self.diffs.len() > self.limit {
+            self.diffs.pop_front();
+        }
+    }
+
+    /// the diffs from `serial` to the newest, `None` when the history does
+    /// not reach back that far
+    pub fn since(&self, serial: u32) -> Option<Vec<&Diff>> {
+        let start: usize = self
+            .diffs
+            .iter()
+            .position(|diff| diff.old_serial() == serial)?;
+        Some(self.diffs.range(start..).collect())
+    }
+
+    /// the diffs from `serial` to the newest condensed into one
+    pub fn condensed(&self, serial: u32) -> Option<Diff> {
+        self.since(serial)?.into_iter().cloned().reduce(Diff::then)
+    }
+
+    /// read a journal written by `save`, a missing file gives an empty one
+    pub fn load(file_name: &str, origin: &Name) -> Result<Self> {
+        let text: String = match fs::read_to_string(file_name) {
+            Ok(text) => text,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(error) => return Err(error.into()),
+        };
+        let mut journal: Journal = Journal::default();
+        for diff in Diff::from_records(parse_records(&text, origin)?)? {
+            journal.push(diff);
+        }
+        Ok(journal)
+    }
+
+    pub fn save(&self, file_name: &str) -> Result<()> {
+        fs::write(file_name, self.to_string())?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for Journal {
+    /// every diff in IXFR order as master file lines
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for diff in &self.diffs {
+            writeln!(f, "; {} -> {}", diff.old_serial(), diff.new_serial())?;
+            for record in diff.records() {
+                writeln!(f, "{}", record)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,3 +1,27 @@
-pub type Result<T> = std::result::Result<T, ()>;
+pub type Result<T> = std::result::Result<T, Error>;
+pub mod acl;
+#[cfg(feature = "async")]
+pub mod async_client;
+pub mod blocklist;
+pub mod cache;
+pub mod client;
+pub mod config;
+pub mod cookie;
 pub mod dns;
+pub mod dnssec;
+pub mod error;
+pub mod hosts;
+pub mod journal;
+pub mod name;
 pub mod parser;
+pub mod recursor;
+pub mod resolver;
+pub mod rpz;
+pub mod rrl;
+pub mod serial;
+pub mod server;
+pub mod tsig;
+pub mod update;
+pub mod zone;
+
+pub use error::Error;
@@ -0,0 +1,366 @@
+use super::{Error, Result};
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+/// maximum length of a single label in octets
+pub const MAX_LABEL_LEN: usize = 63;
+/// maximum length of a name in wire format, length octets and root label included
+pub const MAX_NAME_LEN: usize = 255;
+
+/// A domain name stored as its sequence of wire-format labels.
+///
+/// Labels are kept exactly as they appeared on the wire or in presentation
+/// format, so case and arbitrary octets survive a round-trip. Comparison and
+/// hashing ignore ASCII case as required by RFC 4343, and ordering follows
+/// the canonical DNS name order of RFC 4034 §6.1.
+#[derive(Clone, Default)]
+pub struct Name {
+    /// labels from leftmost to rightmost, the root label is implicit
+    labels: Vec<Vec<u8>>,
+}
+
+impl Name {
+    /// the root name `.`
+    pub fn root() -> Self {
+        Self { labels: Vec::new() }
+    }
+
+    /// build a name from labels ordered leftmost first
+    pub fn from_labels<I, L>(labels: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = L>,
+        L: AsRef<[u8]>,
+    {
+        let mut name: Name = Self::root();
+        for label in labels {
+            name.push_label(label.as_ref())?;
+        }
+        Ok(name)
+    }
+
+    /// append a label on the right hand side, used while decoding
+    pub(crate) fn push_label(&mut self, label: &[u8]) -> Result<()> {
+        if label.is_empty() {
+            return Err(Error::EmptyLabel);
+        }
+        if label.len() > MAX_LABEL_LEN {
+            return Err(Error::LabelTooLong(label.len()));
+        }
+        let wire_len: usize = self.wire_len() + label.len() + 1;
+        if wire_len > MAX_NAME_LEN {
+            return Err(Error::NameTooLong(wire_len));
+        }
+        self.labels.push(label.to_vec());
+        Ok(())
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    /// number of labels, the root label not counted
+    pub fn num_labels(&self) -> usize {
+        self.labels.len()
+    }
+
+    /// labels ordered leftmost first
+    pub fn labels(&self) -> impl DoubleEndedIterator<Item = &[u8]> + ExactSizeIterator {
+        self.labels.iter().map(|label| label.as_slice())
+    }
+
+    /// length of the uncompressed wire format
+    pub fn wire_len(&self) -> usize {
+        self.labels
+            .iter()
+            .map(|label| label.len() + 1)
+            .sum::<usize>()
+            + 1
+    }
+
+    /// the name with its leftmost label removed, `None` for the root
+    pub fn parent(&self) -> Option<Name> {
+        match self.is_root() {
+            true => None,
+            false => Some(Self {
+                labels: self.labels[1..].to_vec(),
+            }),
+        }
+    }
+
+    /// the name with `label` prepended
+    pub fn child(&self, label: &[u8]) -> Result<Name> {
+        let mut name: Name = Self::from_labels([label])?;
+        for label in &self.labels {
+            name.push_label(label)?;
+        }
+        Ok(name)
+    }
+
+    /// concatenate `suffix` to the right of this name
+    pub fn append(&self, suffix: &Name) -> Result<Name> {
+        let mut name: Name = self.clone();
+        for label in &suffix.labels {
+            name.push_label(label)?;
+        }
+        Ok(name)
+    }
+
+    /// whether this name equals `suffix` or lies below it
+    pub fn ends_with(&self, suffix: &Name) -> bool {
+        if suffix.labels.len() > self.labels.len() {
+            return false;
+        }
+        self.labels
+            .iter()
+            .rev()
+            .zip(suffix.labels.iter().rev())
+            .all(|(a, b)| a.eq_ignore_ascii_case(b))
+    }
+
+    /// the name made of the rightmost `num_labels` labels
+    pub fn trim_to(&self, num_labels: usize) -> Name {
+        let skip: usize = self.labels.len().saturating_sub(num_labels);
+        Self {
+            labels: self.labels[skip..].to_vec(),
+        }
+    }
+
+    /// the labels left of `suffix`, `None` if the name does not end with it
+    pub fn strip_suffix(&self, suffix: &Name) -> Option<Name> {
+        match self.ends_with(suffix) {
+            true => Some(Self {
+                labels: self.labels[..self.labels.len() - suffix.labels.len()].to_vec(),
+            }),
+            false => None,
+        }
+    }
+
+    /// copy of the name with every ASCII letter lowercased
+    pub fn to_lowercase(&self) -> Name {
+        Self {
+            labels: self
+                .labels
+                .iter()
+                .map(|label| label.to_ascii_lowercase())
+                .collect(),
+        }
+    }
+
+    /// copy of the name with the case of its letters taken from `bits`,
+    /// uppercase where the bit is set, for 0x20 encoding
+    pub fn with_case(&self, bits: &[u8]) -> Name {
+        let mut bit: usize = 0;
+        Self {
+            labels: self
+                .labels
+                .iter()
+                .map(|label| {
+                    label
+                        .iter()
+                        .map(|octet| {
+                            if !octet.is_ascii_alphabetic() {
+                                return *octet;
+                            }
+                            let upper: bool = bits
+                                .get(bit / 8)
+                                .is_some_and(|byte| byte & (1 << (bit % 8)) != 0);
+                            bit += 1;
+                            match upper {
+                                true => octet.to_ascii_uppercase(),
+                                false => octet.to_ascii_lowercase(),
+                            }
+                        })
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    /// whether the names are equal octet for octet, unlike `==` which
+    /// ignores case
+    pub fn eq_case(&self, other: &Name) -> bool {
+        self.labels == other.labels
+    }
+
+    /// parse a name that may contain Unicode labels, converting them to
+    /// A-labels with UTS #46 mapping and punycode
+    ///
+    /// ASCII input is parsed as is, its case kept for 0x20 randomization,
+    /// and escapes and labels like `_tcp` that UTS #46 would reject allowed.
+    pub fn from_unicode(s: &str) -> Result<Self> {
+        if s.is_ascii() {
+            return s.parse();
+        }
+        match idna::domain_to_ascii(s) {
+            Ok(ascii) => ascii.parse(),
+            Err(err) => Err(Error::InvalidName(format!("{}: {}", s, err))),
+        }
+    }
+
+    /// presentation format with A-labels decoded to U-labels, falls back to
+    /// the ASCII form when a label is not valid punycode
+    pub fn to_unicode(&self) -> String {
+        let ascii: String = self.to_string();
+        let is_a_label = |label: &[u8]| label.len() > 4 && label[..4].eq_ignore_ascii_case(b"xn--");
+        if !self.labels().any(is_a_label) {
+            return ascii;
+        }
+        match idna::domain_to_unicode(&ascii) {
+            (unicode, Ok(())) => unicode,
+            (_, Err(_)) => ascii,
+        }
+    }
+
+    /// uncompressed wire format, root label included
+    pub fn to_wire(&self) -> Vec<u8> {
+        let mut wire: Vec<u8> = Vec::with_capacity(self.wire_len());
+        for label in &self.labels {
+            wire.push(label.len() as u8);
+            wire.extend_from_slice(label);
+        }
+        wire.push(0);
+        wire
+    }
+}
+
+impl PartialEq for Name {
+    fn eq(&self, other: &Self) -> bool {
+        self.labels.len() == other.labels.len()
+            && self
+                .labels
+                .iter()
+                .zip(other.labels.iter())
+                .all(|(a, b)| a.eq_ignore_ascii_case(b))
+    }
+}
+
+impl Eq for Name {}
+
+impl Hash for Name {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.labels.len());
+        for label in &self.labels {
+            state.write_u8(label.len() as u8);
+            for b in label {
+                state.write_u8(b.to_ascii_lowercase());
+            }
+        }
+    }
+}
+
+impl Ord for Name {
+    /// canonical ordering: compare labels right to left as lowercased octet strings
+    fn cmp(&self, other: &Self) -> Ordering {
+        for (a, b) in self.labels.iter().rev().zip(other.labels.iter().rev()) {
+            let ordering: Ordering = a
+                .iter()
+                .map(u8::to_ascii_lowercase)
+                .cmp(b.iter().map(u8::to_ascii_lowercase));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        self.labels.len().cmp(&other.labels.len())
+    }
+}
+
+impl PartialOrd for Name {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl FromStr for Name {
+    type Err = Error;
+
+    /// parse presentation format, `\.`, `\\` and `\DDD` escapes included
+    fn from_str(s: &str) -> Result<Self> {
+        let mut name: Name = Self::root();
+        if s.is_empty() || s == "." {
+            return Ok(name);
+        }
+        let mut label: Vec<u8> = Vec::new();
+        let mut bytes = s.bytes();
+        while let Some(b) = bytes.next() {
+            match b {
+                b'.' => {
+                    name.push_label(&label)?;
+                    label.clear();
+                }
+                b'\\' => match bytes.next() {
+                    Some(d) if d.is_ascii_digit() => {
+                        let mut value: u32 = (d - b'0') as u32;
+                        for _ in 0..2 {
+                            match bytes.next() {
+                                Some(d) if d.is_ascii_digit() => {
+                                    value = value * 10 + (d - b'0') as u32;
+                                }
+                                _ => {
+                                    return Err(Error::InvalidName(format!(
+                                        "invalid \\DDD escape in {}",
+                                        s
+                                    )));
+                                }
+                            }
+                        }
+                        if value > 0xFF {
+                            return Err(Error::InvalidName(format!(
+                                "escaped value out of range in {}",
+                                s
+                            )));
+                        }
+                        label.push(value as u8);
+                    }
+                    Some(c) => label.push(c),
+                    None => {
+                        return Err(Error::InvalidName(format!("dangling escape in {}", s)));
+                    }
+                },
+                _ => label.push(b),
+            }
+        }
+        // a trailing dot marks the name as absolute and leaves no label behind
+        if !label.is_empty() {
+            name.push_label(&label)?;
+        }
+        Ok(name)
+    }
+}
+
+impl fmt::Display for Name {
+    /// presentation format without the trailing dot, the root prints as `.`
+    ///
+    /// The alternate form `{:#}` prints the fully qualified name with its
+    /// trailing dot as used in zone files.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_root() {
+            return f.write_str(".");
+        }
+        for (i, label) in self.labels.iter().enumerate() {
+            if i > 0 {
+                f.write_str(".")?;
+            }
+            for &b in label {
+                match b {
+                    b'.' | b'\\' | b'"' | b'(' | b')' | b';' | b'@' | b'$' => {
+                        write!(f, "\\{}", b as char)?
+                    }
+                    0x21..=0x7E => write!(f, "{}", b as char)?,
+                    _ => write!(f, "\\{:03}", b)?,
+                }
+            }
+        }
+        if f.alternate() {
+            f.write_str(".")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\"", self)
+    }
+}
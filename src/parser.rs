@@ -1,38 +1,61 @@
-use super::Result;
+use super::name::Name;
+use super::{Error, Result};
+use std::fmt;
 use std::fs::File;
 use std::io::{BufReader, Read};
 use std::ops::Range;
 
+/// largest message allowed over plain UDP without EDNS
+pub const MAX_UDP_SIZE: usize = 512;
+/// largest message any transport can carry
+pub const MAX_MESSAGE_SIZE: usize = 65535;
+
 #[derive(Debug)]
 pub struct DnsBytePacketBuffer {
-    buf: [u8; 512],
+    buf: Vec<u8>,
     pos: usize,
     bytes_read: usize,
 }
 
+impl Default for DnsBytePacketBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl DnsBytePacketBuffer {
     pub fn new() -> Self {
+        Self::with_size(MAX_UDP_SIZE)
+    }
+
+    /// buffer able to hold a message of up to `size` bytes
+    pub fn with_size(size: usize) -> Self {
         Self {
-            buf: [0; 512],
+            buf: vec![0; size.min(MAX_MESSAGE_SIZE)],
             pos: 0,
             bytes_read: 0,
         }
     }
 
+    /// buffer holding a copy of `bytes`, anything past 65535 bytes is dropped
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut dbuf: Self = Self::with_size(bytes.len().max(MAX_UDP_SIZE));
+        let len: usize = bytes.len().min(dbuf.buf.len());
+        dbuf.buf[..len].copy_from_slice(&bytes[..len]);
+        dbuf.bytes_read = len;
+        dbuf
+    }
+
     pub fn set_bytes_read(&mut self, bytes_read: usize) {
         self.bytes_read = bytes_read;
     }
 
     pub fn load(file_name: &str) -> Result<Self> {
-        let file: File = File::open(file_name).expect("Unable to find the file");
-        let mut reader: BufReader<File> = BufReader::new(file);
-        let mut buf: [u8; 512] = [0u8; 512];
-        let bytes_read: usize = reader.read(&mut buf).expect("Unable to read from file");
-        Ok(DnsBytePacketBuffer {
-            buf,
-            bytes_read,
-            pos: 0,
-        })
+        let file: File = File::open(file_name)?;
+        let reader: BufReader<File> = BufReader::new(file);
+        let mut buf: Vec<u8> = Vec::new();
+        reader.take(MAX_MESSAGE_SIZE as u64).read_to_end(&mut buf)?;
+        Ok(Self::from_bytes(&buf))
     }
 
     pub fn get_buf(&mut self) -> &mut [u8] {
@@ -43,6 +66,11 @@ impl DnsBytePacketBuffer {
         self.pos
     }
 
+    /// the bytes written so far
+    pub fn written(&self) -> &[u8] {
+        &self.buf[..self.pos]
+    }
+
     /// get byte and change position
     pub fn read(&mut self) -> Result<u8> {
         match self.pos < self.bytes_read {
@@ -51,16 +79,12 @@ impl DnsBytePacketBuffer {
                 self.pos += 1;
                 Ok(data)
             }
-            false => {
-                eprintln!("No more bytes to read");
-                Err(())
-            }
+            false => Err(Error::EndOfBuffer(self.pos)),
         }
     }
     fn seek(&mut self, pos: usize) -> Result<()> {
         if pos > self.bytes_read {
-            eprintln!("can't seek more than bytes read");
-            Err(())
+            Err(Error::EndOfBuffer(pos))
         } else {
             self.pos = pos;
             Ok(())
@@ -74,10 +98,7 @@ impl DnsBytePacketBuffer {
                 let data: u16 = ((self.read()? as u16) << 8) | (self.read()? as u16);
                 Ok(data)
             }
-            false => {
-                eprintln!("No more u16 to read");
-                Err(())
-            }
+            false => Err(Error::EndOfBuffer(self.pos)),
         }
     }
 
@@ -85,10 +106,7 @@ impl DnsBytePacketBuffer {
     pub fn get(&self, index: usize) -> Result<u8> {
         match index < self.bytes_read {
             true => Ok(self.buf[index]),
-            false => {
-                eprintln!("Can't read at index: {}", index);
-                Err(())
-            }
+            false => Err(Error::EndOfBuffer(index)),
         }
     }
 
@@ -99,10 +117,7 @@ impl DnsBytePacketBuffer {
                 self.pos += steps;
                 Ok(())
             }
-            false => {
-                eprintln!("Can't step for: {}", steps);
-                Err(())
-            }
+            false => Err(Error::EndOfBuffer(self.pos + steps)),
         }
     }
 
@@ -116,77 +131,90 @@ impl DnsBytePacketBuffer {
                     | (self.read()? as u32);
                 Ok(data)
             }
-            false => {
-                eprintln!("No more u32 to read");
-                Err(())
-            }
+            false => Err(Error::EndOfBuffer(self.pos)),
         }
     }
 
+    /// read `len` bytes and update position
+    pub fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>> {
+        let bytes: Vec<u8> = self.get_range(self.pos, len)?.to_vec();
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    /// read a length prefixed character-string
+    pub fn read_character_string(&mut self) -> Result<Vec<u8>> {
+        let len: u8 = self.read()?;
+        self.read_bytes(len as usize)
+    }
+
     /// get range of bytes without updating the pos
     pub fn get_buf_range(&self, range: Range<usize>) -> Result<&[u8]> {
-        Ok(&self.buf[range])
+        let end: usize = range.end;
+        self.buf.get(range).ok_or(Error::EndOfBuffer(end))
     }
 
     /// get range of bytes without updating the pos
     pub fn get_range(&self, start: usize, len: usize) -> Result<&[u8]> {
-        match start > 0 && start + len < self.bytes_read {
+        match start + len <= self.bytes_read {
             true => Ok(&self.buf[start..start + len]),
-            false => {
-                eprintln!("Can't get range: {} - {}", start, len);
-                Err(())
-            }
+            false => Err(Error::EndOfBuffer(start + len)),
         }
     }
-    /// read the label
-    pub fn read_label(&mut self) -> Result<String> {
+
+    /// read a possibly compressed name starting at the current position
+    ///
+    /// A compression pointer must jump strictly before the label sequence
+    /// that contains it. Every jump therefore lands on a lower offset than the
+    /// one before, which rules out forward references and loops without
+    /// having to count jumps.
+    pub fn read_label(&mut self) -> Result<Name> {
         let mut pos: usize = self.get_pos();
-        let mut jumped: bool = false;
-        let mut jumps: usize = 0;
-        let mut output: String = String::new();
-        let mut delim = "";
-        const MAX_JUMPS: usize = 5;
+        // offset where the label sequence currently being read starts
+        let mut segment_start: usize = pos;
+        // position right after the name as it appears in the packet
+        let mut end: Option<usize> = None;
+        let mut output: Name = Name::root();
         loop {
-            if jumps > MAX_JUMPS {
-                eprintln!("Limits of jumps exceeded: {}", MAX_JUMPS);
-                return Err(());
-            }
             let len: u8 = self.get(pos)?;
-            // if it is a redirection byte then
-            if (len & 0xC0) == 0xC0 {
-                if !jumped {
-                    self.seek(pos + 2)?;
+            match len & 0xC0 {
+                0xC0 => {
+                    let target: usize =
+                        (((len & 0x3F) as usize) << 8) | self.get(pos + 1)? as usize;
+                    if target > pos {
+                        return Err(Error::ForwardPointer { at: pos, target });
+                    }
+                    if target >= segment_start {
+                        return Err(Error::PointerLoop { at: pos, target });
+                    }
+                    end.get_or_insert(pos + 2);
+                    pos = target;
+                    segment_start = target;
                 }
-                let b2: u16 = self.get(pos + 1)? as u16;
-                let offset: u16 = ((0xC0 ^ len as u16) << 8) | b2 as u16;
-                pos = offset as usize;
-
-                jumped = true;
-                jumps += 1;
-                continue;
-            } else {
-                pos += 1;
-                if len == 0 {
-                    break;
+                0x00 => {
+                    pos += 1;
+                    if len == 0 {
+                        break;
+                    }
+                    output.push_label(self.get_range(pos, len as usize)?)?;
+                    pos += len as usize;
+                }
+                label_type => {
+                    return Err(Error::ReservedLabelType {
+                        at: pos,
+                        label_type,
+                    });
                 }
-                output.push_str(delim);
-                let str_buffer: &[u8] = self.get_range(pos, len as usize)?;
-                output.push_str(&String::from_utf8_lossy(str_buffer).to_lowercase());
-                delim = ".";
-                pos += len as usize;
             }
         }
-        if !jumped {
-            self.seek(pos)?;
-        }
+        self.seek(end.unwrap_or(pos))?;
         Ok(output)
     }
 
     /// write operation
     fn write(&mut self, val: u8) -> Result<()> {
-        if self.pos >= 512 {
-            eprintln!("End of buffer");
-            return Err(());
+        if self.pos >= self.buf.len() {
+            return Err(Error::BufferFull(self.pos));
         }
         self.buf[self.pos] = val;
         self.pos += 1;
@@ -211,21 +239,42 @@ impl DnsBytePacketBuffer {
         self.write(((val >> 24) & 0xFF) as u8)?;
         self.write(((val >> 16) & 0xFF) as u8)?;
         self.write(((val >> 8) & 0xFF) as u8)?;
-        self.write(((val >> 0) & 0xFF) as u8)?;
+        self.write((val & 0xFF) as u8)?;
         Ok(())
     }
 
-    /// write the query name into buffer
-    pub fn write_label(&mut self, qname: &str) -> Result<()> {
-        for label in qname.split('.') {
-            let len = label.len();
-            if len > 0x3f {
-                eprintln!("Single label exceeds 63 characters of length");
-                return Err(());
-            }
+    /// write raw bytes
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        for b in bytes {
+            self.write(*b)?;
+        }
+        Ok(())
+    }
 
-            self.write_u8(len as u8)?;
-            for b in label.as_bytes() {
+    /// write a length prefixed character-string of at most 255 bytes
+    pub fn write_character_string(&mut self, bytes: &[u8]) -> Result<()> {
+        if bytes.len() > 0xFF {
+            return Err(Error::CharacterStringTooLong(bytes.len()));
+        }
+        self.write_u8(bytes.len() as u8)?;
+        self.write_bytes(bytes)
+    }
+
+    /// overwrite an already written u16 at `pos` without moving the position
+    pub fn set_u16(&mut self, pos: usize, val: u16) -> Result<()> {
+        if pos + 1 >= self.pos {
+            return Err(Error::EndOfBuffer(pos + 1));
+        }
+        self.buf[pos] = (val >> 8) as u8;
+        self.buf[pos + 1] = (val & 0xFF) as u8;
+        Ok(())
+    }
+
+    /// write the query name into buffer
+    pub fn write_label(&mut self, qname: &Name) -> Result<()> {
+        for label in qname.labels() {
+            self.write_u8(label.len() as u8)?;
+            for b in label {
                 self.write_u8(*b)?;
             }
         }
@@ -235,9 +284,8 @@ impl DnsBytePacketBuffer {
     }
 }
 
-impl std::string::ToString for DnsBytePacketBuffer {
-    fn to_string(&self) -> String {
-        String::from_utf8(self.buf[..self.bytes_read].to_vec())
-            .expect("unable to convert into string ")
+impl fmt::Display for DnsBytePacketBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&String::from_utf8_lossy(&self.buf[..self.bytes_read]))
     }
 }
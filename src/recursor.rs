@@ -0,0 +1,321 @@
+use super::client::Client;
+use super::dns::{DnsPacket, DnsQuestion, DnsRecord, QueryType, RecordData, ResponseCode};
+use super::name::Name;
+use super::{Error, Result};
+use log::debug;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+/// addresses of a.root-servers.net to m.root-servers.net
+pub const ROOT_HINTS: [Ipv4Addr; 13] = [
+    Ipv4Addr::new(198, 41, 0, 4),
+    Ipv4Addr::new(170, 247, 170, 2),
+    Ipv4Addr::new(192, 33, 4, 12),
+    Ipv4Addr::new(199, 7, 91, 13),
+    Ipv4Addr::new(192, 203, 230, 10),
+    Ipv4Addr::new(192, 5, 5, 241),
+    Ipv4Addr::new(192, 112, 36, 4),
+    Ipv4Addr::new(198, 97, 190, 53),
+    Ipv4Addr::new(192, 36, 148, 17),
+    Ipv4Addr::new(192, 58, 128, 30),
+    Ipv4Addr::new(193, 0, 14, 129),
+    Ipv4Addr::new(199, 7, 83, 42),
+    Ipv4Addr::new(202, 12, 27, 33),
+];
+
+/// most minimised queries for one name, RFC 9156 §2.3
+const MAX_MINIMISE_COUNT: usize = 10;
+
+/// minimised queries adding a single label before labels go in bulk
+const MINIMISE_ONE_LAB: usize = 4;
+
+/// queries one resolution may send, name server addresses included
+const MAX_QUERIES: usize = 64;
+
+/// aliases followed before giving up
+const MAX_CNAME_CHAIN: usize = 8;
+
+/// name server names resolved within the resolution of another name
+const MAX_DEPTH: usize = 4;
+
+/// Iterative resolver asking authoritative servers from the root down.
+///
+/// Queries are minimised, RFC 9156: a server is only asked about the next
+/// label or labels below its zone, with QTYPE A, until the full name is
+/// reached. Servers that answer such a query for an empty non-terminal
+/// with NXDOMAIN or an error get the full name instead.
+#[derive(Debug, Clone)]
+pub struct Recursor {
+    hints: Vec<IpAddr>,
+    /// port name servers are asked on
+    port: u16,
+    timeout: Duration,
+    minimise: bool,
+}
+
+impl Recursor {
+    /// resolver starting at the root servers at `hints`
+    pub fn new(hints: Vec<IpAddr>) -> Self {
+        Self {
+            hints,
+            port: 53,
+            timeout: Duration::from_secs(2),
+            minimise: true,
+        }
+    }
+
+    /// resolver starting at the root servers of the IANA root hints
+    pub fn root() -> Self {
+        Self::new(ROOT_HINTS.iter().copied().map(IpAddr::V4).collect())
+    }
+
+    /// port every name server is asked on, 53 by default
+    pub fn set_port(&mut self, port: u16) {
+        self.port = port;
+    }
+
+    /// how long each server gets to answer
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// whether queries are minimised, on by default
+    pub fn set_minimise(&mut self, minimise: bool) {
+        self.minimise = minimise;
+    }
+
+    /// the answer to a query for `q_type` records of `name`, the CNAME
+    /// records leading to it first
+    pub fn resolve(&self, name: &Name, q_type: QueryType) -> Result<DnsPacket> {
+        let mut budget: usize = MAX_QUERIES;
+        self.resolve_within(name, q_type, &mut budget, 0)
+    }
+
+    fn resolve_within(
+        &self,
+        name: &Name,
+        q_type: QueryType,
+        budget: &mut usize,
+        depth: usize,
+    ) -> Result<DnsPacket> {
+        let mut chain: Vec<DnsRecord> = Vec::new();
+        let mut qname: Name = name.clone();
+        for _ in 0..MAX_CNAME_CHAIN {
+            let mut response: DnsPacket = self.iterate(&qname, q_type, budget, depth)?;
+            let target: Name = alias_target(&response.answers, &qname, q_type);
+            let answered: bool = response
+                .answers
+                .iter()
+                .any(|record| record.label == target && record.r_type == q_type);
+            if answered || target == qname || response.header.r_code != ResponseCode::NOERROR {
+                chain.append(&mut response.answers);
+                response.answers = chain;
+                response.questions = vec![DnsQuestion::new(name.clone(), q_type)];
+                return Ok(response);
+            }
+            // the alias leads out of the zone that answered
+            chain.append(&mut response.answers);
+            qname = target;
+        }
+        Err(Error::Resolve(format!("CNAME chain of {} too long", name)))
+    }
+
+    /// follow referrals from the root to the servers answering `qname`
+    fn iterate(
+        &self,
+        qname: &Name,
+        q_type: QueryType,
+        budget: &mut usize,
+        depth: usize,
+    ) -> Result<DnsPacket> {
+        let mut zone: Name = Name::root();
+        let mut servers: Vec<IpAddr> = self.hints.clone();
+        // the name minimised queries have reached, RFC 9156 §3
+        let mut child: Name = Name::root();
+        let mut minimise: bool = self.minimise;
+        let mut iteration: usize = 0;
+        loop {
+            // the last label goes out with the real QTYPE, which also has a
+            // DS asked of the parent holding it
+            let next: Name = next_child(qname, &child, iteration);
+            let full: bool = !minimise || next.num_labels() >= qname.num_labels();
+            let (ask, ask_type): (Name, QueryType) = match full {
+                true => (qname.clone(), q_type),
+                false => {
+                    iteration += 1;
+                    (next, QueryType::A)
+                }
+            };
+            let response: DnsPacket = self.ask(&servers, &ask, ask_type, budget)?;
+            if let Some((cut, hosts)) = referral(&response, &zone, &ask) {
+                debug!("{} refers {} to {}", zone, ask, cut);
+                servers = self.addresses(&response, &hosts, budget, depth)?;
+                zone = cut.clone();
+                child = cut;
+                continue;
+            }
+            if full {
+                return Ok(response);
+            }
+            match response.header.r_code {
+                // the name is an alias, the full name is asked where it lives
+                ResponseCode::NOERROR
+                    if response
+                        .answers
+                        .iter()
+                        .any(|record| record.r_type == QueryType::CNAME) =>
+                {
+                    minimise = false
+                }
+                // no zone cut at `ask`, one further down maybe
+                ResponseCode::NOERROR => child = ask,
+                // an empty non-terminal denied or an error, which broken
+                // servers answer minimised queries with
+                r_code => {
+                    debug!(
+                        "{} answered minimised {} with {:?}, asking for {}",
+                        zone, ask, r_code, qname
+                    );
+                    minimise = false
+                }
+            }
+        }
+    }
+
+    /// ask the first of `servers` that answers, those failing to serve the
+    /// zone skipped while others are left
+    fn ask(
+        &self,
+        servers: &[IpAddr],
+        name: &Name,
+        q_type: QueryType,
+        budget: &mut usize,
+    ) -> Result<DnsPacket> {
+        let mut last: Result<DnsPacket> = Err(Error::Resolve(format!(
+            "no name server to ask for {}",
+            name
+        )));
+        for server in servers {
+            if *budget == 0 {
+                return Err(Error::Resolve(format!("too many queries for {}", name)));
+            }
+            *budget -= 1;
+            let mut client: Client = Client::new(SocketAddr::new(*server, self.port));
+            client.set_timeout(self.timeout);
+            last = client.iterate(name, q_type);
+            match &last {
+                Ok(response)
+                    if !matches!(
+                        response.header.r_code,
+                        ResponseCode::SERVERFAILURE | ResponseCode::REFUSED
+                    ) =>
+                {
+                    break
+                }
+                _ => {}
+            }
+        }
+        last
+    }
+
+    /// addresses of the name servers `hosts`, from the glue of `referral`
+    /// or resolved when it has none
+    fn addresses(
+        &self,
+        referral: &DnsPacket,
+        hosts: &[Name],
+        budget: &mut usize,
+        depth: usize,
+    ) -> Result<Vec<IpAddr>> {
+        let glue: Vec<IpAddr> = referral
+            .additionals
+            .iter()
+            .filter(|record| hosts.contains(&record.label))
+            .filter_map(|record| match &record.r_data {
+                RecordData::IPADDR(addr) => Some(IpAddr::V4(*addr)),
+                RecordData::AAAA(addr) => Some(IpAddr::V6(*addr)),
+                _ => None,
+            })
+            .collect();
+        if !glue.is_empty() || depth >= MAX_DEPTH {
+            return Ok(glue);
+        }
+        for host in hosts {
+            let Ok(response) = self.resolve_within(host, QueryType::A, budget, depth + 1) else {
+                continue;
+            };
+            let addresses: Vec<IpAddr> = response
+                .answers
+                .iter()
+                .filter_map(|record| match &record.r_data {
+                    RecordData::IPADDR(addr) => Some(IpAddr::V4(*addr)),
+                    _ => None,
+                })
+                .collect();
+            if !addresses.is_empty() {
+                return Ok(addresses);
+            }
+        }
+        Ok(Vec::new())
+    }
+}
+
+/// `child` with the next labels of `qname` added, one at a time for the
+/// first MINIMISE_ONE_LAB queries, then as many as it takes to reach
+/// `qname` within MAX_MINIMISE_COUNT, RFC 9156 §2.3
+fn next_child(qname: &Name, child: &Name, iteration: usize) -> Name {
+    let remaining: usize = qname.num_labels().saturating_sub(child.num_labels());
+    let add: usize = match iteration < MINIMISE_ONE_LAB {
+        true => 1,
+        false => remaining.div_ceil(MAX_MINIMISE_COUNT.saturating_sub(iteration).max(1)),
+    };
+    qname.trim_to(child.num_labels() + add.max(1))
+}
+
+/// the zone cut below `zone` and its name servers that `response` to a
+/// query for `name` refers to
+fn referral(response: &DnsPacket, zone: &Name, name: &Name) -> Option<(Name, Vec<Name>)> {
+    if response.header.r_code != ResponseCode::NOERROR
+        || response.header.aa
+        || !response.answers.is_empty()
+    {
+        return None;
+    }
+    let cut: &Name = &response
+        .authorities
+        .iter()
+        .find(|record| record.r_type == QueryType::NS)?
+        .label;
+    // referrals sideways or up would never end
+    if cut.num_labels() <= zone.num_labels() || !cut.ends_with(zone) || !name.ends_with(cut) {
+        return None;
+    }
+    let hosts: Vec<Name> = response
+        .authorities
+        .iter()
+        .filter(|record| &record.label == cut)
+        .filter_map(|record| match &record.r_data {
+            RecordData::NS(host) => Some(host.clone()),
+            _ => None,
+        })
+        .collect();
+    Some((cut.clone(), hosts))
+}
+
+/// the end of the CNAME chain starting at `name` in `answers`
+pub(crate) fn alias_target(answers: &[DnsRecord], name: &Name, q_type: QueryType) -> Name {
+    let mut target: Name = name.clone();
+    if q_type == QueryType::CNAME {
+        return target;
+    }
+    for _ in 0..MAX_CNAME_CHAIN {
+        match answers.iter().find_map(|record| match &record.r_data {
+            RecordData::CNAME(next) if record.label == target => Some(next.clone()),
+            _ => None,
+        }) {
+            Some(next) => target = next,
+            None => break,
+        }
+    }
+    target
+}
@@ -0,0 +1,213 @@
+use super::client::{random_bytes, Client};
+use super::dns::{DnsPacket, DnsRecord, QueryType, RecordData, ResponseCode};
+use super::hosts::reverse_name;
+use super::name::Name;
+use super::recursor::alias_target;
+use super::{Error, Result};
+use std::net::{IpAddr, SocketAddr};
+
+/// aliases followed before giving up
+const MAX_CNAME_CHAIN: usize = 8;
+
+/// A mail exchanger of a domain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mx {
+    /// lower is tried first
+    pub preference: u16,
+    pub exchange: Name,
+}
+
+/// A server offering a service, RFC 2782.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Srv {
+    /// lower is tried first
+    pub priority: u16,
+    /// relative share among the targets of the same priority
+    pub weight: u16,
+    pub port: u16,
+    pub target: Name,
+}
+
+/// Lookups of addresses, host names, mail exchangers, services and text
+/// through a recursive server.
+///
+/// Every lookup follows CNAME records, asking again for the name an alias
+/// points at when the server did not include its records. A name that
+/// exists without records of the type asked for has none, an error such
+/// as NXDOMAIN is `Error::Lookup`.
+#[derive(Debug)]
+pub struct Resolver {
+    client: Client,
+}
+
+impl Resolver {
+    /// a resolver asking the recursive server at `server`
+    pub fn new(server: SocketAddr) -> Self {
+        Self::with_client(Client::new(server))
+    }
+
+    /// a resolver asking through `client`, with its timeout and TSIG key
+    pub fn with_client(client: Client) -> Self {
+        Self { client }
+    }
+
+    /// the `q_type` records of `name`, or of the name its aliases lead to
+    pub fn lookup(&self, name: &Name, q_type: QueryType) -> Result<Vec<DnsRecord>> {
+        let mut qname: Name = name.clone();
+        for _ in 0..MAX_CNAME_CHAIN {
+            let response: DnsPacket = self.client.query(&qname, q_type, false)?;
+            if response.header.r_code != ResponseCode::NOERROR {
+                return Err(Error::Lookup {
+                    name: qname,
+                    r_code: response.header.r_code,
+                });
+            }
+            let target: Name = alias_target(&response.answers, &qname, q_type);
+            let records: Vec<DnsRecord> = response
+                .answers
+                .into_iter()
+                .filter(|record| record.label == target && record.r_type == q_type)
+                .collect();
+            if !records.is_empty() || target == qname {
+                return Ok(records);
+            }
+            qname = target;
+        }
+        Err(Error::Resolve(format!("CNAME chain of {} too long", name)))
+    }
+
+    /// the IPv4 and then the IPv6 addresses of `name`
+    pub fn lookup_ip(&self, name: &Name) -> Result<Vec<IpAddr>> {
+        let mut addresses: Vec<IpAddr> = Vec::new();
+        for q_type in [QueryType::A, QueryType::AAAA] {
+            addresses.extend(self.lookup(name, q_type)?.into_iter().filter_map(|record| {
+                match record.r_data {
+                    RecordData::IPADDR(addr) => Some(IpAddr::V4(addr)),
+                    RecordData::AAAA(addr) => Some(IpAddr::V6(addr)),
+                    _ => None,
+                }
+            }));
+        }
+        Ok(addresses)
+    }
+
+    /// the host names of `addr`, from the PTR records under in-addr.arpa
+    /// or ip6.arpa
+    pub fn reverse_lookup(&self, addr: IpAddr) -> Result<Vec<Name>> {
+        Ok(self
+            .lookup(&reverse_name(addr), QueryType::PTR)?
+            .into_iter()
+            .filter_map(|record| match record.r_data {
+                RecordData::PTR(host) => Some(host),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// the mail exchangers of `name`, most preferred first
+    pub fn mx_lookup(&self, name: &Name) -> Result<Vec<Mx>> {
+        let mut exchangers: Vec<Mx> = self
+            .lookup(name, QueryType::MX)?
+            .into_iter()
+            .filter_map(|record| match record.r_data {
+                RecordData::MX {
+                    preference,
+                    exchange,
+                } => Some(Mx {
+                    preference,
+                    exchange,
+                }),
+                _ => None,
+            })
+            .collect();
+        exchangers.sort_by_key(|mx| mx.preference);
+        Ok(exchangers)
+    }
+
+    /// the servers of the service `name`, such as `_imaps._tcp.example`,
+    /// in the order to try them, RFC 2782
+    ///
+    /// A single target of `.` says the service is not offered there, and
+    /// no servers are returned.
+    pub fn srv_lookup(&self, name: &Name) -> Result<Vec<Srv>> {
+        let servers: Vec<Srv> = self
+            .lookup(name, QueryType::SRV)?
+            .into_iter()
+            .filter_map(|record| match record.r_data {
+                RecordData::SRV {
+                    priority,
+                    weight,
+                    port,
+                    target,
+                } => Some(Srv {
+                    priority,
+                    weight,
+                    port,
+                    target,
+                }),
+                _ => None,
+            })
+            .collect();
+        if let [only] = servers.as_slice() {
+            if only.target == Name::root() {
+                return Ok(Vec::new());
+            }
+        }
+        let mut randomness: Result<()> = Ok(());
+        let ordered: Vec<Srv> = srv_order(servers, |total| match random_bytes(4) {
+            Ok(bytes) => u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) % (total + 1),
+            Err(error) => {
+                randomness = Err(error);
+                0
+            }
+        });
+        randomness.map(|()| ordered)
+    }
+
+    /// the text of each TXT record of `name`, its character-strings joined
+    /// as for SPF and DKIM, RFC 7208 §3.3
+    pub fn txt_lookup(&self, name: &Name) -> Result<Vec<Vec<u8>>> {
+        Ok(self
+            .lookup(name, QueryType::TXT)?
+            .into_iter()
+            .filter_map(|record| match record.r_data {
+                RecordData::TXT(strings) => Some(strings.concat()),
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+/// `servers` by priority, those of the same priority in the weighted
+/// random order of RFC 2782, with `random(total)` picking a number from 0
+/// to `total`
+///
+/// Of the servers left, the one whose running sum of weights first
+/// reaches the number picked goes next. Servers of weight 0 come first in
+/// the running sum, so they only go first when the number picked is 0.
+pub fn srv_order(mut servers: Vec<Srv>, mut random: impl FnMut(u32) -> u32) -> Vec<Srv> {
+    servers.sort_by_key(|srv| (srv.priority, srv.weight != 0));
+    let mut ordered: Vec<Srv> = Vec::with_capacity(servers.len());
+    while !servers.is_empty() {
+        let priority: u16 = servers[0].priority;
+        let same: usize = servers
+            .iter()
+            .take_while(|srv| srv.priority == priority)
+            .count();
+        let mut group: Vec<Srv> = servers.drain(..same).collect();
+        while !group.is_empty() {
+            let total: u32 = group.iter().map(|srv| srv.weight as u32).sum();
+            let picked: u32 = random(total);
+            let mut running: u32 = 0;
+            let next: usize = group
+                .iter()
+                .position(|srv| {
+                    running += srv.weight as u32;
+                    running >= picked
+                })
+                .unwrap_or(group.len() - 1);
+            ordered.push(group.remove(next));
+        }
+    }
+    ordered
+}
@@ -0,0 +1,330 @@
+use super::acl::{mask, unmap};
+use super::dns::{DnsPacket, DnsQuestion, DnsRecord, QueryType, RecordData, ResponseCode};
+use super::name::Name;
+use super::zone::{Catalog, Zone};
+use std::net::IpAddr;
+
+/// label below the origin of a policy zone holding client address triggers
+pub const CLIENT_IP_LABEL: &str = "rpz-client-ip";
+/// label holding triggers on the addresses of a response
+pub const IP_LABEL: &str = "rpz-ip";
+/// label holding triggers on the name servers of the zone answering
+pub const NSDNAME_LABEL: &str = "rpz-nsdname";
+
+/// What a policy rule is matched against, in the order rules of one policy
+/// zone take precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Trigger {
+    /// the address the query came from, `<prefix>.<address>.rpz-client-ip`
+    CLIENTIP,
+    /// the name queried, or `*.<name>` for the names below it
+    QNAME,
+    /// an address in the answer, `<prefix>.<address>.rpz-ip`
+    IP,
+    /// a name server of the zone answering, below `rpz-nsdname`
+    NSDNAME,
+}
+
+/// What a matching rule does to the response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyAction {
+    /// `CNAME .`
+    NXDOMAIN,
+    /// `CNAME *.`
+    NODATA,
+    /// `CNAME rpz-passthru.`, the response goes out as it is and later
+    /// policy zones are not checked
+    PASSTHRU,
+    /// `CNAME rpz-drop.`, no response at all
+    DROP,
+    /// any other records, answered in place of the response
+    LOCAL(Vec<DnsRecord>),
+}
+
+impl PolicyAction {
+    /// the action the records of a rule stand for, `None` for no records or
+    /// an action not supported such as `rpz-tcp-only`
+    pub fn from_records(records: &[DnsRecord]) -> Option<Self> {
+        let target: Option<&Name> = match records {
+            [] => return None,
+            [DnsRecord {
+                r_data: RecordData::CNAME(target),
+                ..
+            }] => Some(target),
+            _ => None,
+        };
+        let special: Option<String> = target
+            .filter(|target| target.num_labels() <= 1)
+            .map(|target| format!("{:#}", target.to_lowercase()));
+        match special.as_deref() {
+            Some(".") => Some(Self::NXDOMAIN),
+            Some("*.") => Some(Self::NODATA),
+            Some("rpz-passthru.") => Some(Self::PASSTHRU),
+            Some("rpz-drop.") => Some(Self::DROP),
+            Some(other) if other.starts_with("rpz-") => None,
+            _ => Some(Self::LOCAL(records.to_vec())),
+        }
+    }
+}
+
+/// A rule that matched, with the policy zone it came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyHit {
+    pub zone: Name,
+    pub trigger: Trigger,
+    /// owner of the rule in the policy zone
+    pub owner: Name,
+    pub action: PolicyAction,
+    /// SOA of the policy zone, the authority of negative answers
+    pub soa: Option<DnsRecord>,
+}
+
+impl PolicyHit {
+    /// rewrite `response` to `question`, returning false when it is dropped
+    pub fn apply(&self, question: &DnsQuestion, response: &mut DnsPacket) -> bool {
+        let answers: Vec<DnsRecord> = match &self.action {
+            PolicyAction::PASSTHRU => return true,
+            PolicyAction::DROP => return false,
+            PolicyAction::NXDOMAIN | PolicyAction::NODATA => Vec::new(),
+            PolicyAction::LOCAL(records) => local_answers(records, question),
+        };
+        response.header.aa = true;
+        response.header.r_code = match self.action {
+            PolicyAction::NXDOMAIN => ResponseCode::NAMEERROR,
+            _ => ResponseCode::NOERROR,
+        };
+        response.authorities = match answers.is_empty() {
+            true => self.soa.iter().cloned().collect(),
+            false => Vec::new(),
+        };
+        response.answers = answers;
+        response.additionals.clear();
+        true
+    }
+}
+
+/// local data of a rule answering `question`, owned by the name queried
+///
+/// A CNAME stands in for the types the rule has no records of, with a
+/// wildcard target `*.<suffix>` becoming `<name queried>.<suffix>`.
+fn local_answers(records: &[DnsRecord], question: &DnsQuestion) -> Vec<DnsRecord> {
+    let matching: Vec<&DnsRecord> = records
+        .iter()
+        .filter(|r| question.q_type == QueryType::ANY || r.r_type == question.q_type)
+        .collect();
+    let chosen: Vec<&DnsRecord> = match matching.is_empty() {
+        true => records
+            .iter()
+            .filter(|r| r.r_type == QueryType::CNAME)
+            .collect(),
+        false => matching,
+    };
+    chosen
+        .into_iter()
+        .map(|record| {
+            let r_data: RecordData = match &record.r_data {
+                RecordData::CNAME(target) if target.labels().next() == Some(b"*") => {
+                    let suffix: Name = target.parent().unwrap_or_default();
+                    RecordData::CNAME(question.label.append(&suffix).unwrap_or(target.clone()))
+                }
+                r_data => r_data.clone(),
+            };
+            DnsRecord {
+                label: question.label.clone(),
+                r_data,
+                ..record.clone()
+            }
+        })
+        .collect()
+}
+
+/// Response policy zones, RPZ, rewriting the answers of the server.
+///
+/// Policy zones are zones of the catalog, loaded from a file or transferred
+/// from a primary like any other. Each rule is a name below the origin of
+/// its zone, its records saying what happens to the responses it matches.
+/// The first zone with a matching rule decides, within a zone client
+/// address rules come first, then names queried, answer addresses and name
+/// servers. The most specific name and the longest address prefix win
+/// among rules of one kind.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResponsePolicy {
+    zones: Vec<Name>,
+}
+
+impl ResponsePolicy {
+    /// policy from the zones at `zones`, the first taking precedence
+    pub fn new(zones: Vec<Name>) -> Self {
+        Self { zones }
+    }
+
+    pub fn zones(&self) -> &[Name] {
+        &self.zones
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.zones.is_empty()
+    }
+
+    /// the rule deciding what happens to `response` to `question` from
+    /// `client`, if any
+    pub fn check(
+        &self,
+        catalog: &Catalog,
+        client: Option<IpAddr>,
+        question: &DnsQuestion,
+        response: &DnsPacket,
+    ) -> Option<PolicyHit> {
+        let addrs: Vec<IpAddr> = response
+            .answers
+            .iter()
+            .filter_map(|r| match r.r_data {
+                RecordData::IPADDR(addr) => Some(IpAddr::V4(addr)),
+                RecordData::AAAA(addr) => Some(IpAddr::V6(addr)),
+                _ => None,
+            })
+            .collect();
+        let name_servers: Vec<Name> = name_servers(catalog, &question.label);
+        for origin in &self.zones {
+            let zone: &Zone = match catalog.get(origin) {
+                Some(zone) => zone,
+                None => continue,
+            };
+            let hit: Option<(Trigger, Name, PolicyAction)> = client
+                .and_then(|client| address_rule(zone, CLIENT_IP_LABEL, &[client]))
+                .map(|(owner, action)| (Trigger::CLIENTIP, owner, action))
+                .or_else(|| {
+                    name_rule(zone, None, &question.label)
+                        .map(|(owner, action)| (Trigger::QNAME, owner, action))
+                })
+                .or_else(|| {
+                    address_rule(zone, IP_LABEL, &addrs)
+                        .map(|(owner, action)| (Trigger::IP, owner, action))
+                })
+                .or_else(|| {
+                    name_servers
+                        .iter()
+                        .find_map(|ns| name_rule(zone, Some(NSDNAME_LABEL), ns))
+                        .map(|(owner, action)| (Trigger::NSDNAME, owner, action))
+                });
+            if let Some((trigger, owner, action)) = hit {
+                return Some(PolicyHit {
+                    zone: origin.clone(),
+                    trigger,
+                    owner,
+                    action,
+                    soa: zone.soa().cloned(),
+                });
+            }
+        }
+        None
+    }
+}
+
+/// the action of the rule at `owner`, if there is one
+fn rule(zone: &Zone, owner: Name) -> Option<(Name, PolicyAction)> {
+    PolicyAction::from_records(zone.records_at(&owner)).map(|action| (owner, action))
+}
+
+/// the rule for `name` below `label`, or the origin when `None`, the name
+/// itself first, then wildcards from the closest enclosing one out
+fn name_rule(zone: &Zone, label: Option<&str>, name: &Name) -> Option<(Name, PolicyAction)> {
+    let base: Name = match label {
+        Some(label) => zone.origin().child(label.as_bytes()).ok()?,
+        None => zone.origin().clone(),
+    };
+    if let Some(hit) = name.append(&base).ok().and_then(|owner| rule(zone, owner)) {
+        return Some(hit);
+    }
+    (0..name.num_labels()).rev().find_map(|num_labels| {
+        let owner: Name = name
+            .trim_to(num_labels)
+            .append(&base)
+            .and_then(|name| name.child(b"*"))
+            .ok()?;
+        rule(zone, owner)
+    })
+}
+
+/// the rule with the longest prefix below `label` covering one of `addrs`
+fn address_rule(zone: &Zone, label: &str, addrs: &[IpAddr]) -> Option<(Name, PolicyAction)> {
+    let base: Name = zone.origin().child(label.as_bytes()).ok()?;
+    let mut best: Option<(u8, Name, PolicyAction)> = None;
+    for addr in addrs {
+        let addr: IpAddr = unmap(*addr);
+        let longest: u8 = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        let floor: u8 = best.as_ref().map_or(0, |(prefix, _, _)| *prefix);
+        let hit: Option<(u8, Name, PolicyAction)> =
+            (floor + 1..=longest).rev().find_map(|prefix| {
+                let owner: Name = address_name(addr, prefix).append(&base).ok()?;
+                rule(zone, owner).map(|(owner, action)| (prefix, owner, action))
+            });
+        if hit.is_some() {
+            best = hit;
+        }
+    }
+    best.map(|(_, owner, action)| (owner, action))
+}
+
+/// the labels of a rule for `addr/prefix`, the prefix then the address
+/// backwards: `24.0.2.0.192` for 192.0.2.0/24, and `48.zz.db8.2001` for
+/// 2001:db8::/48, `zz` standing for the longest run of zero words
+pub fn address_name(addr: IpAddr, prefix: u8) -> Name {
+    let mut labels: Vec<String> = vec![prefix.to_string()];
+    match mask(addr, prefix) {
+        IpAddr::V4(masked) => {
+            labels.extend(masked.octets().iter().rev().map(u8::to_string));
+        }
+        IpAddr::V6(masked) => {
+            let words: [u16; 8] = masked.segments();
+            // the longest run of two or more zero words, the first of equals
+            let mut zeros: Option<(usize, usize)> = None;
+            let mut start: usize = 0;
+            while start < words.len() {
+                let len: usize = words[start..].iter().take_while(|w| **w == 0).count();
+                if len >= 2 && zeros.is_none_or(|(_, longest)| len > longest) {
+                    zeros = Some((start, len));
+                }
+                start += len.max(1);
+            }
+            let mut forward: Vec<String> = Vec::new();
+            let mut index: usize = 0;
+            while index < words.len() {
+                match zeros {
+                    Some((start, len)) if index == start => {
+                        forward.push("zz".to_string());
+                        index += len;
+                    }
+                    _ => {
+                        forward.push(format!("{:x}", words[index]));
+                        index += 1;
+                    }
+                }
+            }
+            labels.extend(forward.into_iter().rev());
+        }
+    }
+    Name::from_labels(labels).expect("address names are short")
+}
+
+/// name servers of the zone answering for `name`, those of the delegation
+/// when it lies below a zone cut
+fn name_servers(catalog: &Catalog, name: &Name) -> Vec<Name> {
+    let zone: &Zone = match catalog.find(name) {
+        Some(zone) => zone,
+        None => return Vec::new(),
+    };
+    let cut: Name = zone
+        .delegation(name)
+        .unwrap_or_else(|| zone.origin().clone());
+    zone.rrset(&cut, QueryType::NS)
+        .into_iter()
+        .filter_map(|r| match &r.r_data {
+            RecordData::NS(host) => Some(host.clone()),
+            _ => None,
+        })
+        .collect()
+}
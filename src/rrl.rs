@@ -0,0 +1,232 @@
+use super::acl::{mask, unmap, Acl};
+use super::dns::{DnsPacket, DnsQuestion, QueryType, ResponseCode};
+use super::name::Name;
+use log::info;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// buckets kept at most, responses that would need another are limited
+pub const MAX_BUCKETS: usize = 100_000;
+
+/// a bucket refills in a second, an older one is as good as a new one
+const REFILL: Duration = Duration::from_secs(1);
+
+/// how often buckets left alone for `REFILL` are forgotten
+const SWEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// settings of a [`RateLimiter`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimit {
+    /// identical responses a client network gets each second
+    pub responses_per_second: u32,
+    /// every `slip`th limited response is sent truncated, none when 0
+    pub slip: u32,
+    /// leading bits of IPv4 clients counted together
+    pub ipv4_prefix: u8,
+    /// leading bits of IPv6 clients counted together
+    pub ipv6_prefix: u8,
+    /// clients and keys never limited
+    pub exempt: Acl,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            responses_per_second: 5,
+            slip: 2,
+            ipv4_prefix: 24,
+            ipv6_prefix: 56,
+            exempt: Acl::none(),
+        }
+    }
+}
+
+/// what happens to a response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateDecision {
+    SEND,
+    /// sent truncated with no records
+    SLIP,
+    DROP,
+}
+
+/// responses counted together
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ResponseKey {
+    network: IpAddr,
+    r_code: ResponseCode,
+    /// the question name, the zone for NXDOMAIN, root for errors
+    name: Name,
+    /// the question type of answers
+    q_type: Option<QueryType>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    /// responses limited since the bucket last had a token
+    limited: u32,
+}
+
+#[derive(Debug)]
+struct Buckets {
+    by_key: HashMap<ResponseKey, Bucket>,
+    /// when buckets were last swept
+    swept: Instant,
+    /// responses limited because every bucket was taken, since one was free
+    overflowed: u32,
+}
+
+/// Response rate limiting of UDP answers, after the RRL of BIND.
+///
+/// Every client network has a token bucket per kind of response it gets:
+/// answers per name and type, NXDOMAIN per zone and errors per code. A
+/// bucket holds up to a second of responses and refills at the configured
+/// rate. Once it is empty responses are dropped, every `slip`th one going
+/// out empty with TC set so a client behind a forged address can still get
+/// its answer over TCP.
+///
+/// Full buckets are forgotten every few seconds. At most `MAX_BUCKETS` are
+/// kept, so a flood of distinct responses cannot grow them without bound:
+/// responses that would need another bucket are limited as if theirs were
+/// empty, until the next sweep frees some.
+#[derive(Debug)]
+pub struct RateLimiter {
+    limit: RateLimit,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            buckets: Mutex::new(Buckets {
+                by_key: HashMap::new(),
+                swept: Instant::now(),
+                overflowed: 0,
+            }),
+        }
+    }
+
+    pub fn limit(&self) -> &RateLimit {
+        &self.limit
+    }
+
+    /// what to do with `response` to `client`, signed with `key` if any,
+    /// at `now`
+    pub fn check(
+        &self,
+        client: IpAddr,
+        key: Option<&Name>,
+        response: &DnsPacket,
+        now: Instant,
+    ) -> RateDecision {
+        if self.limit.exempt.allows(client, key) {
+            return RateDecision::SEND;
+        }
+        let rate: f64 = self.limit.responses_per_second as f64;
+        let key: ResponseKey = self.response_key(client, response);
+        let mut buckets: MutexGuard<Buckets> = self
+            .buckets
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if now.saturating_duration_since(buckets.swept) >= SWEEP_INTERVAL {
+            buckets
+                .by_key
+                .retain(|_, bucket| now.saturating_duration_since(bucket.updated) < REFILL);
+            buckets.swept = now;
+        }
+        if buckets.by_key.len() >= MAX_BUCKETS && !buckets.by_key.contains_key(&key) {
+            if buckets.overflowed == 0 {
+                info!(
+                    "limiting responses to {} and others: {} buckets in use",
+                    client, MAX_BUCKETS
+                );
+            }
+            buckets.overflowed = buckets.overflowed.saturating_add(1);
+            return self.decide(buckets.overflowed);
+        }
+        buckets.overflowed = 0;
+        let bucket: &mut Bucket = buckets.by_key.entry(key).or_insert(Bucket {
+            tokens: rate,
+            updated: now,
+            limited: 0,
+        });
+        let elapsed: f64 = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(rate);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            bucket.limited = 0;
+            return RateDecision::SEND;
+        }
+        if bucket.limited == 0 {
+            info!(
+                "limiting responses to {} for {} over {} per second",
+                client,
+                response
+                    .questions
+                    .first()
+                    .map(|question| question.label.to_string())
+                    .unwrap_or_default(),
+                self.limit.responses_per_second
+            );
+        }
+        bucket.limited = bucket.limited.saturating_add(1);
+        let limited: u32 = bucket.limited;
+        self.decide(limited)
+    }
+
+    /// the fate of the `limited`th response limited in a row
+    fn decide(&self, limited: u32) -> RateDecision {
+        match self.limit.slip {
+            0 => RateDecision::DROP,
+            slip if limited.is_multiple_of(slip) => RateDecision::SLIP,
+            _ => RateDecision::DROP,
+        }
+    }
+
+    fn response_key(&self, client: IpAddr, response: &DnsPacket) -> ResponseKey {
+        let network: IpAddr = network(client, self.limit.ipv4_prefix, self.limit.ipv6_prefix);
+        let question: Option<&DnsQuestion> = response.questions.first();
+        let (name, q_type): (Name, Option<QueryType>) = match response.header.r_code {
+            ResponseCode::NOERROR => match question {
+                Some(question) => (question.label.clone(), Some(question.q_type)),
+                None => (Name::root(), None),
+            },
+            // random names below a zone count as one
+            ResponseCode::NAMEERROR => match response
+                .authorities
+                .iter()
+                .find(|record| record.r_type == QueryType::SOA)
+            {
+                Some(soa) => (soa.label.clone(), None),
+                None => (
+                    question
+                        .map(|question| question.label.clone())
+                        .unwrap_or_default(),
+                    None,
+                ),
+            },
+            _ => (Name::root(), None),
+        };
+        ResponseKey {
+            network,
+            r_code: response.header.r_code,
+            name,
+            q_type,
+        }
+    }
+}
+
+/// `client` with only its leading `ipv4_prefix` or `ipv6_prefix` bits
+fn network(client: IpAddr, ipv4_prefix: u8, ipv6_prefix: u8) -> IpAddr {
+    let client: IpAddr = unmap(client);
+    match client {
+        IpAddr::V4(_) => mask(client, ipv4_prefix),
+        IpAddr::V6(_) => mask(client, ipv6_prefix),
+    }
+}
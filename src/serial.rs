@@ -0,0 +1,40 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+/// SOA serial number compared with the wrap-around arithmetic of RFC 1982.
+///
+/// Serials half the number space apart are neither smaller nor greater,
+/// `partial_cmp` gives `None` for them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Serial(pub u32);
+
+impl Serial {
+    /// the serial `n` increments later, `n` at most 2^31 - 1, RFC 1982 §3.1
+    pub fn wrapping_add(self, n: u32) -> Serial {
+        debug_assert!(n < 1 << 31);
+        Serial(self.0.wrapping_add(n))
+    }
+}
+
+impl PartialOrd for Serial {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match self.0.wrapping_sub(other.0) {
+            0 => Some(Ordering::Equal),
+            1..0x8000_0000 => Some(Ordering::Greater),
+            0x8000_0000 => None,
+            _ => Some(Ordering::Less),
+        }
+    }
+}
+
+impl From<u32> for Serial {
+    fn from(serial: u32) -> Self {
+        Serial(serial)
+    }
+}
+
+impl fmt::Display for Serial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
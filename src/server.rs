@@ -0,0 +1,1402 @@
+use super::acl::Acl;
+use super::blocklist::Blocklist;
+use super::cache::Cache;
+use super::client::{read_tcp_message, write_tcp_message, Client, UDP_PAYLOAD_SIZE};
+use super::config::{Config, ZoneConfig};
+use super::cookie::{Cookie, ServerCookies, BADCOOKIE};
+use super::dns::{
+    DnsPacket, DnsQuestion, DnsRecord, Edns, OpCode, QueryType, RecordData, ResponseCode,
+};
+use super::dnssec::signer::{Signer, SigningKey, DEFAULT_VALIDITY};
+use super::hosts::Hosts;
+use super::journal::{Diff, Journal};
+use super::name::Name;
+use super::parser::{DnsBytePacketBuffer, MAX_MESSAGE_SIZE, MAX_UDP_SIZE};
+use super::recursor::Recursor;
+use super::rpz::{PolicyHit, ResponsePolicy};
+use super::rrl::{RateDecision, RateLimit, RateLimiter};
+use super::serial::Serial;
+use super::tsig::{KeyRing, TsigKey, Verified};
+use super::update::{bump_serial, Update};
+use super::zone::{Catalog, Zone};
+use super::{Error, Result};
+use log::{debug, info};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// idle TCP connections are closed after this long
+const TCP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// TCP connections served at once unless set otherwise
+pub const DEFAULT_TCP_CLIENTS: usize = 100;
+
+/// refresh and retry intervals of secondary zones not loaded yet
+const DEFAULT_REFRESH: u32 = 3600;
+const DEFAULT_RETRY: u32 = 600;
+
+/// how often zone files of primary zones are checked for changes
+const RELOAD_INTERVAL: Duration = Duration::from_secs(5);
+
+/// floor of the refresh and retry intervals, whatever the SOA says
+const MIN_REFRESH: u32 = 5;
+
+/// threads answering UDP, so a query waiting on recursion holds up no others
+const UDP_THREADS: usize = 4;
+
+/// seconds since the epoch, as RRSIG timestamps count them
+pub fn unix_time() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as u32)
+        .unwrap_or_default()
+}
+
+/// Clients served by their own server, with zones of their own.
+#[derive(Debug)]
+pub struct View {
+    pub name: String,
+    /// client addresses and TSIG keys the view serves
+    pub match_clients: Acl,
+    /// local addresses queries to the view arrive at
+    pub match_destinations: Acl,
+    /// answers the clients of the view, requests reach it verified by the
+    /// server the view is added to
+    pub server: Server,
+}
+
+impl View {
+    pub fn new(name: String, server: Server) -> Self {
+        Self {
+            name,
+            match_clients: Acl::any(),
+            match_destinations: Acl::any(),
+            server,
+        }
+    }
+
+    /// whether a request from `client` to `destination` signed with `key`
+    /// belongs to the view
+    pub fn matches(&self, client: IpAddr, destination: IpAddr, key: Option<&Name>) -> bool {
+        self.match_clients.allows(client, key) && self.match_destinations.allows(destination, key)
+    }
+}
+
+/// Authoritative server answering from a catalog of zones.
+///
+/// Who may query, transfer, NOTIFY and UPDATE is decided by ACLs, the
+/// decisions logged through `log`: refusals at info, queries let through at
+/// debug.
+#[derive(Debug)]
+pub struct Server {
+    catalog: RwLock<Catalog>,
+    /// names pinned by hosts files and static records
+    hosts: RwLock<Hosts>,
+    /// names filtered out, answered after the pinned names
+    blocklist: RwLock<Blocklist>,
+    /// policy zones rewriting the answers from the catalog
+    response_policy: ResponsePolicy,
+    keyring: KeyRing,
+    /// who may query the server at all
+    query_acl: Acl,
+    /// who may query each zone, those of `query_acl` when missing
+    query_acls: BTreeMap<Name, Acl>,
+    /// who may ask for recursion, resolving names outside the catalog
+    recursion_acl: Acl,
+    /// resolves names outside the catalog for clients allowed recursion
+    /// when there are no forwarders
+    recursor: Recursor,
+    /// recursive servers names outside the catalog are asked of instead,
+    /// tried in turn
+    forwarders: Vec<SocketAddr>,
+    /// answers of recursion, kept by each view apart
+    cache: Cache,
+    /// who may transfer each zone, nobody when missing
+    transfer_acls: BTreeMap<Name, Acl>,
+    /// who may announce changes to each secondary zone
+    notify_acls: BTreeMap<Name, Acl>,
+    /// primary zones accepting UPDATE, with where changes are saved
+    update_zones: BTreeMap<Name, ZoneConfig>,
+    /// zones with news to act on: secondaries whose primary sent a NOTIFY,
+    /// primaries changed by UPDATE
+    pending: Mutex<BTreeSet<Name>>,
+    /// wakes `follow` and `watch` when a zone is added to `pending`
+    wakeup: Condvar,
+    /// split horizon, requests go to the first view matching them and the
+    /// server itself answers those matching none
+    views: Vec<View>,
+    /// limits UDP responses of the server and its views, none when missing
+    rate_limiter: Option<RateLimiter>,
+    /// answers COOKIE options of the server and its views, ignored when
+    /// missing
+    cookies: Option<ServerCookies>,
+    /// TCP connections served at once, more are closed right away
+    tcp_clients: usize,
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self::new(Catalog::new())
+    }
+}
+
+impl Server {
+    pub fn new(catalog: Catalog) -> Self {
+        Self {
+            catalog: RwLock::new(catalog),
+            hosts: RwLock::new(Hosts::new()),
+            blocklist: RwLock::new(Blocklist::new()),
+            response_policy: ResponsePolicy::default(),
+            keyring: KeyRing::new(),
+            query_acl: Acl::any(),
+            query_acls: BTreeMap::new(),
+            recursion_acl: Acl::none(),
+            recursor: Recursor::root(),
+            forwarders: Vec::new(),
+            cache: Cache::default(),
+            transfer_acls: BTreeMap::new(),
+            notify_acls: BTreeMap::new(),
+            update_zones: BTreeMap::new(),
+            pending: Mutex::new(BTreeSet::new()),
+            wakeup: Condvar::new(),
+            views: Vec::new(),
+            rate_limiter: None,
+            cookies: None,
+            tcp_clients: DEFAULT_TCP_CLIENTS,
+        }
+    }
+
+    /// TSIG keys requests are verified with, signed requests get signed answers
+    pub fn set_keyring(&mut self, keyring: KeyRing) {
+        self.keyring = keyring;
+    }
+
+    /// clients allowed to query the server, anybody by default
+    pub fn set_query_acl(&mut self, acl: Acl) {
+        self.query_acl = acl;
+    }
+
+    /// clients allowed to query the zone at `origin`, in place of those
+    /// allowed to query the server
+    pub fn set_zone_query_acl(&mut self, origin: Name, acl: Acl) {
+        self.query_acls.insert(origin, acl);
+    }
+
+    /// clients allowed recursion, nobody by default
+    ///
+    /// Their queries for names outside the catalog with RD set are resolved
+    /// by the recursor, and their responses have RA set. Everybody else is
+    /// refused such names.
+    pub fn set_recursion_acl(&mut self, acl: Acl) {
+        self.recursion_acl = acl;
+    }
+
+    /// resolver of names outside the catalog, from the root servers by
+    /// default
+    pub fn set_recursor(&mut self, recursor: Recursor) {
+        self.recursor = recursor;
+    }
+
+    /// recursive servers to forward names outside the catalog to in place
+    /// of the recursor, the next one asked when one fails
+    pub fn set_forwarders(&mut self, forwarders: Vec<SocketAddr>) {
+        self.forwarders = forwarders;
+    }
+
+    /// the answers recursion has kept
+    pub fn cache(&self) -> &Cache {
+        &self.cache
+    }
+
+    /// clients allowed to transfer the zone at `origin`
+    pub fn set_transfer_acl(&mut self, origin: Name, acl: Acl) {
+        self.transfer_acls.insert(origin, acl);
+    }
+
+    /// clients allowed to NOTIFY changes to the secondary zone at `origin`,
+    /// NOTIFY for other zones is answered NOTAUTH
+    pub fn set_notify_acl(&mut self, origin: Name, acl: Acl) {
+        self.notify_acls.insert(origin, acl);
+    }
+
+    /// accept UPDATE for the primary zone of `config` from the clients in
+    /// its `allow_update`, changes are saved to its signed file and journal
+    pub fn allow_updates(&mut self, config: &ZoneConfig) {
+        self.update_zones
+            .insert(config.origin.clone(), config.clone());
+    }
+
+    /// serve the clients of `view` from its server, ahead of views added
+    /// later
+    pub fn add_view(&mut self, view: View) {
+        self.views.push(view);
+    }
+
+    pub fn views(&self) -> &[View] {
+        &self.views
+    }
+
+    /// the server of the first view a request from `client` to
+    /// `destination` signed with `key` matches, this one if none does
+    pub fn route(&self, client: IpAddr, destination: IpAddr, key: Option<&Name>) -> &Server {
+        self.views
+            .iter()
+            .find(|view| view.matches(client, destination, key))
+            .map_or(self, |view| &view.server)
+    }
+
+    /// replace the pinned names of this server and its views, answered
+    /// ahead of the zones
+    pub fn set_hosts(&self, hosts: Hosts) {
+        for view in &self.views {
+            view.server.set_hosts(hosts.clone());
+        }
+        *self
+            .hosts
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = hosts;
+    }
+
+    /// replace the names filtered out by this server and its views,
+    /// answered ahead of the zones but after the pinned names
+    pub fn set_blocklist(&self, blocklist: Blocklist) {
+        for view in &self.views {
+            view.server.set_blocklist(blocklist.clone());
+        }
+        *self
+            .blocklist
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = blocklist;
+    }
+
+    /// policy zones of the catalog rewriting its answers, see
+    /// `ResponsePolicy`
+    pub fn set_response_policy(&mut self, policy: ResponsePolicy) {
+        self.response_policy = policy;
+    }
+
+    /// limit the UDP responses each client network gets, views included,
+    /// see `RateLimiter`
+    pub fn set_rate_limit(&mut self, limit: RateLimit) {
+        self.rate_limiter = Some(RateLimiter::new(limit));
+    }
+
+    /// hand out server cookies to clients sending a COOKIE option, those
+    /// coming back valid exempting the client from rate limiting
+    pub fn set_cookies(&mut self, cookies: ServerCookies) {
+        self.cookies = Some(cookies);
+    }
+
+    /// serve at most `max` TCP connections at once, `DEFAULT_TCP_CLIENTS`
+    /// by default
+    pub fn set_tcp_clients(&mut self, max: usize) {
+        self.tcp_clients = max;
+    }
+
+    /// the zones served, writable so they can be swapped while serving
+    pub fn catalog(&self) -> &RwLock<Catalog> {
+        &self.catalog
+    }
+
+    fn read_catalog(&self) -> RwLockReadGuard<'_, Catalog> {
+        self.catalog
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write_catalog(&self) -> RwLockWriteGuard<'_, Catalog> {
+        self.catalog
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// put `zone` in place of the one with the same origin, queries see
+    /// either the old or the new zone in full
+    pub fn replace_zone(&self, zone: Zone) -> Option<Zone> {
+        self.write_catalog().insert(zone)
+    }
+
+    /// the response to one request
+    ///
+    /// ACLs and rules on client addresses are not applied, and a query the
+    /// response policy drops is refused.
+    pub fn handle(&self, request: &DnsPacket) -> DnsPacket {
+        self.handle_from(request, None, None).unwrap_or_else(|| {
+            let mut response: DnsPacket = reply(request);
+            response.header.r_code = ResponseCode::REFUSED;
+            response
+        })
+    }
+
+    /// the response to a query from `client`, signed with `key` if any,
+    /// `None` when the response policy drops it
+    ///
+    /// Clients the query ACLs do not allow are refused.
+    pub fn handle_from(
+        &self,
+        request: &DnsPacket,
+        client: Option<IpAddr>,
+        key: Option<&Name>,
+    ) -> Option<DnsPacket> {
+        let mut response: DnsPacket = reply(request);
+
+        if request.header.op_code != OpCode::QUERY {
+            response.header.r_code = ResponseCode::NOTIMPLEMENTED;
+            return Some(response);
+        }
+        if request.header.qr || request.questions.len() != 1 {
+            response.header.r_code = ResponseCode::FORMATERROR;
+            return Some(response);
+        }
+        // transfers only run over TCP, see `transfer`
+        if request.questions[0].q_type == QueryType::AXFR {
+            response.header.r_code = ResponseCode::NOTIMPLEMENTED;
+            return Some(response);
+        }
+        let qname: &Name = &request.questions[0].label;
+        if let Some(client) = client {
+            if !self.query_acl.allows(client, key) {
+                info!(
+                    "refused query for {} from {}: not in allow-query",
+                    qname,
+                    requester(client, key)
+                );
+                response.header.r_code = ResponseCode::REFUSED;
+                return Some(response);
+            }
+        }
+        let recursion: bool = client.is_some_and(|client| self.recursion_acl.allows(client, key));
+        response.header.ra = recursion;
+        let hosts: RwLockReadGuard<'_, Hosts> = self
+            .hosts
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if hosts.answer(&request.questions[0], &mut response) {
+            return Some(response);
+        }
+        drop(hosts);
+        let blocklist: RwLockReadGuard<'_, Blocklist> = self
+            .blocklist
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if blocklist.answer(&request.questions[0], &mut response) {
+            return Some(response);
+        }
+        drop(blocklist);
+        let catalog: RwLockReadGuard<'_, Catalog> = self.read_catalog();
+        match (catalog.find(qname), client) {
+            (Some(zone), Some(client))
+                if !self
+                    .query_acls
+                    .get(zone.origin())
+                    .is_none_or(|acl| acl.allows(client, key)) =>
+            {
+                info!(
+                    "refused query for {} from {}: not in allow-query of {}",
+                    qname,
+                    requester(client, key),
+                    zone.origin()
+                );
+                response.header.r_code = ResponseCode::REFUSED;
+                return Some(response);
+            }
+            // the SOA alone tells an IXFR client to retry over TCP, RFC 1995 §2
+            (Some(zone), client)
+                if request.questions[0].q_type == QueryType::IXFR && zone.origin() == qname =>
+            {
+                let allowed: bool = client.is_none_or(|client| {
+                    self.transfer_acls
+                        .get(zone.origin())
+                        .is_some_and(|acl| acl.allows(client, key))
+                });
+                match (allowed, zone.soa()) {
+                    (true, Some(soa)) => {
+                        response.header.aa = true;
+                        response.answers.push(soa.clone());
+                    }
+                    (true, None) => response.header.r_code = ResponseCode::NOTAUTH,
+                    (false, _) => {
+                        if let Some(client) = client {
+                            info!(
+                                "refused IXFR of {} to {}: not in allow-transfer",
+                                qname,
+                                requester(client, key)
+                            );
+                        }
+                        response.header.r_code = ResponseCode::REFUSED
+                    }
+                }
+                return Some(response);
+            }
+            (_, _) if request.questions[0].q_type == QueryType::IXFR => {
+                response.header.r_code = ResponseCode::NOTAUTH;
+                return Some(response);
+            }
+            (Some(zone), _) => {
+                if let Some(client) = client {
+                    debug!("query for {} from {}", qname, requester(client, key));
+                }
+                zone.answer(&request.questions[0], request.dnssec_ok(), &mut response)
+            }
+            (None, Some(client)) if recursion && request.header.rd => {
+                debug!("recursion for {} from {}", qname, requester(client, key));
+                self.recurse(request, &mut response)
+            }
+            (None, client) => {
+                if let (Some(client), true) = (client, request.header.rd) {
+                    info!(
+                        "refused recursion for {} from {}: not in allow-recursion",
+                        qname,
+                        requester(client, key)
+                    );
+                }
+                response.header.r_code = ResponseCode::REFUSED
+            }
+        }
+        let hit: Option<PolicyHit> =
+            self.response_policy
+                .check(&catalog, client, &request.questions[0], &response);
+        match hit {
+            Some(hit) if !hit.apply(&request.questions[0], &mut response) => None,
+            _ => Some(response),
+        }
+    }
+
+    /// resolve the question of `request` into `response`, from the cache
+    /// when it holds the answer, failures answered SERVFAIL
+    fn recurse(&self, request: &DnsPacket, response: &mut DnsPacket) {
+        let question: &DnsQuestion = &request.questions[0];
+        let now: Instant = Instant::now();
+        let resolved: Result<DnsPacket> = match self.cache.get(question, now) {
+            Some(cached) => Ok(cached),
+            None => self
+                .resolve(question)
+                .inspect(|resolved| self.cache.insert(question, resolved, now)),
+        };
+        match resolved {
+            Ok(resolved) => {
+                response.header.r_code = resolved.header.r_code;
+                response.answers = resolved.answers;
+                response.authorities = resolved.authorities;
+            }
+            Err(error) => {
+                info!("resolving {} failed: {}", question.label, error);
+                response.header.r_code = ResponseCode::SERVERFAILURE;
+            }
+        }
+    }
+
+    /// the answer of the first forwarder that gives one, or of the
+    /// recursor when there are no forwarders
+    fn resolve(&self, question: &DnsQuestion) -> Result<DnsPacket> {
+        if self.forwarders.is_empty() {
+            return self.recursor.resolve(&question.label, question.q_type);
+        }
+        let mut last: Result<DnsPacket> = Err(Error::Resolve(format!(
+            "no forwarder answered for {}",
+            question.label
+        )));
+        for forwarder in &self.forwarders {
+            last = Client::new(*forwarder).query(&question.label, question.q_type, false);
+            match &last {
+                Ok(response)
+                    if !matches!(
+                        response.header.r_code,
+                        ResponseCode::SERVERFAILURE | ResponseCode::REFUSED
+                    ) =>
+                {
+                    return last
+                }
+                Ok(response) => debug!(
+                    "forwarder {} answered {} with {:?}",
+                    forwarder, question.label, response.header.r_code
+                ),
+                Err(error) => debug!("forwarder {} failed: {}", forwarder, error),
+            }
+        }
+        last
+    }
+
+    /// the response to a NOTIFY that the zone in the question changed,
+    /// RFC 1996 §3.7, waking `follow` for the zone when `client` may send it
+    pub fn handle_notify(
+        &self,
+        request: &DnsPacket,
+        client: IpAddr,
+        key: Option<&Name>,
+    ) -> DnsPacket {
+        let mut response: DnsPacket = reply(request);
+        if request.header.qr
+            || request.questions.len() != 1
+            || request.questions[0].q_type != QueryType::SOA
+        {
+            response.header.r_code = ResponseCode::FORMATERROR;
+            return response;
+        }
+        let origin: &Name = &request.questions[0].label;
+        match self.notify_acls.get(origin) {
+            None => response.header.r_code = ResponseCode::NOTAUTH,
+            Some(acl) if !acl.allows(client, key) => {
+                info!(
+                    "refused NOTIFY for {} from {}: not in allow-notify",
+                    origin,
+                    requester(client, key)
+                );
+                response.header.r_code = ResponseCode::REFUSED
+            }
+            Some(_) => {
+                info!("NOTIFY for {} from {}", origin, requester(client, key));
+                response.header.aa = true;
+                self.mark_pending(origin.clone());
+            }
+        }
+        response
+    }
+
+    /// the response to a dynamic update, RFC 2136 §3, made when `client`
+    /// may update the zone
+    pub fn handle_update(
+        &self,
+        request: &DnsPacket,
+        client: IpAddr,
+        key: Option<&Name>,
+    ) -> DnsPacket {
+        let mut response: DnsPacket = reply(request);
+        let update: Update = match (request.header.qr, Update::from_packet(request)) {
+            (false, Ok(update)) => update,
+            _ => {
+                response.header.r_code = ResponseCode::FORMATERROR;
+                return response;
+            }
+        };
+        if self.read_catalog().get(&update.zone).is_none() {
+            response.header.r_code = ResponseCode::NOTAUTH;
+            return response;
+        }
+        // secondary zones and zones closed to updates alike
+        let config: &ZoneConfig = match self.update_zones.get(&update.zone) {
+            Some(config) if config.allow_update.allows(client, key) => config,
+            _ => {
+                info!(
+                    "refused UPDATE of {} from {}: not in allow-update",
+                    update.zone,
+                    requester(client, key)
+                );
+                response.header.r_code = ResponseCode::REFUSED;
+                return response;
+            }
+        };
+        info!("UPDATE of {} from {}", update.zone, requester(client, key));
+        response.header.r_code = match self.update(config, &update) {
+            Ok(_) => ResponseCode::NOERROR,
+            Err(Error::Update(r_code)) => r_code,
+            Err(_) => ResponseCode::SERVERFAILURE,
+        };
+        response
+    }
+
+    /// carry out `update` on the primary zone of `config`, returning whether
+    /// it changed
+    ///
+    /// The changed zone is signed again when keys are configured, saved to
+    /// the signed file and journal, and its secondaries notified by `watch`.
+    pub fn update(&self, config: &ZoneConfig, update: &Update) -> Result<bool> {
+        let mut catalog: RwLockWriteGuard<'_, Catalog> = self.write_catalog();
+        let current: &Zone = catalog
+            .get(&config.origin)
+            .ok_or(Error::Update(ResponseCode::NOTAUTH))?;
+        let mut zone: Zone = current.clone();
+        if !update.apply_to(&mut zone)? {
+            return Ok(false);
+        }
+        sign_zone(config, &mut zone, unix_time())?;
+        zone.succeed(current);
+        fs::write(config.signed_file(), zone.to_string())?;
+        zone.journal()
+            .save(&config.journal_file().to_string_lossy())?;
+        catalog.insert(zone);
+        drop(catalog);
+        self.mark_pending(config.origin.clone());
+        Ok(true)
+    }
+
+    fn lock_pending(&self) -> MutexGuard<'_, BTreeSet<Name>> {
+        self.pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// flag news about `origin` and wake whoever waits for them
+    fn mark_pending(&self, origin: Name) {
+        self.lock_pending().insert(origin);
+        self.wakeup.notify_all();
+    }
+
+    /// wait up to `timeout` for news about `origin`, returning whether there
+    /// were any and clearing them
+    fn wait_pending(&self, origin: &Name, timeout: Duration) -> bool {
+        let (mut pending, _) = self
+            .wakeup
+            .wait_timeout_while(self.lock_pending(), timeout, |pending| {
+                !pending.contains(origin)
+            })
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        pending.remove(origin)
+    }
+
+    /// the response to a request from `client`, signed with `key` if any,
+    /// `None` when the response policy drops it
+    fn respond(
+        &self,
+        request: &DnsPacket,
+        client: IpAddr,
+        key: Option<&Name>,
+    ) -> Option<DnsPacket> {
+        match request.header.op_code {
+            OpCode::NOTIFY => Some(self.handle_notify(request, client, key)),
+            OpCode::UPDATE => Some(self.handle_update(request, client, key)),
+            _ => self.handle_from(request, Some(client), key),
+        }
+    }
+
+    /// the encoded response to an encoded request from `client` to the
+    /// local address `destination`, `None` when the request is too broken
+    /// to answer or dropped
+    ///
+    /// `tcp` lifts the UDP size limit, over UDP the limit is the EDNS payload
+    /// size of the request, or 512 without EDNS. The request is answered by
+    /// the view it matches once its TSIG is verified.
+    pub fn handle_bytes(
+        &self,
+        message: &[u8],
+        client: IpAddr,
+        destination: IpAddr,
+        tcp: bool,
+    ) -> Option<Vec<u8>> {
+        let request: DnsPacket =
+            match DnsPacket::from_buffer(&mut DnsBytePacketBuffer::from_bytes(message)) {
+                Ok(request) => request,
+                // a readable header still gets an error back
+                Err(_) if message.len() >= 12 && message[2] & 0x80 == 0 => {
+                    let mut response: DnsPacket = DnsPacket::new();
+                    response.header.id = u16::from_be_bytes([message[0], message[1]]);
+                    response.header.qr = true;
+                    response.header.r_code = ResponseCode::FORMATERROR;
+                    return encode(&mut response, MAX_UDP_SIZE).ok();
+                }
+                Err(_) => return None,
+            };
+        let now: u64 = unix_time() as u64;
+        let cookie: Option<Cookie> = match Cookie::from_packet(&request) {
+            Ok(cookie) => cookie,
+            Err(_) => {
+                let mut response: DnsPacket = reply(&request);
+                response.header.r_code = ResponseCode::FORMATERROR;
+                return encode(&mut response, MAX_UDP_SIZE).ok();
+            }
+        };
+        let valid_cookie: bool = match (&self.cookies, &cookie) {
+            (Some(cookies), Some(cookie)) => cookies.verify(cookie, client, now as u32),
+            _ => false,
+        };
+        if let (Some(cookies), Some(cookie), false) = (&self.cookies, &cookie, tcp) {
+            if cookies.required && !valid_cookie {
+                debug!("BADCOOKIE to {}", client);
+                let mut response: DnsPacket = reply(&request);
+                response.set_extended_rcode(BADCOOKIE);
+                cookies.answer(cookie, client, now as u32, &mut response);
+                return encode(&mut response, MAX_UDP_SIZE).ok();
+            }
+        }
+        let (mut response, signer): (DnsPacket, Option<(&TsigKey, Vec<u8>)>) =
+            match self.keyring.verify(message, now) {
+                Ok(None) => (
+                    self.route(client, destination, None)
+                        .respond(&request, client, None)?,
+                    None,
+                ),
+                Ok(Some(Verified { key, mac, .. })) => (
+                    self.route(client, destination, Some(&key)).respond(
+                        &request,
+                        client,
+                        Some(&key),
+                    )?,
+                    self.keyring.get(&key).map(|key| (key, mac)),
+                ),
+                Err(Error::Tsig(error)) => {
+                    let mut response: DnsPacket = reply(&request);
+                    self.keyring
+                        .reject(&request, &mut response, error, now)
+                        .ok()?;
+                    (response, None)
+                }
+                Err(_) => {
+                    let mut response: DnsPacket = reply(&request);
+                    response.header.r_code = ResponseCode::FORMATERROR;
+                    (response, None)
+                }
+            };
+        if let (Some(cookies), Some(cookie)) = (&self.cookies, &cookie) {
+            cookies.answer(cookie, client, now as u32, &mut response);
+        }
+        // a valid cookie shows the client address is not forged
+        if let (false, Some(limiter), false) = (tcp, &self.rate_limiter, valid_cookie) {
+            let key: Option<&Name> = signer.as_ref().map(|(key, _)| key.name());
+            match limiter.check(client, key, &response, Instant::now()) {
+                RateDecision::SEND => {}
+                // the client can retry over TCP, which is never limited
+                RateDecision::SLIP => {
+                    response.answers.clear();
+                    response.authorities.clear();
+                    response.additionals.clear();
+                    response.header.tc = true;
+                }
+                RateDecision::DROP => return None,
+            }
+        }
+        let limit: usize = match (tcp, &response.edns) {
+            (true, _) => MAX_MESSAGE_SIZE,
+            (false, Some(edns)) => {
+                (edns.udp_payload_size as usize).clamp(MAX_UDP_SIZE, UDP_PAYLOAD_SIZE as usize)
+            }
+            (false, None) => MAX_UDP_SIZE,
+        };
+        let signer: Option<(&TsigKey, &[u8])> =
+            signer.as_ref().map(|(key, mac)| (*key, mac.as_slice()));
+        sign_and_encode(&mut response, limit, signer, now)
+    }
+
+    /// the encoded responses to a request read from a TCP connection, more
+    /// than one for a zone transfer
+    pub fn handle_tcp(&self, message: &[u8], client: IpAddr, destination: IpAddr) -> Vec<Vec<u8>> {
+        let request: Option<DnsPacket> =
+            DnsPacket::from_buffer(&mut DnsBytePacketBuffer::from_bytes(message)).ok();
+        match request {
+            Some(request)
+                if request.header.op_code == OpCode::QUERY
+                    && !request.header.qr
+                    && request.questions.len() == 1
+                    && matches!(
+                        request.questions[0].q_type,
+                        QueryType::AXFR | QueryType::IXFR
+                    ) =>
+            {
+                self.transfer(&request, message, client, destination)
+            }
+            _ => self
+                .handle_bytes(message, client, destination, true)
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    /// stream a zone to `client` as AXFR, RFC 5936, or the changes since
+    /// the serial of the client as IXFR, RFC 1995
+    ///
+    /// The first message opens with the SOA and the last one ends with it.
+    /// IXFR falls back to sending the whole zone when the journal does not
+    /// reach back to the serial of the client. A signed request gets every
+    /// message signed, each chained to the one before. The zone comes from
+    /// the view the request matches.
+    fn transfer(
+        &self,
+        request: &DnsPacket,
+        message: &[u8],
+        client: IpAddr,
+        destination: IpAddr,
+    ) -> Vec<Vec<u8>> {
+        let now: u64 = unix_time() as u64;
+        let (key, request_mac): (Option<&TsigKey>, Vec<u8>) =
+            match self.keyring.verify(message, now) {
+                Ok(None) => (None, Vec::new()),
+                Ok(Some(Verified { key, mac, .. })) => (self.keyring.get(&key), mac),
+                // rejected the same way as any other request
+                Err(_) => {
+                    return self
+                        .handle_bytes(message, client, destination, true)
+                        .into_iter()
+                        .collect()
+                }
+            };
+        let single = |r_code: ResponseCode, answers: Vec<DnsRecord>| -> Vec<Vec<u8>> {
+            let mut response: DnsPacket = reply(request);
+            response.header.r_code = r_code;
+            response.header.aa = !answers.is_empty();
+            response.answers = answers;
+            let signer: Option<(&TsigKey, &[u8])> = key.map(|key| (key, request_mac.as_slice()));
+            sign_and_encode(&mut response, MAX_MESSAGE_SIZE, signer, now)
+                .into_iter()
+                .collect()
+        };
+
+        let origin: &Name = &request.questions[0].label;
+        let view: &Server = self.route(client, destination, key.map(TsigKey::name));
+        let catalog: RwLockReadGuard<'_, Catalog> = view.read_catalog();
+        let zone: &Zone = match catalog.get(origin) {
+            Some(zone) => zone,
+            None => return single(ResponseCode::NOTAUTH, Vec::new()),
+        };
+        let allowed: bool = view
+            .transfer_acls
+            .get(origin)
+            .is_some_and(|acl| acl.allows(client, key.map(TsigKey::name)));
+        let q_type: QueryType = request.questions[0].q_type;
+        let requester: String = requester(client, key.map(TsigKey::name));
+        match allowed {
+            true => info!("{:?} of {} to {}", q_type, origin, requester),
+            false => info!(
+                "refused {:?} of {} to {}: not in allow-transfer",
+                q_type, origin, requester
+            ),
+        }
+        let soa: &DnsRecord = match (allowed, zone.soa()) {
+            (true, Some(soa)) => soa,
+            (true, None) => return single(ResponseCode::SERVERFAILURE, Vec::new()),
+            (false, _) => return single(ResponseCode::REFUSED, Vec::new()),
+        };
+
+        let condensed: Option<Diff> = match request.questions[0].q_type {
+            QueryType::IXFR => match client_serial(request) {
+                // a client at or ahead of our serial needs nothing
+                Some(serial) if Some(Serial(serial)) >= zone.serial().map(Serial) => {
+                    return single(ResponseCode::NOERROR, vec![soa.clone()])
+                }
+                Some(serial) => zone.journal().condensed(serial),
+                None => return single(ResponseCode::FORMATERROR, Vec::new()),
+            },
+            _ => None,
+        };
+        let records: Vec<&DnsRecord> = match &condensed {
+            Some(diff) => std::iter::once(soa)
+                .chain(diff.records())
+                .chain(std::iter::once(soa))
+                .collect(),
+            None => std::iter::once(soa)
+                .chain(zone.records().filter(|record| *record != soa))
+                .chain(std::iter::once(soa))
+                .collect(),
+        };
+
+        // room for the header, the question, EDNS and the TSIG
+        let budget: usize = MAX_MESSAGE_SIZE - 512 - key.map_or(0, TsigKey::record_len);
+        let mut messages: Vec<DnsPacket> = vec![reply(request)];
+        let mut size: usize = 0;
+        for record in records {
+            if size + record.wire_len() > budget {
+                let mut next: DnsPacket = reply(request);
+                next.questions.clear();
+                messages.push(next);
+                size = 0;
+            }
+            size += record.wire_len();
+            let current: &mut DnsPacket = messages.last_mut().expect("one message at least");
+            current.header.aa = true;
+            current.answers.push(record.clone());
+        }
+
+        let mut encoded: Vec<Vec<u8>> = Vec::with_capacity(messages.len());
+        let mut prior_mac: Vec<u8> = request_mac;
+        for (index, mut response) in messages.into_iter().enumerate() {
+            if let Some(key) = key {
+                let signed: Result<Vec<u8>> = match index {
+                    0 => key.sign(&mut response, now, Some(&prior_mac)),
+                    _ => key.sign_next(&mut response, now, &prior_mac),
+                };
+                match signed {
+                    Ok(mac) => prior_mac = mac,
+                    Err(_) => return encoded,
+                }
+            }
+            let mut buffer: DnsBytePacketBuffer = DnsBytePacketBuffer::with_size(MAX_MESSAGE_SIZE);
+            match response.write(&mut buffer) {
+                Ok(_) => encoded.push(buffer.written().to_vec()),
+                Err(_) => return encoded,
+            }
+        }
+        encoded
+    }
+
+    /// answer on UDP and TCP at `addr` until an i/o error stops the UDP loop
+    pub fn run(&self, addr: SocketAddr) -> Result<()> {
+        let socket: UdpSocket = UdpSocket::bind(addr)?;
+        let listener: TcpListener = TcpListener::bind(addr)?;
+        thread::scope(|scope| {
+            scope.spawn(|| self.serve_tcp(&listener));
+            for _ in 1..UDP_THREADS {
+                scope.spawn(|| self.serve_udp(&socket));
+            }
+            self.serve_udp(&socket)
+        })
+    }
+
+    /// answer datagrams on `socket`, its local address standing for the
+    /// destination of every request
+    pub fn serve_udp(&self, socket: &UdpSocket) -> Result<()> {
+        let mut buffer: Vec<u8> = vec![0; MAX_MESSAGE_SIZE];
+        let local: IpAddr = socket.local_addr()?.ip();
+        loop {
+            let (len, peer) = socket.recv_from(&mut buffer)?;
+            if let Some(response) = self.handle_bytes(&buffer[..len], peer.ip(), local, false) {
+                // an unreachable client must not stop the server
+                let _ = socket.send_to(&response, peer);
+            }
+        }
+    }
+
+    /// answer connections on `listener`, each on a thread of its own,
+    /// dropping those beyond the limit set by `set_tcp_clients`
+    pub fn serve_tcp(&self, listener: &TcpListener) {
+        let open: AtomicUsize = AtomicUsize::new(0);
+        let open: &AtomicUsize = &open;
+        thread::scope(|scope| {
+            for stream in listener.incoming().flatten() {
+                if open.load(Ordering::Acquire) >= self.tcp_clients {
+                    debug!("too many TCP clients, dropping {:?}", stream.peer_addr());
+                    continue;
+                }
+                open.fetch_add(1, Ordering::AcqRel);
+                scope.spawn(move || {
+                    let _ = self.serve_connection(stream);
+                    open.fetch_sub(1, Ordering::AcqRel);
+                });
+            }
+        })
+    }
+
+    /// answer length prefixed requests until the client closes or goes idle
+    fn serve_connection(&self, mut stream: TcpStream) -> Result<()> {
+        stream.set_read_timeout(Some(TCP_TIMEOUT))?;
+        let client: IpAddr = stream.peer_addr()?.ip();
+        let local: IpAddr = stream.local_addr()?.ip();
+        loop {
+            let request: Vec<u8> = read_tcp_message(&mut stream)?;
+            let responses: Vec<Vec<u8>> = self.handle_tcp(&request, client, local);
+            if responses.is_empty() {
+                return Ok(());
+            }
+            for response in responses {
+                write_tcp_message(&mut stream, &response)?;
+            }
+        }
+    }
+
+    /// read the primary zone of `config` again, journaling what changed when
+    /// its serial did, returning whether it did
+    pub fn reload(&self, config: &ZoneConfig) -> Result<bool> {
+        // locked first so an UPDATE cannot save the zone meanwhile
+        let mut catalog: RwLockWriteGuard<'_, Catalog> = self.write_catalog();
+        let mut zone: Zone = load_zone(config, unix_time())?;
+        if let Some(current) = catalog.get(&config.origin) {
+            if current.serial() == zone.serial() {
+                return Ok(false);
+            }
+            zone.succeed(current);
+        }
+        zone.journal()
+            .save(&config.journal_file().to_string_lossy())?;
+        catalog.insert(zone);
+        Ok(true)
+    }
+
+    /// sign the primary zone of `config` again under the next serial when
+    /// a signature in it expires within a quarter of the validity of
+    /// signatures from `now`, returning whether it was
+    ///
+    /// The zone is saved to its signed file and journal as after an UPDATE.
+    pub fn resign(&self, config: &ZoneConfig, now: u32) -> Result<bool> {
+        if config.keys.is_empty() {
+            return Ok(false);
+        }
+        let mut catalog: RwLockWriteGuard<'_, Catalog> = self.write_catalog();
+        let Some(current) = catalog.get(&config.origin) else {
+            return Ok(false);
+        };
+        let renew_by: Serial = Serial(now.wrapping_add(DEFAULT_VALIDITY / 4));
+        let expiring: bool = current.records().any(|record| {
+            matches!(record.r_data, RecordData::RRSIG { expiration, .. } if Serial(expiration) < renew_by)
+        });
+        if !expiring {
+            return Ok(false);
+        }
+        let mut zone: Zone = current.clone();
+        bump_serial(&mut zone);
+        sign_zone(config, &mut zone, now)?;
+        zone.succeed(current);
+        fs::write(config.signed_file(), zone.to_string())?;
+        zone.journal()
+            .save(&config.journal_file().to_string_lossy())?;
+        catalog.insert(zone);
+        Ok(true)
+    }
+
+    /// reload the primary zone of `config` whenever its file is modified,
+    /// sending NOTIFY to its secondaries when the serial changed by that or
+    /// by UPDATE, never returning
+    ///
+    /// Signed zones are signed again before their signatures expire, see
+    /// `resign`.
+    pub fn watch(&self, config: &ZoneConfig, keyring: &KeyRing) -> ! {
+        let modified = || {
+            fs::metadata(&config.file)
+                .and_then(|meta| meta.modified())
+                .ok()
+        };
+        let mut seen: Option<SystemTime> = modified();
+        loop {
+            if self.wait_pending(&config.origin, RELOAD_INTERVAL) {
+                self.notify_secondaries(config, keyring);
+            }
+            let now: Option<SystemTime> = modified();
+            // a zone caught half written is retried on the next change
+            if now != seen {
+                match self.reload(config) {
+                    Ok(true) => {
+                        seen = now;
+                        self.notify_secondaries(config, keyring);
+                    }
+                    Ok(false) => seen = now,
+                    Err(_) => {}
+                }
+            }
+            if let Ok(true) = self.resign(config, unix_time()) {
+                info!(
+                    "signed {:#} again ahead of its signatures expiring",
+                    config.origin
+                );
+                seen = modified();
+                self.notify_secondaries(config, keyring);
+            }
+        }
+    }
+
+    /// load the hosts files and static records of `config` again whenever
+    /// one of them is modified, never returning
+    ///
+    /// Files that fail to load keep the names pinned before in place.
+    pub fn watch_hosts(&self, config: &Config) -> ! {
+        let files: Vec<&PathBuf> = config.hosts.iter().chain(&config.static_records).collect();
+        poll_files(&files, || {
+            load_hosts(config)
+                .map(|hosts| self.set_hosts(hosts))
+                .is_ok()
+        })
+    }
+
+    /// load the blocklists and allowlists of `config` again whenever one of
+    /// them is modified, never returning
+    ///
+    /// Files that fail to load keep the names blocked before in place.
+    pub fn watch_blocklists(&self, config: &Config) -> ! {
+        let files: Vec<&PathBuf> = config.blocklists.iter().chain(&config.allowlists).collect();
+        poll_files(&files, || {
+            load_blocklist(config)
+                .map(|blocklist| self.set_blocklist(blocklist))
+                .is_ok()
+        })
+    }
+
+    /// keep the secondary zone of `config` in sync with its primary, never
+    /// returning
+    ///
+    /// The primary is asked for its SOA every refresh interval of the zone,
+    /// or right away when it sends a NOTIFY, and the zone transferred again
+    /// when the serial is greater than ours in serial number arithmetic.
+    /// Failed attempts are retried after the retry interval. Secondaries of
+    /// the zone in turn get a NOTIFY once it changed.
+    pub fn follow(&self, config: &ZoneConfig, keyring: &KeyRing) -> ! {
+        loop {
+            let current: Option<Zone> = self.read_catalog().get(&config.origin).cloned();
+            let (serial, refresh, retry): (Option<u32>, u32, u32) =
+                match current.as_ref().and_then(Zone::soa).map(|soa| &soa.r_data) {
+                    Some(RecordData::SOA {
+                        serial,
+                        refresh,
+                        retry,
+                        ..
+                    }) => (Some(*serial), *refresh, *retry),
+                    _ => (None, DEFAULT_REFRESH, DEFAULT_RETRY),
+                };
+            let pause: u32 = match primary_serial(config, keyring) {
+                Ok(primary) if serial.is_some_and(|serial| Serial(primary) <= Serial(serial)) => {
+                    refresh
+                }
+                Ok(_) => match fetch_zone(config, keyring, current.as_ref()) {
+                    Ok(zone) => {
+                        self.replace_zone(zone);
+                        self.notify_secondaries(config, keyring);
+                        refresh
+                    }
+                    Err(_) => retry,
+                },
+                Err(_) => retry,
+            };
+            let timeout: Duration = Duration::from_secs(pause.max(MIN_REFRESH) as u64);
+            self.wait_pending(&config.origin, timeout);
+        }
+    }
+
+    /// send a NOTIFY with the current SOA of the zone of `config` to each
+    /// of its secondaries at once, returning those that did not take it
+    pub fn notify_secondaries(
+        &self,
+        config: &ZoneConfig,
+        keyring: &KeyRing,
+    ) -> Vec<(SocketAddr, Error)> {
+        let soa: DnsRecord = match self.read_catalog().get(&config.origin).and_then(Zone::soa) {
+            Some(soa) => soa.clone(),
+            None => return Vec::new(),
+        };
+        thread::scope(|scope| {
+            let sent: Vec<_> = config
+                .notify
+                .iter()
+                .map(|secondary| {
+                    let soa: &DnsRecord = &soa;
+                    scope.spawn(move || {
+                        zone_client(*secondary, config, keyring)
+                            .and_then(|client| client.notify(soa))
+                            .err()
+                            .map(|error| (*secondary, error))
+                    })
+                })
+                .collect();
+            sent.into_iter()
+                .filter_map(|handle| handle.join().ok().flatten())
+                .collect()
+        })
+    }
+}
+
+/// `client` as logged, with the key it signed with
+fn requester(client: IpAddr, key: Option<&Name>) -> String {
+    match key {
+        Some(key) => format!("{} key {}", client, key),
+        None => client.to_string(),
+    }
+}
+
+/// serial of the SOA an IXFR request carries in its authority section
+fn client_serial(request: &DnsPacket) -> Option<u32> {
+    request
+        .authorities
+        .iter()
+        .find_map(|record| match record.r_data {
+            RecordData::SOA { serial, .. } if record.label == request.questions[0].label => {
+                Some(serial)
+            }
+            _ => None,
+        })
+}
+
+/// a response to `request` echoing its id, question and EDNS
+fn reply(request: &DnsPacket) -> DnsPacket {
+    let mut response: DnsPacket = DnsPacket::new();
+    response.header.id = request.header.id;
+    response.header.qr = true;
+    response.header.op_code = request.header.op_code;
+    response.header.rd = request.header.rd;
+    response.questions = request.questions.clone();
+    response.edns = request.edns.as_ref().map(|edns| Edns {
+        udp_payload_size: UDP_PAYLOAD_SIZE,
+        dnssec_ok: edns.dnssec_ok,
+        ..Edns::default()
+    });
+    response
+}
+
+/// encode `response` into at most `limit` octets including the TSIG that
+/// `signer`, a key and the MAC of the request, adds
+fn sign_and_encode(
+    response: &mut DnsPacket,
+    limit: usize,
+    signer: Option<(&TsigKey, &[u8])>,
+    now: u64,
+) -> Option<Vec<u8>> {
+    let reserve: usize = signer.map_or(0, |(key, _)| key.record_len());
+    let bytes: Vec<u8> = encode(response, limit - reserve).ok()?;
+    let (key, request_mac) = match signer {
+        Some(signer) => signer,
+        None => return Some(bytes),
+    };
+    key.sign(response, now, Some(request_mac)).ok()?;
+    let mut buffer: DnsBytePacketBuffer = DnsBytePacketBuffer::with_size(limit);
+    response.write(&mut buffer).ok()?;
+    Some(buffer.written().to_vec())
+}
+
+/// encode `response` into at most `limit` octets, dropping every record and
+/// setting TC when it does not fit, RFC 2181 §9
+pub fn encode(response: &mut DnsPacket, limit: usize) -> Result<Vec<u8>> {
+    let mut buffer: DnsBytePacketBuffer = DnsBytePacketBuffer::with_size(limit);
+    if response.write(&mut buffer).is_ok() {
+        return Ok(buffer.written().to_vec());
+    }
+    response.answers.clear();
+    response.authorities.clear();
+    response.additionals.clear();
+    response.header.tc = true;
+    let mut buffer: DnsBytePacketBuffer = DnsBytePacketBuffer::with_size(limit);
+    response.write(&mut buffer)?;
+    Ok(buffer.written().to_vec())
+}
+
+/// a client asking the primary of a secondary zone, signing with the
+/// transfer key of the zone
+fn primary_client(config: &ZoneConfig, keyring: &KeyRing) -> Result<Client> {
+    let primary: SocketAddr = config
+        .primary
+        .ok_or_else(|| Error::Transfer(format!("{} has no primary", config.origin)))?;
+    zone_client(primary, config, keyring)
+}
+
+/// a client talking to `server` about the zone of `config`, signing with
+/// the transfer key of the zone
+fn zone_client(server: SocketAddr, config: &ZoneConfig, keyring: &KeyRing) -> Result<Client> {
+    let mut client: Client = Client::new(server);
+    if let Some(name) = &config.transfer_key {
+        let key: &TsigKey = keyring
+            .get(name)
+            .ok_or_else(|| Error::InvalidKey(format!("no TSIG key {}", name)))?;
+        client.set_tsig(Some(key.clone()));
+    }
+    Ok(client)
+}
+
+/// serial of the zone at the primary
+pub fn primary_serial(config: &ZoneConfig, keyring: &KeyRing) -> Result<u32> {
+    let response: DnsPacket =
+        primary_client(config, keyring)?.query(&config.origin, QueryType::SOA, false)?;
+    response
+        .answers
+        .iter()
+        .find_map(|record| match &record.r_data {
+            RecordData::SOA { serial, .. } if record.label == config.origin => Some(*serial),
+            _ => None,
+        })
+        .ok_or_else(|| Error::Transfer(format!("no SOA for {} at the primary", config.origin)))
+}
+
+/// transfer a secondary zone from its primary, incrementally when there is
+/// a `current` copy, and save it with its journal to the zone file, where it
+/// is loaded from should the primary be down at the next start
+pub fn fetch_zone(config: &ZoneConfig, keyring: &KeyRing, current: Option<&Zone>) -> Result<Zone> {
+    let client: Client = primary_client(config, keyring)?;
+    let zone: Zone = match current {
+        Some(current) => client.ixfr(current)?,
+        None => client.axfr(&config.origin)?,
+    };
+    fs::write(&config.file, zone.to_string())?;
+    zone.journal()
+        .save(&config.journal_file().to_string_lossy())?;
+    Ok(zone)
+}
+
+/// read a zone and sign it when keys are configured
+///
+/// The signed file is read instead of the zone file while its serial is
+/// newer, and the journal along when it ends at the serial of the zone.
+pub fn load_zone(config: &ZoneConfig, now: u32) -> Result<Zone> {
+    let mut zone: Zone = Zone::load(&config.file.to_string_lossy(), &config.origin)?;
+    let signed_file: PathBuf = config.signed_file();
+    if signed_file.exists() {
+        let saved: Zone = Zone::load(&signed_file.to_string_lossy(), &config.origin)?;
+        if saved.serial().map(Serial) > zone.serial().map(Serial) {
+            zone = saved;
+        }
+    }
+    let journal: Journal = Journal::load(&config.journal_file().to_string_lossy(), &config.origin)?;
+    if journal.last_serial().is_some() && journal.last_serial() == zone.serial() {
+        zone.set_journal(journal);
+    }
+    sign_zone(config, &mut zone, now)?;
+    Ok(zone)
+}
+
+/// sign `zone` with the keys of `config`, if it has any
+fn sign_zone(config: &ZoneConfig, zone: &mut Zone, now: u32) -> Result<()> {
+    if config.keys.is_empty() {
+        return Ok(());
+    }
+    let keys: Vec<SigningKey> = config
+        .keys
+        .iter()
+        .map(|file| SigningKey::load(&file.to_string_lossy()))
+        .collect::<Result<Vec<SigningKey>>>()?;
+    let mut signer: Signer = Signer::new(keys);
+    signer.set_nsec3(config.nsec3.clone());
+    signer.sign_zone(zone, now)
+}
+
+/// the names pinned by the hosts files and static records of `config`
+pub fn load_hosts(config: &Config) -> Result<Hosts> {
+    let mut hosts: Hosts = Hosts::new();
+    for file in &config.hosts {
+        hosts.load_hosts(&file.to_string_lossy())?;
+    }
+    for file in &config.static_records {
+        hosts.load_records(&file.to_string_lossy())?;
+    }
+    Ok(hosts)
+}
+
+/// the names filtered out by the blocklists and allowlists of `config`
+pub fn load_blocklist(config: &Config) -> Result<Blocklist> {
+    let mut blocklist: Blocklist = Blocklist::new();
+    blocklist.set_action(config.block_action.clone());
+    for file in &config.blocklists {
+        blocklist.load_blocklist(&file.to_string_lossy())?;
+    }
+    for file in &config.allowlists {
+        blocklist.load_allowlist(&file.to_string_lossy())?;
+    }
+    Ok(blocklist)
+}
+
+/// call `reload` whenever the modification time of one of `files` changes,
+/// again at the next poll until it returns true
+fn poll_files(files: &[&PathBuf], mut reload: impl FnMut() -> bool) -> ! {
+    let modified = || -> Vec<Option<SystemTime>> {
+        files
+            .iter()
+            .map(|file| fs::metadata(file).and_then(|meta| meta.modified()).ok())
+            .collect()
+    };
+    let mut seen: Vec<Option<SystemTime>> = modified();
+    loop {
+        thread::sleep(RELOAD_INTERVAL);
+        let now: Vec<Option<SystemTime>> = modified();
+        if now != seen && reload() {
+            seen = now;
+        }
+    }
+}
+
+/// every zone of `config` outside views, loaded and signed
+pub fn load_catalog(config: &Config) -> Result<Catalog> {
+    load_zones(&config.zones, &config.keyring)
+}
+
+/// the zones of `zones`, loaded and signed, secondaries transferred with
+/// the keys of `keyring`
+pub fn load_zones(zones: &[ZoneConfig], keyring: &KeyRing) -> Result<Catalog> {
+    let now: u32 = unix_time();
+    let mut catalog: Catalog = Catalog::new();
+    for zone in zones {
+        let loaded: Result<Zone> = match zone.primary {
+            // a copy saved earlier is brought up to date, or served as is
+            // when the primary is down
+            Some(_) => {
+                let cached: Option<Zone> = load_zone(zone, now).ok();
+                fetch_zone(zone, keyring, cached.as_ref()).or_else(|error| cached.ok_or(error))
+            }
+            None => load_zone(zone, now),
+        };
+        catalog.insert(loaded?);
+    }
+    Ok(catalog)
+}
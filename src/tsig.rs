@@ -0,0 +1,453 @@
+use super::dns::{DnsClass, DnsHeader, DnsPacket, DnsRecord, RecordData, ResponseCode};
+use super::name::Name;
+use super::parser::{DnsBytePacketBuffer, MAX_MESSAGE_SIZE};
+use super::{Error, Result};
+use ring::hmac;
+use std::collections::HashMap;
+use std::fmt;
+
+/// algorithm names of RFC 8945 §6
+pub const HMAC_SHA256: &str = "hmac-sha256";
+pub const HMAC_SHA512: &str = "hmac-sha512";
+
+/// TSIG error codes carried in the record, the header says NOTAUTH
+pub const BADSIG: u16 = 16;
+pub const BADKEY: u16 = 17;
+pub const BADTIME: u16 = 18;
+
+/// seconds of clock skew allowed by signatures made here
+pub const DEFAULT_FUDGE: u16 = 300;
+
+/// Shared secret used to sign and verify messages.
+#[derive(Clone, PartialEq, Eq)]
+pub struct TsigKey {
+    name: Name,
+    algorithm: Name,
+    secret: Vec<u8>,
+}
+
+impl fmt::Debug for TsigKey {
+    /// the secret is left out so keys can be logged
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TsigKey")
+            .field("name", &self.name)
+            .field("algorithm", &self.algorithm)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TsigKey {
+    /// key `name` for `algorithm`, either hmac-sha256 or hmac-sha512
+    pub fn new(name: Name, algorithm: &str, secret: Vec<u8>) -> Result<Self> {
+        let algorithm: Name = algorithm.parse()?;
+        if hmac_algorithm(&algorithm).is_none() {
+            return Err(Error::InvalidKey(format!(
+                "unsupported TSIG algorithm {}",
+                algorithm
+            )));
+        }
+        Ok(Self {
+            name,
+            algorithm,
+            secret,
+        })
+    }
+
+    pub fn name(&self) -> &Name {
+        &self.name
+    }
+
+    pub fn algorithm(&self) -> &Name {
+        &self.algorithm
+    }
+
+    fn hmac_key(&self) -> hmac::Key {
+        let algorithm: hmac::Algorithm =
+            hmac_algorithm(&self.algorithm).expect("algorithm checked in new");
+        hmac::Key::new(algorithm, &self.secret)
+    }
+
+    fn mac_len(&self) -> usize {
+        self.hmac_key().algorithm().digest_algorithm().output_len()
+    }
+
+    /// octets the TSIG record of this key adds to a message, at most
+    pub fn record_len(&self) -> usize {
+        // owner, type, class, ttl and rd_len, then the data with six octets
+        // of other data as sent with BADTIME
+        self.name.wire_len() + 10 + self.algorithm.wire_len() + 16 + self.mac_len() + 6
+    }
+
+    /// sign `packet` by setting its TSIG record, returning the MAC
+    ///
+    /// A response passes the MAC of the request it answers as `request_mac`.
+    pub fn sign(
+        &self,
+        packet: &mut DnsPacket,
+        now: u64,
+        request_mac: Option<&[u8]>,
+    ) -> Result<Vec<u8>> {
+        self.sign_with_error(packet, now, request_mac, 0, Vec::new())
+    }
+
+    /// sign a message after the first of a multi-message response such as
+    /// a zone transfer, chaining it to the MAC of the message before
+    ///
+    /// Only the timers are covered besides the message, RFC 8945 §5.3.1.
+    pub fn sign_next(&self, packet: &mut DnsPacket, now: u64, prior_mac: &[u8]) -> Result<Vec<u8>> {
+        packet.tsig = None;
+        let mut buffer: DnsBytePacketBuffer = DnsBytePacketBuffer::with_size(MAX_MESSAGE_SIZE);
+        packet.write(&mut buffer)?;
+        let r_data: RecordData = self.unsigned_record(packet.header.id, now, 0, Vec::new());
+        let data: Vec<u8> = self.signed_data(buffer.written(), Some(prior_mac), &r_data, true);
+        Ok(self.set_mac(packet, r_data, &data))
+    }
+
+    fn sign_with_error(
+        &self,
+        packet: &mut DnsPacket,
+        now: u64,
+        request_mac: Option<&[u8]>,
+        error: u16,
+        other: Vec<u8>,
+    ) -> Result<Vec<u8>> {
+        packet.tsig = None;
+        let mut buffer: DnsBytePacketBuffer = DnsBytePacketBuffer::with_size(MAX_MESSAGE_SIZE);
+        packet.write(&mut buffer)?;
+        let r_data: RecordData = self.unsigned_record(packet.header.id, now, error, other);
+        let data: Vec<u8> = self.signed_data(buffer.written(), request_mac, &r_data, false);
+        Ok(self.set_mac(packet, r_data, &data))
+    }
+
+    /// TSIG data of this key still lacking its MAC
+    fn unsigned_record(
+        &self,
+        original_id: u16,
+        now: u64,
+        error: u16,
+        other: Vec<u8>,
+    ) -> RecordData {
+        RecordData::TSIG {
+            algorithm: self.algorithm.clone(),
+            time_signed: now,
+            fudge: DEFAULT_FUDGE,
+            mac: Vec::new(),
+            original_id,
+            error,
+            other,
+        }
+    }
+
+    /// put the MAC over `data` into `r_data` and attach it to `packet`
+    fn set_mac(&self, packet: &mut DnsPacket, mut r_data: RecordData, data: &[u8]) -> Vec<u8> {
+        let tag: Vec<u8> = hmac::sign(&self.hmac_key(), data).as_ref().to_vec();
+        if let RecordData::TSIG { mac, .. } = &mut r_data {
+            *mac = tag.clone();
+        }
+        packet.tsig = Some(tsig_record(self.name.clone(), r_data));
+        tag
+    }
+
+    /// check the TSIG record of the encoded `message`, RFC 8945 §5.2
+    ///
+    /// Fails with [`Error::Tsig`] holding BADKEY, BADSIG or BADTIME, or
+    /// the error a server put in the TSIG of its response.
+    pub fn verify(&self, message: &[u8], now: u64, request_mac: Option<&[u8]>) -> Result<Verified> {
+        self.check(message, &[], now, request_mac, false)
+    }
+
+    /// verify `message`, the signed data starting with the `unsigned`
+    /// messages received since the last signed one
+    fn check(
+        &self,
+        message: &[u8],
+        unsigned: &[u8],
+        now: u64,
+        request_mac: Option<&[u8]>,
+        timers_only: bool,
+    ) -> Result<Verified> {
+        let packet: DnsPacket =
+            DnsPacket::from_buffer(&mut DnsBytePacketBuffer::from_bytes(message))?;
+        let tsig: DnsRecord = packet.tsig.ok_or(Error::Tsig(BADSIG))?;
+        let (algorithm, time_signed, fudge, mac, original_id, error) = match &tsig.r_data {
+            RecordData::TSIG {
+                algorithm,
+                time_signed,
+                fudge,
+                mac,
+                original_id,
+                error,
+                ..
+            } => (algorithm, *time_signed, *fudge, mac, *original_id, *error),
+            _ => return Err(Error::Tsig(BADSIG)),
+        };
+        if tsig.label != self.name || algorithm != &self.algorithm {
+            return Err(Error::Tsig(BADKEY));
+        }
+        // BADKEY and BADSIG answers come back unsigned
+        if error != 0 && mac.is_empty() {
+            return Err(Error::Tsig(error));
+        }
+
+        let mut stripped: Vec<u8> = unsigned.to_vec();
+        let start: usize = stripped.len();
+        stripped.extend_from_slice(&message[..tsig_offset(message)?]);
+        stripped[start..start + 2].copy_from_slice(&original_id.to_be_bytes());
+        let ar_count: u16 = u16::from_be_bytes([stripped[start + 10], stripped[start + 11]]) - 1;
+        stripped[start + 10..start + 12].copy_from_slice(&ar_count.to_be_bytes());
+        let data: Vec<u8> = self.signed_data(&stripped, request_mac, &tsig.r_data, timers_only);
+        if mac.len() != self.mac_len() || hmac::verify(&self.hmac_key(), &data, mac).is_err() {
+            return Err(Error::Tsig(BADSIG));
+        }
+        if now.abs_diff(time_signed) > fudge as u64 {
+            return Err(Error::Tsig(BADTIME));
+        }
+        if error != 0 {
+            return Err(Error::Tsig(error));
+        }
+        Ok(Verified {
+            key: self.name.clone(),
+            mac: mac.clone(),
+            time_signed,
+        })
+    }
+
+    /// what the MAC covers: the request MAC, the message and the TSIG
+    /// variables of RFC 8945 §4.3.3, or just the timers of them
+    fn signed_data(
+        &self,
+        message: &[u8],
+        request_mac: Option<&[u8]>,
+        tsig: &RecordData,
+        timers_only: bool,
+    ) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::with_capacity(message.len() + 128);
+        if let Some(request_mac) = request_mac {
+            data.extend_from_slice(&(request_mac.len() as u16).to_be_bytes());
+            data.extend_from_slice(request_mac);
+        }
+        data.extend_from_slice(message);
+        if let (
+            true,
+            RecordData::TSIG {
+                time_signed, fudge, ..
+            },
+        ) = (timers_only, tsig)
+        {
+            data.extend_from_slice(&time_signed.to_be_bytes()[2..]);
+            data.extend_from_slice(&fudge.to_be_bytes());
+            return data;
+        }
+        data.extend_from_slice(&self.name.to_lowercase().to_wire());
+        data.extend_from_slice(&u16::from(DnsClass::ANY).to_be_bytes());
+        data.extend_from_slice(&0u32.to_be_bytes());
+        if let RecordData::TSIG {
+            algorithm,
+            time_signed,
+            fudge,
+            error,
+            other,
+            ..
+        } = tsig
+        {
+            data.extend_from_slice(&algorithm.to_lowercase().to_wire());
+            data.extend_from_slice(&time_signed.to_be_bytes()[2..]);
+            data.extend_from_slice(&fudge.to_be_bytes());
+            data.extend_from_slice(&error.to_be_bytes());
+            data.extend_from_slice(&(other.len() as u16).to_be_bytes());
+            data.extend_from_slice(other);
+        }
+        data
+    }
+}
+
+fn hmac_algorithm(algorithm: &Name) -> Option<hmac::Algorithm> {
+    match algorithm.to_string().to_ascii_lowercase().as_str() {
+        HMAC_SHA256 => Some(hmac::HMAC_SHA256),
+        HMAC_SHA512 => Some(hmac::HMAC_SHA512),
+        _ => None,
+    }
+}
+
+fn tsig_record(name: Name, r_data: RecordData) -> DnsRecord {
+    DnsRecord {
+        r_class: DnsClass::ANY,
+        ..DnsRecord::new(name, 0, r_data)
+    }
+}
+
+/// offset of the last record of `message`, where its TSIG starts
+fn tsig_offset(message: &[u8]) -> Result<usize> {
+    let mut dbuf: DnsBytePacketBuffer = DnsBytePacketBuffer::from_bytes(message);
+    let header: DnsHeader = DnsHeader::read(&mut dbuf)?;
+    for _ in 0..header.qd_count {
+        dbuf.read_label()?;
+        dbuf._step(4)?;
+    }
+    let records: usize =
+        header.an_count as usize + header.ns_count as usize + header.ar_count as usize;
+    let mut start: usize = dbuf.get_pos();
+    for _ in 0..records {
+        start = dbuf.get_pos();
+        dbuf.read_label()?;
+        dbuf._step(8)?;
+        let rd_len: u16 = dbuf.read_u16()?;
+        dbuf._step(rd_len as usize)?;
+    }
+    Ok(start)
+}
+
+/// Checks the messages of a multi-message response in the order they
+/// arrive.
+///
+/// Up to 99 messages in a row may come unsigned, the next signed one covers
+/// them, RFC 8945 §5.3.1.
+#[derive(Debug)]
+pub struct StreamVerifier<'a> {
+    key: &'a TsigKey,
+    /// MAC of the request, then of the last signed message
+    mac: Vec<u8>,
+    /// messages since the last signed one
+    unsigned: Vec<u8>,
+    pending: usize,
+    first: bool,
+}
+
+/// unsigned messages allowed between two signed ones
+const MAX_UNSIGNED: usize = 99;
+
+impl<'a> StreamVerifier<'a> {
+    pub fn new(key: &'a TsigKey, request_mac: &[u8]) -> Self {
+        Self {
+            key,
+            mac: request_mac.to_vec(),
+            unsigned: Vec::new(),
+            pending: 0,
+            first: true,
+        }
+    }
+
+    /// check the next message, the first one has to be signed
+    pub fn verify(&mut self, message: &[u8], now: u64) -> Result<()> {
+        let packet: DnsPacket =
+            DnsPacket::from_buffer(&mut DnsBytePacketBuffer::from_bytes(message))?;
+        if packet.tsig.is_none() {
+            if self.first || self.pending == MAX_UNSIGNED {
+                return Err(Error::Tsig(BADSIG));
+            }
+            self.unsigned.extend_from_slice(message);
+            self.pending += 1;
+            return Ok(());
+        }
+        let verified: Verified =
+            self.key
+                .check(message, &self.unsigned, now, Some(&self.mac), !self.first)?;
+        self.mac = verified.mac;
+        self.unsigned.clear();
+        self.pending = 0;
+        self.first = false;
+        Ok(())
+    }
+
+    /// whether the last message checked was signed, as the last one of a
+    /// response has to be
+    pub fn is_complete(&self) -> bool {
+        !self.first && self.pending == 0
+    }
+}
+
+/// a message whose TSIG checked out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verified {
+    /// name of the key that signed it
+    pub key: Name,
+    /// its MAC, the response to a request covers it
+    pub mac: Vec<u8>,
+    pub time_signed: u64,
+}
+
+/// TSIG keys by name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyRing {
+    keys: HashMap<Name, TsigKey>,
+}
+
+impl KeyRing {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// add a key, replacing the one of the same name
+    pub fn insert(&mut self, key: TsigKey) -> Option<TsigKey> {
+        self.keys.insert(key.name().clone(), key)
+    }
+
+    pub fn get(&self, name: &Name) -> Option<&TsigKey> {
+        self.keys.get(name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// check the TSIG of a request, `None` when it carries none
+    pub fn verify(&self, message: &[u8], now: u64) -> Result<Option<Verified>> {
+        let packet: DnsPacket =
+            DnsPacket::from_buffer(&mut DnsBytePacketBuffer::from_bytes(message))?;
+        let tsig: DnsRecord = match packet.tsig {
+            Some(tsig) => tsig,
+            None => return Ok(None),
+        };
+        match self.keys.get(&tsig.label) {
+            Some(key) => key.verify(message, now, None).map(Some),
+            None => Err(Error::Tsig(BADKEY)),
+        }
+    }
+
+    /// turn `response` into the NOTAUTH answer to a request whose TSIG
+    /// failed with `error`, RFC 8945 §5.3.2
+    ///
+    /// Only BADTIME answers are signed, they carry the server time so the
+    /// client can tell how far its clock is off.
+    pub fn reject(
+        &self,
+        request: &DnsPacket,
+        response: &mut DnsPacket,
+        error: u16,
+        now: u64,
+    ) -> Result<()> {
+        response.header.r_code = ResponseCode::NOTAUTH;
+        let tsig: &DnsRecord = match &request.tsig {
+            Some(tsig) => tsig,
+            None => return Ok(()),
+        };
+        let (algorithm, time_signed, request_mac) = match &tsig.r_data {
+            RecordData::TSIG {
+                algorithm,
+                time_signed,
+                mac,
+                ..
+            } => (algorithm, *time_signed, mac),
+            _ => return Ok(()),
+        };
+        match (error, self.keys.get(&tsig.label)) {
+            (BADTIME, Some(key)) => {
+                let other: Vec<u8> = now.to_be_bytes()[2..].to_vec();
+                key.sign_with_error(response, time_signed, Some(request_mac), BADTIME, other)?;
+            }
+            _ => {
+                let r_data: RecordData = RecordData::TSIG {
+                    algorithm: algorithm.clone(),
+                    time_signed: now,
+                    fudge: DEFAULT_FUDGE,
+                    mac: Vec::new(),
+                    original_id: response.header.id,
+                    error,
+                    other: Vec::new(),
+                };
+                response.tsig = Some(tsig_record(tsig.label.clone(), r_data));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,323 @@
+use super::dns::{
+    DnsClass, DnsPacket, DnsQuestion, DnsRecord, OpCode, QueryType, RecordData, ResponseCode,
+};
+use super::name::Name;
+use super::serial::Serial;
+use super::zone::Zone;
+use super::{Error, Result};
+
+/// A dynamic update of one zone, RFC 2136: prerequisites that have to hold
+/// and the changes made when they do.
+///
+/// The four sections of a message carry the zone, the prerequisites, the
+/// updates and additional data in place of the question, answer, authority
+/// and additional sections of a query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Update {
+    pub zone: Name,
+    /// checked against the zone before anything changes, RFC 2136 §2.4
+    pub prerequisites: Vec<DnsRecord>,
+    /// records added and deleted in order, RFC 2136 §2.5
+    pub updates: Vec<DnsRecord>,
+}
+
+/// record of `class` with no data, matching any data of `r_type`
+fn any_data(name: Name, r_type: QueryType, class: DnsClass) -> DnsRecord {
+    DnsRecord {
+        r_class: class,
+        ..DnsRecord::new(name, 0, RecordData::UNKNOWN(r_type.into(), Vec::new()))
+    }
+}
+
+/// types that stand for something other than records and cannot be added
+fn is_meta(r_type: QueryType) -> bool {
+    matches!(
+        r_type,
+        QueryType::ANY | QueryType::AXFR | QueryType::IXFR | QueryType::OPT | QueryType::TSIG
+    )
+}
+
+fn refuse(r_code: ResponseCode) -> Error {
+    Error::Update(r_code)
+}
+
+impl Update {
+    pub fn new(zone: Name) -> Self {
+        Self {
+            zone,
+            prerequisites: Vec::new(),
+            updates: Vec::new(),
+        }
+    }
+
+    /// require `name` to own records
+    pub fn require_name(&mut self, name: Name) {
+        self.prerequisites
+            .push(any_data(name, QueryType::ANY, DnsClass::ANY));
+    }
+
+    /// require `name` to own no records
+    pub fn require_no_name(&mut self, name: Name) {
+        self.prerequisites
+            .push(any_data(name, QueryType::ANY, DnsClass::NONE));
+    }
+
+    /// require `name` to own `r_type` records, whatever their data
+    pub fn require_rrset(&mut self, name: Name, r_type: QueryType) {
+        self.prerequisites
+            .push(any_data(name, r_type, DnsClass::ANY));
+    }
+
+    /// require `name` to own no `r_type` records
+    pub fn require_no_rrset(&mut self, name: Name, r_type: QueryType) {
+        self.prerequisites
+            .push(any_data(name, r_type, DnsClass::NONE));
+    }
+
+    /// require the RRset of `record` to hold exactly the records required
+    /// this way, TTLs aside
+    pub fn require(&mut self, record: DnsRecord) {
+        self.prerequisites.push(DnsRecord { ttl: 0, ..record });
+    }
+
+    /// add `record`, replacing the CNAME or SOA of its owner
+    pub fn add(&mut self, record: DnsRecord) {
+        self.updates.push(record);
+    }
+
+    /// delete the RRset of `name` and `r_type`
+    pub fn delete_rrset(&mut self, name: Name, r_type: QueryType) {
+        self.updates.push(any_data(name, r_type, DnsClass::ANY));
+    }
+
+    /// delete every record of `name`, the SOA and NS records of the apex
+    /// excepted
+    pub fn delete_name(&mut self, name: Name) {
+        self.updates
+            .push(any_data(name, QueryType::ANY, DnsClass::ANY));
+    }
+
+    /// delete the record with the owner, type and data of `record`
+    pub fn delete(&mut self, record: DnsRecord) {
+        self.updates.push(DnsRecord {
+            r_class: DnsClass::NONE,
+            ttl: 0,
+            ..record
+        });
+    }
+
+    /// the update an UPDATE message asks for, which has to name the zone
+    /// with a single SOA question
+    pub fn from_packet(packet: &DnsPacket) -> Result<Self> {
+        match packet.questions.as_slice() {
+            [question] if question.q_type == QueryType::SOA => Ok(Self {
+                zone: question.label.clone(),
+                prerequisites: packet.answers.clone(),
+                updates: packet.authorities.clone(),
+            }),
+            _ => Err(refuse(ResponseCode::FORMATERROR)),
+        }
+    }
+
+    /// UPDATE message asking for this update, id left at 0
+    pub fn to_packet(&self) -> DnsPacket {
+        let mut packet: DnsPacket = DnsPacket::new();
+        packet.header.op_code = OpCode::UPDATE;
+        packet
+            .questions
+            .push(DnsQuestion::new(self.zone.clone(), QueryType::SOA));
+        packet.answers = self.prerequisites.clone();
+        packet.authorities = self.updates.clone();
+        packet
+    }
+
+    /// carry out the update on `zone`, returning whether anything changed
+    ///
+    /// Fails with the RCODE to answer and leaves the zone as it was when a
+    /// prerequisite does not hold or an update is malformed. A change the
+    /// update does not bump the serial for increments it by one.
+    pub fn apply_to(&self, zone: &mut Zone) -> Result<bool> {
+        if self.zone != *zone.origin() {
+            return Err(refuse(ResponseCode::NOTAUTH));
+        }
+        let class: DnsClass = zone.soa().map_or(DnsClass::IN, |soa| soa.r_class);
+        self.check_prerequisites(zone, class)?;
+        self.prescan(zone, class)?;
+
+        let mut updated: Zone = zone.clone();
+        let serial: Option<u32> = zone.serial();
+        let mut changed: bool = false;
+        for record in &self.updates {
+            changed |= match record.r_class {
+                DnsClass::ANY => delete_any(&mut updated, record),
+                DnsClass::NONE => delete_one(&mut updated, record),
+                _ => add(&mut updated, record),
+            };
+        }
+        if !changed {
+            return Ok(false);
+        }
+        if updated.serial() == serial {
+            bump_serial(&mut updated);
+        }
+        *zone = updated;
+        Ok(true)
+    }
+
+    /// RFC 2136 §3.2
+    fn check_prerequisites(&self, zone: &Zone, class: DnsClass) -> Result<()> {
+        let mut required: Vec<&DnsRecord> = Vec::new();
+        for record in &self.prerequisites {
+            if record.ttl != 0 {
+                return Err(refuse(ResponseCode::FORMATERROR));
+            }
+            if !record.label.ends_with(zone.origin()) {
+                return Err(refuse(ResponseCode::NOTZONE));
+            }
+            let empty: bool = record.r_data.wire_len() == 0;
+            let in_use: bool = match record.r_type {
+                QueryType::ANY => !zone.records_at(&record.label).is_empty(),
+                r_type => !zone.rrset(&record.label, r_type).is_empty(),
+            };
+            match (record.r_class, record.r_type) {
+                (DnsClass::ANY | DnsClass::NONE, _) if !empty => {
+                    return Err(refuse(ResponseCode::FORMATERROR))
+                }
+                (DnsClass::ANY, QueryType::ANY) if !in_use => {
+                    return Err(refuse(ResponseCode::NAMEERROR))
+                }
+                (DnsClass::ANY, _) if !in_use => return Err(refuse(ResponseCode::NXRRSET)),
+                (DnsClass::NONE, QueryType::ANY) if in_use => {
+                    return Err(refuse(ResponseCode::YXDOMAIN))
+                }
+                (DnsClass::NONE, _) if in_use => return Err(refuse(ResponseCode::YXRRSET)),
+                (DnsClass::ANY | DnsClass::NONE, _) => {}
+                (r_class, r_type) if r_class == class && !is_meta(r_type) => required.push(record),
+                _ => return Err(refuse(ResponseCode::FORMATERROR)),
+            }
+        }
+        // value dependent prerequisites match whole RRsets, RFC 2136 §3.2.3
+        for record in &required {
+            let wanted: Vec<&RecordData> = required
+                .iter()
+                .filter(|r| r.label == record.label && r.r_type == record.r_type)
+                .fold(Vec::new(), |mut wanted, r| {
+                    if !wanted.contains(&&r.r_data) {
+                        wanted.push(&r.r_data);
+                    }
+                    wanted
+                });
+            let rrset: Vec<&DnsRecord> = zone.rrset(&record.label, record.r_type);
+            if rrset.len() != wanted.len() || !rrset.iter().all(|r| wanted.contains(&&r.r_data)) {
+                return Err(refuse(ResponseCode::NXRRSET));
+            }
+        }
+        Ok(())
+    }
+
+    /// RFC 2136 §3.4.1
+    fn prescan(&self, zone: &Zone, class: DnsClass) -> Result<()> {
+        for record in &self.updates {
+            if !record.label.ends_with(zone.origin()) {
+                return Err(refuse(ResponseCode::NOTZONE));
+            }
+            let valid: bool = match record.r_class {
+                DnsClass::ANY => {
+                    record.ttl == 0
+                        && record.r_data.wire_len() == 0
+                        && (record.r_type == QueryType::ANY || !is_meta(record.r_type))
+                }
+                DnsClass::NONE => record.ttl == 0 && !is_meta(record.r_type),
+                r_class => r_class == class && !is_meta(record.r_type),
+            };
+            if !valid {
+                return Err(refuse(ResponseCode::FORMATERROR));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// add `record` unless it would put a CNAME beside other data, RFC 2136
+/// §3.4.2.2, returning whether the zone changed
+fn add(zone: &mut Zone, record: &DnsRecord) -> bool {
+    let existing: &[DnsRecord] = zone.records_at(&record.label);
+    // a record already there, whatever its TTL, changes nothing
+    if existing
+        .iter()
+        .any(|r| r.r_type == record.r_type && r.r_data == record.r_data)
+    {
+        return false;
+    }
+    let has_cname: bool = existing.iter().any(|r| r.r_type == QueryType::CNAME);
+    let has_other: bool = existing.iter().any(|r| r.r_type != QueryType::CNAME);
+    match record.r_type {
+        QueryType::CNAME if has_other => return false,
+        QueryType::CNAME => {
+            zone.remove(&record.label, QueryType::CNAME);
+        }
+        _ if has_cname => return false,
+        QueryType::SOA => match (
+            record.label == *zone.origin(),
+            &record.r_data,
+            zone.serial(),
+        ) {
+            (true, RecordData::SOA { serial, .. }, Some(current))
+                if Serial(*serial) > Serial(current) =>
+            {
+                zone.remove(&record.label, QueryType::SOA);
+            }
+            _ => return false,
+        },
+        _ => {}
+    }
+    zone.insert(record.clone());
+    true
+}
+
+/// delete an RRset or every record of a name, the apex keeping its SOA and
+/// NS records
+fn delete_any(zone: &mut Zone, record: &DnsRecord) -> bool {
+    let apex: bool = record.label == *zone.origin();
+    match record.r_type {
+        QueryType::ANY => {
+            let doomed: Vec<QueryType> = zone
+                .records_at(&record.label)
+                .iter()
+                .map(|r| r.r_type)
+                .filter(|r_type| !(apex && matches!(r_type, QueryType::SOA | QueryType::NS)))
+                .collect();
+            let mut changed: bool = false;
+            for r_type in doomed {
+                changed |= !zone.remove(&record.label, r_type).is_empty();
+            }
+            changed
+        }
+        QueryType::SOA | QueryType::NS if apex => false,
+        r_type => !zone.remove(&record.label, r_type).is_empty(),
+    }
+}
+
+/// delete a single record, never the SOA or the last NS of the apex
+fn delete_one(zone: &mut Zone, record: &DnsRecord) -> bool {
+    let apex: bool = record.label == *zone.origin();
+    match record.r_type {
+        QueryType::SOA => false,
+        QueryType::NS if apex && zone.rrset(&record.label, QueryType::NS).len() <= 1 => false,
+        _ => zone.delete(record),
+    }
+}
+
+/// increment the serial of the zone, RFC 1982 §3.1
+pub(crate) fn bump_serial(zone: &mut Zone) {
+    let mut soa: DnsRecord = match zone.soa() {
+        Some(soa) => soa.clone(),
+        None => return,
+    };
+    if let RecordData::SOA { serial, .. } = &mut soa.r_data {
+        *serial = Serial(*serial).wrapping_add(1).0;
+    }
+    let origin: Name = zone.origin().clone();
+    zone.remove(&origin, QueryType::SOA);
+    zone.insert(soa);
+}
@@ -0,0 +1,1129 @@
+use super::dns::{
+    DnsClass, DnsPacket, DnsQuestion, DnsRecord, QueryType, RecordData, ResponseCode,
+};
+use super::dnssec::{covers, nsec3_hash, nsec3_label, nsec3_owner_hash};
+use super::journal::{Diff, Journal};
+use super::name::Name;
+use super::{Error, Result};
+use data_encoding::{BASE32_DNSSEC, BASE64, HEXUPPER_PERMISSIVE};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::ops::Bound;
+
+/// longest CNAME chain followed inside a zone
+const MAX_CNAME_CHAIN: usize = 8;
+
+/// one field of a zone file entry, escapes are kept as written
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    quoted: bool,
+}
+
+/// the tokens of one record, possibly spanning lines inside parentheses
+#[derive(Debug)]
+struct Entry {
+    line: usize,
+    /// the entry starts with blanks and reuses the previous owner
+    blank_owner: bool,
+    tokens: Vec<Token>,
+}
+
+/// split master file text into entries, RFC 1035 §5.1
+fn entries(text: &str) -> Result<Vec<Entry>> {
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut line: usize = 1;
+    let mut entry: Entry = Entry {
+        line,
+        blank_owner: false,
+        tokens: Vec::new(),
+    };
+    let mut token: Option<Token> = None;
+    let mut depth: usize = 0;
+    let mut in_quotes: bool = false;
+    let mut in_comment: bool = false;
+    let mut line_start: bool = true;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if line_start && depth == 0 && c != '\n' {
+            entry.blank_owner = c == ' ' || c == '\t';
+        }
+        line_start = false;
+        if in_comment && c != '\n' {
+            continue;
+        }
+        match c {
+            '\\' => {
+                let escaped: char = chars.next().ok_or_else(|| Error::Syntax {
+                    line,
+                    reason: "dangling escape".to_string(),
+                })?;
+                let current: &mut Token = token.get_or_insert(Token {
+                    text: String::new(),
+                    quoted: false,
+                });
+                current.text.push('\\');
+                current.text.push(escaped);
+            }
+            '"' if in_quotes => {
+                in_quotes = false;
+                entry.tokens.extend(token.take());
+            }
+            '"' => {
+                entry.tokens.extend(token.take());
+                in_quotes = true;
+                token = Some(Token {
+                    text: String::new(),
+                    quoted: true,
+                });
+            }
+            c if in_quotes => {
+                if c == '\n' {
+                    line += 1;
+                }
+                token.get_or_insert(Token {
+                    text: String::new(),
+                    quoted: true,
+                });
+                if let Some(current) = token.as_mut() {
+                    current.text.push(c);
+                }
+            }
+            ';' => {
+                entry.tokens.extend(token.take());
+                in_comment = true;
+            }
+            '(' => {
+                entry.tokens.extend(token.take());
+                depth += 1;
+            }
+            ')' => {
+                entry.tokens.extend(token.take());
+                depth = depth.checked_sub(1).ok_or_else(|| Error::Syntax {
+                    line,
+                    reason: "unbalanced parenthesis".to_string(),
+                })?;
+            }
+            '\n' => {
+                entry.tokens.extend(token.take());
+                in_comment = false;
+                line += 1;
+                line_start = true;
+                if depth == 0 {
+                    let next: Entry = Entry {
+                        line,
+                        blank_owner: false,
+                        tokens: Vec::new(),
+                    };
+                    let done: Entry = std::mem::replace(&mut entry, next);
+                    if !done.tokens.is_empty() {
+                        entries.push(done);
+                    }
+                }
+            }
+            c if c.is_whitespace() => entry.tokens.extend(token.take()),
+            c => token
+                .get_or_insert(Token {
+                    text: String::new(),
+                    quoted: false,
+                })
+                .text
+                .push(c),
+        }
+    }
+    if in_quotes || depth > 0 {
+        return Err(Error::Syntax {
+            line,
+            reason: "unterminated quote or parenthesis".to_string(),
+        });
+    }
+    entry.tokens.extend(token.take());
+    if !entry.tokens.is_empty() {
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// a name relative to `origin` unless it ends in an unescaped dot
+pub fn parse_name(text: &str, origin: &Name) -> Result<Name> {
+    if text == "@" {
+        return Ok(origin.clone());
+    }
+    let name: Name = text.parse()?;
+    // a dot preceded by an odd number of backslashes is part of the last label
+    let absolute: bool = text
+        .strip_suffix('.')
+        .is_some_and(|rest| rest.bytes().rev().take_while(|b| *b == b'\\').count() % 2 == 0);
+    match absolute {
+        true => Ok(name),
+        false => name.append(origin),
+    }
+}
+
+/// a TTL in seconds or with BIND style unit suffixes such as `1h30m`
+pub fn parse_ttl(text: &str) -> Option<u32> {
+    if let Ok(ttl) = text.parse::<u32>() {
+        return Some(ttl);
+    }
+    let mut total: u32 = 0;
+    let mut value: u32 = 0;
+    let mut digits: bool = false;
+    for c in text.chars() {
+        match c.to_ascii_lowercase() {
+            d if d.is_ascii_digit() => {
+                value = value.checked_mul(10)?.checked_add(d as u32 - '0' as u32)?;
+                digits = true;
+            }
+            unit => {
+                let factor: u32 = match unit {
+                    's' => 1,
+                    'm' => 60,
+                    'h' => 3600,
+                    'd' => 86400,
+                    'w' => 604800,
+                    _ => return None,
+                };
+                if !digits {
+                    return None;
+                }
+                total = total.checked_add(value.checked_mul(factor)?)?;
+                value = 0;
+                digits = false;
+            }
+        }
+    }
+    match digits {
+        true => None,
+        false => Some(total),
+    }
+}
+
+/// decode `\X` and `\DDD` escapes of a character-string
+fn character_string(token: &Token) -> Result<Vec<u8>> {
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut iter = token.text.bytes();
+    while let Some(b) = iter.next() {
+        if b != b'\\' {
+            bytes.push(b);
+            continue;
+        }
+        match iter.next() {
+            Some(d) if d.is_ascii_digit() => {
+                let rest: Vec<u8> = iter.by_ref().take(2).collect();
+                let value: u32 = std::str::from_utf8(&[&[d][..], &rest].concat())
+                    .ok()
+                    .filter(|digits| digits.len() == 3)
+                    .and_then(|digits| digits.parse().ok())
+                    .filter(|value| *value <= 0xFF)
+                    .ok_or_else(|| invalid("invalid \\DDD escape"))?;
+                bytes.push(value as u8);
+            }
+            Some(c) => bytes.push(c),
+            None => return Err(invalid("dangling escape")),
+        }
+    }
+    if bytes.len() > 0xFF {
+        return Err(Error::CharacterStringTooLong(bytes.len()));
+    }
+    Ok(bytes)
+}
+
+/// `YYYYMMDDHHmmSS` as seconds since the epoch, or a plain number
+fn parse_timestamp(text: &str) -> Option<u32> {
+    if text.len() != 14 {
+        return text.parse().ok();
+    }
+    let field = |range: std::ops::Range<usize>| -> Option<i64> { text.get(range)?.parse().ok() };
+    let (year, month, day) = (field(0..4)?, field(4..6)?, field(6..8)?);
+    let (hour, minute, second) = (field(8..10)?, field(10..12)?, field(12..14)?);
+    // civil date to days, http://howardhinnant.github.io/date_algorithms.html
+    let y: i64 = if month <= 2 { year - 1 } else { year };
+    let era: i64 = y.div_euclid(400);
+    let yoe: i64 = y - era * 400;
+    let mp: i64 = (month + 9) % 12;
+    let doy: i64 = (153 * mp + 2) / 5 + day - 1;
+    let doe: i64 = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days: i64 = era * 146097 + doe - 719468;
+    Some((days * 86400 + hour * 3600 + minute * 60 + second) as u32)
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::InvalidRecord(reason.to_string())
+}
+
+/// record data in presentation format, `fields` are the tokens after the type
+fn parse_rdata(r_type: QueryType, fields: &[Token], origin: &Name) -> Result<RecordData> {
+    // RFC 3597 generic encoding works for every type
+    if fields.first().is_some_and(|t| !t.quoted && t.text == "\\#") {
+        let len: usize = fields
+            .get(1)
+            .and_then(|t| t.text.parse().ok())
+            .ok_or_else(|| invalid("missing length of generic data"))?;
+        let hex: String = fields[2..].iter().map(|t| t.text.as_str()).collect();
+        let data: Vec<u8> = HEXUPPER_PERMISSIVE
+            .decode(hex.as_bytes())
+            .map_err(|_| invalid("invalid hex in generic data"))?;
+        if data.len() != len {
+            return Err(invalid("generic data length mismatch"));
+        }
+        return match r_type {
+            QueryType::NULL => Ok(RecordData::NULL(data)),
+            QueryType::UNKNOWN(value) => Ok(RecordData::UNKNOWN(value, data)),
+            known => RecordData::from_wire(known, &data),
+        };
+    }
+
+    let mut fields = fields.iter();
+    let mut next = || fields.next().ok_or_else(|| invalid("too few fields"));
+    let number = |t: &Token| -> Result<u32> {
+        t.text
+            .parse()
+            .map_err(|_| invalid(&format!("invalid number {}", t.text)))
+    };
+    let r_data: RecordData = match r_type {
+        QueryType::A => RecordData::IPADDR(
+            next()?
+                .text
+                .parse::<Ipv4Addr>()
+                .map_err(|_| invalid("invalid IPv4 address"))?,
+        ),
+        QueryType::NS => RecordData::NS(parse_name(&next()?.text, origin)?),
+        QueryType::MD => RecordData::MD(parse_name(&next()?.text, origin)?),
+        QueryType::MF => RecordData::MF(parse_name(&next()?.text, origin)?),
+        QueryType::CNAME => RecordData::CNAME(parse_name(&next()?.text, origin)?),
+        QueryType::MB => RecordData::MB(parse_name(&next()?.text, origin)?),
+        QueryType::MG => RecordData::MG(parse_name(&next()?.text, origin)?),
+        QueryType::MR => RecordData::MR(parse_name(&next()?.text, origin)?),
+        QueryType::PTR => RecordData::PTR(parse_name(&next()?.text, origin)?),
+        QueryType::SOA => {
+            let mname: Name = parse_name(&next()?.text, origin)?;
+            let rname: Name = parse_name(&next()?.text, origin)?;
+            let serial: u32 = number(next()?)?;
+            let mut times: [u32; 4] = [0; 4];
+            for time in times.iter_mut() {
+                let token: &Token = next()?;
+                *time = parse_ttl(&token.text).ok_or_else(|| invalid("invalid SOA timer"))?;
+            }
+            RecordData::SOA {
+                mname,
+                rname,
+                serial,
+                refresh: times[0],
+                retry: times[1],
+                expire: times[2],
+                minimum: times[3],
+            }
+        }
+        QueryType::WKS => {
+            let address: Ipv4Addr = next()?
+                .text
+                .parse()
+                .map_err(|_| invalid("invalid IPv4 address"))?;
+            let protocol: u8 = number(next()?)? as u8;
+            let mut bitmap: Vec<u8> = Vec::new();
+            for token in fields.by_ref() {
+                let port: usize = number(token)? as usize;
+                if port > u16::MAX as usize {
+                    return Err(invalid("port out of range"));
+                }
+                if bitmap.len() <= port / 8 {
+                    bitmap.resize(port / 8 + 1, 0);
+                }
+                bitmap[port / 8] |= 0x80 >> (port % 8);
+            }
+            RecordData::WKS {
+                address,
+                protocol,
+                bitmap,
+            }
+        }
+        QueryType::HINFO => RecordData::HINFO {
+            cpu: character_string(next()?)?,
+            os: character_string(next()?)?,
+        },
+        QueryType::MINFO => RecordData::MINFO {
+            rmailbx: parse_name(&next()?.text, origin)?,
+            emailbx: parse_name(&next()?.text, origin)?,
+        },
+        QueryType::MX => RecordData::MX {
+            preference: number(next()?)? as u16,
+            exchange: parse_name(&next()?.text, origin)?,
+        },
+        QueryType::TXT => RecordData::TXT(
+            fields
+                .by_ref()
+                .map(character_string)
+                .collect::<Result<Vec<Vec<u8>>>>()?,
+        ),
+        QueryType::AAAA => RecordData::AAAA(
+            next()?
+                .text
+                .parse::<Ipv6Addr>()
+                .map_err(|_| invalid("invalid IPv6 address"))?,
+        ),
+        QueryType::SRV => RecordData::SRV {
+            priority: number(next()?)? as u16,
+            weight: number(next()?)? as u16,
+            port: number(next()?)? as u16,
+            target: parse_name(&next()?.text, origin)?,
+        },
+        QueryType::DS => RecordData::DS {
+            key_tag: number(next()?)? as u16,
+            algorithm: number(next()?)? as u8,
+            digest_type: number(next()?)? as u8,
+            digest: HEXUPPER_PERMISSIVE
+                .decode(concat(fields.by_ref()).as_bytes())
+                .map_err(|_| invalid("invalid digest"))?,
+        },
+        QueryType::RRSIG => RecordData::RRSIG {
+            type_covered: next()?.text.parse()?,
+            algorithm: number(next()?)? as u8,
+            labels: number(next()?)? as u8,
+            original_ttl: number(next()?)?,
+            expiration: parse_timestamp(&next()?.text)
+                .ok_or_else(|| invalid("invalid expiration"))?,
+            inception: parse_timestamp(&next()?.text)
+                .ok_or_else(|| invalid("invalid inception"))?,
+            key_tag: number(next()?)? as u16,
+            signer_name: parse_name(&next()?.text, origin)?,
+            signature: BASE64
+                .decode(concat(fields.by_ref()).as_bytes())
+                .map_err(|_| invalid("invalid signature"))?,
+        },
+        QueryType::NSEC => RecordData::NSEC {
+            next_domain: parse_name(&next()?.text, origin)?,
+            types: types(fields.by_ref())?,
+        },
+        QueryType::DNSKEY => RecordData::DNSKEY {
+            flags: number(next()?)? as u16,
+            protocol: number(next()?)? as u8,
+            algorithm: number(next()?)? as u8,
+            public_key: BASE64
+                .decode(concat(fields.by_ref()).as_bytes())
+                .map_err(|_| invalid("invalid public key"))?,
+        },
+        QueryType::NSEC3 => RecordData::NSEC3 {
+            hash_algorithm: number(next()?)? as u8,
+            flags: number(next()?)? as u8,
+            iterations: number(next()?)? as u16,
+            salt: salt(next()?)?,
+            next_hashed: BASE32_DNSSEC
+                .decode(next()?.text.to_ascii_lowercase().as_bytes())
+                .map_err(|_| invalid("invalid next hashed owner"))?,
+            types: types(fields.by_ref())?,
+        },
+        QueryType::NSEC3PARAM => RecordData::NSEC3PARAM {
+            hash_algorithm: number(next()?)? as u8,
+            flags: number(next()?)? as u8,
+            iterations: number(next()?)? as u16,
+            salt: salt(next()?)?,
+        },
+        QueryType::TSIG => {
+            let algorithm: Name = parse_name(&next()?.text, origin)?;
+            let time_signed: u64 = next()?
+                .text
+                .parse()
+                .map_err(|_| invalid("invalid time signed"))?;
+            let fudge: u16 = number(next()?)? as u16;
+            let mac: Vec<u8> = match number(next()?)? {
+                0 => Vec::new(),
+                _ => BASE64
+                    .decode(next()?.text.as_bytes())
+                    .map_err(|_| invalid("invalid MAC"))?,
+            };
+            let original_id: u16 = number(next()?)? as u16;
+            let error: u16 = number(next()?)? as u16;
+            let other: Vec<u8> = match number(next()?)? {
+                0 => Vec::new(),
+                _ => BASE64
+                    .decode(next()?.text.as_bytes())
+                    .map_err(|_| invalid("invalid other data"))?,
+            };
+            RecordData::TSIG {
+                algorithm,
+                time_signed,
+                fudge,
+                mac,
+                original_id,
+                error,
+                other,
+            }
+        }
+        other => {
+            return Err(invalid(&format!("{} needs the generic \\# form", other)));
+        }
+    };
+    match fields.next() {
+        Some(extra) => Err(invalid(&format!("unexpected field {}", extra.text))),
+        None => Ok(r_data),
+    }
+}
+
+fn concat<'a>(fields: impl Iterator<Item = &'a Token>) -> String {
+    fields.map(|t| t.text.as_str()).collect()
+}
+
+fn types<'a>(fields: impl Iterator<Item = &'a Token>) -> Result<Vec<QueryType>> {
+    let mut types: Vec<QueryType> = fields
+        .map(|t| t.text.parse())
+        .collect::<Result<Vec<QueryType>>>()?;
+    types.sort_by_key(|t| u16::from(*t));
+    types.dedup();
+    Ok(types)
+}
+
+fn salt(token: &Token) -> Result<Vec<u8>> {
+    match token.text.as_str() {
+        "-" => Ok(Vec::new()),
+        hex => HEXUPPER_PERMISSIVE
+            .decode(hex.as_bytes())
+            .map_err(|_| invalid("invalid salt")),
+    }
+}
+
+/// records of a master file, relative names are completed with `origin`
+///
+/// `$ORIGIN` and `$TTL` are understood, `$INCLUDE` is not. Records without
+/// a TTL take the `$TTL` default, then the previous record's TTL.
+pub fn parse_records(text: &str, origin: &Name) -> Result<Vec<DnsRecord>> {
+    let mut origin: Name = origin.clone();
+    let mut default_ttl: Option<u32> = None;
+    let mut last_ttl: Option<u32> = None;
+    let mut last_owner: Option<Name> = None;
+    let mut last_class: DnsClass = DnsClass::IN;
+    let mut records: Vec<DnsRecord> = Vec::new();
+    for entry in entries(text)? {
+        let line: usize = entry.line;
+        let syntax = |err: Error| Error::Syntax {
+            line,
+            reason: err.to_string(),
+        };
+        let tokens: &[Token] = &entry.tokens;
+        if !entry.blank_owner && tokens[0].text.starts_with('$') {
+            let argument: &str = tokens.get(1).map(|t| t.text.as_str()).unwrap_or_default();
+            match tokens[0].text.to_ascii_uppercase().as_str() {
+                "$ORIGIN" => origin = parse_name(argument, &origin).map_err(syntax)?,
+                "$TTL" => {
+                    default_ttl =
+                        Some(parse_ttl(argument).ok_or_else(|| syntax(invalid("invalid $TTL")))?);
+                }
+                directive => {
+                    return Err(syntax(invalid(&format!(
+                        "unsupported directive {}",
+                        directive
+                    ))));
+                }
+            }
+            continue;
+        }
+
+        let (owner, mut index): (Name, usize) = match entry.blank_owner {
+            true => match &last_owner {
+                Some(owner) => (owner.clone(), 0),
+                None => return Err(syntax(invalid("no previous owner"))),
+            },
+            false => (parse_name(&tokens[0].text, &origin).map_err(syntax)?, 1),
+        };
+        let mut ttl: Option<u32> = None;
+        let mut class: Option<DnsClass> = None;
+        while let Some(token) = tokens.get(index) {
+            if ttl.is_none() && token.text.starts_with(|c: char| c.is_ascii_digit()) {
+                ttl = Some(parse_ttl(&token.text).ok_or_else(|| syntax(invalid("invalid TTL")))?);
+            } else if let (None, Ok(parsed)) = (class, token.text.parse::<DnsClass>()) {
+                class = Some(parsed);
+            } else {
+                break;
+            }
+            index += 1;
+        }
+        let r_type: QueryType = tokens
+            .get(index)
+            .ok_or_else(|| syntax(invalid("missing type")))?
+            .text
+            .parse()
+            .map_err(syntax)?;
+        let r_data: RecordData =
+            parse_rdata(r_type, &tokens[index + 1..], &origin).map_err(syntax)?;
+        let ttl: u32 = match (ttl, default_ttl, last_ttl, &r_data) {
+            (Some(ttl), _, _, _) | (None, Some(ttl), _, _) | (None, None, Some(ttl), _) => ttl,
+            (None, None, None, RecordData::SOA { minimum, .. }) => *minimum,
+            _ => return Err(syntax(invalid("no TTL and no $TTL default"))),
+        };
+        let class: DnsClass = class.unwrap_or(last_class);
+        last_owner = Some(owner.clone());
+        last_ttl = Some(ttl);
+        last_class = class;
+        records.push(DnsRecord {
+            r_class: class,
+            ..DnsRecord::new(owner, ttl, r_data)
+        });
+    }
+    Ok(records)
+}
+
+/// Authoritative data of one zone, names kept in canonical order.
+#[derive(Debug, Clone)]
+pub struct Zone {
+    origin: Name,
+    records: BTreeMap<Name, Vec<DnsRecord>>,
+    /// how the zone got to its current serial, for IXFR
+    journal: Journal,
+}
+
+impl Zone {
+    /// empty zone, it cannot answer before an SOA is inserted
+    pub fn new(origin: Name) -> Self {
+        Self {
+            origin,
+            records: BTreeMap::new(),
+            journal: Journal::default(),
+        }
+    }
+
+    /// zone holding `records`, which need exactly one SOA at the apex and
+    /// no owner outside `origin`
+    pub fn from_records(origin: Name, records: Vec<DnsRecord>) -> Result<Self> {
+        let mut zone: Zone = Self::new(origin);
+        for record in records {
+            if !record.label.ends_with(&zone.origin) {
+                return Err(Error::InvalidZone(format!(
+                    "{} is outside of {}",
+                    record.label, zone.origin
+                )));
+            }
+            zone.insert(record);
+        }
+        match zone.rrset(&zone.origin, QueryType::SOA).len() {
+            1 => Ok(zone),
+            n => Err(Error::InvalidZone(format!(
+                "{} has {} SOA records at the apex",
+                zone.origin, n
+            ))),
+        }
+    }
+
+    pub fn parse(text: &str, origin: &Name) -> Result<Self> {
+        Self::from_records(origin.clone(), parse_records(text, origin)?)
+    }
+
+    pub fn load(file_name: &str, origin: &Name) -> Result<Self> {
+        Self::parse(&fs::read_to_string(file_name)?, origin)
+    }
+
+    pub fn origin(&self) -> &Name {
+        &self.origin
+    }
+
+    pub fn soa(&self) -> Option<&DnsRecord> {
+        self.rrset(&self.origin, QueryType::SOA).into_iter().next()
+    }
+
+    pub fn serial(&self) -> Option<u32> {
+        match self.soa()?.r_data {
+            RecordData::SOA { serial, .. } => Some(serial),
+            _ => None,
+        }
+    }
+
+    /// every record, owners in canonical order
+    pub fn records(&self) -> impl Iterator<Item = &DnsRecord> {
+        self.records.values().flatten()
+    }
+
+    /// owner names in canonical order
+    pub fn names(&self) -> impl Iterator<Item = &Name> {
+        self.records.keys()
+    }
+
+    /// records owned by `name`
+    pub fn records_at(&self, name: &Name) -> &[DnsRecord] {
+        self.records
+            .get(name)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// add a record, an identical one already present is replaced
+    pub fn insert(&mut self, record: DnsRecord) {
+        let records: &mut Vec<DnsRecord> = self.records.entry(record.label.clone()).or_default();
+        records.retain(|r| !(r.r_type == record.r_type && r.r_data == record.r_data));
+        records.push(record);
+    }
+
+    /// drop the record with the owner, type and data of `record`, whatever
+    /// its TTL, returning whether there was one
+    pub fn delete(&mut self, record: &DnsRecord) -> bool {
+        let records: &mut Vec<DnsRecord> = match self.records.get_mut(&record.label) {
+            Some(records) => records,
+            None => return false,
+        };
+        let len: usize = records.len();
+        records.retain(|r| !(r.r_type == record.r_type && r.r_data == record.r_data));
+        let deleted: bool = records.len() != len;
+        if records.is_empty() {
+            self.records.remove(&record.label);
+        }
+        deleted
+    }
+
+    /// drop the RRset of `name` and `r_type`, returning it
+    pub fn remove(&mut self, name: &Name, r_type: QueryType) -> Vec<DnsRecord> {
+        let mut removed: Vec<DnsRecord> = Vec::new();
+        if let Some(records) = self.records.get_mut(name) {
+            let (gone, kept): (Vec<DnsRecord>, Vec<DnsRecord>) =
+                records.drain(..).partition(|r| r.r_type == r_type);
+            *records = kept;
+            removed = gone;
+            if records.is_empty() {
+                self.records.remove(name);
+            }
+        }
+        removed
+    }
+
+    /// keep only the records `keep` returns true for
+    pub fn retain<F: FnMut(&DnsRecord) -> bool>(&mut self, mut keep: F) {
+        for records in self.records.values_mut() {
+            records.retain(&mut keep);
+        }
+        self.records.retain(|_, records| !records.is_empty());
+    }
+
+    pub fn rrset(&self, name: &Name, r_type: QueryType) -> Vec<&DnsRecord> {
+        self.records_at(name)
+            .iter()
+            .filter(|r| r.r_type == r_type)
+            .collect()
+    }
+
+    /// the topmost zone cut at or above `name`, the apex does not count
+    pub fn delegation(&self, name: &Name) -> Option<Name> {
+        (self.origin.num_labels() + 1..=name.num_labels())
+            .map(|num_labels| name.trim_to(num_labels))
+            .find(|cut| !self.rrset(cut, QueryType::NS).is_empty())
+    }
+
+    /// `name` owns no records but names below it do
+    pub fn is_empty_non_terminal(&self, name: &Name) -> bool {
+        !self.records.contains_key(name)
+            && self
+                .records
+                .range((Bound::Excluded(name), Bound::Unbounded))
+                .next()
+                .is_some_and(|(below, _)| below.ends_with(name))
+    }
+
+    /// `name` owns records or is an empty non-terminal
+    pub fn exists(&self, name: &Name) -> bool {
+        self.records.contains_key(name) || self.is_empty_non_terminal(name)
+    }
+
+    pub fn journal(&self) -> &Journal {
+        &self.journal
+    }
+
+    pub fn set_journal(&mut self, journal: Journal) {
+        self.journal = journal;
+    }
+
+    /// take over the journal of `previous`, the same zone at an older
+    /// serial, adding the diff from there to this zone
+    pub fn succeed(&mut self, previous: &Zone) {
+        self.journal = previous.journal.clone();
+        if let Some(diff) = previous.diff(self) {
+            self.journal.push(diff);
+        }
+    }
+
+    /// what changed from this zone to `newer`, `None` when either lacks
+    /// an SOA or the serial is the same
+    pub fn diff(&self, newer: &Zone) -> Option<Diff> {
+        let (old_soa, new_soa) = (self.soa()?, newer.soa()?);
+        if self.serial() == newer.serial() {
+            return None;
+        }
+        let changed = |from: &Zone, to: &Zone| -> Vec<DnsRecord> {
+            from.records()
+                .filter(|r| *r != from.soa().expect("SOA checked above"))
+                .filter(|r| !to.records_at(&r.label).contains(r))
+                .cloned()
+                .collect()
+        };
+        Some(Diff {
+            old_soa: old_soa.clone(),
+            deleted: changed(self, newer),
+            new_soa: new_soa.clone(),
+            added: changed(newer, self),
+        })
+    }
+
+    /// bring the zone from the old to the new serial of `diff` and add it to
+    /// the journal
+    ///
+    /// Fails leaving the zone as it was when the zone is at another serial
+    /// or a deleted record is missing.
+    pub fn apply(&mut self, diff: Diff) -> Result<()> {
+        if self.serial() != Some(diff.old_serial()) {
+            return Err(Error::InvalidZone(format!(
+                "diff from serial {} does not apply to {} at {:?}",
+                diff.old_serial(),
+                self.origin,
+                self.serial()
+            )));
+        }
+        let mut updated: Zone = self.clone();
+        for record in &diff.deleted {
+            if !updated.delete(record) {
+                return Err(Error::InvalidZone(format!(
+                    "diff deletes {} missing from the zone",
+                    record
+                )));
+            }
+        }
+        for record in &diff.added {
+            if !record.label.ends_with(&self.origin) {
+                return Err(Error::InvalidZone(format!(
+                    "{} is outside of {}",
+                    record.label, self.origin
+                )));
+            }
+            updated.insert(record.clone());
+        }
+        // the SOA keeps its place among the apex records
+        let apex: &mut Vec<DnsRecord> = updated.records.entry(self.origin.clone()).or_default();
+        match apex.iter_mut().find(|r| r.r_type == QueryType::SOA) {
+            Some(soa) => *soa = diff.new_soa.clone(),
+            None => apex.push(diff.new_soa.clone()),
+        }
+        updated.journal.push(diff);
+        *self = updated;
+        Ok(())
+    }
+
+    /// parameters of the NSEC3 chain, if the zone has one
+    pub fn nsec3_params(&self) -> Option<(u16, Vec<u8>)> {
+        self.rrset(&self.origin, QueryType::NSEC3PARAM)
+            .into_iter()
+            .find_map(|r| match &r.r_data {
+                RecordData::NSEC3PARAM {
+                    iterations, salt, ..
+                } => Some((*iterations, salt.clone())),
+                _ => None,
+            })
+    }
+
+    /// the RRset of `name` and `r_type` with its signatures when `dnssec_ok`
+    fn signed_rrset(&self, name: &Name, r_type: QueryType, dnssec_ok: bool) -> Vec<DnsRecord> {
+        self.records_at(name)
+            .iter()
+            .filter(|r| {
+                r.r_type == r_type
+                    || (dnssec_ok
+                        && matches!(r.r_data, RecordData::RRSIG { type_covered, .. } if type_covered == r_type))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// fill `response` with the authoritative answer to `question`
+    pub fn answer(&self, question: &DnsQuestion, dnssec_ok: bool, response: &mut DnsPacket) {
+        response.header.aa = true;
+        response.header.r_code = ResponseCode::NOERROR;
+        let mut qname: Name = question.label.clone();
+        for _ in 0..MAX_CNAME_CHAIN {
+            match self.answer_name(&qname, question.q_type, dnssec_ok, response) {
+                Some(target) if target.ends_with(&self.origin) && target != qname => qname = target,
+                _ => break,
+            }
+        }
+    }
+
+    /// answer for one name of a CNAME chain, returns the alias target to follow
+    fn answer_name(
+        &self,
+        qname: &Name,
+        q_type: QueryType,
+        dnssec_ok: bool,
+        response: &mut DnsPacket,
+    ) -> Option<Name> {
+        if let Some(cut) = self.delegation(qname) {
+            // DS lives on the parent side of the cut
+            if !(&cut == qname && q_type == QueryType::DS) {
+                self.refer(&cut, dnssec_ok, response);
+                return None;
+            }
+        }
+        if self.records.contains_key(qname) {
+            return self.answer_existing(qname, qname, q_type, dnssec_ok, response);
+        }
+        if self.is_empty_non_terminal(qname) {
+            self.negative(dnssec_ok, response);
+            if dnssec_ok {
+                let proof: Vec<DnsRecord> = match self.nsec3_params() {
+                    Some(_) => self.nsec3_matching(qname),
+                    None => self.nsec_covering(qname),
+                };
+                push_unique(&mut response.authorities, proof);
+            }
+            return None;
+        }
+
+        let closest_encloser: Name = (self.origin.num_labels()..qname.num_labels())
+            .rev()
+            .map(|num_labels| qname.trim_to(num_labels))
+            .find(|ancestor| self.exists(ancestor))
+            .unwrap_or_else(|| self.origin.clone());
+        let next_closer: Name = qname.trim_to(closest_encloser.num_labels() + 1);
+        let wildcard: Option<Name> = closest_encloser.child(b"*").ok();
+        if let Some(wildcard) = wildcard.filter(|wildcard| self.records.contains_key(wildcard)) {
+            let target: Option<Name> =
+                self.answer_existing(qname, &wildcard, q_type, dnssec_ok, response);
+            if dnssec_ok {
+                // the query name itself must be shown not to exist
+                let proof: Vec<DnsRecord> = match self.nsec3_params() {
+                    Some(_) => self.nsec3_covering(&next_closer),
+                    None => self.nsec_covering(qname),
+                };
+                push_unique(&mut response.authorities, proof);
+            }
+            return target;
+        }
+
+        response.header.r_code = ResponseCode::NAMEERROR;
+        self.negative(dnssec_ok, response);
+        if dnssec_ok {
+            let wildcard: Name = closest_encloser
+                .child(b"*")
+                .unwrap_or_else(|_| closest_encloser.clone());
+            let proof: Vec<DnsRecord> = match self.nsec3_params() {
+                Some(_) => [
+                    self.nsec3_matching(&closest_encloser),
+                    self.nsec3_covering(&next_closer),
+                    self.nsec3_covering(&wildcard),
+                ]
+                .concat(),
+                None => [self.nsec_covering(qname), self.nsec_covering(&wildcard)].concat(),
+            };
+            push_unique(&mut response.authorities, proof);
+        }
+        None
+    }
+
+    /// answer from the records at `source`, which differs from `qname` for wildcards
+    fn answer_existing(
+        &self,
+        qname: &Name,
+        source: &Name,
+        q_type: QueryType,
+        dnssec_ok: bool,
+        response: &mut DnsPacket,
+    ) -> Option<Name> {
+        let records: Vec<DnsRecord> = match q_type {
+            QueryType::ANY => self
+                .records_at(source)
+                .iter()
+                .filter(|r| dnssec_ok || r.r_type != QueryType::RRSIG)
+                .cloned()
+                .collect(),
+            _ => self.signed_rrset(source, q_type, dnssec_ok),
+        };
+        let (records, target): (Vec<DnsRecord>, Option<Name>) =
+            match records.iter().any(|r| r.r_type != QueryType::RRSIG) {
+                true => (records, None),
+                false => {
+                    let cname: Vec<DnsRecord> =
+                        self.signed_rrset(source, QueryType::CNAME, dnssec_ok);
+                    let target: Option<Name> = cname.iter().find_map(|r| match &r.r_data {
+                        RecordData::CNAME(target) => Some(target.clone()),
+                        _ => None,
+                    });
+                    (cname, target)
+                }
+            };
+        if records.is_empty() {
+            self.negative(dnssec_ok, response);
+            if dnssec_ok {
+                let proof: Vec<DnsRecord> = match self.nsec3_params() {
+                    Some(_) => self.nsec3_matching(source),
+                    None => self.signed_rrset(source, QueryType::NSEC, true),
+                };
+                push_unique(&mut response.authorities, proof);
+            }
+            return None;
+        }
+        for mut record in records {
+            record.label = qname.clone();
+            self.additional(&record.r_data, dnssec_ok, response);
+            response.answers.push(record);
+        }
+        target
+    }
+
+    /// referral to the child zone at `cut`
+    fn refer(&self, cut: &Name, dnssec_ok: bool, response: &mut DnsPacket) {
+        response.header.aa = false;
+        let ns: Vec<DnsRecord> = self.signed_rrset(cut, QueryType::NS, false);
+        for record in &ns {
+            self.additional(&record.r_data, dnssec_ok, response);
+        }
+        response.authorities.extend(ns);
+        if dnssec_ok {
+            let ds: Vec<DnsRecord> = self.signed_rrset(cut, QueryType::DS, true);
+            let proof: Vec<DnsRecord> = match (ds.is_empty(), self.nsec3_params()) {
+                (false, _) => ds,
+                (true, Some(_)) => self.nsec3_matching(cut),
+                (true, None) => self.signed_rrset(cut, QueryType::NSEC, true),
+            };
+            response.authorities.extend(proof);
+        }
+    }
+
+    /// SOA for a negative answer, its TTL capped by the minimum field, RFC 2308
+    fn negative(&self, dnssec_ok: bool, response: &mut DnsPacket) {
+        let soa: Vec<DnsRecord> = self
+            .signed_rrset(&self.origin, QueryType::SOA, dnssec_ok)
+            .into_iter()
+            .map(|mut record| {
+                if let Some(RecordData::SOA { minimum, .. }) = self.soa().map(|soa| &soa.r_data) {
+                    record.ttl = record.ttl.min(*minimum);
+                }
+                record
+            })
+            .collect();
+        push_unique(&mut response.authorities, soa);
+    }
+
+    /// A and AAAA records of in-zone names an NS, MX or SRV record points at
+    fn additional(&self, r_data: &RecordData, dnssec_ok: bool, response: &mut DnsPacket) {
+        let target: &Name = match r_data {
+            RecordData::NS(target)
+            | RecordData::MX {
+                exchange: target, ..
+            }
+            | RecordData::SRV { target, .. } => target,
+            _ => return,
+        };
+        if target.ends_with(&self.origin) {
+            for r_type in [QueryType::A, QueryType::AAAA] {
+                let glue: Vec<DnsRecord> = self.signed_rrset(target, r_type, dnssec_ok);
+                push_unique(&mut response.additionals, glue);
+            }
+        }
+    }
+
+    /// the NSEC RRset whose span covers or matches `name`
+    fn nsec_covering(&self, name: &Name) -> Vec<DnsRecord> {
+        let has_nsec = |(_, records): &(&Name, &Vec<DnsRecord>)| {
+            records.iter().any(|r| r.r_type == QueryType::NSEC)
+        };
+        let owner: Option<&Name> = self
+            .records
+            .range(..=name)
+            .rev()
+            .find(has_nsec)
+            .or_else(|| self.records.iter().rev().find(has_nsec))
+            .map(|(owner, _)| owner);
+        match owner {
+            Some(owner) => self.signed_rrset(owner, QueryType::NSEC, true),
+            None => Vec::new(),
+        }
+    }
+
+    /// the NSEC3 RRset whose owner is the hash of `name`
+    fn nsec3_matching(&self, name: &Name) -> Vec<DnsRecord> {
+        let (iterations, salt) = match self.nsec3_params() {
+            Some(params) => params,
+            None => return Vec::new(),
+        };
+        let label: Vec<u8> = nsec3_label(&nsec3_hash(name, iterations, &salt));
+        match self.origin.child(&label) {
+            Ok(owner) => self.signed_rrset(&owner, QueryType::NSEC3, true),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// the NSEC3 RRset whose span covers the hash of `name`
+    fn nsec3_covering(&self, name: &Name) -> Vec<DnsRecord> {
+        let (iterations, salt) = match self.nsec3_params() {
+            Some(params) => params,
+            None => return Vec::new(),
+        };
+        let hash: Vec<u8> = nsec3_hash(name, iterations, &salt);
+        let owner: Option<&Name> = self.records().find_map(|r| match &r.r_data {
+            RecordData::NSEC3 { next_hashed, .. } => nsec3_owner_hash(&r.label)
+                .filter(|owner| covers(&owner[..], &next_hashed[..], &hash[..]))
+                .map(|_| &r.label),
+            _ => None,
+        });
+        match owner {
+            Some(owner) => self.signed_rrset(owner, QueryType::NSEC3, true),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// append records not already in `section`
+fn push_unique(section: &mut Vec<DnsRecord>, records: Vec<DnsRecord>) {
+    for record in records {
+        if !section.contains(&record) {
+            section.push(record);
+        }
+    }
+}
+
+impl fmt::Display for Zone {
+    /// master file with absolute names, the SOA first
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let apex_soa =
+            |record: &&DnsRecord| record.r_type == QueryType::SOA && record.label == self.origin;
+        for record in self.records().filter(apex_soa) {
+            writeln!(f, "{}", record)?;
+        }
+        for record in self.records().filter(|record| !apex_soa(record)) {
+            writeln!(f, "{}", record)?;
+        }
+        Ok(())
+    }
+}
+
+/// Zones served by one server, found by the deepest matching origin.
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    zones: BTreeMap<Name, Zone>,
+}
+
+impl Catalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// add or replace the zone with the same origin
+    pub fn insert(&mut self, zone: Zone) -> Option<Zone> {
+        self.zones.insert(zone.origin().clone(), zone)
+    }
+
+    pub fn remove(&mut self, origin: &Name) -> Option<Zone> {
+        self.zones.remove(origin)
+    }
+
+    pub fn get(&self, origin: &Name) -> Option<&Zone> {
+        self.zones.get(origin)
+    }
+
+    pub fn get_mut(&mut self, origin: &Name) -> Option<&mut Zone> {
+        self.zones.get_mut(origin)
+    }
+
+    /// the zone closest enclosing `name`
+    pub fn find(&self, name: &Name) -> Option<&Zone> {
+        (0..=name.num_labels())
+            .rev()
+            .find_map(|num_labels| self.zones.get(&name.trim_to(num_labels)))
+    }
+
+    pub fn zones(&self) -> impl Iterator<Item = &Zone> {
+        self.zones.values()
+    }
+}
@@ -0,0 +1,246 @@
+mod common;
+
+use common::{decode, encode, ip, key, name, request, LOCALHOST};
+use lib::client::{read_tcp_message, write_tcp_message};
+use lib::config::Config;
+use lib::dns::{DnsPacket, QueryType, RecordData, ResponseCode};
+use lib::recursor::Recursor;
+use lib::server::{unix_time, Server, DEFAULT_TCP_CLIENTS};
+use lib::tsig::KeyRing;
+use lib::zone::{Catalog, Zone};
+use std::io::Read;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// a server for `example` and `public.example` letting 10.0.0.0/8 and the
+/// trusted key query
+fn acl_server() -> Server {
+    let mut catalog: Catalog = Catalog::new();
+    for origin in ["example", "public.example"] {
+        let zone: Zone = Zone::parse(
+            "$TTL 60\n@ SOA ns hostmaster 1 2 3 4 5\n@ NS ns\nns A 192.0.2.53\n",
+            &name(origin),
+        )
+        .unwrap();
+        catalog.insert(zone);
+    }
+    let mut server: Server = Server::new(catalog);
+    let mut keyring: KeyRing = KeyRing::new();
+    keyring.insert(key("trusted.example"));
+    server.set_keyring(keyring);
+    server.set_query_acl("10.0.0.0/8,key:trusted.example".parse().unwrap());
+    server.set_zone_query_acl(name("public.example"), "any".parse().unwrap());
+    server
+}
+
+fn r_code(server: &Server, request: &mut DnsPacket, client: &str) -> ResponseCode {
+    let answer: Vec<u8> = server
+        .handle_bytes(&encode(request), ip(client), LOCALHOST, false)
+        .unwrap();
+    decode(&answer).header.r_code
+}
+
+#[test]
+fn clients_outside_the_query_acl_are_refused() {
+    let server: Server = acl_server();
+    let mut soa: DnsPacket = request("example", QueryType::SOA);
+    assert_eq!(r_code(&server, &mut soa, "10.1.2.3"), ResponseCode::NOERROR);
+    assert_eq!(
+        r_code(&server, &mut soa, "192.0.2.7"),
+        ResponseCode::REFUSED
+    );
+    assert_eq!(
+        r_code(&server, &mut soa, "::ffff:10.1.2.3"),
+        ResponseCode::NOERROR
+    );
+
+    // a signed query gets through from anywhere
+    let mut signed: DnsPacket = request("example", QueryType::SOA);
+    key("trusted.example")
+        .sign(&mut signed, unix_time() as u64, None)
+        .unwrap();
+    assert_eq!(
+        r_code(&server, &mut signed, "192.0.2.7"),
+        ResponseCode::NOERROR
+    );
+
+    // without a client address nothing is checked
+    assert_eq!(server.handle(&soa).header.r_code, ResponseCode::NOERROR);
+}
+
+#[test]
+fn zone_acls_replace_the_server_acl() {
+    let mut server: Server = acl_server();
+    let mut public: DnsPacket = request("public.example", QueryType::SOA);
+    // the server ACL comes first
+    assert_eq!(
+        r_code(&server, &mut public, "192.0.2.7"),
+        ResponseCode::REFUSED
+    );
+    server.set_query_acl("any".parse().unwrap());
+    server.set_zone_query_acl(name("example"), "10.0.0.0/8".parse().unwrap());
+    assert_eq!(
+        r_code(&server, &mut public, "192.0.2.7"),
+        ResponseCode::NOERROR
+    );
+    let mut private: DnsPacket = request("www.example", QueryType::A);
+    assert_eq!(
+        r_code(&server, &mut private, "192.0.2.7"),
+        ResponseCode::REFUSED
+    );
+    assert_eq!(
+        r_code(&server, &mut private, "10.1.2.3"),
+        ResponseCode::NAMEERROR
+    );
+}
+
+/// a recursor whose only root server is a server for `example.org` on a
+/// local port
+fn local_recursor() -> Recursor {
+    let mut catalog: Catalog = Catalog::new();
+    catalog.insert(
+        Zone::parse(
+            "$TTL 60\n@ SOA ns hostmaster 1 2 3 4 5\n@ NS ns\nwww A 192.0.2.80\n",
+            &name("example.org"),
+        )
+        .unwrap(),
+    );
+    let server: Arc<Server> = Arc::new(Server::new(catalog));
+    let socket: UdpSocket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port: u16 = socket.local_addr().unwrap().port();
+    thread::spawn(move || server.serve_udp(&socket));
+    let mut recursor: Recursor = Recursor::new(vec![LOCALHOST]);
+    recursor.set_port(port);
+    recursor.set_minimise(false);
+    recursor
+}
+
+#[test]
+fn recursion_is_for_the_recursion_acl_and_transfers_are_refused() {
+    let mut server: Server = acl_server();
+    server.set_recursion_acl("10.0.0.0/8".parse().unwrap());
+    server.set_recursor(local_recursor());
+    let mut elsewhere: DnsPacket = request("www.example.org", QueryType::A);
+    elsewhere.header.rd = true;
+    let answer: Vec<u8> = server
+        .handle_bytes(&encode(&mut elsewhere), ip("10.1.2.3"), LOCALHOST, false)
+        .unwrap();
+    let resolved: DnsPacket = decode(&answer);
+    assert!(resolved.header.ra);
+    assert_eq!(resolved.header.r_code, ResponseCode::NOERROR);
+    assert_eq!(
+        resolved.answers[0].r_data,
+        RecordData::IPADDR(Ipv4Addr::new(192, 0, 2, 80))
+    );
+    // the key may query but not recurse
+    let refused: DnsPacket = server
+        .handle_from(
+            &elsewhere,
+            Some(ip("192.0.2.7")),
+            Some(&name("trusted.example")),
+        )
+        .unwrap();
+    assert!(!refused.header.ra);
+    assert_eq!(refused.header.r_code, ResponseCode::REFUSED);
+    // without RD there is no recursion either
+    elsewhere.header.rd = false;
+    assert_eq!(
+        r_code(&server, &mut elsewhere, "10.1.2.3"),
+        ResponseCode::REFUSED
+    );
+
+    let mut axfr: DnsPacket = request("example", QueryType::AXFR);
+    let message: Vec<u8> = encode(&mut axfr);
+    let refused: Vec<Vec<u8>> = server.handle_tcp(&message, ip("10.1.2.3"), LOCALHOST);
+    assert_eq!(decode(&refused[0]).header.r_code, ResponseCode::REFUSED);
+    server.set_transfer_acl(name("example"), "10.0.0.0/8".parse().unwrap());
+    let transferred: Vec<Vec<u8>> = server.handle_tcp(&message, ip("10.1.2.3"), LOCALHOST);
+    assert_eq!(decode(&transferred[0]).header.r_code, ResponseCode::NOERROR);
+}
+
+#[test]
+fn configs_set_query_and_recursion_acls() {
+    let config: Config = Config::parse(
+        "\
+allow-query 10.0.0.0/8,key:trusted.example.
+allow-recursion 127.0.0.1
+tcp-clients 10
+zone example. example.zone allow-query=any
+zone example.net. example.net.zone
+",
+        Path::new(""),
+    )
+    .unwrap();
+    assert!(config.allow_query.allows(ip("10.9.9.9"), None));
+    assert!(!config.allow_query.allows(ip("192.0.2.7"), None));
+    assert!(config.allow_recursion.allows(LOCALHOST, None));
+    assert!(config.zones[0]
+        .allow_query
+        .as_ref()
+        .is_some_and(|acl| acl.allows(ip("192.0.2.7"), None)));
+    assert!(config.zones[1].allow_query.is_none());
+    assert_eq!(config.tcp_clients, 10);
+
+    let defaults: Config = Config::parse("", Path::new("")).unwrap();
+    assert!(defaults.allow_query.allows(ip("192.0.2.7"), None));
+    assert!(!defaults.allow_recursion.allows(LOCALHOST, None));
+    assert_eq!(defaults.tcp_clients, DEFAULT_TCP_CLIENTS);
+    assert!(Config::parse("tcp-clients 0\n", Path::new("")).is_err());
+    assert!(Config::parse("allow-query\n", Path::new("")).is_err());
+    assert!(Config::parse("allow-query 10.0.0.0/33\n", Path::new("")).is_err());
+}
+
+/// the SOA of `public.example` over a new connection to `addr`, none when
+/// the server closes it unanswered
+fn ask_tcp(addr: SocketAddr) -> Option<DnsPacket> {
+    let mut stream: TcpStream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    write_tcp_message(
+        &mut stream,
+        &encode(&mut request("public.example", QueryType::SOA)),
+    )
+    .ok()?;
+    read_tcp_message(&mut stream)
+        .ok()
+        .map(|answer| decode(&answer))
+}
+
+#[test]
+fn tcp_connections_beyond_the_limit_are_dropped() {
+    let mut server: Server = acl_server();
+    server.set_query_acl("any".parse().unwrap());
+    server.set_tcp_clients(1);
+    let listener: TcpListener = TcpListener::bind((LOCALHOST, 0)).unwrap();
+    let addr: SocketAddr = listener.local_addr().unwrap();
+    let server: Arc<Server> = Arc::new(server);
+    thread::spawn(move || server.serve_tcp(&listener));
+
+    let mut held: TcpStream = TcpStream::connect(addr).unwrap();
+    let mut dropped: TcpStream = TcpStream::connect(addr).unwrap();
+    dropped
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    assert_eq!(dropped.read(&mut [0; 2]).unwrap(), 0);
+    write_tcp_message(
+        &mut held,
+        &encode(&mut request("public.example", QueryType::SOA)),
+    )
+    .unwrap();
+    assert_eq!(
+        decode(&read_tcp_message(&mut held).unwrap()).answers.len(),
+        1
+    );
+
+    // the slot frees up once the held connection closes
+    drop(held);
+    let answered: bool = (0..50).any(|_| {
+        thread::sleep(Duration::from_millis(20));
+        ask_tcp(addr).is_some()
+    });
+    assert!(answered);
+}
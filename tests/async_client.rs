@@ -0,0 +1,139 @@
+#![cfg(feature = "async")]
+
+mod common;
+
+use common::name;
+use lib::async_client::{AsyncClient, Lookup};
+use lib::dns::{DnsPacket, DnsRecord, QueryType, RecordData, ResponseCode};
+use lib::name::Name;
+use lib::parser::DnsBytePacketBuffer;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::thread;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// receive one query on `socket`, returning it and where it came from
+fn receive(socket: &UdpSocket) -> (DnsPacket, SocketAddr) {
+    let mut buffer: DnsBytePacketBuffer = DnsBytePacketBuffer::new();
+    let (len, from) = socket.recv_from(buffer.get_buf()).unwrap();
+    buffer.set_bytes_read(len);
+    (DnsPacket::from_buffer(&mut buffer).unwrap(), from)
+}
+
+/// answer `query` with an address ending in the first label of the name
+fn answer(socket: &UdpSocket, query: &DnsPacket, to: SocketAddr) {
+    let mut response: DnsPacket = query.clone();
+    response.header.qr = true;
+    response.edns = None;
+    let label: Name = query.questions[0].label.clone();
+    let last: u8 = format!("{}", label)
+        .split('.')
+        .next()
+        .and_then(|host| host.trim_start_matches("host").parse().ok())
+        .unwrap_or(0);
+    response.answers.push(DnsRecord::new(
+        label,
+        60,
+        RecordData::IPADDR(Ipv4Addr::new(192, 0, 2, last)),
+    ));
+    let mut buffer: DnsBytePacketBuffer = DnsBytePacketBuffer::new();
+    response.write(&mut buffer).unwrap();
+    socket.send_to(buffer.written(), to).unwrap();
+}
+
+#[tokio::test]
+async fn lookups_share_one_socket() {
+    const LOOKUPS: u8 = 100;
+    let server: UdpSocket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr: SocketAddr = server.local_addr().unwrap();
+    let ports = thread::spawn(move || {
+        // every query is in flight before the first answer, which come
+        // back in reverse order
+        let queries: Vec<(DnsPacket, SocketAddr)> =
+            (0..LOOKUPS).map(|_| receive(&server)).collect();
+        for (query, from) in queries.iter().rev() {
+            answer(&server, query, *from);
+        }
+        queries
+            .iter()
+            .map(|(_, from)| from.port())
+            .collect::<Vec<u16>>()
+    });
+
+    let client: AsyncClient = AsyncClient::new(addr).await.unwrap();
+    let lookups: Vec<JoinHandle<RecordData>> = (0..LOOKUPS)
+        .map(|n| {
+            let client: AsyncClient = client.clone();
+            tokio::spawn(async move {
+                let lookup: Lookup = client
+                    .lookup(&name(&format!("host{}.example", n)), QueryType::A)
+                    .await
+                    .unwrap();
+                assert_eq!(lookup.r_code, ResponseCode::NOERROR);
+                let address: RecordData = lookup.records().next().unwrap().r_data.clone();
+                address
+            })
+        })
+        .collect();
+    for (n, lookup) in lookups.into_iter().enumerate() {
+        assert_eq!(
+            lookup.await.unwrap(),
+            RecordData::IPADDR(Ipv4Addr::new(192, 0, 2, n as u8))
+        );
+    }
+    let ports: Vec<u16> = ports.join().unwrap();
+    let port: u16 = client.local_addr().unwrap().port();
+    assert!(ports.iter().all(|from| *from == port));
+}
+
+#[tokio::test]
+async fn lost_queries_are_sent_again() {
+    let server: UdpSocket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr: SocketAddr = server.local_addr().unwrap();
+    let ids = thread::spawn(move || {
+        let (lost, _) = receive(&server);
+        let (query, from) = receive(&server);
+        // a late answer to the lost query is no answer to the next
+        answer(&server, &lost, from);
+        answer(&server, &query, from);
+        (lost.header.id, query.header.id)
+    });
+
+    let mut client: AsyncClient = AsyncClient::new(addr).await.unwrap();
+    client.set_timeout(Duration::from_millis(200));
+    let lookup: Lookup = client
+        .lookup(&name("host7.example"), QueryType::A)
+        .await
+        .unwrap();
+    assert_eq!(
+        lookup.records().next().unwrap().r_data,
+        RecordData::IPADDR(Ipv4Addr::new(192, 0, 2, 7))
+    );
+    let (lost, sent): (u16, u16) = ids.join().unwrap();
+    assert_ne!(lost, sent);
+}
+
+#[tokio::test]
+async fn lookups_time_out() {
+    let server: UdpSocket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    server
+        .set_read_timeout(Some(Duration::from_secs(1)))
+        .unwrap();
+    let addr: SocketAddr = server.local_addr().unwrap();
+    let received = thread::spawn(move || {
+        let mut buffer: [u8; 512] = [0; 512];
+        (0..)
+            .take_while(|_| server.recv_from(&mut buffer).is_ok())
+            .count()
+    });
+
+    let mut client: AsyncClient = AsyncClient::new(addr).await.unwrap();
+    client.set_timeout(Duration::from_millis(100));
+    client.set_attempts(2);
+    match client.lookup(&name("host1.example"), QueryType::A).await {
+        Err(lib::Error::Io(error)) => assert_eq!(error.kind(), io::ErrorKind::TimedOut),
+        other => panic!("expected a timeout, got {:?}", other),
+    }
+    assert_eq!(received.join().unwrap(), 2);
+}
@@ -0,0 +1,153 @@
+mod common;
+
+use common::{name, request};
+use lib::blocklist::{BlockAction, Blocklist, SuffixSet};
+use lib::config::Config;
+use lib::dns::{DnsPacket, QueryType, RecordData, ResponseCode};
+use lib::name::Name;
+use lib::server::{load_blocklist, Server};
+use lib::zone::{Catalog, Zone};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+
+const LIST: &str = "\
+# plain and hosts-style entries
+ads.example
+0.0.0.0 tracker.example metrics.example # trackers
+127.0.0.1 localhost
+! adblock filters
+[Adblock Plus 2.0]
+||Banner.Example^
+||popup.example^$third-party
+@@||ok.banner.example^
+||cdn.example/ads/*
+example.org##.ad-slot
+not a domain
+";
+
+fn query(server: &Server, host: &str, q_type: QueryType) -> DnsPacket {
+    server.handle(&request(host, q_type))
+}
+
+fn blocklist() -> Blocklist {
+    let mut blocklist: Blocklist = Blocklist::new();
+    assert_eq!(blocklist.parse_blocklist(LIST), 6);
+    blocklist
+}
+
+#[test]
+fn suffixes_match_names_below_them() {
+    let mut set: SuffixSet = SuffixSet::new();
+    assert!(set.insert(&name("ads.example")));
+    assert!(!set.insert(&name("ADS.example")));
+    assert!(set.insert(&name("deep.ads.example")));
+    assert_eq!(set.len(), 2);
+    assert!(set.matches(&name("ads.example")));
+    assert!(set.matches(&name("x.y.Ads.Example")));
+    assert!(!set.matches(&name("example")));
+    assert!(!set.matches(&name("bads.example")));
+    assert!(!set.matches(&Name::root()));
+
+    let mut root: SuffixSet = SuffixSet::new();
+    root.insert(&Name::root());
+    assert!(root.matches(&name("anything.example")));
+}
+
+#[test]
+fn lists_mix_formats() {
+    let blocklist: Blocklist = blocklist();
+    for blocked in [
+        "ads.example",
+        "www.ads.example",
+        "tracker.example",
+        "metrics.example",
+        "banner.example",
+        "popup.example",
+    ] {
+        assert!(blocklist.is_blocked(&name(blocked)), "{}", blocked);
+    }
+    for open in [
+        "localhost",
+        "ok.banner.example",
+        "www.ok.banner.example",
+        "cdn.example",
+        "example.org",
+        "example",
+    ] {
+        assert!(!blocklist.is_blocked(&name(open)), "{}", open);
+    }
+
+    let mut blocklist: Blocklist = blocklist;
+    assert_eq!(
+        blocklist.parse_allowlist("tracker.example\n@@||www.ads.example^\n"),
+        2
+    );
+    assert!(!blocklist.is_blocked(&name("tracker.example")));
+    assert!(!blocklist.is_blocked(&name("www.ads.example")));
+    assert!(blocklist.is_blocked(&name("ads.example")));
+}
+
+#[test]
+fn blocked_names_get_the_configured_answer() {
+    let zone: Zone = Zone::parse(
+        "$TTL 60\n@ SOA ns hostmaster 1 2 3 4 5\n@ NS ns\nads A 192.0.2.1\nwww A 192.0.2.2\n",
+        &name("example"),
+    )
+    .unwrap();
+    let mut catalog: Catalog = Catalog::new();
+    catalog.insert(zone);
+    let server: Server = Server::new(catalog);
+    let mut blocklist: Blocklist = blocklist();
+    server.set_blocklist(blocklist.clone());
+
+    let nxdomain: DnsPacket = query(&server, "ads.example", QueryType::A);
+    assert_eq!(nxdomain.header.r_code, ResponseCode::NAMEERROR);
+    assert!(nxdomain.answers.is_empty());
+    assert_eq!(
+        query(&server, "www.example", QueryType::A).answers[0].r_data,
+        RecordData::IPADDR(Ipv4Addr::new(192, 0, 2, 2))
+    );
+
+    blocklist.set_action(BlockAction::NULL);
+    server.set_blocklist(blocklist.clone());
+    let null: DnsPacket = query(&server, "ads.example", QueryType::AAAA);
+    assert_eq!(null.header.r_code, ResponseCode::NOERROR);
+    assert_eq!(
+        null.answers[0].r_data,
+        RecordData::AAAA(Ipv6Addr::UNSPECIFIED)
+    );
+
+    blocklist.set_action("192.0.2.80".parse().unwrap());
+    server.set_blocklist(blocklist);
+    let sinkhole: DnsPacket = query(&server, "x.tracker.example", QueryType::A);
+    assert_eq!(
+        sinkhole.answers[0].r_data,
+        RecordData::IPADDR(Ipv4Addr::new(192, 0, 2, 80))
+    );
+    // no address of the family asked for, no data
+    let nodata: DnsPacket = query(&server, "x.tracker.example", QueryType::AAAA);
+    assert_eq!(nodata.header.r_code, ResponseCode::NOERROR);
+    assert!(nodata.answers.is_empty());
+}
+
+#[test]
+fn configs_name_lists_and_actions() {
+    let dir: PathBuf = std::env::temp_dir().join(format!("blocklist-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("ads.txt"), LIST).unwrap();
+    std::fs::write(dir.join("allowed.txt"), "tracker.example\n").unwrap();
+    let config: Config = Config::parse(
+        "blocklist ads.txt\nallowlist allowed.txt\nblock-action 192.0.2.80,2001:db8::80\n",
+        &dir,
+    )
+    .unwrap();
+    let loaded: lib::Result<Blocklist> = load_blocklist(&config);
+    std::fs::remove_dir_all(&dir).unwrap();
+    let loaded: Blocklist = loaded.unwrap();
+    assert_eq!(loaded.action().to_string(), "192.0.2.80,2001:db8::80");
+    assert!(loaded.is_blocked(&name("ads.example")));
+    assert!(!loaded.is_blocked(&name("tracker.example")));
+
+    assert!(Config::parse("block-action drop\n", Path::new("")).is_err());
+    assert!(Config::parse("blocklist\n", Path::new("")).is_err());
+}
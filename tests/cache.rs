@@ -0,0 +1,81 @@
+mod common;
+
+use common::name;
+use lib::cache::Cache;
+use lib::dns::{DnsPacket, DnsQuestion, DnsRecord, QueryType, RecordData, ResponseCode};
+use std::net::Ipv4Addr;
+use std::time::{Duration, Instant};
+
+fn question(host: &str) -> DnsQuestion {
+    DnsQuestion::new(name(host), QueryType::A)
+}
+
+/// an answer with the address of `host` valid for `ttl` seconds
+fn answer(host: &str, ttl: u32) -> DnsPacket {
+    let mut response: DnsPacket = DnsPacket::new();
+    response.header.qr = true;
+    response.answers.push(DnsRecord::new(
+        name(host),
+        ttl,
+        RecordData::IPADDR(Ipv4Addr::new(192, 0, 2, 1)),
+    ));
+    response
+}
+
+#[test]
+fn answers_are_kept_for_their_ttl() {
+    let cache: Cache = Cache::default();
+    let now: Instant = Instant::now();
+    cache.insert(&question("www.example"), &answer("www.example", 60), now);
+    let later: DnsPacket = cache
+        .get(&question("WWW.example"), now + Duration::from_secs(20))
+        .unwrap();
+    assert_eq!(later.answers[0].ttl, 40);
+    assert!(cache
+        .get(&question("www.example"), now + Duration::from_secs(60))
+        .is_none());
+
+    // a name error is kept for the negative TTL of the SOA
+    let mut denied: DnsPacket = DnsPacket::new();
+    denied.header.r_code = ResponseCode::NAMEERROR;
+    denied.authorities.push(DnsRecord::new(
+        name("example"),
+        300,
+        RecordData::SOA {
+            mname: name("ns.example"),
+            rname: name("hostmaster.example"),
+            serial: 1,
+            refresh: 2,
+            retry: 3,
+            expire: 4,
+            minimum: 30,
+        },
+    ));
+    cache.insert(&question("nowhere.example"), &denied, now);
+    let kept: DnsPacket = cache
+        .get(&question("nowhere.example"), now + Duration::from_secs(29))
+        .unwrap();
+    assert_eq!(kept.header.r_code, ResponseCode::NAMEERROR);
+    assert!(cache
+        .get(&question("nowhere.example"), now + Duration::from_secs(30))
+        .is_none());
+
+    // failures are asked again
+    let mut failed: DnsPacket = answer("www.example.org", 60);
+    failed.header.r_code = ResponseCode::SERVERFAILURE;
+    cache.insert(&question("www.example.org"), &failed, now);
+    assert!(cache.get(&question("www.example.org"), now).is_none());
+}
+
+#[test]
+fn a_full_cache_drops_what_expires_soonest() {
+    let cache: Cache = Cache::new(2);
+    let now: Instant = Instant::now();
+    cache.insert(&question("a.example"), &answer("a.example", 60), now);
+    cache.insert(&question("b.example"), &answer("b.example", 10), now);
+    cache.insert(&question("c.example"), &answer("c.example", 30), now);
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get(&question("a.example"), now).is_some());
+    assert!(cache.get(&question("b.example"), now).is_none());
+    assert!(cache.get(&question("c.example"), now).is_some());
+}
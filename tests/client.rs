@@ -0,0 +1,164 @@
+mod common;
+
+use common::{encode, name};
+use lib::client::Client;
+use lib::dns::{DnsPacket, DnsQuestion, DnsRecord, QueryType, RecordData};
+use lib::name::Name;
+use lib::parser::DnsBytePacketBuffer;
+use lib::Error;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// receive one query on `socket`, returning it and where it came from
+fn receive(socket: &UdpSocket) -> (DnsPacket, SocketAddr) {
+    let mut buffer: DnsBytePacketBuffer = DnsBytePacketBuffer::new();
+    let (len, from) = socket.recv_from(buffer.get_buf()).unwrap();
+    buffer.set_bytes_read(len);
+    (DnsPacket::from_buffer(&mut buffer).unwrap(), from)
+}
+
+/// an answer to `query` giving `www.example` the address `last`
+fn answer(query: &DnsPacket, last: u8) -> DnsPacket {
+    let mut response: DnsPacket = query.clone();
+    response.header.qr = true;
+    response.edns = None;
+    response.answers.push(DnsRecord::new(
+        name("www.example"),
+        60,
+        RecordData::IPADDR(Ipv4Addr::new(192, 0, 2, last)),
+    ));
+    response
+}
+
+#[test]
+fn only_the_matching_response_is_taken() {
+    let server: UdpSocket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr: SocketAddr = server.local_addr().unwrap();
+    let spoofer: UdpSocket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    thread::spawn(move || {
+        let (query, from) = receive(&server);
+        // from another address, to another id, to another question
+        spoofer
+            .send_to(&encode(&mut answer(&query, 66)), from)
+            .unwrap();
+        let mut wrong_id: DnsPacket = answer(&query, 67);
+        wrong_id.header.id = query.header.id.wrapping_add(1);
+        server.send_to(&encode(&mut wrong_id), from).unwrap();
+        let mut wrong_question: DnsPacket = answer(&query, 68);
+        wrong_question.questions = vec![DnsQuestion::new(name("mail.example"), QueryType::A)];
+        server.send_to(&encode(&mut wrong_question), from).unwrap();
+        server
+            .send_to(&encode(&mut answer(&query, 1)), from)
+            .unwrap();
+    });
+
+    let response: DnsPacket = Client::new(addr)
+        .query(&name("www.example"), QueryType::A, false)
+        .unwrap();
+    assert_eq!(
+        response.answers[0].r_data,
+        RecordData::IPADDR(Ipv4Addr::new(192, 0, 2, 1))
+    );
+}
+
+#[test]
+fn dropped_datagrams_do_not_extend_the_timeout() {
+    let server: UdpSocket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr: SocketAddr = server.local_addr().unwrap();
+    thread::spawn(move || {
+        let (query, from) = receive(&server);
+        let mut wrong_id: DnsPacket = answer(&query, 67);
+        wrong_id.header.id = query.header.id.wrapping_add(1);
+        // a forged answer every 100ms for a second, never the real one
+        for _ in 0..10 {
+            thread::sleep(Duration::from_millis(100));
+            let _ = server.send_to(&encode(&mut wrong_id), from);
+        }
+    });
+
+    let mut client: Client = Client::new(addr);
+    client.set_timeout(Duration::from_millis(300));
+    let started: Instant = Instant::now();
+    match client.query(&name("www.example"), QueryType::A, false) {
+        Err(Error::Io(error)) => assert_eq!(error.kind(), io::ErrorKind::TimedOut),
+        other => panic!("expected a timeout, got {:?}", other),
+    }
+    assert!(started.elapsed() < Duration::from_millis(600));
+}
+
+#[test]
+fn queries_get_random_ids_and_ports() {
+    let server: UdpSocket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr: SocketAddr = server.local_addr().unwrap();
+    const QUERIES: usize = 8;
+    let seen = thread::spawn(move || {
+        (0..QUERIES)
+            .map(|_| {
+                let (query, from) = receive(&server);
+                server
+                    .send_to(&encode(&mut answer(&query, 1)), from)
+                    .unwrap();
+                (query.header.id, from.port())
+            })
+            .collect::<Vec<(u16, u16)>>()
+    });
+
+    let mut client: Client = Client::new(addr);
+    client.set_timeout(Duration::from_secs(2));
+    for _ in 0..QUERIES {
+        client
+            .query(&name("www.example"), QueryType::A, false)
+            .unwrap();
+    }
+    let mut seen: Vec<(u16, u16)> = seen.join().unwrap();
+    // ids are not counted up from the first
+    assert!(seen
+        .windows(2)
+        .any(|pair| pair[1].0 != pair[0].0.wrapping_add(1)));
+    seen.sort_by_key(|(_, port)| *port);
+    seen.dedup_by_key(|(_, port)| *port);
+    assert!(seen.len() > 1);
+}
+
+#[test]
+fn names_take_case_from_bits() {
+    let name: Name = name("www-1.Example");
+    let mixed: Name = name.with_case(&[0b1010_0101]);
+    assert_eq!(format!("{}", mixed), "WwW-1.exAmPle");
+    assert_eq!(mixed, name);
+    assert!(!mixed.eq_case(&name));
+    assert!(mixed.eq_case(&mixed.clone()));
+    // letters past the bits given are lowercased
+    assert_eq!(format!("{}", name.with_case(&[])), "www-1.example");
+}
+
+#[test]
+fn randomized_case_has_to_be_echoed() {
+    let server: UdpSocket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr: SocketAddr = server.local_addr().unwrap();
+    let asked = thread::spawn(move || {
+        let (query, from) = receive(&server);
+        // a server or spoofer that does not keep the case
+        let mut lowered: DnsPacket = answer(&query, 66);
+        lowered.questions[0].label = query.questions[0].label.to_lowercase();
+        server.send_to(&encode(&mut lowered), from).unwrap();
+        server
+            .send_to(&encode(&mut answer(&query, 1)), from)
+            .unwrap();
+        query.questions[0].label.clone()
+    });
+
+    let mut client: Client = Client::new(addr);
+    client.set_randomize_case(true);
+    let long: Name = name("abcdefghijklmnopqrstuvwxyz.example");
+    let response: DnsPacket = client.query(&long, QueryType::A, false).unwrap();
+    assert_eq!(
+        response.answers[0].r_data,
+        RecordData::IPADDR(Ipv4Addr::new(192, 0, 2, 1))
+    );
+    let asked: Name = asked.join().unwrap();
+    assert_eq!(asked, long);
+    assert!(!asked.eq_case(&long));
+}
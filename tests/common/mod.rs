@@ -0,0 +1,43 @@
+// each test crate uses only some of the helpers
+#![allow(dead_code)]
+
+use lib::dns::{DnsPacket, DnsQuestion, QueryType};
+use lib::name::Name;
+use lib::parser::DnsBytePacketBuffer;
+use lib::tsig::{TsigKey, HMAC_SHA256};
+use std::net::{IpAddr, Ipv4Addr};
+
+pub const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+/// the secret of `key`, `MDEyMzQ1Njc4OWFiY2RlZg==` in config files
+pub const SECRET: &[u8] = b"0123456789abcdef";
+
+pub fn name(s: &str) -> Name {
+    s.parse().unwrap()
+}
+
+pub fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+/// the HMAC-SHA256 TSIG key `owner` with `SECRET`
+pub fn key(owner: &str) -> TsigKey {
+    TsigKey::new(name(owner), HMAC_SHA256, SECRET.to_vec()).unwrap()
+}
+
+/// a query for `host` and `q_type`
+pub fn request(host: &str, q_type: QueryType) -> DnsPacket {
+    let mut request: DnsPacket = DnsPacket::new();
+    request.questions.push(DnsQuestion::new(name(host), q_type));
+    request
+}
+
+pub fn encode(packet: &mut DnsPacket) -> Vec<u8> {
+    let mut buffer: DnsBytePacketBuffer = DnsBytePacketBuffer::new();
+    packet.write(&mut buffer).unwrap();
+    buffer.written().to_vec()
+}
+
+pub fn decode(message: &[u8]) -> DnsPacket {
+    DnsPacket::from_buffer(&mut DnsBytePacketBuffer::from_bytes(message)).unwrap()
+}
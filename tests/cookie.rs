@@ -0,0 +1,188 @@
+mod common;
+
+use common::{decode, encode, ip, name, request};
+use lib::client::Client;
+use lib::config::Config;
+use lib::cookie::{Cookie, ServerCookies, BADCOOKIE, COOKIE};
+use lib::dns::{DnsPacket, Edns, EdnsOption, QueryType, ResponseCode};
+use lib::rrl::RateLimit;
+use lib::server::{unix_time, Server};
+use lib::zone::{Catalog, Zone};
+use std::net::{SocketAddr, UdpSocket};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+
+const SECRET: [u8; 16] = [
+    0xe5, 0xe9, 0x73, 0xe5, 0xa6, 0xb2, 0xa4, 0x3f, 0x48, 0xe7, 0xdc, 0x84, 0x9e, 0x37, 0xbf, 0xcf,
+];
+
+fn server() -> Server {
+    let zone: Zone = Zone::parse(
+        "$TTL 60\n@ SOA ns hostmaster 1 2 3 4 5\n@ NS ns\nns A 192.0.2.53\n",
+        &name("example"),
+    )
+    .unwrap();
+    let mut catalog: Catalog = Catalog::new();
+    catalog.insert(zone);
+    let mut server: Server = Server::new(catalog);
+    server.set_cookies(ServerCookies::new(Some(SECRET), unix_time()).unwrap());
+    server
+}
+
+/// a query for ns.example carrying `cookie` as the COOKIE option
+fn query(cookie: &[u8]) -> Vec<u8> {
+    let mut request: DnsPacket = request("ns.example", QueryType::A);
+    request.edns = Some(Edns {
+        options: vec![EdnsOption {
+            code: COOKIE,
+            data: cookie.to_vec(),
+        }],
+        ..Edns::default()
+    });
+    encode(&mut request)
+}
+
+fn ask(server: &Server, cookie: &[u8], client: &str) -> Option<DnsPacket> {
+    let answer: Vec<u8> =
+        server.handle_bytes(&query(cookie), ip(client), ip("127.0.0.1"), false)?;
+    Some(decode(&answer))
+}
+
+#[test]
+fn server_cookies_follow_rfc_9018() {
+    // the IPv4 test vector of RFC 9018 appendix A.1
+    let cookies: ServerCookies = ServerCookies::new(Some(SECRET), 0).unwrap();
+    let client_cookie: [u8; 8] = [0x24, 0x64, 0xc4, 0xab, 0xcf, 0x10, 0xc9, 0x57];
+    let made: Vec<u8> = cookies.make(&client_cookie, ip("198.51.100.100"), 1559731985);
+    assert_eq!(
+        made,
+        [
+            0x01, 0x00, 0x00, 0x00, 0x5c, 0xf7, 0x9f, 0x11, 0x1f, 0x81, 0x30, 0xc3, 0xee, 0xe2,
+            0x94, 0x80
+        ]
+    );
+
+    let cookie: Cookie = Cookie {
+        client: client_cookie,
+        server: made,
+    };
+    assert!(cookies.verify(&cookie, ip("198.51.100.100"), 1559731985 + 1800));
+    assert!(cookies.verify(&cookie, ip("::ffff:198.51.100.100"), 1559731985));
+    // too old, from the future, for another client
+    assert!(!cookies.verify(&cookie, ip("198.51.100.100"), 1559731985 + 3601));
+    assert!(!cookies.verify(&cookie, ip("198.51.100.100"), 1559731985 - 301));
+    assert!(!cookies.verify(&cookie, ip("198.51.100.101"), 1559731985));
+
+    // a rotated random secret still verifies until it is rotated out
+    let rotating: ServerCookies = ServerCookies::new(None, 1000).unwrap();
+    let cookie: Cookie = Cookie {
+        client: client_cookie,
+        server: rotating.make(&client_cookie, ip("192.0.2.1"), 1000),
+    };
+    rotating.rotate(1000).unwrap();
+    assert!(rotating.verify(&cookie, ip("192.0.2.1"), 1000));
+    rotating.rotate(1000).unwrap();
+    assert!(!rotating.verify(&cookie, ip("192.0.2.1"), 1000));
+}
+
+#[test]
+fn servers_hand_out_and_check_cookies() {
+    let mut server: Server = server();
+    let client_cookie: [u8; 8] = *b"clientck";
+
+    let first: DnsPacket = ask(&server, &client_cookie, "192.0.2.7").unwrap();
+    assert_eq!(first.answers.len(), 1);
+    let returned: Cookie = Cookie::from_packet(&first).unwrap().unwrap();
+    assert_eq!(returned.client, client_cookie);
+    assert_eq!(returned.server.len(), 16);
+
+    // malformed options are a format error
+    assert_eq!(
+        ask(&server, &[1, 2, 3], "192.0.2.7").unwrap().header.r_code,
+        ResponseCode::FORMATERROR
+    );
+
+    server.set_cookies({
+        let mut cookies: ServerCookies = ServerCookies::new(Some(SECRET), unix_time()).unwrap();
+        cookies.required = true;
+        cookies
+    });
+    let refused: DnsPacket = ask(&server, &client_cookie, "192.0.2.7").unwrap();
+    assert_eq!(refused.extended_rcode(), BADCOOKIE);
+    assert!(refused.answers.is_empty());
+    assert!(Cookie::from_packet(&refused).unwrap().is_some());
+    let answered: DnsPacket = ask(&server, &returned.to_bytes(), "192.0.2.7").unwrap();
+    assert_eq!(answered.header.r_code, ResponseCode::NOERROR);
+    assert_eq!(answered.answers.len(), 1);
+    // the cookie is bound to the client address
+    assert_eq!(
+        ask(&server, &returned.to_bytes(), "192.0.2.8")
+            .unwrap()
+            .extended_rcode(),
+        BADCOOKIE
+    );
+}
+
+#[test]
+fn valid_cookies_bypass_rate_limiting() {
+    let mut server: Server = server();
+    server.set_rate_limit(RateLimit {
+        responses_per_second: 1,
+        slip: 0,
+        ..RateLimit::default()
+    });
+    let client_cookie: [u8; 8] = *b"clientck";
+    let first: DnsPacket = ask(&server, &client_cookie, "192.0.2.7").unwrap();
+    let returned: Cookie = Cookie::from_packet(&first).unwrap().unwrap();
+    assert!(ask(&server, &client_cookie, "192.0.2.7").is_none());
+    for _ in 0..5 {
+        assert!(ask(&server, &returned.to_bytes(), "192.0.2.7").is_some());
+    }
+}
+
+#[test]
+fn clients_learn_server_cookies() {
+    let mut server: Server = server();
+    server.set_cookies({
+        let mut cookies: ServerCookies = ServerCookies::new(None, unix_time()).unwrap();
+        cookies.required = true;
+        cookies
+    });
+    let server: Arc<Server> = Arc::new(server);
+    let socket: UdpSocket = UdpSocket::bind(("127.0.0.1", 0)).unwrap();
+    let addr: SocketAddr = socket.local_addr().unwrap();
+    thread::spawn(move || server.serve_udp(&socket));
+
+    let client: Client = Client::new(addr);
+    assert!(client.cookie().unwrap().server.is_empty());
+    // the BADCOOKIE of the first query is retried with the server cookie
+    let response: DnsPacket = client
+        .query(&name("ns.example"), QueryType::A, false)
+        .unwrap();
+    assert_eq!(response.header.r_code, ResponseCode::NOERROR);
+    assert_eq!(response.answers.len(), 1);
+    assert_eq!(client.cookie().unwrap().server.len(), 16);
+
+    // another client, another client cookie
+    assert_ne!(
+        Client::new(addr).cookie().unwrap().client,
+        client.cookie().unwrap().client
+    );
+}
+
+#[test]
+fn configs_set_cookie_secrets() {
+    let config: Config = Config::parse(
+        "cookies secret=e5e973e5a6b2a43f48e7dc849e37bfcf require=yes\n",
+        Path::new(""),
+    )
+    .unwrap();
+    assert_eq!(config.cookie_secret, Some(SECRET));
+    assert!(config.require_cookies);
+    let defaults: Config = Config::parse("", Path::new("")).unwrap();
+    assert_eq!(defaults.cookie_secret, None);
+    assert!(!defaults.require_cookies);
+    assert!(Config::parse("cookies secret=e5e9\n", Path::new("")).is_err());
+    assert!(Config::parse("cookies require=maybe\n", Path::new("")).is_err());
+}
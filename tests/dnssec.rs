@@ -0,0 +1,261 @@
+mod common;
+
+use common::name;
+use lib::dns::{DnsPacket, DnsQuestion, DnsRecord, QueryType, RecordData, ResponseCode};
+use lib::dnssec::anchor::TrustAnchors;
+use lib::dnssec::validator::{Lookup, Status, Validator};
+use lib::dnssec::{key_tag, nsec3_hash, nsec3_label, rrsig_labels, signed_data, ALG_ED25519};
+use lib::name::Name;
+use lib::Result;
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use std::net::Ipv4Addr;
+
+const NOW: u32 = 1_700_000_000;
+
+fn dnskey(key: &Ed25519KeyPair) -> RecordData {
+    RecordData::DNSKEY {
+        flags: 257,
+        protocol: 3,
+        algorithm: ALG_ED25519,
+        public_key: key.public_key().as_ref().to_vec(),
+    }
+}
+
+/// RRSIG over the records of `set` made by the key of `example.`
+fn sign(set: &[&DnsRecord], key: &Ed25519KeyPair) -> DnsRecord {
+    let mut rrsig: RecordData = RecordData::RRSIG {
+        type_covered: set[0].r_type,
+        algorithm: ALG_ED25519,
+        labels: rrsig_labels(&set[0].label),
+        original_ttl: set[0].ttl,
+        expiration: NOW + 3600,
+        inception: NOW - 3600,
+        key_tag: key_tag(&dnskey(key)).unwrap(),
+        signer_name: name("example"),
+        signature: Vec::new(),
+    };
+    let data: Vec<u8> = signed_data(&rrsig, set).unwrap();
+    if let RecordData::RRSIG { signature, .. } = &mut rrsig {
+        *signature = key.sign(&data).as_ref().to_vec();
+    }
+    DnsRecord::new(set[0].label.clone(), set[0].ttl, rrsig)
+}
+
+/// the signed zone `example.` answering like a recursive server would
+struct Zone {
+    records: Vec<DnsRecord>,
+}
+
+impl Zone {
+    fn new(key: &Ed25519KeyPair) -> Self {
+        let soa: RecordData = RecordData::SOA {
+            mname: name("ns.example"),
+            rname: name("admin.example"),
+            serial: 1,
+            refresh: 3600,
+            retry: 600,
+            expire: 86400,
+            minimum: 300,
+        };
+        let unsigned: Vec<DnsRecord> = vec![
+            DnsRecord::new(name("example"), 300, soa),
+            DnsRecord::new(name("example"), 300, dnskey(key)),
+            DnsRecord::new(
+                name("example"),
+                300,
+                RecordData::NSEC {
+                    next_domain: name("www.example"),
+                    types: vec![
+                        QueryType::SOA,
+                        QueryType::RRSIG,
+                        QueryType::NSEC,
+                        QueryType::DNSKEY,
+                    ],
+                },
+            ),
+            DnsRecord::new(
+                name("www.example"),
+                300,
+                RecordData::IPADDR(Ipv4Addr::new(192, 0, 2, 1)),
+            ),
+            DnsRecord::new(
+                name("www.example"),
+                300,
+                RecordData::NSEC {
+                    next_domain: name("example"),
+                    types: vec![QueryType::A, QueryType::RRSIG, QueryType::NSEC],
+                },
+            ),
+        ];
+        let mut records: Vec<DnsRecord> = Vec::new();
+        for record in &unsigned {
+            records.push(record.clone());
+            records.push(sign(&[record], key));
+        }
+        Self { records }
+    }
+
+    fn with_sigs(&self, owner: &Name, r_type: QueryType) -> Vec<DnsRecord> {
+        self.records
+            .iter()
+            .filter(|record| {
+                &record.label == owner
+                    && (record.r_type == r_type
+                        || matches!(record.r_data, RecordData::RRSIG { type_covered, .. } if type_covered == r_type))
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn respond(&self, qname: &Name, q_type: QueryType) -> DnsPacket {
+        let mut packet: DnsPacket = DnsPacket::new();
+        packet
+            .questions
+            .push(DnsQuestion::new(qname.clone(), q_type));
+        packet.answers = self.with_sigs(qname, q_type);
+        if packet.answers.is_empty() {
+            let exists: bool = self
+                .records
+                .iter()
+                .any(|record| record.label.ends_with(qname));
+            packet.header.r_code = match exists {
+                true => ResponseCode::NOERROR,
+                false => ResponseCode::NAMEERROR,
+            };
+            for owner in [name("example"), name("www.example")] {
+                packet
+                    .authorities
+                    .extend(self.with_sigs(&owner, QueryType::NSEC));
+            }
+        }
+        packet
+    }
+}
+
+impl Lookup for Zone {
+    fn lookup(&self, name: &Name, q_type: QueryType) -> Result<DnsPacket> {
+        Ok(self.respond(name, q_type))
+    }
+}
+
+fn setup() -> (Ed25519KeyPair, Zone, TrustAnchors) {
+    let key: Ed25519KeyPair = Ed25519KeyPair::from_seed_unchecked(&[7; 32]).unwrap();
+    let zone: Zone = Zone::new(&key);
+    let mut anchors: TrustAnchors = TrustAnchors::new();
+    anchors.add(DnsRecord::new(name("example"), 300, dnskey(&key)));
+    (key, zone, anchors)
+}
+
+fn validate(zone: &Zone, anchors: TrustAnchors, response: &mut DnsPacket) -> Status {
+    let mut validator: Validator<&Zone> = Validator::new(zone, anchors);
+    validator.set_time(NOW);
+    validator.validate(response)
+}
+
+#[test]
+fn signed_answer_is_secure() {
+    let (_, zone, anchors) = setup();
+    let mut response: DnsPacket = zone.respond(&name("www.example"), QueryType::A);
+    assert_eq!(validate(&zone, anchors, &mut response), Status::Secure);
+    assert!(response.header.ad);
+}
+
+#[test]
+fn tampered_answer_is_bogus() {
+    let (_, zone, anchors) = setup();
+    let mut response: DnsPacket = zone.respond(&name("www.example"), QueryType::A);
+    response.answers[0].r_data = RecordData::IPADDR(Ipv4Addr::new(192, 0, 2, 66));
+    assert!(matches!(
+        validate(&zone, anchors, &mut response),
+        Status::Bogus(_)
+    ));
+    assert!(!response.header.ad);
+}
+
+#[test]
+fn stripped_signature_is_bogus() {
+    let (_, zone, anchors) = setup();
+    let mut response: DnsPacket = zone.respond(&name("www.example"), QueryType::A);
+    response
+        .answers
+        .retain(|record| record.r_type != QueryType::RRSIG);
+    assert!(matches!(
+        validate(&zone, anchors, &mut response),
+        Status::Bogus(_)
+    ));
+}
+
+#[test]
+fn expired_signature_is_bogus() {
+    let (_, zone, anchors) = setup();
+    let mut response: DnsPacket = zone.respond(&name("www.example"), QueryType::A);
+    let mut validator: Validator<&Zone> = Validator::new(&zone, anchors);
+    validator.set_time(NOW + 7200);
+    assert!(matches!(
+        validator.validate(&mut response),
+        Status::Bogus(_)
+    ));
+}
+
+#[test]
+fn nsec_denials_are_secure() {
+    let (_, zone, anchors) = setup();
+    let mut nx_domain: DnsPacket = zone.respond(&name("nope.example"), QueryType::A);
+    assert_eq!(nx_domain.header.r_code, ResponseCode::NAMEERROR);
+    assert_eq!(
+        validate(&zone, anchors.clone(), &mut nx_domain),
+        Status::Secure
+    );
+
+    let mut no_data: DnsPacket = zone.respond(&name("www.example"), QueryType::MX);
+    assert_eq!(
+        validate(&zone, anchors.clone(), &mut no_data),
+        Status::Secure
+    );
+
+    // the NSEC at www.example shows an A record, so NODATA for A is a lie
+    let mut lie: DnsPacket = zone.respond(&name("www.example"), QueryType::A);
+    lie.answers.clear();
+    lie.authorities = zone
+        .respond(&name("www.example"), QueryType::MX)
+        .authorities;
+    assert!(matches!(
+        validate(&zone, anchors, &mut lie),
+        Status::Bogus(_)
+    ));
+}
+
+#[test]
+fn no_anchor_is_insecure() {
+    let (_, zone, _) = setup();
+    let mut response: DnsPacket = zone.respond(&name("www.example"), QueryType::A);
+    assert_eq!(
+        validate(&zone, TrustAnchors::new(), &mut response),
+        Status::Insecure
+    );
+}
+
+#[test]
+fn anchors_parse_and_root_is_built_in() {
+    let anchors: TrustAnchors = TrustAnchors::parse(
+        "; comment\nexample. 3600 IN DS 60485 5 1 2BB183AF5F22588179A53B0A 98631FAD1A292118\n",
+    )
+    .unwrap();
+    assert_eq!(anchors.ds(&name("example")).len(), 1);
+    assert_eq!(TrustAnchors::root().ds(&Name::root()).len(), 2);
+}
+
+#[test]
+fn nsec3_hash_matches_rfc5155() {
+    // RFC 5155 Appendix A, salt aabbccdd and 12 iterations
+    let hash: Vec<u8> = nsec3_hash(&name("example"), 12, &[0xaa, 0xbb, 0xcc, 0xdd]);
+    assert_eq!(
+        nsec3_label(&hash),
+        b"0p9mhaveqvm6t7vbl5lop2u3t2rp3tom".to_vec()
+    );
+    let hash: Vec<u8> = nsec3_hash(&name("a.example"), 12, &[0xaa, 0xbb, 0xcc, 0xdd]);
+    assert_eq!(
+        nsec3_label(&hash),
+        b"35mthgpgcu1qg68fab165klnsnk3dpvl".to_vec()
+    );
+}
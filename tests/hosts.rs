@@ -0,0 +1,118 @@
+mod common;
+
+use common::{name, request};
+use lib::config::Config;
+use lib::dns::{DnsPacket, QueryType, RecordData, ResponseCode};
+use lib::hosts::{reverse_name, Hosts};
+use lib::name::Name;
+use lib::server::{load_hosts, Server};
+use lib::zone::{Catalog, Zone};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+
+const HOSTS: &str = "\
+# comment
+127.0.0.1\tlocalhost
+192.0.2.10  www.example www   # the web server
+192.0.2.10  alias.example
+fe80::1%lo0 localhost
+";
+
+fn query(server: &Server, host: &str, q_type: QueryType) -> DnsPacket {
+    server.handle(&request(host, q_type))
+}
+
+fn hosts() -> Hosts {
+    let mut hosts: Hosts = Hosts::new();
+    hosts.parse_hosts(HOSTS).unwrap();
+    hosts
+        .parse_records("mail.example. 300 MX 10 www.example.\n")
+        .unwrap();
+    hosts
+}
+
+#[test]
+fn hosts_files_pin_names() {
+    let hosts: Hosts = hosts();
+    let www: Vec<RecordData> = hosts
+        .records_at(&name("www.example"))
+        .iter()
+        .map(|r| r.r_data.clone())
+        .collect();
+    assert_eq!(www, vec![RecordData::IPADDR(Ipv4Addr::new(192, 0, 2, 10))]);
+    assert_eq!(hosts.records_at(&name("alias.example")).len(), 1);
+    assert_eq!(hosts.records_at(&name("localhost")).len(), 2);
+
+    // the first name of the first line is what the address points back to
+    let reverse: Name = reverse_name(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10)));
+    assert_eq!(reverse, name("10.2.0.192.in-addr.arpa"));
+    assert_eq!(
+        hosts.records_at(&reverse)[0].r_data,
+        RecordData::PTR(name("www.example"))
+    );
+    assert_eq!(
+        reverse_name(IpAddr::V6("2001:db8::1".parse::<Ipv6Addr>().unwrap())),
+        name("1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa")
+    );
+
+    assert!(Hosts::new().parse_hosts("192.0.2.300 bad\n").is_err());
+    assert!(Hosts::new().parse_hosts("192.0.2.1\n").is_err());
+}
+
+#[test]
+fn pinned_names_come_first() {
+    let zone: Zone = Zone::parse(
+        "$TTL 60\n@ SOA ns hostmaster 1 2 3 4 5\n@ NS ns\nwww A 192.0.2.1\nwww MX 10 www\n",
+        &name("example"),
+    )
+    .unwrap();
+    let mut catalog: Catalog = Catalog::new();
+    catalog.insert(zone);
+    let server: Server = Server::new(catalog);
+    server.set_hosts(hosts());
+
+    let a: DnsPacket = query(&server, "WWW.example", QueryType::A);
+    assert!(a.header.aa);
+    assert_eq!(a.answers.len(), 1);
+    assert_eq!(
+        a.answers[0].r_data,
+        RecordData::IPADDR(Ipv4Addr::new(192, 0, 2, 10))
+    );
+
+    // no address of the other family leaks from the zone or elsewhere
+    let aaaa: DnsPacket = query(&server, "www.example", QueryType::AAAA);
+    assert_eq!(aaaa.header.r_code, ResponseCode::NOERROR);
+    assert!(aaaa.answers.is_empty());
+
+    // other types come from the zone unless pinned as well
+    assert_eq!(
+        query(&server, "www.example", QueryType::MX).answers.len(),
+        1
+    );
+    assert_eq!(
+        query(&server, "mail.example", QueryType::MX).answers.len(),
+        1
+    );
+
+    let ptr: DnsPacket = query(&server, "10.2.0.192.in-addr.arpa", QueryType::PTR);
+    assert_eq!(ptr.answers[0].r_data, RecordData::PTR(name("www.example")));
+    assert_eq!(
+        query(&server, "other.example.net", QueryType::A)
+            .header
+            .r_code,
+        ResponseCode::REFUSED
+    );
+}
+
+#[test]
+fn configs_name_hosts_and_static_files() {
+    let dir: PathBuf = std::env::temp_dir().join(format!("hosts-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("hosts"), HOSTS).unwrap();
+    std::fs::write(dir.join("pinned"), "mail.example. 300 MX 10 www.example.\n").unwrap();
+    let config: Config = Config::parse("hosts hosts\nstatic pinned\n", &dir).unwrap();
+    let loaded: lib::Result<Hosts> = load_hosts(&config);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(loaded.unwrap(), hosts());
+    assert!(Config::parse("hosts\n", Path::new("")).is_err());
+}
@@ -0,0 +1,173 @@
+mod common;
+
+use common::{decode, encode, name, request, LOCALHOST};
+use lib::client::Client;
+use lib::dns::{DnsPacket, DnsRecord, QueryType, RecordData, ResponseCode};
+use lib::journal::{Diff, Journal};
+use lib::server::Server;
+use lib::zone::{Catalog, Zone};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::sync::Arc;
+use std::thread;
+
+fn zone(serial: u32, hosts: &str) -> Zone {
+    let text: String = format!(
+        "$TTL 60\n@ SOA ns hostmaster {} 2 3 4 5\n@ NS ns\n{}",
+        serial, hosts
+    );
+    Zone::parse(&text, &name("example")).unwrap()
+}
+
+fn a(host: &str, last: u8) -> DnsRecord {
+    DnsRecord::new(
+        name(host),
+        60,
+        RecordData::IPADDR(Ipv4Addr::new(192, 0, 2, last)),
+    )
+}
+
+/// the example zone at serial 3, changed twice since serial 1
+fn changed() -> Zone {
+    let v1: Zone = zone(1, "a A 192.0.2.1\nb A 192.0.2.2\n");
+    let v2: Zone = zone(2, "a A 192.0.2.1\nc A 192.0.2.3\n");
+    let mut v3: Zone = zone(3, "a A 192.0.2.10\nb A 192.0.2.2\nc A 192.0.2.3\n");
+    let mut history: Zone = v2.clone();
+    history.succeed(&v1);
+    v3.succeed(&history);
+    v3
+}
+
+fn listen(zone: Zone) -> SocketAddr {
+    let mut catalog: Catalog = Catalog::new();
+    catalog.insert(zone);
+    let mut server: Server = Server::new(catalog);
+    server.set_transfer_acl(name("example"), "127.0.0.1".parse().unwrap());
+    let listener: TcpListener = TcpListener::bind((LOCALHOST, 0)).unwrap();
+    let addr: SocketAddr = listener.local_addr().unwrap();
+    let server: Arc<Server> = Arc::new(server);
+    thread::spawn(move || server.serve_tcp(&listener));
+    addr
+}
+
+#[test]
+fn diffs_condense() {
+    let latest: Zone = changed();
+    let journal: &Journal = latest.journal();
+    assert_eq!(journal.diffs().count(), 2);
+    assert_eq!(journal.last_serial(), Some(3));
+
+    // b went away and came back, a changed once
+    let diff: Diff = journal.condensed(1).unwrap();
+    assert_eq!((diff.old_serial(), diff.new_serial()), (1, 3));
+    assert_eq!(diff.deleted, vec![a("a.example", 1)]);
+    assert_eq!(diff.added.len(), 2);
+    assert!(diff.added.contains(&a("a.example", 10)));
+    assert!(diff.added.contains(&a("c.example", 3)));
+    assert!(journal.condensed(0).is_none());
+
+    let mut old: Zone = zone(1, "a A 192.0.2.1\nb A 192.0.2.2\n");
+    old.apply(diff.clone()).unwrap();
+    assert_eq!(
+        old.records().collect::<Vec<_>>(),
+        latest.records().collect::<Vec<_>>()
+    );
+    // a diff applies at its old serial only
+    assert!(old.apply(diff).is_err());
+}
+
+#[test]
+fn compressed_records_cancel() {
+    // as read from a packet, mail.example compressed to a pointer
+    let mx: DnsRecord = DnsRecord::new(
+        name("example"),
+        60,
+        RecordData::MX {
+            preference: 10,
+            exchange: name("mail.example"),
+        },
+    );
+    let compressed: DnsRecord = DnsRecord {
+        rd_len: 4,
+        ..mx.clone()
+    };
+    let soa = |serial: u32| -> DnsRecord { zone(serial, "").soa().unwrap().clone() };
+    let added: Diff = Diff {
+        old_soa: soa(1),
+        deleted: Vec::new(),
+        new_soa: soa(2),
+        added: vec![mx],
+    };
+    let deleted: Diff = Diff {
+        old_soa: soa(2),
+        deleted: vec![compressed.clone()],
+        new_soa: soa(3),
+        added: Vec::new(),
+    };
+    let diff: Diff = added.then(deleted);
+    assert!(diff.deleted.is_empty() && diff.added.is_empty());
+
+    let mut received: Zone = zone(1, "");
+    received.insert(compressed);
+    let diff: Diff = received.diff(&zone(2, "@ MX 10 mail.example.\n")).unwrap();
+    assert!(diff.deleted.is_empty() && diff.added.is_empty());
+}
+
+#[test]
+fn journals_round_trip_through_files() {
+    let zone: Zone = changed();
+    let file: String = std::env::temp_dir()
+        .join(format!("ixfr-{}.jnl", std::process::id()))
+        .to_string_lossy()
+        .into_owned();
+    zone.journal().save(&file).unwrap();
+    let loaded: Journal = Journal::load(&file, &name("example")).unwrap();
+    std::fs::remove_file(&file).unwrap();
+    assert_eq!(&loaded, zone.journal());
+    assert!(Journal::load(&file, &name("example")).unwrap().is_empty());
+}
+
+#[test]
+fn secondaries_catch_up_incrementally() {
+    let addr: SocketAddr = listen(changed());
+    let client: Client = Client::new(addr);
+
+    let old: Zone = zone(1, "a A 192.0.2.1\nb A 192.0.2.2\n");
+    let updated: Zone = client.ixfr(&old).unwrap();
+    assert_eq!(updated.serial(), Some(3));
+    assert_eq!(updated.records().count(), 5);
+    // the secondary can serve IXFR from serial 1 in turn
+    assert_eq!(updated.journal().condensed(1).unwrap().new_serial(), 3);
+
+    let current: Zone = client.ixfr(&updated).unwrap();
+    assert_eq!(current.serial(), Some(3));
+
+    // too old for the journal, the whole zone comes instead
+    let ancient: Zone = zone(0, "");
+    let full: Zone = client.ixfr(&ancient).unwrap();
+    assert_eq!(full.serial(), Some(3));
+    assert_eq!(full.records().count(), 5);
+    assert_eq!(full.journal().last_serial(), Some(3));
+}
+
+#[test]
+fn udp_ixfr_gets_the_soa() {
+    let mut catalog: Catalog = Catalog::new();
+    catalog.insert(changed());
+    let mut server: Server = Server::new(catalog);
+    let message: Vec<u8> = encode(&mut request("example", QueryType::IXFR));
+    let ask = |server: &Server| -> DnsPacket {
+        let answer: Vec<u8> = server
+            .handle_bytes(&message, LOCALHOST, LOCALHOST, false)
+            .unwrap();
+        decode(&answer)
+    };
+    // nothing about the zone for clients that may not transfer it
+    let refused: DnsPacket = ask(&server);
+    assert_eq!(refused.header.r_code, ResponseCode::REFUSED);
+    assert!(!refused.header.aa && refused.answers.is_empty());
+
+    server.set_transfer_acl(name("example"), "127.0.0.1".parse().unwrap());
+    let response: DnsPacket = ask(&server);
+    assert_eq!(response.answers.len(), 1);
+    assert_eq!(response.answers[0].r_type, QueryType::SOA);
+}
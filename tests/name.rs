@@ -0,0 +1,123 @@
+mod common;
+
+use common::name;
+use lib::name::Name;
+use lib::Error;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+fn hash(name: &Name) -> u64 {
+    let mut hasher: DefaultHasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[test]
+fn names_sort_in_canonical_order() {
+    // the example of RFC 4034 §6.1
+    let ordered: Vec<Name> = [
+        "example",
+        "a.example",
+        "yljkjljk.a.example",
+        "Z.a.example",
+        "zABC.a.EXAMPLE",
+        "z.example",
+        "\\001.z.example",
+        "*.z.example",
+        "\\200.z.example",
+    ]
+    .iter()
+    .map(|s| name(s))
+    .collect();
+    let mut sorted: Vec<Name> = ordered.iter().rev().cloned().collect();
+    sorted.sort();
+    assert_eq!(sorted, ordered);
+    assert!(Name::root() < name("example"));
+}
+
+#[test]
+fn escapes_parse_and_print() {
+    let dotted: Name = name("a\\.b.example");
+    assert_eq!(dotted.num_labels(), 2);
+    assert_eq!(dotted.labels().next(), Some(&b"a.b"[..]));
+    assert_eq!(dotted.to_string(), "a\\.b.example");
+
+    let octets: Name = name("\\000\\065\\255.example.");
+    assert_eq!(octets.labels().next(), Some(&[0, b'A', 255][..]));
+    // printable octets print as themselves, others as \DDD
+    assert_eq!(octets.to_string(), "\\000A\\255.example");
+    assert_eq!(format!("{:#}", octets), "\\000A\\255.example.");
+    assert_eq!(name(&octets.to_string()), octets);
+
+    assert!(matches!(
+        "\\256.example".parse::<Name>(),
+        Err(Error::InvalidName(_))
+    ));
+    assert!(matches!(
+        "\\06.example".parse::<Name>(),
+        Err(Error::InvalidName(_))
+    ));
+    assert!(matches!(
+        "example\\".parse::<Name>(),
+        Err(Error::InvalidName(_))
+    ));
+    assert!(matches!(
+        "a..example".parse::<Name>(),
+        Err(Error::EmptyLabel)
+    ));
+}
+
+#[test]
+fn labels_and_names_are_limited_in_length() {
+    let longest: String = "a".repeat(63);
+    assert_eq!(name(&longest).wire_len(), 65);
+    assert!(matches!(
+        format!("{}a.example", longest).parse::<Name>(),
+        Err(Error::LabelTooLong(64))
+    ));
+    // four labels of 62 octets take 4 * 63 + 1 = 253 octets on the wire
+    let label: String = "b".repeat(62);
+    let fits: String = [label.as_str(); 4].join(".");
+    assert_eq!(name(&fits).wire_len(), 253);
+    assert_eq!(name(&format!("c.{}", fits)).wire_len(), 255);
+    assert!(matches!(
+        format!("cc.{}", fits).parse::<Name>(),
+        Err(Error::NameTooLong(256))
+    ));
+}
+
+#[test]
+fn case_is_ignored_by_equality_and_hashing() {
+    let lower: Name = name("www.example");
+    let mixed: Name = name("WwW.ExAmPlE.");
+    assert_eq!(lower, mixed);
+    assert_eq!(hash(&lower), hash(&mixed));
+    assert!(!lower.eq_case(&mixed));
+    // the case is kept as given
+    assert_eq!(mixed.to_string(), "WwW.ExAmPlE");
+    assert_ne!(lower, name("www.example.net"));
+    // only ASCII letters fold
+    assert_ne!(name("\\200.example"), name("\\232.example"));
+}
+
+#[test]
+fn unicode_labels_convert_to_a_labels() {
+    let books: Name = Name::from_unicode("bücher.example").unwrap();
+    assert!(books.eq_case(&name("xn--bcher-kva.example")));
+    assert_eq!(books.to_unicode(), "bücher.example");
+    // UTS #46 maps to lower case, A-labels decode whatever their case
+    assert!(Name::from_unicode("Bücher.Example")
+        .unwrap()
+        .eq_case(&books));
+    assert_eq!(name("XN--BCHER-KVA.Example").to_unicode(), "bücher.example");
+    // ASCII is taken verbatim
+    let verbatim: Name = Name::from_unicode("_Sip._tcp.WwW.example").unwrap();
+    assert!(verbatim.eq_case(&name("_Sip._tcp.WwW.example")));
+    // labels that are not valid punycode stay as they are
+    assert_eq!(name("xn--zz.example").to_unicode(), "xn--zz.example");
+    // a zero width joiner outside the contexts RFC 5892 allows
+    assert!(matches!(
+        Name::from_unicode("a\u{200D}b.example"),
+        Err(Error::InvalidName(_))
+    ));
+}
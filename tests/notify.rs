@@ -0,0 +1,174 @@
+mod common;
+
+use common::{decode, encode, name, request, LOCALHOST};
+use lib::config::{Config, ZoneConfig};
+use lib::dns::{DnsPacket, OpCode, QueryType, ResponseCode};
+use lib::serial::Serial;
+use lib::server::Server;
+use lib::tsig::KeyRing;
+use lib::zone::{Catalog, Zone};
+use std::net::{IpAddr, SocketAddr, TcpListener, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn zone(serial: u32) -> Zone {
+    let text: String = format!(
+        "$TTL 60\n@ SOA ns hostmaster {} 2 3 4 5\n@ NS ns\nwww A 192.0.2.{}\n",
+        serial,
+        serial % 256
+    );
+    Zone::parse(&text, &name("example")).unwrap()
+}
+
+fn notify_request(origin: &str, q_type: QueryType) -> Vec<u8> {
+    let mut request: DnsPacket = request(origin, q_type);
+    request.header.op_code = OpCode::NOTIFY;
+    request.header.aa = true;
+    encode(&mut request)
+}
+
+/// serve `server` over UDP and TCP on the same free local port
+fn listen(server: Arc<Server>) -> SocketAddr {
+    let listener: TcpListener = TcpListener::bind((LOCALHOST, 0)).unwrap();
+    let addr: SocketAddr = listener.local_addr().unwrap();
+    let socket: UdpSocket = UdpSocket::bind(addr).unwrap();
+    let tcp: Arc<Server> = server.clone();
+    thread::spawn(move || tcp.serve_tcp(&listener));
+    thread::spawn(move || server.serve_udp(&socket));
+    addr
+}
+
+#[test]
+fn serials_wrap_around() {
+    assert!(Serial(1) > Serial(0));
+    assert!(Serial(0) > Serial(u32::MAX));
+    assert!(Serial(u32::MAX).wrapping_add(2) > Serial(u32::MAX));
+    assert_eq!(Serial(u32::MAX).wrapping_add(2), Serial(1));
+    assert!(Serial(0x7FFF_FFFF) > Serial(0));
+    // exactly half the space apart is undefined, RFC 1982 §3.2
+    assert_eq!(Serial(0).partial_cmp(&Serial(0x8000_0000)), None);
+}
+
+#[test]
+fn notify_is_acknowledged_from_allowed_clients() {
+    let mut catalog: Catalog = Catalog::new();
+    catalog.insert(zone(1));
+    let mut server: Server = Server::new(catalog);
+    server.set_notify_acl(name("example"), "127.0.0.1".parse().unwrap());
+
+    let answer: Vec<u8> = server
+        .handle_bytes(
+            &notify_request("example", QueryType::SOA),
+            LOCALHOST,
+            LOCALHOST,
+            false,
+        )
+        .unwrap();
+    let response: DnsPacket = decode(&answer);
+    assert_eq!(response.header.op_code, OpCode::NOTIFY);
+    assert_eq!(response.header.r_code, ResponseCode::NOERROR);
+    assert!(response.header.qr && response.header.aa);
+
+    let stranger: IpAddr = "192.0.2.9".parse().unwrap();
+    let refused: Vec<u8> = server
+        .handle_bytes(
+            &notify_request("example", QueryType::SOA),
+            stranger,
+            LOCALHOST,
+            false,
+        )
+        .unwrap();
+    assert_eq!(decode(&refused).header.r_code, ResponseCode::REFUSED);
+
+    let other: Vec<u8> = server
+        .handle_bytes(
+            &notify_request("example.net", QueryType::SOA),
+            LOCALHOST,
+            LOCALHOST,
+            false,
+        )
+        .unwrap();
+    assert_eq!(decode(&other).header.r_code, ResponseCode::NOTAUTH);
+
+    let malformed: Vec<u8> = server
+        .handle_bytes(
+            &notify_request("example", QueryType::A),
+            LOCALHOST,
+            LOCALHOST,
+            false,
+        )
+        .unwrap();
+    assert_eq!(decode(&malformed).header.r_code, ResponseCode::FORMATERROR);
+
+    // the primary may notify by default
+    let config: Config = Config::parse(
+        "zone example. db primary=192.0.2.1:53 notify=192.0.2.3:53,[2001:db8::3]:53\n",
+        Path::new(""),
+    )
+    .unwrap();
+    assert!(config.zones[0]
+        .allow_notify
+        .allows("192.0.2.1".parse().unwrap(), None));
+    assert!(!config.zones[0].allow_notify.allows(stranger, None));
+    assert_eq!(config.zones[0].notify.len(), 2);
+}
+
+#[test]
+fn notify_brings_secondaries_up_to_date() {
+    let mut catalog: Catalog = Catalog::new();
+    catalog.insert(zone(u32::MAX));
+    let mut primary: Server = Server::new(catalog);
+    primary.set_transfer_acl(name("example"), "127.0.0.1".parse().unwrap());
+    let primary: Arc<Server> = Arc::new(primary);
+    let primary_addr: SocketAddr = listen(primary.clone());
+
+    let file: PathBuf = std::env::temp_dir().join(format!("notify-{}.zone", std::process::id()));
+    let config: Config = Config::parse(
+        &format!(
+            "zone example. {} primary={}\n",
+            file.display(),
+            primary_addr
+        ),
+        Path::new(""),
+    )
+    .unwrap();
+    let secondary_config: ZoneConfig = config.zones[0].clone();
+    let mut catalog: Catalog = Catalog::new();
+    catalog.insert(zone(u32::MAX));
+    let mut secondary: Server = Server::new(catalog);
+    secondary.set_notify_acl(name("example"), secondary_config.allow_notify.clone());
+    let secondary: Arc<Server> = Arc::new(secondary);
+    let secondary_addr: SocketAddr = listen(secondary.clone());
+    let follower: Arc<Server> = secondary.clone();
+    thread::spawn(move || follower.follow(&secondary_config, &KeyRing::new()));
+    // let the first check find the zone current
+    thread::sleep(Duration::from_millis(200));
+
+    // the serial wraps around and is still newer
+    primary.replace_zone(zone(0));
+    let mut primary_config: ZoneConfig = config.zones[0].clone();
+    primary_config.primary = None;
+    primary_config.notify = vec![secondary_addr];
+    assert!(primary
+        .notify_secondaries(&primary_config, &KeyRing::new())
+        .is_empty());
+
+    let start: Instant = Instant::now();
+    let serial = || {
+        secondary
+            .catalog()
+            .read()
+            .unwrap()
+            .get(&name("example"))
+            .unwrap()
+            .serial()
+    };
+    while serial() != Some(0) && start.elapsed() < Duration::from_secs(3) {
+        thread::sleep(Duration::from_millis(20));
+    }
+    assert_eq!(serial(), Some(0));
+    std::fs::remove_file(&file).unwrap();
+    std::fs::remove_file(config.zones[0].journal_file()).unwrap();
+}
@@ -0,0 +1,91 @@
+use lib::name::Name;
+use lib::parser::DnsBytePacketBuffer;
+use lib::{Error, Result};
+
+/// the name starting `at` octets into `bytes`
+fn read_at(bytes: &[u8], at: usize) -> Result<Name> {
+    let mut buffer: DnsBytePacketBuffer = DnsBytePacketBuffer::new();
+    buffer.get_buf()[..bytes.len()].copy_from_slice(bytes);
+    buffer.set_bytes_read(bytes.len());
+    buffer._step(at)?;
+    buffer.read_label()
+}
+
+#[test]
+fn pointers_only_lead_back_out_of_the_name() {
+    // forward, to octets not read yet
+    assert!(matches!(
+        read_at(&[0xC0, 0x05, 0, 0, 0, 1, b'a', 0], 0),
+        Err(Error::ForwardPointer { at: 0, target: 5 })
+    ));
+    // to itself
+    assert!(matches!(
+        read_at(&[0, 0, 0xC0, 0x02], 2),
+        Err(Error::PointerLoop { at: 2, target: 2 })
+    ));
+    // back to the start of the name it ends
+    assert!(matches!(
+        read_at(&[1, b'a', 0xC0, 0x00], 0),
+        Err(Error::PointerLoop { at: 2, target: 0 })
+    ));
+    // from the name pointed to, back into the name pointing, which lies
+    // ahead of it
+    assert!(matches!(
+        read_at(&[1, b'a', 0xC0, 0x04, 1, b'b', 0xC0, 0x00], 4),
+        Err(Error::ForwardPointer { at: 2, target: 4 })
+    ));
+}
+
+#[test]
+fn reserved_label_types_are_rejected() {
+    assert!(matches!(
+        read_at(&[0x41, b'a', 0], 0),
+        Err(Error::ReservedLabelType {
+            at: 0,
+            label_type: 0x40
+        })
+    ));
+    assert!(matches!(
+        read_at(&[1, b'a', 0x81, b'b', 0], 0),
+        Err(Error::ReservedLabelType {
+            at: 2,
+            label_type: 0x80
+        })
+    ));
+}
+
+#[test]
+fn names_over_255_octets_are_rejected() {
+    let mut bytes: Vec<u8> = Vec::new();
+    for _ in 0..4 {
+        bytes.push(63);
+        bytes.extend_from_slice(&[b'a'; 63]);
+    }
+    bytes.push(0);
+    assert!(matches!(read_at(&bytes, 0), Err(Error::NameTooLong(257))));
+    // the same through pointers, each label once in the packet
+    let mut pointed: Vec<u8> = bytes[..64].to_vec();
+    pointed.push(0);
+    let mut previous: usize = 0;
+    for _ in 1..4 {
+        let start: usize = pointed.len();
+        pointed.push(63);
+        pointed.extend_from_slice(&[b'a'; 63]);
+        pointed.extend_from_slice(&[0xC0, previous as u8]);
+        previous = start;
+    }
+    assert!(matches!(
+        read_at(&pointed, previous),
+        Err(Error::NameTooLong(257))
+    ));
+}
+
+#[test]
+fn names_at_offset_0_can_be_pointed_to() {
+    let bytes: [u8; 9] = [3, b'w', b'w', b'w', 0, 1, b'x', 0xC0, 0x00];
+    assert_eq!(read_at(&bytes, 0).unwrap(), "www".parse::<Name>().unwrap());
+    assert_eq!(
+        read_at(&bytes, 5).unwrap(),
+        "x.www".parse::<Name>().unwrap()
+    );
+}
@@ -0,0 +1,185 @@
+mod common;
+
+use common::name;
+use lib::dns::{DnsPacket, QueryType, RecordData, ResponseCode};
+use lib::parser::DnsBytePacketBuffer;
+use lib::recursor::Recursor;
+use lib::server::Server;
+use lib::zone::{Catalog, Zone};
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const ROOT: &str = "$TTL 60
+@ SOA a.root hostmaster 1 2 3 4 5
+@ NS a.root
+a.root A 127.0.0.1
+example NS ns.example
+ns.example A 127.0.0.2
+";
+
+const EXAMPLE: &str = "$TTL 60
+@ SOA ns hostmaster 1 2 3 4 5
+@ NS ns
+ns A 127.0.0.2
+www.a.b.c A 192.0.2.1
+alias CNAME www.deep
+deep NS ns.deep
+ns.deep A 127.0.0.3
+";
+
+const DEEP: &str = "$TTL 60
+@ SOA ns hostmaster 1 2 3 4 5
+@ NS ns
+ns A 127.0.0.3
+www A 192.0.2.2
+";
+
+/// the questions a server was asked, as "name TYPE"
+type Asked = Arc<Mutex<Vec<String>>>;
+
+/// serve `zone` for `origin` on `addr`, answering empty non-terminals with
+/// NXDOMAIN when `broken`
+fn serve(addr: Ipv4Addr, port: u16, zone: &str, origin: &str, broken: bool) -> Asked {
+    let mut catalog: Catalog = Catalog::new();
+    catalog.insert(Zone::parse(zone, &name(origin)).unwrap());
+    let server: Server = Server::new(catalog);
+    let socket: UdpSocket = UdpSocket::bind((addr, port)).unwrap();
+    let asked: Asked = Arc::default();
+    let seen: Asked = asked.clone();
+    thread::spawn(move || loop {
+        let mut buffer: DnsBytePacketBuffer = DnsBytePacketBuffer::new();
+        let (len, from) = socket.recv_from(buffer.get_buf()).unwrap();
+        buffer.set_bytes_read(len);
+        let request: DnsPacket = DnsPacket::from_buffer(&mut buffer).unwrap();
+        let question = &request.questions[0];
+        seen.lock().unwrap().push(format!(
+            "{} {:?}",
+            question.label.to_lowercase(),
+            question.q_type
+        ));
+        let mut response: DnsPacket = server.handle(&request);
+        let nodata: bool = response.header.r_code == ResponseCode::NOERROR
+            && response.header.aa
+            && response.answers.is_empty();
+        if broken && nodata {
+            response.header.r_code = ResponseCode::NAMEERROR;
+        }
+        let mut out: DnsBytePacketBuffer = DnsBytePacketBuffer::new();
+        response.write(&mut out).unwrap();
+        socket.send_to(out.written(), from).unwrap();
+    });
+    asked
+}
+
+/// the root, example and deep.example servers on one free port, and what
+/// each of them was asked
+fn servers(broken: bool) -> (Recursor, [Asked; 3]) {
+    let port: u16 = UdpSocket::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let asked: [Asked; 3] = [
+        serve(Ipv4Addr::new(127, 0, 0, 1), port, ROOT, ".", false),
+        serve(
+            Ipv4Addr::new(127, 0, 0, 2),
+            port,
+            EXAMPLE,
+            "example",
+            broken,
+        ),
+        serve(
+            Ipv4Addr::new(127, 0, 0, 3),
+            port,
+            DEEP,
+            "deep.example",
+            false,
+        ),
+    ];
+    let mut recursor: Recursor = Recursor::new(vec![IpAddr::V4(Ipv4Addr::LOCALHOST)]);
+    recursor.set_port(port);
+    recursor.set_timeout(Duration::from_secs(1));
+    (recursor, asked)
+}
+
+fn asked(asked: &Asked) -> Vec<String> {
+    asked.lock().unwrap().clone()
+}
+
+fn address(response: &DnsPacket) -> Option<&RecordData> {
+    response
+        .answers
+        .iter()
+        .find(|record| record.r_type == QueryType::A)
+        .map(|record| &record.r_data)
+}
+
+#[test]
+fn queries_reveal_one_label_at_a_time() {
+    let (recursor, [root, example, _]) = servers(false);
+    let response: DnsPacket = recursor
+        .resolve(&name("www.a.b.c.example"), QueryType::AAAA)
+        .unwrap();
+    assert_eq!(response.header.r_code, ResponseCode::NOERROR);
+    assert!(response.answers.is_empty());
+    // the root only learns of the top level domain
+    assert_eq!(asked(&root), ["example A"]);
+    assert_eq!(
+        asked(&example),
+        [
+            "c.example A",
+            "b.c.example A",
+            "a.b.c.example A",
+            "www.a.b.c.example AAAA"
+        ]
+    );
+}
+
+#[test]
+fn delegations_and_aliases_are_followed() {
+    let (recursor, [root, example, deep]) = servers(false);
+    let response: DnsPacket = recursor
+        .resolve(&name("alias.example"), QueryType::A)
+        .unwrap();
+    assert_eq!(response.questions[0].label, name("alias.example"));
+    assert_eq!(response.answers[0].r_type, QueryType::CNAME);
+    assert_eq!(
+        address(&response),
+        Some(&RecordData::IPADDR(Ipv4Addr::new(192, 0, 2, 2)))
+    );
+    // the alias is resolved from the root again, the delegation of
+    // deep.example found by a minimised query
+    assert_eq!(asked(&root), ["example A", "example A"]);
+    assert_eq!(asked(&example), ["alias.example A", "deep.example A"]);
+    assert_eq!(asked(&deep), ["www.deep.example A"]);
+}
+
+#[test]
+fn broken_servers_get_the_full_name() {
+    let (recursor, [_, example, _]) = servers(true);
+    let response: DnsPacket = recursor
+        .resolve(&name("www.a.b.c.example"), QueryType::A)
+        .unwrap();
+    assert_eq!(
+        address(&response),
+        Some(&RecordData::IPADDR(Ipv4Addr::new(192, 0, 2, 1)))
+    );
+    assert_eq!(asked(&example), ["c.example A", "www.a.b.c.example A"]);
+}
+
+#[test]
+fn minimising_can_be_turned_off() {
+    let (mut recursor, [root, example, _]) = servers(false);
+    recursor.set_minimise(false);
+    let response: DnsPacket = recursor
+        .resolve(&name("www.a.b.c.example"), QueryType::A)
+        .unwrap();
+    assert_eq!(
+        address(&response),
+        Some(&RecordData::IPADDR(Ipv4Addr::new(192, 0, 2, 1)))
+    );
+    assert_eq!(asked(&root), ["www.a.b.c.example A"]);
+    assert_eq!(asked(&example), ["www.a.b.c.example A"]);
+}
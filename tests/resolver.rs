@@ -0,0 +1,185 @@
+mod common;
+
+use common::{decode, encode, ip, name};
+use lib::dns::{DnsPacket, QueryType, RecordData, ResponseCode};
+use lib::name::Name;
+use lib::resolver::{srv_order, Mx, Resolver, Srv};
+use lib::server::Server;
+use lib::zone::{Catalog, Zone};
+use lib::Error;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::Arc;
+use std::thread;
+
+const EXAMPLE: &str = "$TTL 60
+@ SOA ns hostmaster 1 2 3 4 5
+@ NS ns
+@ MX 20 mx2
+@ MX 10 mx1
+@ TXT \"v=spf1 \" \"-all\"
+ns A 192.0.2.53
+www A 192.0.2.1
+www AAAA 2001:db8::1
+alias CNAME www.other.test.
+_imaps._tcp SRV 0 1 993 mail
+_none._tcp SRV 0 0 0 .
+";
+
+const OTHER: &str = "$TTL 60
+@ SOA ns hostmaster 1 2 3 4 5
+@ NS ns
+www A 198.51.100.1
+";
+
+const REVERSE: &str = "$TTL 60
+@ SOA ns.example. hostmaster.example. 1 2 3 4 5
+@ NS ns.example.
+1 PTR www.example.
+";
+
+fn resolver() -> Resolver {
+    let mut catalog: Catalog = Catalog::new();
+    catalog.insert(Zone::parse(EXAMPLE, &name("example")).unwrap());
+    catalog.insert(Zone::parse(OTHER, &name("other.test")).unwrap());
+    catalog.insert(Zone::parse(REVERSE, &name("2.0.192.in-addr.arpa")).unwrap());
+    let server: Arc<Server> = Arc::new(Server::new(catalog));
+    let socket: UdpSocket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr: SocketAddr = socket.local_addr().unwrap();
+    thread::spawn(move || server.serve_udp(&socket));
+    Resolver::new(addr)
+}
+
+fn srv(priority: u16, weight: u16, target: &str) -> Srv {
+    Srv {
+        priority,
+        weight,
+        port: 443,
+        target: name(target),
+    }
+}
+
+#[test]
+fn addresses_and_aliases() {
+    let resolver: Resolver = resolver();
+    assert_eq!(
+        resolver.lookup_ip(&name("www.example")).unwrap(),
+        [ip("192.0.2.1"), ip("2001:db8::1")]
+    );
+    // the alias leads into another zone, asked for separately
+    assert_eq!(
+        resolver.lookup_ip(&name("alias.example")).unwrap(),
+        [ip("198.51.100.1")]
+    );
+    assert!(matches!(
+        resolver.lookup_ip(&name("nowhere.example")),
+        Err(Error::Lookup {
+            r_code: ResponseCode::NAMEERROR,
+            ..
+        })
+    ));
+    assert_eq!(
+        resolver.reverse_lookup(ip("192.0.2.1")).unwrap(),
+        [name("www.example")]
+    );
+}
+
+#[test]
+fn mail_services_and_text() {
+    let resolver: Resolver = resolver();
+    assert_eq!(
+        resolver.mx_lookup(&name("example")).unwrap(),
+        [
+            Mx {
+                preference: 10,
+                exchange: name("mx1.example")
+            },
+            Mx {
+                preference: 20,
+                exchange: name("mx2.example")
+            }
+        ]
+    );
+    assert_eq!(
+        resolver.srv_lookup(&name("_imaps._tcp.example")).unwrap(),
+        [Srv {
+            priority: 0,
+            weight: 1,
+            port: 993,
+            target: name("mail.example")
+        }]
+    );
+    assert!(resolver
+        .srv_lookup(&name("_none._tcp.example"))
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        resolver.txt_lookup(&name("example")).unwrap(),
+        [b"v=spf1 -all".to_vec()]
+    );
+    // no records of the type is no error
+    assert!(resolver
+        .txt_lookup(&name("www.example"))
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn services_are_ordered_by_priority_and_weight() {
+    let servers: Vec<Srv> = vec![
+        srv(20, 0, "backup.example"),
+        srv(10, 60, "big.example"),
+        srv(10, 0, "spare.example"),
+        srv(10, 40, "small.example"),
+    ];
+    let targets = |picks: &[u32]| -> Vec<Name> {
+        let mut picks = picks.iter();
+        srv_order(servers.clone(), |total| {
+            let picked: u32 = *picks.next().unwrap();
+            assert!(picked <= total);
+            picked
+        })
+        .into_iter()
+        .map(|srv| srv.target)
+        .collect()
+    };
+    // 0 picks the weightless server, which is otherwise left for last
+    assert_eq!(
+        targets(&[0, 0, 0, 0]),
+        [
+            name("spare.example"),
+            name("big.example"),
+            name("small.example"),
+            name("backup.example")
+        ]
+    );
+    assert_eq!(
+        targets(&[61, 1, 0, 0]),
+        [
+            name("small.example"),
+            name("big.example"),
+            name("spare.example"),
+            name("backup.example")
+        ]
+    );
+}
+
+#[test]
+fn srv_records_round_trip() {
+    let zone: Zone = Zone::parse(EXAMPLE, &name("example")).unwrap();
+    let mut packet: DnsPacket = DnsPacket::new();
+    packet.answers = zone
+        .records()
+        .filter(|record| record.r_type == QueryType::SRV)
+        .cloned()
+        .collect();
+    assert_eq!(
+        format!("{}", packet.answers[0].r_data),
+        "0 1 993 mail.example."
+    );
+    let decoded: DnsPacket = decode(&encode(&mut packet));
+    assert_eq!(decoded.answers, packet.answers);
+    assert!(matches!(
+        decoded.answers[1].r_data,
+        RecordData::SRV { port: 0, .. }
+    ));
+}
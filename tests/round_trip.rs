@@ -0,0 +1,369 @@
+use lib::dns::{
+    DnsClass, DnsHeader, DnsPacket, DnsQuestion, DnsRecord, Edns, EdnsOption, OpCode, QueryType,
+    RecordData, ResponseCode,
+};
+use lib::name::Name;
+use lib::parser::DnsBytePacketBuffer;
+use lib::zone::parse_records;
+use proptest::collection::{btree_set, vec};
+use proptest::prelude::*;
+use proptest::sample::select;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+fn name() -> impl Strategy<Value = Name> {
+    vec(vec(any::<u8>(), 1..=12), 0..=4).prop_map(|labels| Name::from_labels(labels).unwrap())
+}
+
+fn character_string() -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u8>(), 0..=16)
+}
+
+fn ipv4() -> impl Strategy<Value = Ipv4Addr> {
+    any::<[u8; 4]>().prop_map(Ipv4Addr::from)
+}
+
+fn bytes(max: usize) -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u8>(), 0..=max)
+}
+
+/// distinct types in ascending order as decoded from a type bitmap
+fn types() -> impl Strategy<Value = Vec<QueryType>> {
+    btree_set(any::<u16>(), 0..=8)
+        .prop_map(|types| types.into_iter().map(QueryType::from).collect())
+}
+
+fn response_code() -> impl Strategy<Value = ResponseCode> {
+    select(vec![
+        ResponseCode::NOERROR,
+        ResponseCode::FORMATERROR,
+        ResponseCode::SERVERFAILURE,
+        ResponseCode::NAMEERROR,
+        ResponseCode::NOTIMPLEMENTED,
+        ResponseCode::REFUSED,
+    ])
+}
+
+fn class() -> impl Strategy<Value = DnsClass> {
+    any::<u16>().prop_map(DnsClass::from)
+}
+
+fn header() -> impl Strategy<Value = DnsHeader> {
+    (
+        any::<u16>(),
+        any::<bool>(),
+        (0u8..16).prop_map(OpCode::from),
+        any::<bool>(),
+        any::<bool>(),
+        any::<bool>(),
+        any::<bool>(),
+        any::<bool>(),
+        any::<(bool, bool)>(),
+        response_code(),
+    )
+        .prop_map(
+            |(id, qr, op_code, aa, tc, rd, ra, z, (ad, cd), r_code)| DnsHeader {
+                id,
+                qr,
+                op_code,
+                aa,
+                tc,
+                rd,
+                ra,
+                z,
+                ad,
+                cd,
+                r_code,
+                ..DnsHeader::new()
+            },
+        )
+}
+
+fn question() -> impl Strategy<Value = DnsQuestion> {
+    (name(), any::<u16>(), class()).prop_map(|(label, q_type, q_class)| DnsQuestion {
+        label,
+        q_type: q_type.into(),
+        q_class,
+    })
+}
+
+fn record_data() -> impl Strategy<Value = RecordData> {
+    prop_oneof![
+        ipv4().prop_map(RecordData::IPADDR),
+        name().prop_map(RecordData::NS),
+        name().prop_map(RecordData::MD),
+        name().prop_map(RecordData::MF),
+        name().prop_map(RecordData::CNAME),
+        (name(), name(), any::<[u32; 5]>()).prop_map(|(mname, rname, times)| {
+            RecordData::SOA {
+                mname,
+                rname,
+                serial: times[0],
+                refresh: times[1],
+                retry: times[2],
+                expire: times[3],
+                minimum: times[4],
+            }
+        }),
+        name().prop_map(RecordData::MB),
+        name().prop_map(RecordData::MG),
+        name().prop_map(RecordData::MR),
+        vec(any::<u8>(), 0..=32).prop_map(RecordData::NULL),
+        (ipv4(), any::<u8>(), vec(any::<u8>(), 0..=16)).prop_map(|(address, protocol, bitmap)| {
+            RecordData::WKS {
+                address,
+                protocol,
+                bitmap,
+            }
+        }),
+        name().prop_map(RecordData::PTR),
+        (character_string(), character_string())
+            .prop_map(|(cpu, os)| RecordData::HINFO { cpu, os }),
+        (name(), name()).prop_map(|(rmailbx, emailbx)| RecordData::MINFO { rmailbx, emailbx }),
+        (any::<u16>(), name()).prop_map(|(preference, exchange)| RecordData::MX {
+            preference,
+            exchange
+        }),
+        vec(character_string(), 0..=4).prop_map(RecordData::TXT),
+        any::<[u8; 16]>().prop_map(|octets| RecordData::AAAA(Ipv6Addr::from(octets))),
+        (any::<u16>(), any::<u8>(), any::<u8>(), bytes(32)).prop_map(
+            |(key_tag, algorithm, digest_type, digest)| RecordData::DS {
+                key_tag,
+                algorithm,
+                digest_type,
+                digest,
+            }
+        ),
+        (
+            any::<u16>(),
+            any::<(u8, u8, u32, u32, u32, u16)>(),
+            name(),
+            bytes(64)
+        )
+            .prop_map(|(type_covered, fields, signer_name, signature)| {
+                let (algorithm, labels, original_ttl, expiration, inception, key_tag) = fields;
+                RecordData::RRSIG {
+                    type_covered: type_covered.into(),
+                    algorithm,
+                    labels,
+                    original_ttl,
+                    expiration,
+                    inception,
+                    key_tag,
+                    signer_name,
+                    signature,
+                }
+            }),
+        (name(), types()).prop_map(|(next_domain, types)| RecordData::NSEC { next_domain, types }),
+        (any::<u16>(), any::<u8>(), any::<u8>(), bytes(64)).prop_map(
+            |(flags, protocol, algorithm, public_key)| RecordData::DNSKEY {
+                flags,
+                protocol,
+                algorithm,
+                public_key,
+            }
+        ),
+        (any::<(u8, u8, u16)>(), bytes(16), bytes(20), types()).prop_map(
+            |((hash_algorithm, flags, iterations), salt, next_hashed, types)| {
+                RecordData::NSEC3 {
+                    hash_algorithm,
+                    flags,
+                    iterations,
+                    salt,
+                    next_hashed,
+                    types,
+                }
+            }
+        ),
+        (any::<(u8, u8, u16)>(), bytes(16)).prop_map(
+            |((hash_algorithm, flags, iterations), salt)| RecordData::NSEC3PARAM {
+                hash_algorithm,
+                flags,
+                iterations,
+                salt,
+            }
+        ),
+        tsig_data(),
+        (
+            any::<u16>().prop_filter("type must be unknown", |r_type| matches!(
+                QueryType::from(*r_type),
+                QueryType::UNKNOWN(_)
+            )),
+            vec(any::<u8>(), 0..=32)
+        )
+            .prop_map(|(r_type, data)| RecordData::UNKNOWN(r_type, data)),
+    ]
+}
+
+fn tsig_data() -> impl Strategy<Value = RecordData> {
+    (
+        name(),
+        0u64..1 << 48,
+        any::<(u16, u16, u16)>(),
+        bytes(64),
+        bytes(6),
+    )
+        .prop_map(
+            |(algorithm, time_signed, (fudge, original_id, error), mac, other)| RecordData::TSIG {
+                algorithm,
+                time_signed,
+                fudge,
+                mac,
+                original_id,
+                error,
+                other,
+            },
+        )
+}
+
+fn record() -> impl Strategy<Value = DnsRecord> {
+    (name(), class(), any::<u32>(), record_data()).prop_map(|(label, r_class, ttl, r_data)| {
+        DnsRecord {
+            r_class,
+            ..DnsRecord::new(label, ttl, r_data)
+        }
+    })
+}
+
+fn edns() -> impl Strategy<Value = Edns> {
+    (
+        any::<(u16, u8, u8, bool)>(),
+        vec((any::<u16>(), bytes(8)), 0..=2),
+    )
+        .prop_map(
+            |((udp_payload_size, extended_rcode, version, dnssec_ok), options)| Edns {
+                udp_payload_size,
+                extended_rcode,
+                version,
+                dnssec_ok,
+                options: options
+                    .into_iter()
+                    .map(|(code, data)| EdnsOption { code, data })
+                    .collect(),
+            },
+        )
+}
+
+fn packet() -> impl Strategy<Value = DnsPacket> {
+    (
+        header(),
+        vec(question(), 0..=2),
+        vec(record(), 0..=3),
+        vec(record(), 0..=2),
+        // a trailing TSIG record is read into `tsig`
+        vec(record(), 0..=2).prop_filter("TSIG must come last", |records| {
+            records
+                .iter()
+                .all(|record| record.r_type != QueryType::TSIG)
+        }),
+        proptest::option::of(edns()),
+        proptest::option::of((name(), tsig_data())),
+    )
+        .prop_map(
+            |(header, questions, answers, authorities, additionals, edns, tsig)| DnsPacket {
+                header,
+                questions,
+                answers,
+                authorities,
+                additionals,
+                edns,
+                tsig: tsig.map(|(key, r_data)| DnsRecord {
+                    r_class: DnsClass::ANY,
+                    ..DnsRecord::new(key, 0, r_data)
+                }),
+            },
+        )
+}
+
+/// write the packet, parse the bytes back and return what was parsed
+fn round_trip(packet: &mut DnsPacket) -> Option<DnsPacket> {
+    let mut buffer = DnsBytePacketBuffer::new();
+    // generated packets are not guaranteed to fit in 512 bytes
+    packet.write(&mut buffer).ok()?;
+    let bytes: Vec<u8> = buffer.get_buf_range(0..buffer.get_pos()).unwrap().to_vec();
+    Some(DnsPacket::from_buffer(&mut DnsBytePacketBuffer::from_bytes(&bytes)).unwrap())
+}
+
+proptest! {
+    #[test]
+    fn header_round_trip(header in header()) {
+        let mut packet = DnsPacket { header, ..DnsPacket::new() };
+        let parsed = round_trip(&mut packet).unwrap();
+        prop_assert_eq!(parsed.header, packet.header);
+    }
+
+    #[test]
+    fn question_round_trip(question in question()) {
+        let mut packet = DnsPacket::new();
+        packet.questions.push(question);
+        let parsed = round_trip(&mut packet).unwrap();
+        prop_assert_eq!(&parsed, &packet);
+        // names compare case-insensitively, the labels themselves must be untouched
+        prop_assert!(parsed.questions[0].label.labels().eq(packet.questions[0].label.labels()));
+    }
+
+    #[test]
+    fn record_round_trip(record in record()) {
+        let mut packet = DnsPacket::new();
+        packet.answers.push(record);
+        let parsed = round_trip(&mut packet);
+        prop_assume!(parsed.is_some());
+        prop_assert_eq!(parsed.unwrap(), packet);
+    }
+
+    #[test]
+    fn packet_round_trip(mut packet in packet()) {
+        let parsed = round_trip(&mut packet);
+        prop_assume!(parsed.is_some());
+        prop_assert_eq!(parsed.unwrap(), packet);
+    }
+
+    #[test]
+    fn presentation_round_trip(record in record()) {
+        // neither trailing zero octets of a WKS bitmap nor an empty NSEC3
+        // hash survive the text form
+        prop_assume!(match &record.r_data {
+            RecordData::WKS { bitmap, .. } => bitmap.last() != Some(&0),
+            RecordData::NSEC3 { next_hashed, .. } => !next_hashed.is_empty(),
+            _ => true,
+        });
+        let text: String = record.to_string();
+        let parsed: Vec<DnsRecord> = parse_records(&text, &Name::root()).unwrap();
+        prop_assert_eq!(parsed, vec![record]);
+    }
+}
+
+#[test]
+fn compressed_records_equal_their_uncompressed_form() {
+    #[rustfmt::skip]
+    let bytes: Vec<u8> = vec![
+        0x12, 0x34, 0x81, 0x80, 0, 1, 0, 2, 0, 0, 0, 0,
+        // example. MX IN at offset 12
+        7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0, 0, 15, 0, 1,
+        // example. MX 10 mail.example.
+        0xC0, 12, 0, 15, 0, 1, 0, 0, 0x0E, 0x10, 0, 9,
+        0, 10, 4, b'm', b'a', b'i', b'l', 0xC0, 12,
+        // example. NS mail.example.
+        0xC0, 12, 0, 2, 0, 1, 0, 0, 0x0E, 0x10, 0, 2,
+        0xC0, 39,
+    ];
+    let packet: DnsPacket =
+        DnsPacket::from_buffer(&mut DnsBytePacketBuffer::from_bytes(&bytes)).unwrap();
+    let example: Name = "example".parse().unwrap();
+    let mail: Name = "mail.example".parse().unwrap();
+    let expected: Vec<DnsRecord> = vec![
+        DnsRecord::new(
+            example.clone(),
+            3600,
+            RecordData::MX {
+                preference: 10,
+                exchange: mail.clone(),
+            },
+        ),
+        DnsRecord::new(example, 3600, RecordData::NS(mail)),
+    ];
+    // 9 and 2 octets on the wire against 16 and 14 uncompressed
+    assert_eq!(packet.answers[0].rd_len, 9);
+    assert_eq!(packet.answers[1].rd_len, 2);
+    assert_eq!(expected[0].rd_len, 16);
+    assert_eq!(packet.answers, expected);
+}
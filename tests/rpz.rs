@@ -0,0 +1,203 @@
+mod common;
+
+use common::{name, request};
+use lib::config::Config;
+use lib::dns::{DnsPacket, QueryType, RecordData, ResponseCode};
+use lib::parser::DnsBytePacketBuffer;
+use lib::rpz::{address_name, ResponsePolicy};
+use lib::server::Server;
+use lib::zone::{Catalog, Zone};
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::Path;
+
+const ZONE: &str = "\
+$TTL 60
+@ SOA ns hostmaster 1 2 3 4 5
+@ NS ns
+ns A 192.0.2.53
+ns2 A 192.0.2.54
+www A 192.0.2.1
+mail A 192.0.2.2
+shop A 192.0.2.3
+sub NS ns.evil.example.net.
+";
+
+const POLICY: &str = "\
+$TTL 60
+@ SOA localhost. hostmaster 1 2 3 4 5
+@ NS localhost.
+bad.example CNAME .
+*.bad.example CNAME .
+ok.bad.example CNAME rpz-passthru.
+nodata.example CNAME *.
+drop.example CNAME rpz-drop.
+garden.example A 192.0.2.99
+garden.example TXT \"blocked by policy\"
+*.walled.example CNAME *.garden.example.net.
+mail.example A 192.0.2.77
+shop.example CNAME rpz-passthru.
+32.3.2.0.192.rpz-ip CNAME .
+32.53.2.0.192.rpz-ip CNAME .
+30.52.2.0.192.rpz-ip CNAME *.
+24.0.100.51.198.rpz-client-ip CNAME rpz-drop.
+48.zz.db8.2001.rpz-client-ip CNAME .
+ns.evil.example.net.rpz-nsdname CNAME .
+";
+
+const SECOND: &str = "\
+$TTL 60
+@ SOA localhost. hostmaster 1 2 3 4 5
+@ NS localhost.
+mail.example CNAME .
+www.example CNAME .
+";
+
+fn policy_server(policy: &[&str]) -> Server {
+    let mut catalog: Catalog = Catalog::new();
+    catalog.insert(Zone::parse(ZONE, &name("example")).unwrap());
+    catalog.insert(Zone::parse(POLICY, &name("rpz.example")).unwrap());
+    catalog.insert(Zone::parse(SECOND, &name("rpz2.example")).unwrap());
+    let mut server: Server = Server::new(catalog);
+    server.set_response_policy(ResponsePolicy::new(
+        policy.iter().map(|origin| name(origin)).collect(),
+    ));
+    server
+}
+
+fn r_code(server: &Server, host: &str) -> ResponseCode {
+    server.handle(&request(host, QueryType::A)).header.r_code
+}
+
+#[test]
+fn address_rules_name_prefixes() {
+    let v4: IpAddr = "192.0.2.77".parse().unwrap();
+    assert_eq!(address_name(v4, 32), name("32.77.2.0.192"));
+    assert_eq!(address_name(v4, 24), name("24.0.2.0.192"));
+    let v6: IpAddr = "2001:db8:0:1::5".parse().unwrap();
+    assert_eq!(address_name(v6, 128), name("128.5.zz.1.0.db8.2001"));
+    assert_eq!(address_name(v6, 48), name("48.zz.db8.2001"));
+}
+
+#[test]
+fn name_rules_rewrite_answers() {
+    let server: Server = policy_server(&["rpz.example"]);
+
+    // no rule, the zone answers
+    assert_eq!(
+        server.handle(&request("www.example", QueryType::A)).answers[0].r_data,
+        RecordData::IPADDR(Ipv4Addr::new(192, 0, 2, 1))
+    );
+
+    let nxdomain: DnsPacket = server.handle(&request("bad.example", QueryType::A));
+    assert_eq!(nxdomain.header.r_code, ResponseCode::NAMEERROR);
+    assert_eq!(nxdomain.authorities[0].r_type, QueryType::SOA);
+    assert_eq!(nxdomain.authorities[0].label, name("rpz.example"));
+    assert_eq!(
+        r_code(&server, "deep.x.bad.example"),
+        ResponseCode::NAMEERROR
+    );
+    // passed through to the zone, which has no such name either
+    let passed: DnsPacket = server.handle(&request("ok.bad.example", QueryType::A));
+    assert_eq!(passed.header.r_code, ResponseCode::NAMEERROR);
+    assert_eq!(passed.authorities[0].label, name("example"));
+
+    let nodata: DnsPacket = server.handle(&request("nodata.example", QueryType::A));
+    assert_eq!(nodata.header.r_code, ResponseCode::NOERROR);
+    assert!(nodata.answers.is_empty());
+
+    // local data answers for the types it has and nothing else
+    let garden: DnsPacket = server.handle(&request("garden.example", QueryType::A));
+    assert_eq!(garden.answers.len(), 1);
+    assert_eq!(garden.answers[0].label, name("garden.example"));
+    assert_eq!(
+        garden.answers[0].r_data,
+        RecordData::IPADDR(Ipv4Addr::new(192, 0, 2, 99))
+    );
+    let garden: DnsPacket = server.handle(&request("garden.example", QueryType::MX));
+    assert!(garden.answers.is_empty());
+
+    let walled: DnsPacket = server.handle(&request("a.walled.example", QueryType::A));
+    assert_eq!(walled.answers[0].label, name("a.walled.example"));
+    assert_eq!(
+        walled.answers[0].r_data,
+        RecordData::CNAME(name("a.walled.example.garden.example.net"))
+    );
+
+    // dropped queries get no answer on the wire
+    let mut buffer: DnsBytePacketBuffer = DnsBytePacketBuffer::new();
+    request("drop.example", QueryType::A)
+        .write(&mut buffer)
+        .unwrap();
+    let client: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    assert!(server
+        .handle_bytes(buffer.written(), client, client, false)
+        .is_none());
+    assert_eq!(r_code(&server, "drop.example"), ResponseCode::REFUSED);
+}
+
+#[test]
+fn address_and_name_server_rules() {
+    let server: Server = policy_server(&["rpz.example"]);
+
+    // the longest prefix covering an answer decides
+    assert_eq!(r_code(&server, "ns.example"), ResponseCode::NAMEERROR);
+    let ns2: DnsPacket = server.handle(&request("ns2.example", QueryType::A));
+    assert_eq!(ns2.header.r_code, ResponseCode::NOERROR);
+    assert!(ns2.answers.is_empty());
+    // a name rule comes before an address rule of the same zone
+    assert_eq!(
+        server
+            .handle(&request("shop.example", QueryType::A))
+            .answers[0]
+            .r_data,
+        RecordData::IPADDR(Ipv4Addr::new(192, 0, 2, 3))
+    );
+
+    // names below a delegation to a blocked name server
+    assert_eq!(r_code(&server, "host.sub.example"), ResponseCode::NAMEERROR);
+
+    let from = |client: &str| {
+        server.handle_from(
+            &request("www.example", QueryType::A),
+            Some(client.parse().unwrap()),
+            None,
+        )
+    };
+    assert!(from("198.51.100.7").is_none());
+    assert!(from("::ffff:198.51.100.7").is_none());
+    assert_eq!(
+        from("2001:db8::1").unwrap().header.r_code,
+        ResponseCode::NAMEERROR
+    );
+    assert_eq!(
+        from("198.51.101.7").unwrap().header.r_code,
+        ResponseCode::NOERROR
+    );
+}
+
+#[test]
+fn earlier_policy_zones_win() {
+    let server: Server = policy_server(&["rpz.example", "rpz2.example"]);
+    assert_eq!(
+        server
+            .handle(&request("mail.example", QueryType::A))
+            .answers[0]
+            .r_data,
+        RecordData::IPADDR(Ipv4Addr::new(192, 0, 2, 77))
+    );
+    assert_eq!(r_code(&server, "www.example"), ResponseCode::NAMEERROR);
+    let server: Server = policy_server(&["rpz2.example", "rpz.example"]);
+    assert_eq!(r_code(&server, "mail.example"), ResponseCode::NAMEERROR);
+}
+
+#[test]
+fn configs_name_policy_zones() {
+    let config: Config = Config::parse(
+        "zone rpz.example. rpz.zone primary=127.0.0.1:5300\nresponse-policy rpz.example.\n",
+        Path::new(""),
+    )
+    .unwrap();
+    assert_eq!(config.response_policy, vec![name("rpz.example")]);
+    assert!(Config::parse("response-policy rpz.example.\n", Path::new("")).is_err());
+    assert!(Config::parse("response-policy\n", Path::new("")).is_err());
+}
@@ -0,0 +1,198 @@
+mod common;
+
+use common::{decode, encode, ip, name, request};
+use lib::config::Config;
+use lib::dns::{DnsPacket, QueryType, ResponseCode};
+use lib::rrl::{RateDecision, RateLimit, RateLimiter, MAX_BUCKETS};
+use lib::server::Server;
+use lib::zone::{Catalog, Zone};
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const ZONE: &str = "$TTL 60\n@ SOA ns hostmaster 1 2 3 4 5\n@ NS ns\nns A 192.0.2.53\n";
+
+fn server() -> Server {
+    let mut catalog: Catalog = Catalog::new();
+    catalog.insert(Zone::parse(ZONE, &name("example")).unwrap());
+    Server::new(catalog)
+}
+
+fn limiter(responses_per_second: u32, slip: u32) -> RateLimiter {
+    RateLimiter::new(RateLimit {
+        responses_per_second,
+        slip,
+        exempt: "192.0.2.1".parse().unwrap(),
+        ..RateLimit::default()
+    })
+}
+
+/// decisions on the answers to `count` queries for `host` at `now`
+fn decisions(
+    limiter: &RateLimiter,
+    server: &Server,
+    client: &str,
+    host: &str,
+    count: usize,
+    now: Instant,
+) -> Vec<RateDecision> {
+    let response: DnsPacket = server.handle(&request(host, QueryType::A));
+    (0..count)
+        .map(|_| limiter.check(ip(client), None, &response, now))
+        .collect()
+}
+
+#[test]
+fn buckets_refill_over_time() {
+    let server: Server = server();
+    let limiter: RateLimiter = limiter(2, 0);
+    let now: Instant = Instant::now();
+    assert_eq!(
+        decisions(&limiter, &server, "198.51.100.7", "ns.example", 3, now),
+        [RateDecision::SEND, RateDecision::SEND, RateDecision::DROP]
+    );
+    // the same /24 shares the bucket, another network does not
+    assert_eq!(
+        decisions(&limiter, &server, "198.51.100.8", "ns.example", 1, now),
+        [RateDecision::DROP]
+    );
+    assert_eq!(
+        decisions(&limiter, &server, "198.51.101.8", "ns.example", 1, now),
+        [RateDecision::SEND]
+    );
+    // half a second brings one token back
+    let later: Instant = now + Duration::from_millis(500);
+    assert_eq!(
+        decisions(&limiter, &server, "198.51.100.7", "ns.example", 2, later),
+        [RateDecision::SEND, RateDecision::DROP]
+    );
+    // exempt clients are never limited
+    assert!(
+        decisions(&limiter, &server, "192.0.2.1", "ns.example", 10, now)
+            .iter()
+            .all(|decision| *decision == RateDecision::SEND)
+    );
+}
+
+#[test]
+fn responses_are_counted_by_kind() {
+    let server: Server = server();
+    let limiter: RateLimiter = limiter(1, 2);
+    let now: Instant = Instant::now();
+    assert_eq!(
+        decisions(&limiter, &server, "198.51.100.7", "ns.example", 1, now),
+        [RateDecision::SEND]
+    );
+    // another answer has a bucket of its own
+    assert_eq!(
+        decisions(&limiter, &server, "198.51.100.7", "example", 1, now),
+        [RateDecision::SEND]
+    );
+    // random names below a zone share the NXDOMAIN bucket of the zone
+    assert_eq!(
+        decisions(&limiter, &server, "198.51.100.7", "a1.example", 1, now),
+        [RateDecision::SEND]
+    );
+    assert_eq!(
+        decisions(&limiter, &server, "198.51.100.7", "b2.example", 4, now),
+        [
+            RateDecision::DROP,
+            RateDecision::SLIP,
+            RateDecision::DROP,
+            RateDecision::SLIP
+        ]
+    );
+}
+
+#[test]
+fn buckets_are_capped_and_swept() {
+    let server: Server = server();
+    let limiter: RateLimiter = limiter(1, 0);
+    let response: DnsPacket = server.handle(&request("ns.example", QueryType::A));
+    let now: Instant = Instant::now();
+    let client = |n: usize| IpAddr::V4(Ipv4Addr::from(0x2000_0000 + ((n as u32) << 8)));
+    for n in 0..MAX_BUCKETS {
+        assert_eq!(
+            limiter.check(client(n), None, &response, now),
+            RateDecision::SEND
+        );
+    }
+    // a network needing one more bucket is limited, those with one are not
+    let later: Instant = now + Duration::from_secs(1);
+    assert_eq!(
+        limiter.check(client(MAX_BUCKETS), None, &response, later),
+        RateDecision::DROP
+    );
+    assert_eq!(
+        limiter.check(client(0), None, &response, later),
+        RateDecision::SEND
+    );
+    // buckets left full are forgotten a few seconds on, making room
+    let swept: Instant = now + Duration::from_secs(10);
+    assert_eq!(
+        limiter.check(client(MAX_BUCKETS), None, &response, swept),
+        RateDecision::SEND
+    );
+}
+
+#[test]
+fn limited_udp_answers_slip_truncated() {
+    let mut server: Server = server();
+    server.set_rate_limit(RateLimit {
+        responses_per_second: 1,
+        slip: 2,
+        ..RateLimit::default()
+    });
+    let message: Vec<u8> = encode(&mut request("ns.example", QueryType::A));
+    let client: IpAddr = ip("198.51.100.7");
+    let local: IpAddr = ip("127.0.0.1");
+
+    let answer: DnsPacket = decode(&server.handle_bytes(&message, client, local, false).unwrap());
+    assert_eq!(answer.answers.len(), 1);
+    assert!(server
+        .handle_bytes(&message, client, local, false)
+        .is_none());
+    let slipped: DnsPacket = decode(&server.handle_bytes(&message, client, local, false).unwrap());
+    assert!(slipped.header.tc);
+    assert!(slipped.answers.is_empty());
+    assert_eq!(slipped.questions.len(), 1);
+
+    // TCP is never limited
+    let answer: DnsPacket = decode(&server.handle_bytes(&message, client, local, true).unwrap());
+    assert_eq!(answer.header.r_code, ResponseCode::NOERROR);
+    assert_eq!(answer.answers.len(), 1);
+}
+
+#[test]
+fn configs_set_rate_limits() {
+    let config: Config = Config::parse(
+        "rate-limit responses-per-second=10 slip=0 ipv6-prefix=48 exempt=10.0.0.0/8\n",
+        Path::new(""),
+    )
+    .unwrap();
+    let limit: RateLimit = config.rate_limit.unwrap();
+    assert_eq!(limit.responses_per_second, 10);
+    assert_eq!(limit.slip, 0);
+    assert_eq!(limit.ipv4_prefix, 24);
+    assert_eq!(limit.ipv6_prefix, 48);
+    assert!(limit.exempt.allows(ip("10.1.2.3"), None));
+
+    assert!(Config::parse("", Path::new(""))
+        .unwrap()
+        .rate_limit
+        .is_none());
+    assert_eq!(
+        Config::parse("rate-limit\n", Path::new(""))
+            .unwrap()
+            .rate_limit,
+        Some(RateLimit::default())
+    );
+    for bad in [
+        "rate-limit responses-per-second=0\n",
+        "rate-limit ipv4-prefix=33\n",
+        "rate-limit slip=-1\n",
+        "rate-limit burst=3\n",
+    ] {
+        assert!(Config::parse(bad, Path::new("")).is_err(), "{}", bad);
+    }
+}
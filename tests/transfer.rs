@@ -0,0 +1,164 @@
+mod common;
+
+use common::{decode, encode, key, name, request, LOCALHOST};
+use lib::acl::Acl;
+use lib::client::Client;
+use lib::config::{Config, ZoneConfig};
+use lib::dns::{DnsPacket, QueryType, ResponseCode};
+use lib::server::{fetch_zone, Server};
+use lib::tsig::{KeyRing, TsigKey, HMAC_SHA256};
+use lib::zone::{Catalog, Zone};
+use lib::Error;
+use std::fmt::Write;
+use std::net::{SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+
+/// the example zone with `hosts` A records, enough to need several messages
+fn zone(serial: u32, hosts: usize) -> Zone {
+    let mut text: String = format!("$TTL 60\n@ SOA ns hostmaster {} 2 3 4 5\n@ NS ns\n", serial);
+    for host in 0..hosts {
+        writeln!(text, "host{} A 192.0.2.{}", host, host % 256).unwrap();
+    }
+    Zone::parse(&text, &name("example")).unwrap()
+}
+
+fn server(acl: &str) -> Server {
+    let mut catalog: Catalog = Catalog::new();
+    catalog.insert(zone(1, 3000));
+    let mut server: Server = Server::new(catalog);
+    let mut keyring: KeyRing = KeyRing::new();
+    keyring.insert(key("transfer.example"));
+    server.set_keyring(keyring);
+    server.set_transfer_acl(name("example"), acl.parse().unwrap());
+    server
+}
+
+/// serve `server` over TCP on a free local port
+fn listen(server: Server) -> SocketAddr {
+    let listener: TcpListener = TcpListener::bind((LOCALHOST, 0)).unwrap();
+    let addr: SocketAddr = listener.local_addr().unwrap();
+    let server: Arc<Server> = Arc::new(server);
+    thread::spawn(move || server.serve_tcp(&listener));
+    addr
+}
+
+fn axfr_request() -> Vec<u8> {
+    encode(&mut request("example", QueryType::AXFR))
+}
+
+fn r_code(message: &[u8]) -> ResponseCode {
+    decode(message).header.r_code
+}
+
+#[test]
+fn zones_stream_in_several_messages() {
+    let server: Server = server("127.0.0.0/8");
+    let messages: Vec<Vec<u8>> = server.handle_tcp(&axfr_request(), LOCALHOST, LOCALHOST);
+    assert!(messages.len() > 1);
+    let packets: Vec<DnsPacket> = messages.iter().map(|message| decode(message)).collect();
+    let first: &DnsPacket = &packets[0];
+    let last: &DnsPacket = packets.last().unwrap();
+    assert_eq!(first.questions.len(), 1);
+    assert_eq!(first.answers[0].r_type, QueryType::SOA);
+    assert_eq!(last.answers.last(), Some(&first.answers[0]));
+    let records: usize = packets.iter().map(|packet| packet.answers.len()).sum();
+    assert_eq!(records, 3000 + 3);
+
+    // only over TCP
+    let udp: Vec<u8> = server
+        .handle_bytes(&axfr_request(), LOCALHOST, LOCALHOST, false)
+        .unwrap();
+    assert_eq!(r_code(&udp), ResponseCode::NOTIMPLEMENTED);
+}
+
+#[test]
+fn transfers_are_restricted() {
+    let refused: Vec<Vec<u8>> =
+        server("192.0.2.0/24").handle_tcp(&axfr_request(), LOCALHOST, LOCALHOST);
+    assert_eq!(refused.len(), 1);
+    assert_eq!(r_code(&refused[0]), ResponseCode::REFUSED);
+
+    let nobody: Vec<Vec<u8>> = server("none").handle_tcp(&axfr_request(), LOCALHOST, LOCALHOST);
+    assert_eq!(r_code(&nobody[0]), ResponseCode::REFUSED);
+
+    let acl: Acl = "10.0.0.0/8,2001:db8::/32,key:transfer.example"
+        .parse()
+        .unwrap();
+    assert!(acl.allows("10.1.2.3".parse().unwrap(), None));
+    assert!(acl.allows("::ffff:10.1.2.3".parse().unwrap(), None));
+    assert!(acl.allows("2001:db8::1".parse().unwrap(), None));
+    assert!(!acl.allows("11.0.0.1".parse().unwrap(), None));
+    assert!(acl.allows("11.0.0.1".parse().unwrap(), Some(&name("transfer.example"))));
+    assert!("10.0.0.0/33".parse::<Acl>().is_err());
+}
+
+#[test]
+fn secondaries_transfer_over_tcp() {
+    let addr: SocketAddr = listen(server("127.0.0.1"));
+    let transferred: Zone = Client::new(addr).axfr(&name("example")).unwrap();
+    assert_eq!(transferred.serial(), Some(1));
+    assert_eq!(transferred.records().count(), 3000 + 2);
+
+    let mut catalog: Catalog = Catalog::new();
+    catalog.insert(zone(0, 1));
+    let secondary: Server = Server::new(catalog);
+    assert_eq!(
+        secondary.replace_zone(transferred).unwrap().serial(),
+        Some(0)
+    );
+    let catalog = secondary.catalog().read().unwrap();
+    assert_eq!(catalog.get(&name("example")).unwrap().serial(), Some(1));
+}
+
+#[test]
+fn signed_transfers_need_the_key() {
+    let addr: SocketAddr = listen(server("key:transfer.example"));
+    let mut client: Client = Client::new(addr);
+    assert!(matches!(
+        client.axfr(&name("example")),
+        Err(Error::Transfer(_))
+    ));
+    client.set_tsig(Some(key("transfer.example")));
+    assert_eq!(
+        client.axfr(&name("example")).unwrap().records().count(),
+        3000 + 2
+    );
+
+    let wrong: TsigKey =
+        TsigKey::new(name("transfer.example"), HMAC_SHA256, b"guess".to_vec()).unwrap();
+    client.set_tsig(Some(wrong));
+    assert!(matches!(client.axfr(&name("example")), Err(Error::Tsig(_))));
+}
+
+#[test]
+fn fetched_zones_are_cached_in_the_file() {
+    let addr: SocketAddr = listen(server("any"));
+    let file: PathBuf = std::env::temp_dir().join(format!("axfr-{}.zone", std::process::id()));
+    let config: Config = Config::parse(
+        &format!(
+            "key transfer.example hmac-sha256 MDEyMzQ1Njc4OWFiY2RlZg==\nzone example. {} primary={} transfer-key=transfer.example\n",
+            file.display(),
+            addr
+        ),
+        Path::new(""),
+    )
+    .unwrap();
+    let secondary: &ZoneConfig = &config.zones[0];
+    assert_eq!(secondary.primary, Some(addr));
+    let fetched: Zone = fetch_zone(secondary, &config.keyring, None).unwrap();
+    let cached: Zone = Zone::load(&file.to_string_lossy(), &name("example")).unwrap();
+    std::fs::remove_file(&file).unwrap();
+    std::fs::remove_file(secondary.journal_file()).unwrap();
+    assert_eq!(
+        cached.records().collect::<Vec<_>>(),
+        fetched.records().collect::<Vec<_>>()
+    );
+
+    assert!(Config::parse(
+        "zone example. db primary=192.0.2.1:53 transfer-key=missing\n",
+        Path::new("")
+    )
+    .is_err());
+}
@@ -0,0 +1,153 @@
+mod common;
+
+use common::{decode, encode, name, LOCALHOST, SECRET};
+use lib::config::Config;
+use lib::dns::{DnsPacket, DnsQuestion, QueryType, RecordData, ResponseCode};
+use lib::server::{unix_time, Server};
+use lib::tsig::{KeyRing, TsigKey, BADKEY, BADSIG, BADTIME, HMAC_SHA256, HMAC_SHA512};
+use lib::zone::{Catalog, Zone};
+use lib::Error;
+use std::path::Path;
+
+const NOW: u64 = 1_700_000_000;
+fn key(algorithm: &str) -> TsigKey {
+    TsigKey::new(name("transfer.example"), algorithm, SECRET.to_vec()).unwrap()
+}
+
+fn query() -> DnsPacket {
+    let mut packet: DnsPacket = DnsPacket::new();
+    packet.header.id = 4242;
+    packet
+        .questions
+        .push(DnsQuestion::new(name("example"), QueryType::SOA));
+    packet
+}
+
+fn tsig_error(message: &[u8]) -> u16 {
+    let packet: DnsPacket = decode(message);
+    match packet.tsig.unwrap().r_data {
+        RecordData::TSIG { error, .. } => error,
+        other => panic!("not a TSIG: {:?}", other),
+    }
+}
+
+#[test]
+fn signed_messages_verify() {
+    for algorithm in [HMAC_SHA256, HMAC_SHA512] {
+        let key: TsigKey = key(algorithm);
+        let mut packet: DnsPacket = query();
+        let mac: Vec<u8> = key.sign(&mut packet, NOW, None).unwrap();
+        let message: Vec<u8> = encode(&mut packet);
+        let verified = key.verify(&message, NOW + 10, None).unwrap();
+        assert_eq!(verified.mac, mac);
+        assert_eq!(verified.key, name("transfer.example"));
+
+        // a response covers the request MAC
+        let mut response: DnsPacket = query();
+        response.header.qr = true;
+        key.sign(&mut response, NOW, Some(&mac)).unwrap();
+        let message: Vec<u8> = encode(&mut response);
+        assert!(key.verify(&message, NOW, Some(&mac)).is_ok());
+        assert!(matches!(
+            key.verify(&message, NOW, Some(&[0; 32])),
+            Err(Error::Tsig(BADSIG))
+        ));
+    }
+}
+
+#[test]
+fn bad_signatures_keys_and_times_are_told_apart() {
+    let key: TsigKey = key(HMAC_SHA256);
+    let mut packet: DnsPacket = query();
+    key.sign(&mut packet, NOW, None).unwrap();
+    let message: Vec<u8> = encode(&mut packet);
+
+    let mut tampered: Vec<u8> = message.clone();
+    // the id may change on the way, the flags may not
+    tampered[2] ^= 1;
+    assert!(matches!(
+        key.verify(&tampered, NOW, None),
+        Err(Error::Tsig(BADSIG))
+    ));
+
+    let other: TsigKey = TsigKey::new(name("other"), HMAC_SHA256, b"x".to_vec()).unwrap();
+    assert!(matches!(
+        other.verify(&message, NOW, None),
+        Err(Error::Tsig(BADKEY))
+    ));
+    assert!(matches!(
+        KeyRing::new().verify(&message, NOW),
+        Err(Error::Tsig(BADKEY))
+    ));
+
+    assert!(matches!(
+        key.verify(&message, NOW + 301, None),
+        Err(Error::Tsig(BADTIME))
+    ));
+    assert!(TsigKey::new(name("k"), "hmac-md5", Vec::new()).is_err());
+}
+
+#[test]
+fn server_signs_answers_to_signed_requests() {
+    let zone: Zone =
+        Zone::parse("$TTL 60\n@ SOA ns hostmaster 1 2 3 4 5\n", &name("example")).unwrap();
+    let mut catalog: Catalog = Catalog::new();
+    catalog.insert(zone);
+    let mut server: Server = Server::new(catalog);
+    let mut keyring: KeyRing = KeyRing::new();
+    keyring.insert(key(HMAC_SHA256));
+    server.set_keyring(keyring);
+    let key: TsigKey = key(HMAC_SHA256);
+    let now: u64 = unix_time() as u64;
+
+    let mut request: DnsPacket = query();
+    let mac: Vec<u8> = key.sign(&mut request, now, None).unwrap();
+    let answer: Vec<u8> = server
+        .handle_bytes(&encode(&mut request), LOCALHOST, LOCALHOST, false)
+        .unwrap();
+    assert!(key.verify(&answer, now, Some(&mac)).is_ok());
+
+    // unsigned requests still get unsigned answers
+    let answer: Vec<u8> = server
+        .handle_bytes(&encode(&mut query()), LOCALHOST, LOCALHOST, false)
+        .unwrap();
+    let packet: DnsPacket = decode(&answer);
+    assert!(packet.tsig.is_none());
+    assert_eq!(packet.answers.len(), 1);
+
+    let mut stale: DnsPacket = query();
+    let mac: Vec<u8> = key.sign(&mut stale, now - 3600, None).unwrap();
+    let answer: Vec<u8> = server
+        .handle_bytes(&encode(&mut stale), LOCALHOST, LOCALHOST, false)
+        .unwrap();
+    assert_eq!(tsig_error(&answer), BADTIME);
+    assert!(matches!(
+        key.verify(&answer, now - 3600, Some(&mac)),
+        Err(Error::Tsig(BADTIME))
+    ));
+
+    let stranger: TsigKey = TsigKey::new(name("stranger"), HMAC_SHA256, b"x".to_vec()).unwrap();
+    let mut unknown: DnsPacket = query();
+    stranger.sign(&mut unknown, now, None).unwrap();
+    let answer: Vec<u8> = server
+        .handle_bytes(&encode(&mut unknown), LOCALHOST, LOCALHOST, false)
+        .unwrap();
+    let packet: DnsPacket = decode(&answer);
+    assert_eq!(packet.header.r_code, ResponseCode::NOTAUTH);
+    assert!(packet.answers.is_empty());
+    assert_eq!(tsig_error(&answer), BADKEY);
+}
+
+#[test]
+fn keys_load_from_config() {
+    let config: Config = Config::parse(
+        "key transfer.example hmac-sha256 MDEyMzQ1Njc4OWFiY2RlZg==\n",
+        Path::new(""),
+    )
+    .unwrap();
+    assert_eq!(
+        config.keyring.get(&name("transfer.example")),
+        Some(&key(HMAC_SHA256))
+    );
+    assert!(Config::parse("key k hmac-sha256 not*base64\n", Path::new("")).is_err());
+}
@@ -0,0 +1,218 @@
+mod common;
+
+use common::{decode, encode, key, name, LOCALHOST};
+use lib::client::Client;
+use lib::config::Config;
+use lib::dns::{DnsPacket, DnsRecord, QueryType, RecordData, ResponseCode};
+use lib::journal::Journal;
+use lib::server::{load_zone, Server};
+use lib::update::Update;
+use lib::zone::{Catalog, Zone};
+use lib::Error;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+
+const ZONE: &str = "$TTL 60\n@ SOA ns hostmaster 1 2 3 4 5\n@ NS ns\nns A 192.0.2.53\nwww A 192.0.2.1\nwww A 192.0.2.2\n";
+
+fn zone() -> Zone {
+    Zone::parse(ZONE, &name("example")).unwrap()
+}
+
+fn a(host: &str, last: u8) -> DnsRecord {
+    DnsRecord::new(
+        name(host),
+        60,
+        RecordData::IPADDR(Ipv4Addr::new(192, 0, 2, last)),
+    )
+}
+
+fn refused(result: lib::Result<bool>) -> ResponseCode {
+    match result {
+        Err(Error::Update(r_code)) => r_code,
+        other => panic!("expected a refusal, got {:?}", other),
+    }
+}
+
+#[test]
+fn prerequisites_guard_updates() {
+    let mut zone: Zone = zone();
+    let mut register: Update = Update::new(name("example"));
+    register.require_no_name(name("host.example"));
+    register.add(a("host.example", 7));
+    assert!(register.apply_to(&mut zone).unwrap());
+    assert_eq!(zone.rrset(&name("host.example"), QueryType::A).len(), 1);
+    assert_eq!(zone.serial(), Some(2));
+
+    // nothing changes when a prerequisite fails
+    assert_eq!(
+        refused(register.apply_to(&mut zone)),
+        ResponseCode::YXDOMAIN
+    );
+    assert_eq!(zone.serial(), Some(2));
+
+    let mut update: Update = Update::new(name("example"));
+    update.require_name(name("missing.example"));
+    assert_eq!(refused(update.apply_to(&mut zone)), ResponseCode::NAMEERROR);
+
+    let mut update: Update = Update::new(name("example"));
+    update.require_rrset(name("www.example"), QueryType::MX);
+    assert_eq!(refused(update.apply_to(&mut zone)), ResponseCode::NXRRSET);
+
+    let mut update: Update = Update::new(name("example"));
+    update.require_no_rrset(name("www.example"), QueryType::A);
+    assert_eq!(refused(update.apply_to(&mut zone)), ResponseCode::YXRRSET);
+
+    // value dependent prerequisites need the whole RRset
+    let mut update: Update = Update::new(name("example"));
+    update.require(a("www.example", 1));
+    update.delete_rrset(name("www.example"), QueryType::A);
+    assert_eq!(refused(update.apply_to(&mut zone)), ResponseCode::NXRRSET);
+    update.require(a("www.example", 2));
+    assert!(update.apply_to(&mut zone).unwrap());
+    assert!(zone.records_at(&name("www.example")).is_empty());
+
+    let mut update: Update = Update::new(name("example"));
+    update.add(a("www.example.net", 1));
+    assert_eq!(refused(update.apply_to(&mut zone)), ResponseCode::NOTZONE);
+
+    let mut update: Update = Update::new(name("example.net"));
+    update.add(a("www.example.net", 1));
+    assert_eq!(refused(update.apply_to(&mut zone)), ResponseCode::NOTAUTH);
+}
+
+#[test]
+fn updates_keep_the_zone_sound() {
+    let mut zone: Zone = zone();
+
+    // the apex keeps its SOA and last NS
+    let mut update: Update = Update::new(name("example"));
+    update.delete_name(name("example"));
+    update.delete(DnsRecord::new(
+        name("example"),
+        60,
+        RecordData::NS(name("ns.example")),
+    ));
+    assert!(!update.apply_to(&mut zone).unwrap());
+    assert_eq!(zone.serial(), Some(1));
+    assert_eq!(zone.rrset(&name("example"), QueryType::NS).len(), 1);
+
+    // no CNAME beside other data
+    let mut update: Update = Update::new(name("example"));
+    update.add(DnsRecord::new(
+        name("www.example"),
+        60,
+        RecordData::CNAME(name("ns.example")),
+    ));
+    assert!(!update.apply_to(&mut zone).unwrap());
+
+    // adding a record again is a no-op, even with another TTL
+    let mut update: Update = Update::new(name("example"));
+    update.add(DnsRecord {
+        ttl: 300,
+        ..a("www.example", 1)
+    });
+    assert!(!update.apply_to(&mut zone).unwrap());
+    assert_eq!(zone.serial(), Some(1));
+
+    // a newer SOA replaces the serial bump
+    let mut update: Update = Update::new(name("example"));
+    update.delete(a("www.example", 1));
+    update.add(DnsRecord::new(
+        name("example"),
+        60,
+        RecordData::SOA {
+            mname: name("ns.example"),
+            rname: name("hostmaster.example"),
+            serial: 10,
+            refresh: 2,
+            retry: 3,
+            expire: 4,
+            minimum: 5,
+        },
+    ));
+    assert!(update.apply_to(&mut zone).unwrap());
+    assert_eq!(zone.serial(), Some(10));
+    assert_eq!(zone.rrset(&name("www.example"), QueryType::A).len(), 1);
+}
+
+#[test]
+fn updates_cross_the_wire() {
+    let mut update: Update = Update::new(name("example"));
+    update.require_no_name(name("host.example"));
+    update.require_rrset(name("www.example"), QueryType::A);
+    update.require(a("www.example", 1));
+    update.delete_rrset(name("www.example"), QueryType::A);
+    update.delete_name(name("old.example"));
+    update.delete(a("ns.example", 53));
+    update.add(a("host.example", 7));
+    let packet: DnsPacket = decode(&encode(&mut update.to_packet()));
+    assert_eq!(Update::from_packet(&packet).unwrap(), update);
+}
+
+#[test]
+fn clients_update_primary_zones() {
+    let file: PathBuf = std::env::temp_dir().join(format!("update-{}.zone", std::process::id()));
+    std::fs::write(&file, ZONE).unwrap();
+    let config: Config = Config::parse(
+        &format!(
+            "key update.example hmac-sha256 MDEyMzQ1Njc4OWFiY2RlZg==\nzone example. {} allow-update=key:update.example\n",
+            file.display()
+        ),
+        Path::new(""),
+    )
+    .unwrap();
+    let mut catalog: Catalog = Catalog::new();
+    catalog.insert(zone());
+    let mut server: Server = Server::new(catalog);
+    server.set_keyring(config.keyring.clone());
+    server.allow_updates(&config.zones[0]);
+    let server: Arc<Server> = Arc::new(server);
+    let socket: UdpSocket = UdpSocket::bind((LOCALHOST, 0)).unwrap();
+    let addr: SocketAddr = socket.local_addr().unwrap();
+    let serving: Arc<Server> = server.clone();
+    thread::spawn(move || serving.serve_udp(&socket));
+
+    let mut register: Update = Update::new(name("example"));
+    register.require_no_name(name("host.example"));
+    register.add(a("host.example", 7));
+    let mut client: Client = Client::new(addr);
+    assert!(matches!(
+        client.update(&register),
+        Err(Error::Update(ResponseCode::REFUSED))
+    ));
+    client.set_tsig(Some(key("update.example")));
+    client.update(&register).unwrap();
+    assert!(matches!(
+        client.update(&register),
+        Err(Error::Update(ResponseCode::YXDOMAIN))
+    ));
+
+    let served: Zone = server
+        .catalog()
+        .read()
+        .unwrap()
+        .get(&name("example"))
+        .unwrap()
+        .clone();
+    assert_eq!(served.serial(), Some(2));
+    // the zone file is left alone, a restart reads the signed file
+    let source: String = std::fs::read_to_string(&file).unwrap();
+    let saved: Zone = load_zone(&config.zones[0], 0).unwrap();
+    let journal: Journal = Journal::load(
+        &config.zones[0].journal_file().to_string_lossy(),
+        &name("example"),
+    )
+    .unwrap();
+    std::fs::remove_file(&file).unwrap();
+    std::fs::remove_file(config.zones[0].journal_file()).unwrap();
+    std::fs::remove_file(config.zones[0].signed_file()).unwrap();
+    assert_eq!(source, ZONE);
+    assert_eq!(saved.serial(), Some(2));
+    assert_eq!(saved.rrset(&name("host.example"), QueryType::A).len(), 1);
+    assert_eq!(
+        journal.condensed(1).unwrap().added,
+        vec![a("host.example", 7)]
+    );
+}
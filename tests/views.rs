@@ -0,0 +1,231 @@
+mod common;
+
+use common::{decode, encode, ip, key, name, request, LOCALHOST};
+use lib::config::Config;
+use lib::dns::{DnsPacket, DnsQuestion, QueryType, RecordData, ResponseCode};
+use lib::server::{unix_time, Server, View};
+use lib::tsig::KeyRing;
+use lib::zone::{Catalog, Zone};
+use std::net::{SocketAddr, UdpSocket};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+
+/// a server for `example` where www has the address `www`
+fn zone_server(www: &str) -> Server {
+    let zone: Zone = Zone::parse(
+        &format!(
+            "$TTL 60\n@ SOA ns hostmaster 1 2 3 4 5\n@ NS ns\nns A 192.0.2.53\nwww A {}\n",
+            www
+        ),
+        &name("example"),
+    )
+    .unwrap();
+    let mut catalog: Catalog = Catalog::new();
+    catalog.insert(zone);
+    Server::new(catalog)
+}
+
+fn split_server() -> Server {
+    let mut server: Server = zone_server("198.51.100.1");
+    let mut keyring: KeyRing = KeyRing::new();
+    keyring.insert(key("internal.example"));
+    server.set_keyring(keyring);
+
+    let mut internal: View = View::new("internal".to_string(), zone_server("10.0.0.1"));
+    internal.match_clients = "10.0.0.0/8,key:internal.example".parse().unwrap();
+    internal
+        .server
+        .set_transfer_acl(name("example"), "10.0.0.0/8".parse().unwrap());
+    server.add_view(internal);
+    let mut lab: View = View::new("lab".to_string(), zone_server("192.0.2.80"));
+    lab.match_destinations = "192.0.2.53".parse().unwrap();
+    server.add_view(lab);
+    server
+}
+
+fn www(server: &Server, message: &[u8], client: &str, destination: &str) -> RecordData {
+    let answer: Vec<u8> = server
+        .handle_bytes(message, ip(client), ip(destination), false)
+        .unwrap();
+    decode(&answer).answers[0].r_data.clone()
+}
+
+fn address(s: &str) -> RecordData {
+    RecordData::IPADDR(s.parse().unwrap())
+}
+
+/// the zone `example.org` where www has the address `www`
+fn org_zone(www: &str) -> Zone {
+    Zone::parse(
+        &format!(
+            "$TTL 60\n@ SOA ns hostmaster 1 2 3 4 5\n@ NS ns\nwww A {}\n",
+            www
+        ),
+        &name("example.org"),
+    )
+    .unwrap()
+}
+
+/// a server for `example.org` on a local port, with where it listens
+fn forwarder(www: &str) -> (Arc<Server>, SocketAddr) {
+    let mut catalog: Catalog = Catalog::new();
+    catalog.insert(org_zone(www));
+    let server: Arc<Server> = Arc::new(Server::new(catalog));
+    let socket: UdpSocket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr: SocketAddr = socket.local_addr().unwrap();
+    let serving: Arc<Server> = server.clone();
+    thread::spawn(move || serving.serve_udp(&socket));
+    (server, addr)
+}
+
+#[test]
+fn queries_go_to_the_first_matching_view() {
+    let server: Server = split_server();
+    let message: Vec<u8> = encode(&mut request("www.example", QueryType::A));
+    assert_eq!(
+        www(&server, &message, "10.1.2.3", "127.0.0.1"),
+        address("10.0.0.1")
+    );
+    assert_eq!(
+        www(&server, &message, "192.0.2.7", "192.0.2.53"),
+        address("192.0.2.80")
+    );
+    // the client decides before the destination, views go in order
+    assert_eq!(
+        www(&server, &message, "10.1.2.3", "192.0.2.53"),
+        address("10.0.0.1")
+    );
+    // no view matches, the server answers itself
+    assert_eq!(
+        www(&server, &message, "192.0.2.7", "127.0.0.1"),
+        address("198.51.100.1")
+    );
+}
+
+#[test]
+fn tsig_keys_select_views() {
+    let server: Server = split_server();
+    let mut request: DnsPacket = request("www.example", QueryType::A);
+    key("internal.example")
+        .sign(&mut request, unix_time() as u64, None)
+        .unwrap();
+    let message: Vec<u8> = encode(&mut request);
+    assert_eq!(
+        www(&server, &message, "192.0.2.7", "127.0.0.1"),
+        address("10.0.0.1")
+    );
+}
+
+#[test]
+fn transfers_come_from_the_view() {
+    let server: Server = split_server();
+    let mut request: DnsPacket = DnsPacket::new();
+    request
+        .questions
+        .push(DnsQuestion::new(name("example"), QueryType::AXFR));
+    let message: Vec<u8> = encode(&mut request);
+
+    let messages: Vec<Vec<u8>> = server.handle_tcp(&message, ip("10.1.2.3"), LOCALHOST);
+    let records: Vec<RecordData> = messages
+        .iter()
+        .flat_map(|message| decode(message).answers)
+        .map(|record| record.r_data)
+        .collect();
+    assert!(records.contains(&address("10.0.0.1")));
+    assert!(!records.contains(&address("198.51.100.1")));
+
+    // the default view allows no transfers
+    let refused: Vec<Vec<u8>> = server.handle_tcp(&message, ip("192.0.2.7"), LOCALHOST);
+    assert_eq!(decode(&refused[0]).header.r_code, ResponseCode::REFUSED);
+}
+
+#[test]
+fn views_forward_to_their_own_servers_and_cache_apart() {
+    let (_, outside) = forwarder("192.0.2.1");
+    let (inside_server, inside) = forwarder("10.0.0.2");
+    let mut server: Server = zone_server("198.51.100.1");
+    server.set_recursion_acl("any".parse().unwrap());
+    server.set_forwarders(vec![outside]);
+    let mut internal: View = View::new("internal".to_string(), zone_server("10.0.0.1"));
+    internal.match_clients = "10.0.0.0/8".parse().unwrap();
+    internal
+        .server
+        .set_recursion_acl("10.0.0.0/8".parse().unwrap());
+    internal.server.set_forwarders(vec![inside]);
+    server.add_view(internal);
+
+    let mut request: DnsPacket = DnsPacket::new();
+    request.header.rd = true;
+    request
+        .questions
+        .push(DnsQuestion::new(name("www.example.org"), QueryType::A));
+    let message: Vec<u8> = encode(&mut request);
+    assert_eq!(
+        www(&server, &message, "192.0.2.7", "127.0.0.1"),
+        address("192.0.2.1")
+    );
+    assert_eq!(
+        www(&server, &message, "10.1.2.3", "127.0.0.1"),
+        address("10.0.0.2")
+    );
+    assert_eq!(server.cache().len(), 1);
+    assert_eq!(server.views()[0].server.cache().len(), 1);
+
+    // the view answers from its cache until the TTL runs out
+    inside_server.replace_zone(org_zone("10.0.0.3"));
+    assert_eq!(
+        www(&server, &message, "10.1.2.3", "127.0.0.1"),
+        address("10.0.0.2")
+    );
+}
+
+#[test]
+fn configs_group_zones_in_views() {
+    let config: Config = Config::parse(
+        "\
+zone example. external.zone
+view internal match-clients=10.0.0.0/8,key:internal.example.
+zone example. internal.zone
+zone rpz.example. rpz.zone
+response-policy rpz.example.
+view lab match-destinations=192.0.2.53
+zone example. lab.zone
+forwarders 10.0.0.1,[2001:db8::1]:5353
+",
+        Path::new("/etc/dns"),
+    )
+    .unwrap();
+    assert_eq!(config.zones.len(), 1);
+    assert!(config.response_policy.is_empty());
+    assert_eq!(config.views.len(), 2);
+    let internal = &config.views[0];
+    assert_eq!(internal.name, "internal");
+    assert_eq!(internal.zones.len(), 2);
+    assert_eq!(internal.zones[0].file, Path::new("/etc/dns/internal.zone"));
+    assert_eq!(internal.response_policy, vec![name("rpz.example")]);
+    assert!(internal.match_clients.allows(ip("10.9.9.9"), None));
+    assert!(!internal.match_clients.allows(ip("192.0.2.7"), None));
+    assert!(config.forwarders.is_empty() && internal.forwarders.is_empty());
+    assert_eq!(
+        config.views[1].forwarders,
+        vec![
+            "10.0.0.1:53".parse::<SocketAddr>().unwrap(),
+            "[2001:db8::1]:5353".parse().unwrap()
+        ]
+    );
+    assert!(config.views[1].match_clients.allows(ip("192.0.2.7"), None));
+    assert!(config.views[1]
+        .match_destinations
+        .allows(ip("192.0.2.53"), None));
+
+    // policy zones are looked up in the view they are named in
+    assert!(Config::parse(
+        "zone rpz.example. rpz.zone\nview a\nresponse-policy rpz.example.\n",
+        Path::new("")
+    )
+    .is_err());
+    assert!(Config::parse("view a\nview a\n", Path::new("")).is_err());
+    assert!(Config::parse("view a colour=blue\n", Path::new("")).is_err());
+    assert!(Config::parse("forwarders 10.0.0.1:99999\n", Path::new("")).is_err());
+}
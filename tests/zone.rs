@@ -0,0 +1,278 @@
+mod common;
+
+use common::name;
+use lib::config::{Config, ZoneConfig};
+use lib::dns::{DnsPacket, DnsQuestion, DnsRecord, Edns, QueryType, RecordData, ResponseCode};
+use lib::dnssec::anchor::TrustAnchors;
+use lib::dnssec::signer::{Nsec3Config, Signer, SigningKey, DEFAULT_VALIDITY};
+use lib::dnssec::validator::{Lookup, Status, Validator};
+use lib::dnssec::{ds_record, ALG_ECDSAP256SHA256, ALG_ED25519, DIGEST_SHA256};
+use lib::name::Name;
+use lib::server::{load_zone, Server};
+use lib::zone::{Catalog, Zone};
+use lib::Result;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+
+const NOW: u32 = 1_700_000_000;
+
+const EXAMPLE: &str = r#"
+$TTL 3600
+@       IN  SOA ns hostmaster (
+                1       ; serial
+                2h 1h 2w
+                300 )   ; minimum
+        IN  NS  ns
+        IN  MX  10 mail
+ns          A   192.0.2.1
+mail        A   192.0.2.2
+mail        AAAA 2001:db8::2
+www         CNAME ns
+*.wild      TXT "wild card" "\065"
+x.y         A   192.0.2.3
+sub         NS  ns.sub
+ns.sub      A   192.0.2.53
+ns.sub      AAAA 2001:db8::53
+"#;
+
+fn query(qname: &str, q_type: QueryType, dnssec_ok: bool) -> DnsPacket {
+    let mut packet: DnsPacket = DnsPacket::new();
+    packet.questions.push(DnsQuestion::new(name(qname), q_type));
+    packet.edns = Some(Edns {
+        dnssec_ok,
+        ..Edns::default()
+    });
+    packet
+}
+
+/// a server answering for `example.` and `example.` alone
+struct Local(Server);
+
+impl Local {
+    fn ask(&self, qname: &str, q_type: QueryType) -> DnsPacket {
+        self.0.handle(&query(qname, q_type, true))
+    }
+}
+
+impl Lookup for Local {
+    fn lookup(&self, name: &Name, q_type: QueryType) -> Result<DnsPacket> {
+        let mut request: DnsPacket = query("example", q_type, true);
+        request.questions[0].label = name.clone();
+        Ok(self.0.handle(&request))
+    }
+}
+
+fn serve(zone: Zone) -> Local {
+    let mut catalog: Catalog = Catalog::new();
+    catalog.insert(zone);
+    Local(Server::new(catalog))
+}
+
+/// the example zone signed by a fresh KSK and ZSK, with the KSK as anchor
+fn signed(nsec3: Option<Nsec3Config>) -> (Local, TrustAnchors) {
+    let mut zone: Zone = Zone::parse(EXAMPLE, &name("example")).unwrap();
+    let ksk: SigningKey = SigningKey::generate(name("example"), ALG_ED25519, true).unwrap();
+    let zsk: SigningKey =
+        SigningKey::generate(name("example"), ALG_ECDSAP256SHA256, false).unwrap();
+    let mut anchors: TrustAnchors = TrustAnchors::new();
+    anchors.add(DnsRecord::new(name("example"), 3600, ksk.dnskey()));
+    let mut signer: Signer = Signer::new(vec![ksk, zsk]);
+    signer.set_nsec3(nsec3);
+    signer.sign_zone(&mut zone, NOW).unwrap();
+    (serve(zone), anchors)
+}
+
+fn validate(local: &Local, anchors: &TrustAnchors, response: &mut DnsPacket) -> Status {
+    let mut validator: Validator<&Local> = Validator::new(local, anchors.clone());
+    validator.set_time(NOW);
+    validator.validate(response)
+}
+
+#[test]
+fn zone_file_answers() {
+    let local: Local = serve(Zone::parse(EXAMPLE, &name("example")).unwrap());
+
+    let mx: DnsPacket = local.ask("example", QueryType::MX);
+    assert!(mx.header.aa);
+    assert_eq!(mx.answers.len(), 1);
+    let glue: Vec<&RecordData> = mx.additionals.iter().map(|r| &r.r_data).collect();
+    assert_eq!(
+        glue,
+        vec![
+            &RecordData::IPADDR(Ipv4Addr::new(192, 0, 2, 2)),
+            &RecordData::AAAA("2001:db8::2".parse().unwrap()),
+        ]
+    );
+
+    let www: DnsPacket = local.ask("www.example", QueryType::A);
+    let types: Vec<QueryType> = www.answers.iter().map(|r| r.r_type).collect();
+    assert_eq!(types, vec![QueryType::CNAME, QueryType::A]);
+
+    let wild: DnsPacket = local.ask("a.wild.example", QueryType::TXT);
+    assert_eq!(wild.answers[0].label, name("a.wild.example"));
+    assert_eq!(
+        wild.answers[0].r_data,
+        RecordData::TXT(vec![b"wild card".to_vec(), b"A".to_vec()])
+    );
+
+    let nx: DnsPacket = local.ask("nope.example", QueryType::A);
+    assert_eq!(nx.header.r_code, ResponseCode::NAMEERROR);
+    // negative TTL is capped by the SOA minimum
+    assert_eq!(nx.authorities[0].ttl, 300);
+
+    let ent: DnsPacket = local.ask("y.example", QueryType::A);
+    assert_eq!(ent.header.r_code, ResponseCode::NOERROR);
+    assert!(ent.answers.is_empty());
+
+    let referral: DnsPacket = local.ask("host.sub.example", QueryType::A);
+    assert!(!referral.header.aa);
+    assert_eq!(
+        referral.authorities[0].r_data,
+        RecordData::NS(name("ns.sub.example"))
+    );
+    let types: Vec<QueryType> = referral.additionals.iter().map(|r| r.r_type).collect();
+    assert_eq!(types, vec![QueryType::A, QueryType::AAAA]);
+    assert_eq!(referral.additionals[1].label, name("ns.sub.example"));
+
+    let any: DnsPacket = local.ask("example", QueryType::ANY);
+    let types: Vec<QueryType> = any.answers.iter().map(|r| r.r_type).collect();
+    assert_eq!(types, vec![QueryType::SOA, QueryType::NS, QueryType::MX]);
+
+    let elsewhere: DnsPacket = local.ask("example.org", QueryType::A);
+    assert_eq!(elsewhere.header.r_code, ResponseCode::REFUSED);
+}
+
+#[test]
+fn zone_without_soa_is_rejected() {
+    assert!(Zone::parse("$TTL 60\nwww A 192.0.2.1\n", &name("example")).is_err());
+    let outside: String = format!("{}www.example.org. A 192.0.2.1\n", EXAMPLE);
+    assert!(Zone::parse(&outside, &name("example")).is_err());
+}
+
+fn check_signed(nsec3: Option<Nsec3Config>) {
+    let (local, anchors) = signed(nsec3);
+    let cases: [(&str, QueryType, ResponseCode); 6] = [
+        ("ns.example", QueryType::A, ResponseCode::NOERROR),
+        ("www.example", QueryType::A, ResponseCode::NOERROR),
+        ("nope.example", QueryType::A, ResponseCode::NAMEERROR),
+        ("ns.example", QueryType::MX, ResponseCode::NOERROR),
+        ("y.example", QueryType::A, ResponseCode::NOERROR),
+        ("a.wild.example", QueryType::TXT, ResponseCode::NOERROR),
+    ];
+    for (qname, q_type, r_code) in cases {
+        let mut response: DnsPacket = local.ask(qname, q_type);
+        assert_eq!(response.header.r_code, r_code, "{} {}", qname, q_type);
+        assert_eq!(
+            validate(&local, &anchors, &mut response),
+            Status::Secure,
+            "{} {}",
+            qname,
+            q_type
+        );
+    }
+
+    // the parent proves the delegation to sub.example is unsigned
+    let mut no_ds: DnsPacket = local.ask("sub.example", QueryType::DS);
+    assert!(no_ds.answers.is_empty());
+    assert_eq!(validate(&local, &anchors, &mut no_ds), Status::Secure);
+
+    // signatures are only handed out when asked for
+    let plain: DnsPacket = local.0.handle(&query("ns.example", QueryType::A, false));
+    assert!(plain.answers.iter().all(|r| r.r_type != QueryType::RRSIG));
+}
+
+#[test]
+fn nsec_signed_zone_validates() {
+    check_signed(None);
+}
+
+#[test]
+fn nsec3_signed_zone_validates() {
+    check_signed(Some(Nsec3Config {
+        iterations: 0,
+        salt: vec![0xaa, 0xbb],
+    }));
+}
+
+#[test]
+fn signing_keys_round_trip_and_give_ds() {
+    let key: SigningKey = SigningKey::generate(name("example"), ALG_ED25519, true).unwrap();
+    let loaded: SigningKey = SigningKey::parse(&key.to_string()).unwrap();
+    assert_eq!(loaded.dnskey(), key.dnskey());
+    assert!(loaded.is_ksk());
+    let ds: DnsRecord = ds_record(key.zone(), 3600, &key.dnskey(), DIGEST_SHA256)
+        .unwrap()
+        .unwrap();
+    assert!(matches!(ds.r_data, RecordData::DS { key_tag, .. } if key_tag == key.key_tag()));
+}
+
+#[test]
+fn signatures_are_renewed_before_they_expire() {
+    let dir: PathBuf = std::env::temp_dir().join(format!("resign-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("example.zone"), EXAMPLE).unwrap();
+    let key: SigningKey = SigningKey::generate(name("example"), ALG_ED25519, true).unwrap();
+    std::fs::write(dir.join("example.private"), key.to_string()).unwrap();
+    let config: Config =
+        Config::parse("zone example. example.zone key=example.private\n", &dir).unwrap();
+    let zone: &ZoneConfig = &config.zones[0];
+    let mut catalog: Catalog = Catalog::new();
+    catalog.insert(load_zone(zone, NOW).unwrap());
+    let server: Server = Server::new(catalog);
+    let expirations = || -> Vec<u32> {
+        let catalog = server.catalog().read().unwrap();
+        catalog
+            .get(&name("example"))
+            .unwrap()
+            .records()
+            .filter_map(|record| match record.r_data {
+                RecordData::RRSIG { expiration, .. } => Some(expiration),
+                _ => None,
+            })
+            .collect()
+    };
+
+    // more than a quarter of the validity left
+    assert!(!server.resign(zone, NOW + 20 * 86400).unwrap());
+    assert!(expirations()
+        .iter()
+        .all(|expiration| *expiration == NOW + DEFAULT_VALIDITY));
+    let later: u32 = NOW + 23 * 86400;
+    assert!(server.resign(zone, later).unwrap());
+    assert!(expirations()
+        .iter()
+        .all(|expiration| *expiration == later + DEFAULT_VALIDITY));
+    let source: String = std::fs::read_to_string(&zone.file).unwrap();
+    let saved: Zone = load_zone(zone, later).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(source, EXAMPLE);
+    assert_eq!(saved.serial(), Some(2));
+    assert_eq!(
+        server
+            .catalog()
+            .read()
+            .unwrap()
+            .get(&name("example"))
+            .unwrap()
+            .journal()
+            .last_serial(),
+        Some(2)
+    );
+}
+
+#[test]
+fn config_parses() {
+    let config: Config = Config::parse(
+        "# zones\nlisten 0.0.0.0:53\nzone example. db.example key=K.private nsec3=0:aabb\n",
+        Path::new("/etc/dns"),
+    )
+    .unwrap();
+    assert_eq!(config.listen.port(), 53);
+    assert_eq!(config.zones[0].file, Path::new("/etc/dns/db.example"));
+    assert_eq!(config.zones[0].keys, vec![Path::new("/etc/dns/K.private")]);
+    assert_eq!(
+        config.zones[0].nsec3.as_ref().unwrap().salt,
+        vec![0xaa, 0xbb]
+    );
+    assert!(Config::parse("zone example.\n", Path::new("")).is_err());
+}